@@ -204,7 +204,7 @@ impl EventTreeRealmComponent {
                     }
                 }
             }
-            EventType::AgentCompleted { result } => {
+            EventType::AgentCompleted { result, .. } => {
                 if let EventSource::Agent { agent_id, .. } = &event.source {
                     info!(agent_id = agent_id, "Agent completed");
                     if let Some(node) = self.tree.find_node_mut(agent_id) {
@@ -257,7 +257,7 @@ impl EventTreeRealmComponent {
                     }
                 }
             }
-            EventType::ExecutionCompleted { result } => {
+            EventType::ExecutionCompleted { result, .. } => {
                 info!(executionid = event.id, "Execution completed");
                 if let Some(root) = self.tree.find_node_mut(&event.id) {
                     root.complete();
@@ -374,6 +374,81 @@ impl EventTreeRealmComponent {
                 }
             }
 
+            EventType::TaskEvaluated { task_id, evaluator_id, wave_index, round, score, min_score, passed, summary, .. } => {
+                info!(
+                    executionid = event.id,
+                    task_id = task_id,
+                    round = round,
+                    score = score,
+                    passed = passed,
+                    "Task evaluated"
+                );
+
+                let task_node_id = format!("{}/wave-{}/{}", event.id, wave_index, task_id);
+                // The revision being scored is done
+                if *round > 0 {
+                    if let Some(revision_node) = self.tree.find_node_mut(&format!("{}/revision-{}", task_node_id, round)) {
+                        revision_node.complete();
+                    }
+                }
+
+                let evaluation_id = format!("{}/evaluation-{}", task_node_id, round);
+                self.tree.add_child(
+                    task_node_id,
+                    evaluation_id.clone(),
+                    format!("Evaluation by {}: {:.2} (min {:.2}) - {}", evaluator_id, score, min_score, summary),
+                );
+                if let Some(evaluation_node) = self.tree.find_node_mut(&evaluation_id) {
+                    if *passed {
+                        evaluation_node.complete();
+                    } else {
+                        evaluation_node.warn(format!("Score {:.2} below {:.2}", score, min_score));
+                    }
+                }
+            }
+
+            EventType::TaskRevisionStarted { task_id, wave_index, round, max_rounds, score } => {
+                info!(
+                    executionid = event.id,
+                    task_id = task_id,
+                    round = round,
+                    "Task revision started"
+                );
+
+                let task_node_id = format!("{}/wave-{}/{}", event.id, wave_index, task_id);
+                let revision_id = format!("{}/revision-{}", task_node_id, round);
+                self.tree.add_child(
+                    task_node_id,
+                    revision_id.clone(),
+                    format!("Revision {}/{} (previous score {:.2})", round, max_rounds, score),
+                );
+                if let Some(revision_node) = self.tree.find_node_mut(&revision_id) {
+                    revision_node.start();
+                }
+            }
+
+            EventType::TaskDelegated { parent_task_id, task_id, agent_id, wave_index, depth, description } => {
+                info!(
+                    executionid = event.id,
+                    parent_task_id = parent_task_id,
+                    task_id = task_id,
+                    depth = depth,
+                    "Task delegated"
+                );
+
+                // Sub-tasks hang below the task that delegated them
+                let wave_id = format!("{}/wave-{}", event.id, wave_index);
+                let sub_task_id = format!("{}/{}", wave_id, task_id);
+                self.tree.add_child(
+                    format!("{}/{}", wave_id, parent_task_id),
+                    sub_task_id.clone(),
+                    format!("{} - {} ({})", task_id, agent_id, description),
+                );
+                if let Some(sub_task_node) = self.tree.find_node_mut(&sub_task_id) {
+                    sub_task_node.start();
+                }
+            }
+
             EventType::ExecutionPlanReady { plan } => {
                 info!(
                     executionid = event.id,
@@ -3,9 +3,11 @@
 //! The coding agent specializes in generating, reviewing, and refactoring code.
 //! It integrates with Rig for LLM calls and supports local Ollama models.
 
+use crate::tools::AgentTools;
+use std::sync::Arc;
 use crate::agents::base::TypedAgent;
 use ai_agent_common::AgentType;
-use async_openai::{config::OpenAIConfig, Client};
+use ai_agent_common::llm::LlmProvider;
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -16,6 +18,9 @@ pub struct CodingOutput {
     // pub code: String,
     // pub language: String,
     pub change_log: Vec<ChangeLog>,
+    /// Self-assessed confidence in the result (0.0 - 1.0)
+    #[serde(default)]
+    pub confidence: Option<f32>,
     // pub tests: Option<String>,
     // #[serde(default)]
     // pub dependencies: Vec<String>,
@@ -31,10 +36,12 @@ pub struct ChangeLog {
 pub struct CodingAgent {
     id: String,
     model: String,
-    client: Client<OpenAIConfig>,
+    provider: Arc<dyn LlmProvider>,
     system_prompt: String,
     temperature: f32,
     max_tokens: usize,
+    enable_streaming: bool,
+    tools: Arc<AgentTools>,
 }
 
 impl CodingAgent {
@@ -45,21 +52,20 @@ impl CodingAgent {
         system_prompt: String,
         temperature: f32,
         max_tokens: usize,
-        ollama_base_url: Option<&str>,
+        enable_streaming: bool,
+        tools: Arc<AgentTools>,
+        provider: Arc<dyn LlmProvider>,
     ) -> Self {
-        let base_url = ollama_base_url.unwrap_or("http://localhost:11434/v1");
-        let config = OpenAIConfig::new()
-            .with_api_key("ollama") // Required but unused
-            .with_api_base(base_url);
-        let client = Client::with_config(config);
         let system_prompt = CodingAgent::build_system_prompt(system_prompt);
         Self {
             id,
-            client,
+            provider,
             system_prompt,
             model,
             temperature: temperature.clamp(0.0, 2.0),
             max_tokens,
+            enable_streaming,
+            tools,
         }
     }
 
@@ -93,8 +99,14 @@ impl TypedAgent for CodingAgent {
     fn temperature(&self) -> f32 {
         self.temperature
     }
-    fn client(&self) -> &Client<OpenAIConfig> {
-        &self.client
+    fn provider(&self) -> &dyn LlmProvider {
+        self.provider.as_ref()
+    }
+    fn enable_streaming(&self) -> bool {
+        self.enable_streaming
+    }
+    fn tools(&self) -> &AgentTools {
+        &self.tools
     }
     type Output = CodingOutput;
 
@@ -122,6 +134,11 @@ impl TypedAgent for CodingAgent {
                 description: "Generate required code to complete the user prompt. Use `write_file` to write all code to disk. Do NOT output code in text - only use the write_file tool.".to_string(),
                 execution_mode: StepExecutionMode::ReAct { max_iterations: Some(1) }, // Allow multiple writes if needed
                 required_tools: vec!["write_file".to_string()],
+                optional_tools: [
+                    "read_file", "edit_file", "list_directory", "create_directory", "file_exists",
+                    "file_metadata", "delete_file", "search_code", "code_navigation", "lsp", "git",
+                    "run_command", "run_tests", "delegate", "search_context",
+                ].map(String::from).to_vec(),
                 parameters: HashMap::new(),
                 formatted: false, // Must produce CodingOutput JSON
             }
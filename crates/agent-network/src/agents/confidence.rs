@@ -0,0 +1,212 @@
+//! Confidence estimation
+//!
+//! Combines the signals an agent run leaves behind (the model's own assessment,
+//! token log-probabilities, tool failures and evaluator scores) into the
+//! confidence used for HITL gating.
+
+use ai_agent_common::llm::structured::extract_json;
+use async_openai::types::CreateChatCompletionResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::tools::ToolExecution;
+
+/// Confidence assumed when a run produced no signals
+pub const PRIOR_CONFIDENCE: f32 = 0.8;
+
+/// Weight of the evaluator score relative to the other signals
+const EVALUATOR_WEIGHT: f32 = 2.0;
+
+/// `schema` with an optional `confidence` property for the model's self-assessment, so
+/// formatted steps can report it; schemas that are not objects or declare one are unchanged
+pub fn with_confidence_property(schema: &Value) -> Value {
+    let mut schema = schema.clone();
+    if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+        properties.entry("confidence").or_insert_with(|| serde_json::json!({
+            "type": "number",
+            "minimum": 0.0,
+            "maximum": 1.0,
+            "description": "How likely the output is correct and complete (0.0 - 1.0)",
+        }));
+    }
+    schema
+}
+
+/// Signals collected while an agent works
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfidenceSignals {
+    /// Lowest confidence the model reported for its own output
+    pub self_assessment: Option<f32>,
+    /// Sum of the completion tokens' log-probabilities
+    pub logprob_sum: f64,
+    /// Completion tokens with a log-probability
+    pub logprob_tokens: usize,
+    /// Tool calls executed
+    pub tool_calls: usize,
+    /// Tool calls that failed
+    pub tool_failures: usize,
+    /// Latest evaluator score (0.0 - 1.0)
+    pub evaluator_score: Option<f32>,
+}
+
+impl ConfidenceSignals {
+    /// Record the log-probabilities of a completion and any confidence it reports
+    pub fn record_response(&mut self, response: &CreateChatCompletionResponse) {
+        let Some(choice) = response.choices.first() else { return };
+        if let Some(tokens) = choice.logprobs.as_ref().and_then(|logprobs| logprobs.content.as_ref()) {
+            self.record_logprobs(tokens.iter().map(|token| token.logprob));
+        }
+        if let Some(content) = &choice.message.content {
+            self.record_output(content);
+        }
+    }
+
+    pub fn record_logprobs(&mut self, logprobs: impl IntoIterator<Item = f32>) {
+        for logprob in logprobs.into_iter().filter(|logprob| logprob.is_finite()) {
+            self.logprob_sum += f64::from(logprob);
+            self.logprob_tokens += 1;
+        }
+    }
+
+    /// Record the `confidence` field of a structured output, if it has one
+    pub fn record_output(&mut self, output: &str) {
+        let confidence = extract_json(output)
+            .and_then(|value| value.get("confidence").and_then(Value::as_f64));
+        if let Some(confidence) = confidence {
+            self.record_self_assessment(confidence as f32);
+        }
+    }
+
+    /// Record a self-assessment, ignoring values outside 0.0 - 1.0
+    pub fn record_self_assessment(&mut self, confidence: f32) {
+        if (0.0..=1.0).contains(&confidence) {
+            self.self_assessment = Some(self.self_assessment.map_or(confidence, |current| current.min(confidence)));
+        }
+    }
+
+    pub fn record_tool(&mut self, execution: &ToolExecution) {
+        self.tool_calls += 1;
+        if !execution.result.success {
+            self.tool_failures += 1;
+        }
+    }
+
+    pub fn record_evaluation(&mut self, score: f32) {
+        self.evaluator_score = Some(score.clamp(0.0, 1.0));
+    }
+
+    /// Fold in the signals of another step
+    pub fn merge(&mut self, other: &ConfidenceSignals) {
+        if let Some(confidence) = other.self_assessment {
+            self.record_self_assessment(confidence);
+        }
+        self.logprob_sum += other.logprob_sum;
+        self.logprob_tokens += other.logprob_tokens;
+        self.tool_calls += other.tool_calls;
+        self.tool_failures += other.tool_failures;
+        if other.evaluator_score.is_some() {
+            self.evaluator_score = other.evaluator_score;
+        }
+    }
+
+    /// Geometric mean of the completion tokens' probabilities
+    pub fn token_confidence(&self) -> Option<f32> {
+        (self.logprob_tokens > 0).then(|| (self.logprob_sum / self.logprob_tokens as f64).exp() as f32)
+    }
+
+    /// Share of tool calls that succeeded, Laplace-smoothed so a single failure does not
+    /// read as certain failure
+    pub fn tool_success_rate(&self) -> Option<f32> {
+        (self.tool_calls > 0).then(|| (self.tool_calls - self.tool_failures + 1) as f32 / (self.tool_calls + 2) as f32)
+    }
+
+    /// Weighted mean of the available signals, or [`PRIOR_CONFIDENCE`] without any
+    pub fn confidence(&self) -> f32 {
+        let signals = [
+            (self.self_assessment, 1.0),
+            (self.token_confidence(), 1.0),
+            (self.tool_success_rate(), 1.0),
+            (self.evaluator_score, EVALUATOR_WEIGHT),
+        ];
+        let (sum, weight) = signals.iter()
+            .filter_map(|(value, weight)| value.map(|value| (value * weight, *weight)))
+            .fold((0.0, 0.0), |(sum, total), (value, weight)| (sum + value, total + weight));
+        if weight == 0.0 { PRIOR_CONFIDENCE } else { (sum / weight).clamp(0.0, 1.0) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolResult;
+
+    fn execution(success: bool) -> ToolExecution {
+        ToolExecution {
+            tool_name: "read_file".to_string(),
+            arguments: "{}".to_string(),
+            result: ToolResult { success, output: String::new() },
+            execution_time_ms: 0,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_no_signals_falls_back_to_prior() {
+        assert_eq!(ConfidenceSignals::default().confidence(), PRIOR_CONFIDENCE);
+    }
+
+    #[test]
+    fn test_signals_are_combined() {
+        let mut signals = ConfidenceSignals::default();
+        signals.record_output(r#"{"status": "done", "confidence": 0.4}"#);
+        signals.record_output("not json");
+        signals.record_logprobs([0.0, 0.0]);
+        signals.record_tool(&execution(true));
+        signals.record_tool(&execution(false));
+
+        assert_eq!(signals.self_assessment, Some(0.4));
+        assert_eq!(signals.token_confidence(), Some(1.0));
+        assert_eq!(signals.tool_success_rate(), Some(0.5));
+        assert!((signals.confidence() - (0.4 + 1.0 + 0.5) / 3.0).abs() < 1e-6);
+
+        signals.record_evaluation(0.1);
+        assert!((signals.confidence() - (0.4 + 1.0 + 0.5 + 0.2) / 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_merge_keeps_lowest_self_assessment() {
+        let mut first = ConfidenceSignals::default();
+        first.record_self_assessment(0.9);
+        first.record_self_assessment(1.5);
+        let mut second = ConfidenceSignals::default();
+        second.record_self_assessment(0.6);
+        second.record_tool(&execution(false));
+
+        first.merge(&second);
+        assert_eq!(first.self_assessment, Some(0.6));
+        assert_eq!(first.tool_success_rate(), Some(1.0 / 3.0));
+    }
+
+    #[test]
+    fn test_one_failed_tool_call_does_not_zero_the_success_rate() {
+        let mut signals = ConfidenceSignals::default();
+        signals.record_tool(&execution(false));
+        assert!((signals.confidence() - 1.0 / 3.0).abs() < 1e-6);
+
+        for _ in 0..17 {
+            signals.record_tool(&execution(true));
+        }
+        assert_eq!(signals.tool_success_rate(), Some(0.9));
+    }
+
+    #[test]
+    fn test_confidence_property_is_added_to_object_schemas() {
+        let schema = serde_json::json!({"type": "object", "properties": {"summary": {"type": "string"}}});
+        assert_eq!(with_confidence_property(&schema)["properties"]["confidence"]["type"], "number");
+
+        let declared = serde_json::json!({"properties": {"confidence": {"type": "string"}}});
+        assert_eq!(with_confidence_property(&declared), declared);
+        let list = serde_json::json!({"type": "array"});
+        assert_eq!(with_confidence_property(&list), list);
+    }
+}
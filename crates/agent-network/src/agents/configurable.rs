@@ -0,0 +1,402 @@
+//! Generic agent defined entirely in config
+//!
+//! System prompt, output schema and workflow steps all come from the
+//! agent's `[[agent_network.agents]]` table, so new specializations (e.g. a test writer
+//! or a security reviewer) need no Rust code.
+
+use crate::tools::AgentTools;
+use std::sync::Arc;
+use crate::agents::base::{TypedAgent, WorkflowStep};
+use ai_agent_common::{AgentConfig, AgentType};
+use async_trait::async_trait;
+use ai_agent_common::llm::LlmProvider;
+use serde_json::Value;
+
+pub struct ConfigurableAgent {
+    id: String,
+    agent_type: AgentType,
+    model: String,
+    provider: Arc<dyn LlmProvider>,
+    system_prompt: String,
+    temperature: f32,
+    enable_streaming: bool,
+    tools: Arc<AgentTools>,
+    steps: Vec<WorkflowStep>,
+    /// Explicit output schema; any JSON value when unset
+    output_schema: Option<Value>,
+}
+
+impl ConfigurableAgent {
+    /// Create an agent from its config table
+    pub fn new(
+        config: &AgentConfig,
+        tools: Arc<AgentTools>,
+        provider: Arc<dyn LlmProvider>,
+    ) -> Self {
+        Self {
+            id: config.id.clone(),
+            agent_type: config.agent_type,
+            model: config.model.clone(),
+            provider,
+            system_prompt: config.system_prompt.clone(),
+            temperature: config.temperature.clamp(0.0, 2.0),
+            enable_streaming: config.enable_streaming,
+            tools,
+            steps: config.steps.iter().map(WorkflowStep::from).collect(),
+            output_schema: config.output_schema.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl TypedAgent for ConfigurableAgent {
+    fn id(&self) -> &str { &self.id }
+    fn agent_type(&self) -> AgentType { self.agent_type }
+    fn system_prompt(&self) -> &str { &self.system_prompt }
+    fn model(&self) -> &str { &self.model }
+    fn temperature(&self) -> f32 { self.temperature }
+    fn provider(&self) -> &dyn LlmProvider { self.provider.as_ref() }
+    fn enable_streaming(&self) -> bool { self.enable_streaming }
+    fn tools(&self) -> &AgentTools { &self.tools }
+    type Output = Value;
+
+    fn define_workflow_steps(&self, _context: &crate::agents::AgentContext) -> Vec<WorkflowStep> {
+        self.steps.clone()
+    }
+
+    fn output_schema(&self) -> Value {
+        match &self.output_schema {
+            Some(schema) => schema.clone(),
+            None => serde_json::to_value(schemars::schema_for!(Value)).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::base::StepExecutionMode;
+    use crate::agents::AgentContext;
+    use crate::execution_manager::BidirectionalEventChannel;
+    use crate::hitl::ToolPolicy;
+    use crate::tools::{ToolRegistry, ToolSet};
+    use ai_agent_common::llm::{MockProvider, MockResponse};
+    use ai_agent_common::{EventType, HitlConfig, ProjectScope, StatusEvent};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_steps_and_schema_come_from_config() {
+        let config: AgentConfig = toml::from_str(r#"
+            id = "test-writer"
+            agent_type = "Coding"
+            model = "qwen3:8b"
+            system_prompt = "You write tests."
+            configurable = true
+            available_tools = ["read_file", "write_file"]
+
+            [output_schema]
+            type = "object"
+            required = ["tests_written"]
+
+            [[steps]]
+            id = "read_code"
+            name = "Read Code"
+            description = "Read the code under test."
+            mode = "ReAct"
+            max_iterations = 4
+            required_tools = ["read_file"]
+            optional_tools = ["search_code", "write_file"]
+
+            [[steps]]
+            id = "write_tests"
+            description = "Write the tests."
+            formatted = true
+        "#).unwrap();
+        let tools = Arc::new(AgentTools::from_agent_config(Arc::new(ToolRegistry::default()), &config).unwrap());
+        let agent = ConfigurableAgent::new(&config, tools, Arc::new(MockProvider::new()));
+
+        let steps = &agent.steps;
+        assert_eq!(steps.len(), 2);
+        assert!(matches!(steps[0].execution_mode, StepExecutionMode::ReAct { max_iterations: Some(4) }));
+        assert_eq!(steps[0].required_tools, vec!["read_file".to_string()]);
+        // Steps are offered their own tools; one listing none gets all of the agent's
+        let agent_tools = vec!["read_file".to_string(), "write_file".to_string()];
+        assert_eq!(steps[0].tools(&agent_tools), agent_tools);
+        assert_eq!(steps[1].tools(&agent_tools), agent_tools);
+        assert_eq!(steps[0].tools(&agent_tools[..1]), vec!["read_file".to_string()]);
+        assert_eq!(steps[1].name, "write_tests");
+        assert!(matches!(steps[1].execution_mode, StepExecutionMode::OneShot));
+        assert!(steps[1].formatted);
+
+        assert_eq!(agent.output_schema()["required"][0], "tests_written");
+        assert_eq!(TypedAgent::agent_type(&agent), AgentType::Coding);
+    }
+
+    #[tokio::test]
+    async fn test_formatted_step_output_is_repaired() {
+        let config: AgentConfig = toml::from_str(r#"
+            id = "summarizer"
+            agent_type = "Writing"
+            model = "qwen3:8b"
+            system_prompt = "You summarize."
+            configurable = true
+
+            [output_schema]
+            type = "object"
+            required = ["summary"]
+            properties = { summary = { type = "string" } }
+
+            [[steps]]
+            id = "summarize"
+            description = "Summarize the change."
+            formatted = true
+        "#).unwrap();
+        let provider = Arc::new(MockProvider::new()
+            .respond_text("The change adds a parser.")
+            .respond_json(serde_json::json!({"summary": 1}))
+            .respond_json(serde_json::json!({"summary": "Adds a parser."})));
+        let tools = Arc::new(AgentTools::from_agent_config(Arc::new(ToolRegistry::default()), &config).unwrap());
+        let agent = ConfigurableAgent::new(&config, tools, provider.clone());
+
+        let context = crate::agents::AgentContext::new("Summarize the diff".to_string(), "conversation-1".to_string(), Some("task-1".to_string()));
+        let channel = crate::execution_manager::BidirectionalEventChannel::new("configurable-test".to_string());
+        let result = agent.execute_step_oneshot(&context, &agent.steps[0], &channel).await.unwrap();
+
+        // Two repairs, each answering the previous validation errors
+        assert_eq!(result.output.as_deref(), Some(r#"{"summary":"Adds a parser."}"#));
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 3);
+        let repair = serde_json::to_string(&requests[1].messages).unwrap();
+        assert!(repair.contains("the response contains no JSON value"), "{}", repair);
+        let repair = serde_json::to_string(&requests[2].messages).unwrap();
+        assert!(repair.contains("/summary: expected string, found number"), "{}", repair);
+        assert!(result.token_usage.total() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_formatted_step_reports_confidence() {
+        let config: AgentConfig = toml::from_str(r#"
+            id = "summarizer"
+            agent_type = "Writing"
+            model = "qwen3:8b"
+            system_prompt = "You summarize."
+            configurable = true
+
+            [output_schema]
+            type = "object"
+            required = ["summary"]
+            additionalProperties = false
+            properties = { summary = { type = "string" } }
+
+            [[steps]]
+            id = "summarize"
+            description = "Summarize the change."
+            formatted = true
+        "#).unwrap();
+        let provider = Arc::new(MockProvider::new()
+            .respond_json(serde_json::json!({"summary": "Adds a parser.", "confidence": 0.3})));
+        let tools = Arc::new(AgentTools::from_agent_config(Arc::new(ToolRegistry::default()), &config).unwrap());
+        let agent = ConfigurableAgent::new(&config, tools, provider.clone());
+
+        let context = crate::agents::AgentContext::new("Summarize the diff".to_string(), "conversation-1".to_string(), Some("task-1".to_string()));
+        let channel = crate::execution_manager::BidirectionalEventChannel::new("configurable-test".to_string());
+        let result = agent.execute_step_oneshot(&context, &agent.steps[0], &channel).await.unwrap();
+
+        let request = serde_json::to_value(&provider.requests()[0]).unwrap();
+        assert!(request["messages"].to_string().contains("Include a `confidence` field"));
+        assert_eq!(request["response_format"]["json_schema"]["schema"]["properties"]["confidence"]["type"], "number");
+        // Recorded as the self-assessment, dropped from the output the task's schema describes
+        assert_eq!(result.confidence.self_assessment, Some(0.3));
+        assert_eq!(result.output.as_deref(), Some(r#"{"summary":"Adds a parser."}"#));
+    }
+
+    /// ReAct agent over a fresh project holding `a.txt` and `b.txt`
+    fn react_agent(name: &str, provider: Arc<MockProvider>, policy: ToolPolicy) -> (ConfigurableAgent, Arc<ToolSet>, AgentContext, PathBuf) {
+        let config: AgentConfig = toml::from_str(r#"
+            id = "editor"
+            agent_type = "Coding"
+            model = "qwen3:8b"
+            system_prompt = "You edit files."
+            configurable = true
+            available_tools = ["read_file", "write_file"]
+
+            [[steps]]
+            id = "edit"
+            description = "Edit the files."
+            mode = "ReAct"
+        "#).unwrap();
+        let dir = std::env::temp_dir().join(format!("q_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "alpha").unwrap();
+        std::fs::write(dir.join("b.txt"), "beta").unwrap();
+
+        let tools = AgentTools::from_agent_config(Arc::new(ToolRegistry::default()), &config).unwrap()
+            .with_policy(Arc::new(policy));
+        let root = dir.to_string_lossy().to_string();
+        let toolset = tools.toolset(&root).unwrap();
+        let agent = ConfigurableAgent::new(&config, Arc::new(tools), provider);
+        let context = AgentContext::new("Edit the files".to_string(), "conversation-1".to_string(), Some("task-1".to_string()))
+            .with_project_scope(ProjectScope::new(root, None, HashMap::new()));
+        (agent, toolset, context, dir)
+    }
+
+    fn read(path: &str) -> (String, serde_json::Value) {
+        ("read_file".to_string(), serde_json::json!({"path": path}))
+    }
+
+    fn write(path: &str) -> (String, serde_json::Value) {
+        ("write_file".to_string(), serde_json::json!({"path": path, "content": "gamma"}))
+    }
+
+    /// Role and tool call id of each message after the initial prompt
+    fn transcript(request: &async_openai::types::CreateChatCompletionRequest) -> Vec<(String, String)> {
+        let messages = serde_json::to_value(&request.messages).unwrap();
+        messages.as_array().unwrap().iter()
+            .skip_while(|message| message["role"] != "assistant")
+            .map(|message| {
+                let id = match message["role"].as_str().unwrap() {
+                    "assistant" => message["tool_calls"].as_array().unwrap().iter()
+                        .map(|call| call["id"].as_str().unwrap()).collect::<Vec<_>>().join(","),
+                    _ => message["tool_call_id"].as_str().unwrap_or_default().to_string(),
+                };
+                (message["role"].as_str().unwrap().to_string(), id)
+            })
+            .collect()
+    }
+
+    /// Answer every HITL request on the channel; the agent registers its waiter just after
+    /// announcing the request, so the decision is repeated until it lands
+    fn review(channel: &BidirectionalEventChannel, approved: bool, reasoning: &str) -> tokio::task::JoinHandle<()> {
+        let mut events = channel.subscribe_outbound();
+        let reviewer = channel.clone();
+        let reasoning = reasoning.to_string();
+        tokio::spawn(async move {
+            while let Ok(event) = events.recv().await {
+                if !matches!(event.event, EventType::HitlRequested { .. }) {
+                    continue;
+                }
+                for _ in 0..100 {
+                    let decision = StatusEvent {
+                        event: EventType::HitlDecision { approved, modified_content: None, reasoning: Some(reasoning.clone()) },
+                        ..event.clone()
+                    };
+                    let _ = reviewer.receive_inbound(decision).await;
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_react_turn_answers_every_tool_call() {
+        let provider = Arc::new(MockProvider::new());
+        provider.push(MockResponse::ToolCalls(vec![read("a.txt"), write("c.txt"), read("b.txt")]));
+        provider.push(MockResponse::Text("Done".to_string()));
+        let disabled: HitlConfig = toml::from_str("enabled = false").unwrap();
+        let (agent, toolset, context, dir) = react_agent("react_turn", provider.clone(), ToolPolicy::from_config(&disabled).unwrap());
+        let channel = BidirectionalEventChannel::new("configurable-test".to_string());
+
+        let result = agent.execute_step_react(&context, &agent.steps[0], toolset, None, &channel, &None).await.unwrap();
+        assert_eq!(result.tool_executions.len(), 3);
+        assert_eq!(std::fs::read_to_string(dir.join("c.txt")).unwrap(), "gamma");
+
+        // The assistant message carrying all calls comes first, then one result per call
+        let requests = provider.requests();
+        assert_eq!(transcript(&requests[1]), vec![
+            ("assistant".to_string(), "call_0,call_1,call_2".to_string()),
+            ("tool".to_string(), "call_0".to_string()),
+            ("tool".to_string(), "call_1".to_string()),
+            ("tool".to_string(), "call_2".to_string()),
+        ]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_turn_results_follow_the_call_order() {
+        let provider = Arc::new(MockProvider::new());
+        provider.push(MockResponse::ToolCalls(vec![read("a.txt"), write("c.txt"), read("b.txt")]));
+        provider.push(MockResponse::Text("Done".to_string()));
+        let (agent, toolset, context, dir) = react_agent("react_mixed", provider.clone(), ToolPolicy::default());
+        let channel = BidirectionalEventChannel::new("configurable-test".to_string());
+
+        // The reads are auto-approved and batched around the write, which needs approval
+        let responder = review(&channel, true, "looks fine");
+        let result = agent.execute_step_react(&context, &agent.steps[0], toolset, None, &channel, &None).await.unwrap();
+        responder.abort();
+        assert_eq!(result.tool_executions.len(), 3);
+        assert_eq!(std::fs::read_to_string(dir.join("c.txt")).unwrap(), "gamma");
+
+        let requests = provider.requests();
+        assert_eq!(transcript(&requests[1]), vec![
+            ("assistant".to_string(), "call_0,call_1,call_2".to_string()),
+            ("tool".to_string(), "call_0".to_string()),
+            ("tool".to_string(), "call_1".to_string()),
+            ("tool".to_string(), "call_2".to_string()),
+            ("user".to_string(), String::new()),
+        ]);
+        let messages = serde_json::to_value(&requests[1].messages).unwrap();
+        let contents: Vec<&str> = messages.as_array().unwrap().iter().rev().take(4)
+            .map(|message| message["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(contents[0], "## HITL Feedback:\nlooks fine");
+        assert!(contents[1].contains("beta"), "{}", contents[1]);
+        assert!(contents[3].contains("alpha"), "{}", contents[3]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_call_skips_the_rest_of_the_turn() {
+        let provider = Arc::new(MockProvider::new());
+        provider.push(MockResponse::ToolCalls(vec![write("c.txt"), read("a.txt")]));
+        provider.push(MockResponse::Text("Done".to_string()));
+        let (agent, toolset, context, dir) = react_agent("react_rejected", provider.clone(), ToolPolicy::default());
+        let channel = BidirectionalEventChannel::new("configurable-test".to_string());
+
+        let responder = review(&channel, false, "not now");
+
+        let result = agent.execute_step_react(&context, &agent.steps[0], toolset, None, &channel, &None).await.unwrap();
+        responder.abort();
+        assert!(result.tool_executions.is_empty());
+        assert!(!dir.join("c.txt").exists());
+
+        let requests = provider.requests();
+        assert_eq!(transcript(&requests[1]).into_iter().map(|(_, id)| id).collect::<Vec<_>>(), vec!["call_0,call_1", "call_0", "call_1"]);
+        let messages = serde_json::to_value(&requests[1].messages).unwrap();
+        let messages = messages.as_array().unwrap();
+        assert!(messages[messages.len() - 2]["content"].as_str().unwrap().contains("rejected by human reviewer with reason: not now"));
+        assert_eq!(messages[messages.len() - 1]["content"], "Skipped: an earlier call in this turn was rejected");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_only_required_step_tools_must_be_configured() {
+        let agent = |step_tools: &str| toml::from_str::<AgentConfig>(&format!(r#"
+            id = "reviewer"
+            agent_type = "Coding"
+            model = "qwen3:8b"
+            system_prompt = "You review."
+            configurable = true
+            available_tools = ["read_file"]
+
+            [[steps]]
+            id = "inspect"
+            description = "Read the changes."
+            mode = "ReAct"
+            {}
+        "#, step_tools)).unwrap();
+        let pool = |config: AgentConfig| {
+            let mut system = ai_agent_common::SystemConfig::default();
+            system.agent_network.agents = vec![config];
+            async move { crate::agents::AgentPool::with_provider(&system, Arc::new(MockProvider::new())).await }
+        };
+
+        assert!(pool(agent(r#"optional_tools = ["read_file", "search_code"]"#)).await.is_ok());
+        let err = pool(agent(r#"required_tools = ["search_code"]"#)).await.err().unwrap();
+        assert!(err.to_string().contains("requires tool 'search_code'"), "{}", err);
+    }
+}
@@ -3,10 +3,12 @@
 //! The coding agent specializes in generating, reviewing, and refactoring code.
 //! It integrates with Rig for LLM calls and supports local Ollama models.
 
+use crate::tools::AgentTools;
+use std::sync::Arc;
 use crate::agents::base::TypedAgent;
 use ai_agent_common::{AgentType, QualityStrategy};
 use async_trait::async_trait;
-use async_openai::{Client, config::OpenAIConfig};
+use ai_agent_common::llm::LlmProvider;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, instrument};
@@ -14,6 +16,7 @@ use tracing::{debug, info, instrument};
 /// Evaluator structured output
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct EvaluatorOutput {
+    /// Quality of the evaluated output (0.0 - 1.0)
     pub score: f32,
     #[serde(default)]
     pub issues: Vec<String>,
@@ -26,10 +29,12 @@ pub struct EvaluatorOutput {
 pub struct EvaluatorAgent {
     id: String,
     model: String,
-    client: Client<OpenAIConfig>,
+    provider: Arc<dyn LlmProvider>,
     system_prompt: String,
     temperature: f32,
     max_tokens: usize,
+    enable_streaming: bool,
+    tools: Arc<AgentTools>,
     quality_strategy: QualityStrategy
 }
 
@@ -41,22 +46,21 @@ impl EvaluatorAgent {
         system_prompt: String,
         temperature: f32,
         max_tokens: usize,
+        enable_streaming: bool,
         quality_strategy: QualityStrategy,
-        ollama_base_url: Option<&str>,
+        tools: Arc<AgentTools>,
+        provider: Arc<dyn LlmProvider>,
     ) -> Self {
-        let base_url = ollama_base_url.unwrap_or("http://localhost:11434/v1");
-        let config = OpenAIConfig::new()
-            .with_api_key("ollama") // Required but unused
-            .with_api_base(base_url);
-        let client = Client::with_config(config);
         Self {
             id,
-            client,
+            provider,
             system_prompt,
             quality_strategy,
             model,
             temperature: temperature.clamp(0.0, 2.0),
             max_tokens,
+            enable_streaming,
+            tools,
         }
     }
 }
@@ -68,7 +72,9 @@ impl TypedAgent for EvaluatorAgent {
     fn system_prompt(&self) -> &str { &self.system_prompt }
     fn model(&self) -> &str { &self.model }
     fn temperature(&self) -> f32 { self.temperature }
-    fn client(&self) -> &Client<OpenAIConfig> { &self.client }
+    fn provider(&self) -> &dyn LlmProvider { self.provider.as_ref() }
+    fn enable_streaming(&self) -> bool { self.enable_streaming }
+    fn tools(&self) -> &AgentTools { &self.tools }
     type Output = EvaluatorOutput;
 
     /// Define evaluator workflow steps with file reading logic
@@ -83,14 +89,13 @@ impl TypedAgent for EvaluatorAgent {
                 if let Some(tool_array) = tool_executions.as_array() {
                     for tool_execution in tool_array {
                         if let Some(tool_name) = tool_execution.get("tool_name").and_then(|v| v.as_str()) {
-                            if tool_name == "filesystem" {
-                                if let Some(params) = tool_execution.get("parameters") {
-                                    if let Some(command) = params.get("command").and_then(|v| v.as_str()) {
-                                        if command == "write" {
-                                            if let Some(file_path) = params.get("path").and_then(|v| v.as_str()) {
-                                                files_to_evaluate.push(file_path.to_string());
-                                            }
-                                        }
+                            if tool_name == "write_file" || tool_name == "edit_file" {
+                                // Arguments are the raw JSON string the model sent
+                                let arguments = tool_execution.get("arguments").and_then(|v| v.as_str())
+                                    .and_then(|arguments| serde_json::from_str::<serde_json::Value>(arguments).ok());
+                                if let Some(file_path) = arguments.as_ref().and_then(|a| a.get("path")).and_then(|v| v.as_str()) {
+                                    if !files_to_evaluate.iter().any(|path| path == file_path) {
+                                        files_to_evaluate.push(file_path.to_string());
                                     }
                                 }
                             }
@@ -100,10 +105,19 @@ impl TypedAgent for EvaluatorAgent {
             }
         }
 
-        // Build file contents for evaluation
+        // Build file contents for evaluation; shadow executions only wrote to the overlay
+        let root = context.project_scope.as_ref().map(|scope| scope.root.as_str()).unwrap_or_default();
+        let read_file = |file_path: &str| match context.shadow.as_deref() {
+            Some(shadow) => {
+                let path = std::path::Path::new(file_path);
+                let relative = path.strip_prefix(root).or_else(|_| path.strip_prefix(shadow.root())).unwrap_or(path);
+                shadow.read_to_string_blocking(relative)
+            }
+            None => std::fs::read_to_string(std::path::Path::new(root).join(file_path)),
+        };
         let mut file_contents_param = HashMap::new();
         for file_path in files_to_evaluate {
-            match std::fs::read_to_string(&file_path) {
+            match read_file(&file_path) {
                 Ok(content) => {
                     file_contents_param.insert(file_path.clone(), serde_json::Value::String(content));
                 }
@@ -132,8 +146,9 @@ impl TypedAgent for EvaluatorAgent {
             ),
             execution_mode: StepExecutionMode::OneShot, // Evaluator doesn't need tools, just analyzes
             required_tools: vec![], // No tools needed for evaluation
+            optional_tools: vec![],
             parameters: step_parameters,
-            formatted: false,
+            formatted: true, // Scores drive the quality feedback loop
         }]
     }
 }
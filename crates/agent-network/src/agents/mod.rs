@@ -4,22 +4,29 @@
 
 pub mod base;
 pub mod coding;
+pub mod confidence;
+pub mod configurable;
 pub mod evaluator;
 pub mod planning;
 pub mod pool;
+pub mod steps;
 pub mod writing;
 
 pub use base::{Agent, AgentContext, ConversationMessage};
 pub use coding::CodingAgent;
+pub use configurable::ConfigurableAgent;
 pub use evaluator::EvaluatorAgent;
 pub use planning::{PlanningAgent};
 pub use pool::{AgentPool, PoolStatistics};
+pub use steps::StepOverrides;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use anyhow::{anyhow, Context, Result};
 pub use writing::WritingAgent;
 
+use ai_agent_common::TokenUsage;
 use crate::tools::ToolExecution;
+use confidence::{ConfidenceSignals, PRIOR_CONFIDENCE};
 
 /// Result from agent execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,11 +40,15 @@ pub struct AgentResult {
     /// Confidence score (0.0 - 1.0)
     pub confidence: f32,
 
+    /// Signals the confidence score was estimated from
+    #[serde(default)]
+    pub confidence_signals: ConfidenceSignals,
+
     /// Whether human review is needed
     pub requires_hitl: bool,
 
     /// Tokens used in execution
-    pub tokens_used: Option<usize>,
+    pub tokens_used: Option<TokenUsage>,
 
     /// Reasoning or explanation
     pub reasoning: Option<String>,
@@ -60,7 +71,8 @@ impl AgentResult {
         Ok(Self {
             agent_id: agent_id.to_string(),
             output,
-            confidence: 0.8,
+            confidence: PRIOR_CONFIDENCE,
+            confidence_signals: ConfidenceSignals::default(),
             requires_hitl: false,
             tokens_used: None,
             reasoning: None,
@@ -74,7 +86,8 @@ impl AgentResult {
         Ok(Self {
             agent_id: agent_id.to_string(),
             output,
-            confidence: 0.8,
+            confidence: PRIOR_CONFIDENCE,
+            confidence_signals: ConfidenceSignals::default(),
             requires_hitl: false,
             tokens_used: None,
             reasoning: None,
@@ -100,6 +113,13 @@ impl AgentResult {
         self
     }
 
+    /// Set the confidence signals, re-estimating the confidence score from them
+    pub fn with_confidence_signals(mut self, signals: ConfidenceSignals) -> Self {
+        self.confidence = signals.confidence();
+        self.confidence_signals = signals;
+        self
+    }
+
     /// Mark as requiring HITL
     pub fn requiring_hitl(mut self) -> Self {
         self.requires_hitl = true;
@@ -107,7 +127,7 @@ impl AgentResult {
     }
 
     /// Set tokens used
-    pub fn with_tokens(mut self, tokens: usize) -> Self {
+    pub fn with_tokens(mut self, tokens: TokenUsage) -> Self {
         self.tokens_used = Some(tokens);
         self
     }
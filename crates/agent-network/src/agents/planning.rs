@@ -3,10 +3,12 @@
 //! The coding agent specializes in generating, reviewing, and refactoring code.
 //! It integrates with Rig for LLM calls and supports local Ollama models.
 
+use crate::tools::AgentTools;
+use std::sync::Arc;
 use crate::{ agents::{base::TypedAgent, AgentContext}, orchestrator::AgentCapability};
 use ai_agent_common::{AgentType, ErrorRecoveryStrategy};
 use async_trait::async_trait;
-use async_openai::{Client, config::OpenAIConfig};
+use ai_agent_common::llm::LlmProvider;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, instrument};
@@ -38,6 +40,10 @@ pub struct SubtaskSpec {
     /// Agent type to execute this
     pub agent_type: AgentType,
 
+    /// Specific agent (from available_agents) to execute this; the first of the type when unset
+    #[serde(default)]
+    pub agent_id: Option<String>,
+
     /// IDs of tasks that must complete first
     pub dependencies: Vec<String>,
 
@@ -50,10 +56,12 @@ pub struct SubtaskSpec {
 pub struct PlanningAgent {
     id: String,
     model: String,
-    client: Client<OpenAIConfig>,
+    provider: Arc<dyn LlmProvider>,
     system_prompt: String,
     temperature: f32,
     max_tokens: usize,
+    enable_streaming: bool,
+    tools: Arc<AgentTools>,
 }
 
 impl PlanningAgent {
@@ -64,21 +72,20 @@ impl PlanningAgent {
         system_prompt: String,
         temperature: f32,
         max_tokens: usize,
-        ollama_base_url: Option<&str>,
+        enable_streaming: bool,
+        tools: Arc<AgentTools>,
+        provider: Arc<dyn LlmProvider>,
     ) -> Self {
-        let base_url = ollama_base_url.unwrap_or("http://localhost:11434/v1");
-        let config = OpenAIConfig::new()
-            .with_api_key("ollama") // Required but unused
-            .with_api_base(base_url);
-        let client = Client::with_config(config);
         let system_prompt = PlanningAgent::build_system_prompt(&system_prompt);
         Self {
             id,
-            client,
+            provider,
             system_prompt,
             model,
             temperature: temperature.clamp(0.0, 2.0),
             max_tokens,
+            enable_streaming,
+            tools,
         }
     }
 
@@ -100,6 +107,7 @@ impl PlanningAgent {
             1. The entries of a subtasks dependencies MUST match actual subtask ids and agent_type of the task you're depending on.
             2. Use the exact agent types from the available_agents list provided to you.
             3. If task 'task-2' depends on task 'task-1', write: 'dependencies': ['task-1']
+            4. If several available agents share an agent type, set 'agent_id' to the one whose capabilities fit the subtask best.
 
             ## Examples by Complexity:
 
@@ -129,7 +137,9 @@ impl TypedAgent for PlanningAgent {
     fn system_prompt(&self) -> &str { &self.system_prompt }
     fn model(&self) -> &str { &self.model }
     fn temperature(&self) -> f32 { self.temperature }
-    fn client(&self) -> &Client<OpenAIConfig> { &self.client }
+    fn provider(&self) -> &dyn LlmProvider { self.provider.as_ref() }
+    fn enable_streaming(&self) -> bool { self.enable_streaming }
+    fn tools(&self) -> &AgentTools { &self.tools }
     type Output = TaskDecompositionPlan;
 
     /// Define planning workflow steps
@@ -154,6 +164,7 @@ impl TypedAgent for PlanningAgent {
                 description: "Generate a structured task decomposition plan based on complexity analysis and project understanding".to_string(),
                 execution_mode: StepExecutionMode::OneShot, // Needs filesystem tool
                 required_tools: vec![],
+                optional_tools: vec![],
                 parameters: HashMap::new(),
                 formatted: true,
             }
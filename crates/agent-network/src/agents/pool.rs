@@ -4,14 +4,18 @@
 //! Provides agent lookup by ID and type.
 
 use crate::agents::{
-    coding::CodingAgent, evaluator::EvaluatorAgent, planning::PlanningAgent,
-    writing::WritingAgent, Agent,
+    base::TypedAgent, coding::CodingAgent, configurable::ConfigurableAgent,
+    evaluator::EvaluatorAgent, planning::PlanningAgent, steps::StepOverrides,
+    writing::WritingAgent, Agent, AgentContext,
 };
 use crate::error::{AgentNetworkError, AgentNetworkResult};
+use crate::hitl::ToolPolicy;
+use crate::tools::{mcp, AgentTools, ToolRegistry};
 use std::collections::HashMap;
 use std::sync::Arc;
+use ai_agent_common::llm::{self, LlmProvider};
 use ai_agent_common::{AgentConfig, AgentType, QualityStrategy, SystemConfig};
-use tracing::{debug, info, instrument};
+use tracing::{debug, info, instrument, warn};
 
 /// Agent pool managing all available agents
 pub struct AgentPool {
@@ -24,45 +28,92 @@ pub struct AgentPool {
 
 impl AgentPool {
     /// Create a new agent pool from configurations
+    ///
+    /// Each agent talks to the LLM provider resolved from its own and the `[llm]` settings.
     pub async fn new(config: &SystemConfig) -> AgentNetworkResult<Self> {
+        Self::build(config, |agent_config| {
+            llm::provider_from_config(&config.llm_config(Some(agent_config)))
+                .map_err(|e| AgentNetworkError::config(format!("Agent {}: {:#}", agent_config.id, e)))
+        }).await
+    }
+
+    /// Create a pool whose agents all share one LLM provider (e.g. a `MockProvider` in tests)
+    pub async fn with_provider(config: &SystemConfig, provider: Arc<dyn LlmProvider>) -> AgentNetworkResult<Self> {
+        Self::build(config, |_| Ok(provider.clone())).await
+    }
+
+    async fn build(
+        config: &SystemConfig,
+        provider_for: impl Fn(&AgentConfig) -> AgentNetworkResult<Arc<dyn LlmProvider>>,
+    ) -> AgentNetworkResult<Self> {
         let mut agents: HashMap<String, Arc<dyn Agent>> = HashMap::new();
         let mut agents_by_type: HashMap<AgentType, Vec<String>> = HashMap::new();
-        let ollama_url = config.embedding.ollama_host.clone() +":" + &config.embedding.ollama_port.to_string() + "/v1";
+        let registry = Arc::new(ToolRegistry::from_config(config));
+        let policy = Arc::new(ToolPolicy::from_config(&config.agent_network.hitl)
+            .map_err(|e| AgentNetworkError::config(format!("{:#}", e)))?);
 
         for config in &config.agent_network.agents {
             debug!("Initializing agent: {} ({})", config.id, config.agent_type);
 
+            let mcp_tools = mcp::connect_servers(&config.mcp_servers).await;
+            let tools = Arc::new(AgentTools::from_agent_config(registry.clone(), config)
+                .map_err(|e| AgentNetworkError::config(format!("Agent {}: {}", config.id, e)))?
+                .with_external_tools(mcp_tools)
+                .with_policy(policy.clone()));
+            let provider = provider_for(config)?;
+
             let agent: Arc<dyn Agent> = match config.agent_type {
-                AgentType::Coding => Arc::new(CodingAgent::new(
+                _ if config.configurable => Arc::new(ConfigurableAgent::new(
+                    config,
+                    tools.clone(),
+                    provider,
+                )),
+                AgentType::Coding => Self::with_step_overrides(CodingAgent::new(
                     config.id.clone(),
                     config.model.clone(),
                     config.system_prompt.clone(),
                     config.temperature,
                     config.max_tokens,
-                    Some(&ollama_url),
-                )),
-                AgentType::Planning => Arc::new(PlanningAgent::new(
+                    config.enable_streaming,
+                    tools.clone(),
+                    provider,
+                ), config)?,
+                AgentType::Planning => Self::with_step_overrides(PlanningAgent::new(
                     config.id.clone(),
                     config.model.clone(),
                     config.system_prompt.clone(),
                     config.temperature,
                     config.max_tokens,
-                    Some(&ollama_url),
-                )),
+                    config.enable_streaming,
+                    tools.clone(),
+                    provider,
+                ), config)?,
+                AgentType::Writing => Self::with_step_overrides(WritingAgent::new(
+                    config.id.clone(),
+                    config.model.clone(),
+                    config.system_prompt.clone(),
+                    config.temperature,
+                    config.max_tokens,
+                    config.enable_streaming,
+                    tools.clone(),
+                    provider,
+                ), config)?,
                 AgentType::Evaluator => {
                     let quality_strategy = config
                         .quality_strategy
                         .unwrap_or(QualityStrategy::OnlyForCritical);
 
-                    Arc::new(EvaluatorAgent::new(
+                    Self::with_step_overrides(EvaluatorAgent::new(
                         config.id.clone(),
                         config.model.clone(),
                         config.system_prompt.clone(),
                         config.temperature,
                         config.max_tokens,
+                        config.enable_streaming,
                         quality_strategy,
-                        Some(&ollama_url),
-                    ))
+                        tools.clone(),
+                        provider,
+                    ), config)?
                 }
                 _ => {
                     return Err(AgentNetworkError::config(format!(
@@ -72,6 +123,8 @@ impl AgentPool {
                 }
             };
 
+            Self::check_step_tools(agent.as_ref(), &tools)?;
+
             agents.insert(config.id.clone(), agent);
             agents_by_type
                 .entry(config.agent_type.clone())
@@ -93,6 +146,39 @@ impl AgentPool {
         })
     }
 
+    /// Wrap a built-in agent so its workflow follows the steps configured for it
+    fn with_step_overrides<A: TypedAgent + 'static>(agent: A, config: &AgentConfig) -> AgentNetworkResult<Arc<dyn Agent>> {
+        if config.steps.is_empty() {
+            return Ok(Arc::new(agent));
+        }
+        let probe = AgentContext::new(String::new(), String::new(), None);
+        let agent = StepOverrides::new(agent, config.steps.clone(), &probe)
+            .map_err(|e| AgentNetworkError::config(format!("{:#}", e)))?;
+        Ok(Arc::new(agent))
+    }
+
+    /// Fail if one of the agent's workflow steps requires a tool the agent is not configured
+    /// with; missing optional tools are only logged
+    fn check_step_tools(agent: &dyn Agent, tools: &AgentTools) -> AgentNetworkResult<()> {
+        let probe = AgentContext::new(String::new(), String::new(), None);
+        for step in agent.define_workflow_steps(&probe) {
+            if let Some(missing) = step.required_tools.iter().find(|t| !tools.contains(t)) {
+                return Err(AgentNetworkError::config(format!(
+                    "Agent {} step '{}' requires tool '{}', which is missing from its available_tools",
+                    agent.id(), step.id, missing
+                )));
+            }
+            let unavailable: Vec<&str> = step.optional_tools.iter()
+                .filter(|t| !tools.contains(t))
+                .map(String::as_str)
+                .collect();
+            if !unavailable.is_empty() {
+                warn!("Agent {} step '{}' runs without optional tools it is not configured with: {}", agent.id(), step.id, unavailable.join(", "));
+            }
+        }
+        Ok(())
+    }
+
     /// Get agent by ID
     pub fn get_agent(&self, agent_id: &str) -> Option<Arc<dyn Agent>> {
         self.agents.get(agent_id).cloned()
@@ -124,9 +210,16 @@ impl AgentPool {
         self.agents.len()
     }
 
-    /// List all agent IDs
+    /// List all agent IDs, sorted so prompts listing the agents are reproducible
     pub fn list_agent_ids(&self) -> Vec<String> {
-        self.agents.keys().cloned().collect()
+        let mut ids: Vec<String> = self.agents.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Tools an agent is configured with
+    pub fn tool_names(&self, agent_id: &str) -> Option<Vec<String>> {
+        self.agents.get(agent_id).map(|agent| agent.tool_names())
     }
 
     /// Check if agent exists
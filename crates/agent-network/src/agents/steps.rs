@@ -0,0 +1,167 @@
+//! Config-defined workflow step overrides for built-in agents
+//!
+//! `[[agent_network.agents.steps]]` tables replace a built-in step with the same id or
+//! insert a new one (before/after a built-in step, or at the end). Step outputs keep flowing
+//! to later steps through `WorkflowState::shared_context`.
+
+use crate::agents::base::{TypedAgent, WorkflowStep};
+use crate::agents::AgentContext;
+use crate::tools::AgentTools;
+use ai_agent_common::llm::LlmProvider;
+use ai_agent_common::{AgentType, WorkflowStepConfig};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use tracing::warn;
+
+/// Apply config-defined steps to an agent's built-in workflow
+pub fn merge_workflow_steps(mut steps: Vec<WorkflowStep>, overrides: &[WorkflowStepConfig]) -> Result<Vec<WorkflowStep>> {
+    for config in overrides {
+        let mut step = WorkflowStep::from(config);
+        if let Some(existing) = steps.iter_mut().find(|s| s.id == config.id) {
+            // Callers of a built-in step may rely on its structured output
+            step.formatted = config.formatted.unwrap_or(existing.formatted);
+            *existing = step;
+            continue;
+        }
+
+        let anchor = match (&config.before, &config.after) {
+            (Some(id), _) => Some((id, 0)),
+            (None, Some(id)) => Some((id, 1)),
+            (None, None) => None,
+        };
+        match anchor {
+            Some((anchor_id, offset)) => {
+                let position = steps.iter().position(|s| s.id == *anchor_id)
+                    .ok_or_else(|| anyhow!("Step '{}' is positioned relative to unknown step '{}'", config.id, anchor_id))?;
+                steps.insert(position + offset, step);
+            }
+            None => steps.push(step),
+        }
+    }
+    Ok(steps)
+}
+
+/// A built-in agent whose workflow steps are overridden or extended from config
+pub struct StepOverrides<A> {
+    agent: A,
+    overrides: Vec<WorkflowStepConfig>,
+}
+
+impl<A: TypedAgent> StepOverrides<A> {
+    /// Wrap an agent, failing if a step is anchored to a step the agent does not define
+    pub fn new(agent: A, overrides: Vec<WorkflowStepConfig>, probe: &AgentContext) -> Result<Self> {
+        merge_workflow_steps(agent.define_workflow_steps(probe), &overrides)
+            .map_err(|e| anyhow!("Agent {}: {}", agent.id(), e))?;
+        Ok(Self { agent, overrides })
+    }
+}
+
+#[async_trait]
+impl<A: TypedAgent> TypedAgent for StepOverrides<A> {
+    fn id(&self) -> &str { self.agent.id() }
+    fn agent_type(&self) -> AgentType { self.agent.agent_type() }
+    fn system_prompt(&self) -> &str { self.agent.system_prompt() }
+    fn model(&self) -> &str { self.agent.model() }
+    fn temperature(&self) -> f32 { self.agent.temperature() }
+    fn provider(&self) -> &dyn LlmProvider { self.agent.provider() }
+    fn enable_streaming(&self) -> bool { self.agent.enable_streaming() }
+    fn tools(&self) -> &AgentTools { self.agent.tools() }
+    type Output = A::Output;
+
+    fn define_workflow_steps(&self, context: &AgentContext) -> Vec<WorkflowStep> {
+        let steps = self.agent.define_workflow_steps(context);
+        // Built-in steps may depend on the context; fall back to them if an anchor went missing
+        merge_workflow_steps(steps.clone(), &self.overrides).unwrap_or_else(|e| {
+            warn!("Ignoring step overrides of agent {}: {}", self.id(), e);
+            steps
+        })
+    }
+
+    fn output_schema(&self) -> Value {
+        self.agent.output_schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::base::StepExecutionMode;
+    use std::collections::HashMap;
+
+    fn step(id: &str) -> WorkflowStep {
+        WorkflowStep {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            execution_mode: StepExecutionMode::OneShot,
+            required_tools: vec![],
+            optional_tools: vec![],
+            formatted: true,
+            parameters: HashMap::new(),
+        }
+    }
+
+    fn overrides(toml_steps: &str) -> Vec<WorkflowStepConfig> {
+        #[derive(serde::Deserialize)]
+        struct Steps { steps: Vec<WorkflowStepConfig> }
+        toml::from_str::<Steps>(toml_steps).unwrap().steps
+    }
+
+    #[test]
+    fn test_steps_are_replaced_inserted_and_appended() {
+        let configs = overrides(r#"
+            [[steps]]
+            id = "analyze_codebase"
+            description = "Read the existing code first."
+            mode = "ReAct"
+            required_tools = ["read_file"]
+            before = "implement_code"
+
+            [[steps]]
+            id = "implement_code"
+            description = "Implement the change."
+            mode = "ReAct"
+            max_iterations = 3
+
+            [[steps]]
+            id = "summarize"
+            description = "Summarize the change."
+        "#);
+
+        let steps = merge_workflow_steps(vec![step("implement_code")], &configs).unwrap();
+        let ids: Vec<_> = steps.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["analyze_codebase", "implement_code", "summarize"]);
+        assert_eq!(steps[0].required_tools, vec!["read_file".to_string()]);
+        assert_eq!(steps[1].description, "Implement the change.");
+        assert!(matches!(steps[1].execution_mode, StepExecutionMode::ReAct { max_iterations: Some(3) }));
+        assert!(steps[1].formatted, "replaced steps inherit formatted");
+        assert!(!steps[0].formatted && !steps[2].formatted);
+    }
+
+    #[test]
+    fn test_replaced_step_can_turn_formatting_off() {
+        let configs = overrides(r#"
+            [[steps]]
+            id = "generate_plan"
+            description = "Plan in prose."
+            formatted = false
+        "#);
+
+        let steps = merge_workflow_steps(vec![step("generate_plan")], &configs).unwrap();
+        assert!(!steps[0].formatted);
+    }
+
+    #[test]
+    fn test_unknown_anchor_is_rejected() {
+        let configs = overrides(r#"
+            [[steps]]
+            id = "review"
+            description = "Review the plan."
+            after = "missing_step"
+        "#);
+
+        let err = merge_workflow_steps(vec![step("generate_plan")], &configs).unwrap_err();
+        assert!(err.to_string().contains("unknown step 'missing_step'"));
+    }
+}
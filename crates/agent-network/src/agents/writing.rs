@@ -2,10 +2,12 @@
 //!
 //! Generates documentation, commit messages, and communication.
 
+use crate::tools::AgentTools;
+use std::sync::Arc;
 use crate::agents::base::TypedAgent;
 use ai_agent_common::AgentType;
 use async_trait::async_trait;
-use async_openai::{Client, config::OpenAIConfig};
+use ai_agent_common::llm::LlmProvider;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -17,6 +19,9 @@ pub struct WritingOutput {
     pub word_count: usize,
     #[serde(default)]
     pub topics: Vec<String>,
+    /// Self-assessed confidence in the result (0.0 - 1.0)
+    #[serde(default)]
+    pub confidence: Option<f32>,
 }
 
 pub struct WritingAgent {
@@ -25,31 +30,32 @@ pub struct WritingAgent {
     system_prompt: String,
     temperature: f32,
     max_tokens: usize,
-    client: Client<OpenAIConfig>,
+    enable_streaming: bool,
+    tools: Arc<AgentTools>,
+    provider: Arc<dyn LlmProvider>,
 }
 
 impl WritingAgent {
-    /// Create a new coding agent
+    /// Create a new writing agent
     pub fn new(
         id: String,
         model: String,
         system_prompt: String,
         temperature: f32,
         max_tokens: usize,
-        ollama_base_url: Option<&str>,
+        enable_streaming: bool,
+        tools: Arc<AgentTools>,
+        provider: Arc<dyn LlmProvider>,
     ) -> Self {
-        let base_url = ollama_base_url.unwrap_or("http://localhost:11434/v1");
-        let config = OpenAIConfig::new()
-            .with_api_key("ollama") // Required but unused
-            .with_api_base(base_url);
-        let client = Client::with_config(config);
         Self {
             id,
-            client,
+            provider,
             system_prompt,
             model,
             temperature: temperature.clamp(0.0, 2.0),
             max_tokens,
+            enable_streaming,
+            tools,
         }
     }
 }
@@ -61,7 +67,9 @@ impl TypedAgent for WritingAgent {
     fn system_prompt(&self) -> &str { &self.system_prompt }
     fn model(&self) -> &str { &self.model }
     fn temperature(&self) -> f32 { self.temperature }
-    fn client(&self) -> &Client<OpenAIConfig> { &self.client }
+    fn provider(&self) -> &dyn LlmProvider { self.provider.as_ref() }
+    fn enable_streaming(&self) -> bool { self.enable_streaming }
+    fn tools(&self) -> &AgentTools { &self.tools }
     type Output = WritingOutput;
 
     /// Define writing workflow steps
@@ -76,6 +84,7 @@ impl TypedAgent for WritingAgent {
             description: "Generate written content based on the provided requirements and context".to_string(),
             execution_mode: StepExecutionMode::OneShot, // Writing is typically pure LLM generation
             required_tools: vec![], // No tools needed for content generation
+            optional_tools: vec![],
             parameters: HashMap::new(),
             formatted: false,
         }]
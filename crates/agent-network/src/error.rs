@@ -119,6 +119,12 @@ pub enum AgentNetworkError {
     #[error("Timeout: {operation}")]
     Timeout { operation: String },
 
+    #[error("Token budget exceeded for {scope}: {used} of {limit} tokens used")]
+    TokenBudgetExceeded { scope: String, used: usize, limit: usize },
+
+    #[error("Delegation refused: {0}")]
+    DelegationRefused(String),
+
     #[error("Other error: {0}")]
     Other(#[from] anyhow::Error),
 }
@@ -10,7 +10,7 @@ use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use ai_agent_common::llm::EmbeddingClient;
+use ai_agent_common::llm::{EmbeddingClient, LlmProvider};
 use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
 use uuid::Uuid;
 use anyhow::{Context, Result};
@@ -23,11 +23,14 @@ use crate::filelocks::FileLockManager;
 use crate::hitl::AuditLogger;
 use crate::orchestrator::Orchestrator;
 use crate::sharedcontext::SharedContext;
+use crate::journal::{FileJournal, RestoreReport};
+use crate::token_budget::TokenBudgetManager;
+use crate::shadow::ShadowWorkspace;
 use ai_agent_common::{
     ConversationId, ProjectScope, SystemConfig, StatusEvent, EventSource, EventType,
 };
-use ai_agent_history::HistoryManager;
 use ai_agent_rag::SmartMultiSourceRag;
+use ai_agent_storage::PostgresClient;
 
 /// Bidirectional event channel for communication between server components and WebSocket clients
 /// This is the CORE communication primitive - all events flow through this channel
@@ -275,9 +278,6 @@ pub struct ExecutionManager {
     /// RAG system
     rag: Arc<SmartMultiSourceRag>,
 
-    /// History manager
-    history_manager: Arc<RwLock<HistoryManager>>,
-
     /// System config
     config: Arc<SystemConfig>,
 
@@ -291,16 +291,30 @@ pub struct ExecutionManager {
 
     audit_logger: Arc<AuditLogger>,
 
-    embedding_client: Arc<EmbeddingClient>,
+    /// Postgres client persisting file journals for undo
+    postgres: Arc<PostgresClient>,
 }
 
 impl ExecutionManager {
     #[instrument(name = "execution_manager_init", skip(config), fields(agents = config.agent_network.agents.len()))]
     pub async fn new(config: SystemConfig) -> Result<Self> {
+        let agent_pool = AgentPool::new(&config).await?;
+        Self::with_agent_pool(config, agent_pool).await
+    }
+
+    /// Create a manager whose agents all share one LLM provider (e.g. a `MockProvider` or a
+    /// replaying cassette in tests)
+    #[instrument(name = "execution_manager_init", skip(config, provider), fields(agents = config.agent_network.agents.len()))]
+    pub async fn with_provider(config: SystemConfig, provider: Arc<dyn LlmProvider>) -> Result<Self> {
+        let agent_pool = AgentPool::with_provider(&config, provider).await?;
+        Self::with_agent_pool(config, agent_pool).await
+    }
+
+    async fn with_agent_pool(config: SystemConfig, agent_pool: AgentPool) -> Result<Self> {
         info!("Initializing ExecutionManager");
 
         // Initialize all components
-        let agent_pool = Arc::new(AgentPool::new(&config).await?);
+        let agent_pool = Arc::new(agent_pool);
         let shared_context = Arc::new(RwLock::new(SharedContext::new()));
         let coordination_manager = Arc::new(CoordinationManager::new());
         let file_lock_manager = Arc::new(FileLockManager::new(30));
@@ -308,16 +322,15 @@ impl ExecutionManager {
         // HITL setup
         let audit_logger = Arc::new(AuditLogger);
 
-        // RAG and history setup
+        // RAG setup
         let embedding_client = Arc::new(EmbeddingClient::new(
             &config.embedding.dense_model,
             config.embedding.vector_size
         )?);
 
-        let rag = SmartMultiSourceRag::new(&config, embedding_client.clone()).await?;
-        let history_manager = Arc::new(RwLock::new(
-            HistoryManager::new(&config.storage.postgres_url, &config.rag).await?
-        ));
+        let rag = SmartMultiSourceRag::new(&config, embedding_client).await?;
+
+        let postgres = PostgresClient::shared(&config.storage.postgres_url).await?;
 
         let last_cleanup = Arc::new(Mutex::new(Instant::now()));
 
@@ -332,13 +345,22 @@ impl ExecutionManager {
             file_lock_manager,
             audit_logger,
             rag,
-            history_manager,
-            embedding_client,
+            postgres,
             subscription_ttl: 500,
             last_cleanup,
         })
     }
 
+    /// Tools the given agent is configured with, as built by the tool registry
+    pub fn agent_tool_names(&self, agent_id: &str) -> Option<Vec<String>> {
+        self.agent_pool.tool_names(agent_id)
+    }
+
+    /// RAG pipeline shared by all executions
+    pub fn rag(&self) -> Arc<SmartMultiSourceRag> {
+        self.rag.clone()
+    }
+
     /// Create new subscription and return the subscription ID
     #[instrument(skip(self), fields(client_id = ?client_id))]
     pub async fn create_subscription(&self, client_id: Option<String>) -> Result<String> {
@@ -386,6 +408,11 @@ impl ExecutionManager {
         }
     }
 
+    /// Remove a subscription that is no longer needed (e.g., a finished MCP task)
+    pub async fn remove_subscription(&self, subscription_id: &str) {
+        self.subscriptions.write().await.remove(subscription_id);
+    }
+
     /// Get subscription info
     pub async fn get_subscription_info(&self, subscription_id: &str) -> Option<SubscriptionStatusInfo> {
         let subscriptions = self.subscriptions.read().await;
@@ -400,18 +427,41 @@ impl ExecutionManager {
     }
 
     /// Execute query asynchronously
-    /// This creates a background task that uses the subscription's bidirectional channel
+    /// This creates a background task that uses the subscription's bidirectional channel.
+    /// In shadow mode all file changes go to an overlay that is applied or discarded later.
     #[instrument(skip(self, query), fields(query_len = query.len()))]
     pub async fn execute_query(
         &self,
         query: &String,
         project_scope: ProjectScope,
         subscription_id: &String,
-    ) -> Result<()> {
+        shadow: bool,
+    ) -> Result<ConversationId> {
         // Get the bidirectional channel for this subscription
         let event_channel = self.get_channel(subscription_id).await?;
         let conversation_id = ConversationId::new();
 
+        let shadow = if shadow {
+            let workspace = ShadowWorkspace::create(
+                &self.config.agent_network.shadow.dir,
+                &conversation_id.to_string(),
+                std::path::Path::new(&project_scope.root),
+            )?;
+            Some(Arc::new(workspace))
+        } else {
+            None
+        };
+
+        // Journal file changes for rollback of failed attempts; only real-tree changes are
+        // persisted, a shadow execution is undone by discarding its overlay
+        let mut journal = FileJournal::new(conversation_id.to_string(), &project_scope.root)
+            .with_shadow(shadow.clone());
+        if shadow.is_none() {
+            journal = journal.with_store(self.postgres.clone());
+        }
+        let journal = Arc::new(journal);
+        let token_budget = Arc::new(TokenBudgetManager::from_config(&self.config.agent_network.token_budget));
+
         info!("🚀 Starting query execution for subscription {}", subscription_id);
 
         // Send execution started event
@@ -435,15 +485,13 @@ impl ExecutionManager {
         let coordination_manager_clone = self.coordination_manager.clone();
         let file_lock_manager_clone = self.file_lock_manager.clone();
         let audit_logger_clone = self.audit_logger.clone();
-        let rag_clone = self.rag.clone();
-        let history_manager_clone = self.history_manager.clone();
-        let embedding_client_clone = self.embedding_client.clone();
+        let started_conversation_id = conversation_id.clone();
 
         // Execute in background task
         tokio::spawn(async move {
             info!("🔄 Background task started for conversation {}", conversation_id);
 
-            match Orchestrator::execute_query(
+            let result = Orchestrator::execute_query(
                 &query_clone,
                 project_scope_clone,
                 conversation_id_clone,
@@ -454,18 +502,37 @@ impl ExecutionManager {
                 coordination_manager_clone,
                 file_lock_manager_clone,
                 audit_logger_clone,
-                rag_clone,
-                history_manager_clone,
-                embedding_client_clone
-            ).await {
+                shadow.clone(),
+                Some(journal),
+                Some(token_budget.clone()),
+            ).await;
+
+            // Report shadow changes before the terminal event, also for failed executions
+            if let Some(shadow) = shadow {
+                match Self::shadow_changes_event(&shadow).await {
+                    Ok(event) => {
+                        let _ = event_channel_clone.send(StatusEvent {
+                            id: conversation_id.to_string(),
+                            timestamp: Utc::now(),
+                            source: EventSource::Orchestrator,
+                            event,
+                        }).await;
+                    }
+                    Err(e) => warn!("Failed to collect shadow changes: {}", e),
+                }
+            }
+
+            match result {
                 Ok(result) => {
-                    info!("✅ Query execution completed successfully");
+                    let token_usage = token_budget.total();
+                    info!("✅ Query execution completed successfully ({} tokens)", token_usage.total());
                     let _ = event_channel_clone.send(StatusEvent {
                         id: conversation_id.to_string(),
                         timestamp: Utc::now(),
                         source: EventSource::Orchestrator,
                         event: EventType::ExecutionCompleted {
                             result: result.clone(),
+                            token_usage,
                         },
                     }).await;
                     Ok(result)
@@ -485,7 +552,59 @@ impl ExecutionManager {
             }
         });
 
-        Ok(())
+        Ok(started_conversation_id)
+    }
+
+    async fn shadow_changes_event(shadow: &ShadowWorkspace) -> Result<EventType> {
+        let files = shadow.changes().await?.into_iter()
+            .map(|change| change.path.to_string_lossy().to_string())
+            .collect();
+        let diff = shadow.store_diff().await?;
+        Ok(EventType::ShadowChangesReady { files, diff })
+    }
+
+    fn open_shadow(&self, conversation_id: &ConversationId) -> Result<ShadowWorkspace> {
+        ShadowWorkspace::open(&self.config.agent_network.shadow.dir, &conversation_id.to_string())
+    }
+
+    /// Revert every file change an execution made. Files changed since the execution wrote
+    /// them are reported as conflicts and kept. Must not be called while the execution is
+    /// still running.
+    pub async fn undo_execution(&self, conversation_id: &ConversationId) -> Result<RestoreReport> {
+        let report = if self.has_shadow(conversation_id) {
+            let shadow = self.open_shadow(conversation_id)?;
+            let restored = shadow.changes().await?.into_iter().map(|change| change.path).collect();
+            shadow.discard().await?;
+            RestoreReport { restored, conflicts: Vec::new() }
+        } else {
+            match FileJournal::load(self.postgres.clone(), &conversation_id.to_string()).await? {
+                Some(journal) => journal.undo().await?,
+                None => RestoreReport::default(),
+            }
+        };
+        info!("Undid execution {} ({} paths restored, {} conflicts)", conversation_id, report.restored.len(), report.conflicts.len());
+        Ok(report)
+    }
+
+    /// Whether a conversation has pending shadow changes
+    pub fn has_shadow(&self, conversation_id: &ConversationId) -> bool {
+        ShadowWorkspace::exists(&self.config.agent_network.shadow.dir, &conversation_id.to_string())
+    }
+
+    /// Current diff of a shadow execution against the project tree
+    pub async fn shadow_diff(&self, conversation_id: &ConversationId) -> Result<String> {
+        self.open_shadow(conversation_id)?.diff().await
+    }
+
+    /// Commit the changes of a shadow execution to the project tree
+    pub async fn apply_shadow(&self, conversation_id: &ConversationId) -> Result<Vec<String>> {
+        let applied = self.open_shadow(conversation_id)?.apply().await?;
+        Ok(applied.into_iter().map(|path| path.to_string_lossy().to_string()).collect())
+    }
+
+    /// Drop the changes of a shadow execution
+    pub async fn discard_shadow(&self, conversation_id: &ConversationId) -> Result<()> {
+        self.open_shadow(conversation_id)?.discard().await
     }
 
     /// Cleanup expired subscriptions
@@ -4,12 +4,14 @@
 
 pub mod assessor;
 pub mod audit;
+pub mod policy;
 
 use std::collections::HashMap;
 
 use ai_agent_common::{AgentType, RiskLevel};
 pub use assessor::*;
 pub use audit::*;
+pub use policy::{FileFacts, PolicyDecision, ToolCall, ToolPolicy};
 
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
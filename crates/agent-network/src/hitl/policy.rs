@@ -0,0 +1,392 @@
+//! Tool risk policy engine
+//!
+//! Classifies each tool call an agent wants to make into a `RiskLevel` and decides whether
+//! it needs human approval. Rules come from `[[agent_network.hitl.rules]]`; the first
+//! matching rule wins, otherwise the tool's own classification (git, run_command, ...) or
+//! the built-in defaults apply. Calls made while the agent's confidence is low (below 0.75,
+//! i.e. `RiskLevel::from_confidence` is High or worse) are escalated one level before the
+//! threshold is applied. Evaluation only depends on the `ToolCall` description, so rules can
+//! be tested without an LLM or a real project.
+
+use ai_agent_common::{AgentType, ApprovalRequirement, HitlConfig, RiskLevel, ToolRiskRule};
+use anyhow::{Context, Result};
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use serde_json::Value;
+use std::path::{Component, Path};
+
+/// What is known about the file targeted by a call's `path` argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileFacts {
+    pub exists: bool,
+    /// Larger of the existing file and the content being written, in bytes
+    pub size: u64,
+}
+
+/// A tool call to classify
+#[derive(Debug, Clone)]
+pub struct ToolCall<'a> {
+    pub tool_name: &'a str,
+    pub agent_id: &'a str,
+    pub agent_type: AgentType,
+    pub arguments: Value,
+    /// The `path` argument, relative to the project root and with `.`/`..` resolved
+    pub path: Option<String>,
+    /// The `path` argument leaves the project root; such calls are always Critical
+    pub outside_root: bool,
+    pub file: Option<FileFacts>,
+    /// The agent's estimated confidence when making the call (0.0 - 1.0)
+    pub confidence: Option<f32>,
+}
+
+impl<'a> ToolCall<'a> {
+    /// Describe a call from its raw JSON arguments; `path` is made relative to `root`
+    pub fn new(tool_name: &'a str, agent_id: &'a str, agent_type: AgentType, arguments: &str, root: &str) -> Self {
+        let arguments: Value = serde_json::from_str(arguments).unwrap_or(Value::Null);
+        let path = arguments.get("path").and_then(Value::as_str).map(|path| relative_path(path, root));
+        let outside_root = matches!(path, Some(None));
+        Self { tool_name, agent_id, agent_type, arguments, path: path.flatten(), outside_root, file: None, confidence: None }
+    }
+
+    pub fn with_file(mut self, file: FileFacts) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.confidence = Some(confidence);
+        self
+    }
+
+    /// Look up the targeted file under `root` to fill in `file`
+    pub fn probe_file(self, root: &str) -> Self {
+        let Some(path) = &self.path else { return self };
+        let existing = std::fs::metadata(Path::new(root).join(path)).ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len());
+        let written = self.arguments.get("content").and_then(Value::as_str).map(|content| content.len() as u64);
+
+        let file = FileFacts {
+            exists: existing.is_some(),
+            size: existing.unwrap_or(0).max(written.unwrap_or(0)),
+        };
+        self.with_file(file)
+    }
+}
+
+/// Path relative to `root` with `.` and `..` resolved lexically, the way the filesystem tools
+/// resolve it; `None` when it leaves the root
+fn relative_path(path: &str, root: &str) -> Option<String> {
+    let path = Path::new(path);
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut parts: Vec<&str> = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            // Absolute paths outside the root are taken relative to it, like the tools do
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Outcome of evaluating a call against the policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolicyDecision {
+    pub risk_level: RiskLevel,
+    pub needs_approval: bool,
+    /// Index of the configured rule that matched, if any
+    pub rule: Option<usize>,
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    rule: ToolRiskRule,
+    tools: Option<GlobSet>,
+    paths: Option<GlobSet>,
+    arguments: Vec<(String, GlobMatcher)>,
+}
+
+impl CompiledRule {
+    fn compile(rule: &ToolRiskRule) -> Result<Self> {
+        let tools = glob_set(&rule.tools, false)?;
+        // Path globs do not let `*` cross directories, so "src/*" and "src/**" differ
+        let paths = glob_set(&rule.paths, true)?;
+        let arguments = rule.arguments.iter()
+            .map(|(field, pattern)| Ok((field.clone(), glob(pattern, false)?.compile_matcher())))
+            .collect::<Result<_>>()?;
+        Ok(Self { rule: rule.clone(), tools, paths, arguments })
+    }
+
+    fn matches(&self, call: &ToolCall) -> bool {
+        let rule = &self.rule;
+        if self.tools.as_ref().is_some_and(|tools| !tools.is_match(call.tool_name)) {
+            return false;
+        }
+        if !rule.agent_ids.is_empty() && !rule.agent_ids.iter().any(|id| id == call.agent_id) {
+            return false;
+        }
+        if !rule.agent_types.is_empty() && !rule.agent_types.contains(&call.agent_type) {
+            return false;
+        }
+        if let Some(paths) = &self.paths {
+            match &call.path {
+                Some(path) if paths.is_match(path) => {}
+                _ => return false,
+            }
+        }
+        let arguments_match = self.arguments.iter().all(|(field, matcher)| match call.arguments.get(field) {
+            Some(Value::String(value)) => matcher.is_match(value),
+            Some(value) => matcher.is_match(value.to_string()),
+            None => false,
+        });
+        if !arguments_match {
+            return false;
+        }
+        if rule.min_file_size.is_some() || rule.max_file_size.is_some() || rule.new_file.is_some() {
+            let Some(file) = call.file else { return false };
+            if rule.min_file_size.is_some_and(|min| file.size < min)
+                || rule.max_file_size.is_some_and(|max| file.size > max)
+                || rule.new_file.is_some_and(|new_file| new_file == file.exists)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn glob(pattern: &str, literal_separator: bool) -> Result<Glob> {
+    GlobBuilder::new(pattern)
+        .literal_separator(literal_separator)
+        .build()
+        .with_context(|| format!("Invalid glob in HITL rule: {}", pattern))
+}
+
+fn glob_set(patterns: &[String], literal_separator: bool) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(glob(pattern, literal_separator)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Risk of tools that neither match a rule nor classify their own calls
+fn default_risk(tool_name: &str) -> RiskLevel {
+    match tool_name {
+        "delete_file" => RiskLevel::Critical,
+        "write_file" | "edit_file" | "create_directory" => RiskLevel::High,
+        "read_file" | "list_directory" => RiskLevel::Medium,
+        "file_exists" | "file_metadata" | "search_code" | "code_navigation" | "lsp" => RiskLevel::Low,
+        // Unknown tools default to high risk
+        _ => RiskLevel::High,
+    }
+}
+
+/// Configured tool risk policy
+#[derive(Debug)]
+pub struct ToolPolicy {
+    enabled: bool,
+    threshold: RiskLevel,
+    rules: Vec<CompiledRule>,
+}
+
+impl ToolPolicy {
+    pub fn from_config(config: &HitlConfig) -> Result<Self> {
+        let rules = config.rules.iter().enumerate()
+            .map(|(index, rule)| CompiledRule::compile(rule).with_context(|| format!("HITL rule #{}", index + 1)))
+            .collect::<Result<_>>()?;
+        Ok(Self { enabled: config.enabled, threshold: config.risk_threshold, rules })
+    }
+
+    /// Classify a call; `tool_risk` is the tool's own classification of it, if any
+    pub fn evaluate(&self, call: &ToolCall, tool_risk: Option<RiskLevel>) -> PolicyDecision {
+        // No path rule can vouch for a target outside the project
+        if call.outside_root {
+            return PolicyDecision {
+                risk_level: RiskLevel::Critical,
+                needs_approval: self.needs_approval(RiskLevel::Critical, ApprovalRequirement::Threshold),
+                rule: None,
+            };
+        }
+        if let Some((index, compiled)) = self.rules.iter().enumerate().find(|(_, rule)| rule.matches(call)) {
+            let risk_level = escalate_for_confidence(compiled.rule.risk_level, call.confidence);
+            return PolicyDecision {
+                risk_level,
+                needs_approval: self.needs_approval(risk_level, compiled.rule.approval),
+                rule: Some(index),
+            };
+        }
+
+        let risk_level = escalate_for_confidence(tool_risk.unwrap_or_else(|| default_risk(call.tool_name)), call.confidence);
+        PolicyDecision {
+            risk_level,
+            needs_approval: self.needs_approval(risk_level, ApprovalRequirement::Threshold),
+            rule: None,
+        }
+    }
+
+    fn needs_approval(&self, risk_level: RiskLevel, approval: ApprovalRequirement) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match approval {
+            ApprovalRequirement::Always => true,
+            ApprovalRequirement::Never => false,
+            ApprovalRequirement::Threshold => risk_level >= self.threshold,
+        }
+    }
+}
+
+/// Raise the risk of a call one level when the agent making it is unsure of itself
+fn escalate_for_confidence(risk_level: RiskLevel, confidence: Option<f32>) -> RiskLevel {
+    match confidence.map(RiskLevel::from_confidence) {
+        Some(RiskLevel::High | RiskLevel::Critical) => risk_level.escalated(),
+        _ => risk_level,
+    }
+}
+
+impl Default for ToolPolicy {
+    fn default() -> Self {
+        Self { enabled: true, threshold: RiskLevel::High, rules: Vec::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(rules: &str) -> ToolPolicy {
+        let config: HitlConfig = toml::from_str(rules).unwrap();
+        ToolPolicy::from_config(&config).unwrap()
+    }
+
+    fn write(path: &str) -> ToolCall<'static> {
+        let arguments = serde_json::json!({ "path": path, "content": "x" }).to_string();
+        ToolCall::new("write_file", "coding-1", AgentType::Coding, &arguments, "/project")
+    }
+
+    #[test]
+    fn test_path_rules_and_threshold() {
+        let policy = policy(r#"
+            risk_threshold = "High"
+
+            [[rules]]
+            tools = ["write_file", "edit_file"]
+            paths = [".github/**", "Cargo.toml"]
+            risk_level = "Critical"
+            approval = "Always"
+
+            [[rules]]
+            tools = ["write_file"]
+            paths = ["src/**"]
+            risk_level = "Medium"
+        "#);
+
+        let decision = policy.evaluate(&write("/project/.github/workflows/ci.yml"), None);
+        assert_eq!((decision.risk_level, decision.needs_approval, decision.rule), (RiskLevel::Critical, true, Some(0)));
+        assert_eq!(policy.evaluate(&write("Cargo.toml"), None).rule, Some(0));
+        assert_eq!(policy.evaluate(&write("crates/x/Cargo.toml"), None).rule, None);
+
+        let decision = policy.evaluate(&write("./src/agents/base.rs"), None);
+        assert_eq!((decision.risk_level, decision.needs_approval), (RiskLevel::Medium, false));
+
+        // `..` is resolved before matching, so "src/**" cannot be used to reach ".github/"
+        let decision = policy.evaluate(&write("src/../.github/workflows/ci.yml"), None);
+        assert_eq!((decision.risk_level, decision.rule), (RiskLevel::Critical, Some(0)));
+        assert_eq!(policy.evaluate(&write("/project/src/./lib/../main.rs"), None).rule, Some(1));
+        let decision = policy.evaluate(&write("src/../../etc/passwd"), None);
+        assert_eq!((decision.risk_level, decision.needs_approval, decision.rule), (RiskLevel::Critical, true, None));
+
+        // No rule matches: built-in default (High) reaches the threshold
+        let decision = policy.evaluate(&write("README.md"), None);
+        assert_eq!((decision.risk_level, decision.needs_approval, decision.rule), (RiskLevel::High, true, None));
+    }
+
+    #[test]
+    fn test_agent_argument_and_file_conditions() {
+        let policy = policy(r#"
+            risk_threshold = "Medium"
+
+            [[rules]]
+            agent_types = ["Coding"]
+            tools = ["write_file"]
+            new_file = true
+            max_file_size = 1000
+            risk_level = "Low"
+
+            [[rules]]
+            agent_ids = ["coding-1"]
+            tools = ["git"]
+            arguments = { command = "commit" }
+            risk_level = "High"
+            approval = "Never"
+        "#);
+
+        let small_new = FileFacts { exists: false, size: 10 };
+        assert_eq!(policy.evaluate(&write("src/new.rs").with_file(small_new), None).risk_level, RiskLevel::Low);
+        let existing = FileFacts { exists: true, size: 10 };
+        assert_eq!(policy.evaluate(&write("src/lib.rs").with_file(existing), None).rule, None);
+        let large_new = FileFacts { exists: false, size: 5000 };
+        assert_eq!(policy.evaluate(&write("src/big.rs").with_file(large_new), None).rule, None);
+        // File conditions never match when the file was not probed
+        assert_eq!(policy.evaluate(&write("src/new.rs"), None).rule, None);
+
+        let commit = ToolCall::new("git", "coding-1", AgentType::Coding, r#"{"command": "commit"}"#, "/project");
+        let decision = policy.evaluate(&commit, Some(RiskLevel::Critical));
+        assert_eq!((decision.risk_level, decision.needs_approval), (RiskLevel::High, false));
+
+        // The tool's own classification applies when no rule matches
+        let push = ToolCall::new("git", "coding-1", AgentType::Coding, r#"{"command": "push"}"#, "/project");
+        let decision = policy.evaluate(&push, Some(RiskLevel::Critical));
+        assert_eq!((decision.risk_level, decision.needs_approval), (RiskLevel::Critical, true));
+
+        let status = ToolCall::new("git", "coding-1", AgentType::Coding, r#"{"command": "status"}"#, "/project");
+        assert!(!policy.evaluate(&status, Some(RiskLevel::Low)).needs_approval);
+    }
+
+    #[test]
+    fn test_low_confidence_escalates_risk() {
+        let policy = policy(r#"
+            risk_threshold = "High"
+
+            [[rules]]
+            tools = ["write_file"]
+            paths = ["src/**"]
+            risk_level = "Medium"
+
+            [[rules]]
+            tools = ["write_file"]
+            paths = ["docs/**"]
+            risk_level = "Medium"
+            approval = "Never"
+        "#);
+
+        let confident = policy.evaluate(&write("src/lib.rs").with_confidence(0.95), None);
+        assert_eq!((confident.risk_level, confident.needs_approval), (RiskLevel::Medium, false));
+        let unsure = policy.evaluate(&write("src/lib.rs").with_confidence(0.5), None);
+        assert_eq!((unsure.risk_level, unsure.needs_approval), (RiskLevel::High, true));
+
+        // Explicit approval requirements still win
+        let docs = policy.evaluate(&write("docs/guide.md").with_confidence(0.5), None);
+        assert_eq!((docs.risk_level, docs.needs_approval), (RiskLevel::High, false));
+
+        let read = ToolCall::new("read_file", "coding-1", AgentType::Coding, r#"{"path": "src/lib.rs"}"#, "/project");
+        assert_eq!(policy.evaluate(&read.with_confidence(0.7), Some(RiskLevel::Low)).risk_level, RiskLevel::Medium);
+    }
+
+    #[test]
+    fn test_disabled_hitl_and_invalid_globs() {
+        let disabled = policy("enabled = false\n[[rules]]\nrisk_level = \"Critical\"\napproval = \"Always\"");
+        let decision = disabled.evaluate(&write("src/lib.rs"), None);
+        assert_eq!((decision.risk_level, decision.needs_approval), (RiskLevel::Critical, false));
+
+        let config: HitlConfig = toml::from_str("[[rules]]\npaths = [\"src/[\"]\nrisk_level = \"Low\"").unwrap();
+        assert!(ToolPolicy::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+//! File change journal for transactional task execution
+//!
+//! Before a filesystem tool mutates a path it records the path's pre-image (previous content,
+//! or the fact that it did not exist) for the current task attempt, and afterwards the
+//! post-image it left behind. A failed attempt is rolled back before the task is retried, and
+//! a whole execution can be undone later. A path is only restored while it still matches its
+//! post-image; paths changed since then are reported as conflicts and left alone. Journals of
+//! executions on the real tree are persisted next to the workflow checkpoints in Postgres;
+//! in shadow mode the journal only covers retries, since discarding the overlay undoes
+//! everything else.
+//!
+//! Only changes made through the filesystem tools are journaled; side effects of
+//! `run_command` and friends are not.
+
+use ai_agent_storage::{FileJournalRecord, PostgresClient};
+use anyhow::{anyhow, Result};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::fs;
+use tracing::{debug, info, warn};
+use crate::shadow::ShadowWorkspace;
+
+/// State of a journaled path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileState {
+    /// The file existed with this content
+    File(Vec<u8>),
+    /// The file did not exist
+    Missing,
+    /// The directory did not exist
+    NoDirectory,
+}
+
+impl FileState {
+    fn kind(&self) -> &'static str {
+        match self {
+            FileState::File(_) => "file",
+            FileState::Missing => "missing",
+            FileState::NoDirectory => "no_directory",
+        }
+    }
+
+    fn content(&self) -> Option<Vec<u8>> {
+        match self {
+            FileState::File(content) => Some(content.clone()),
+            _ => None,
+        }
+    }
+
+    fn from_parts(kind: &str, content: Option<Vec<u8>>, path: &str) -> Result<Self> {
+        match (kind, content) {
+            ("file", Some(content)) => Ok(FileState::File(content)),
+            ("missing", _) => Ok(FileState::Missing),
+            ("no_directory", _) => Ok(FileState::NoDirectory),
+            (kind, _) => Err(anyhow!("Invalid journal entry kind '{}' for {}", kind, path)),
+        }
+    }
+}
+
+/// A recorded change; paths are relative to the project root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub task_id: String,
+    pub attempt: usize,
+    pub path: PathBuf,
+    /// State before the first change of the attempt
+    pub pre_image: FileState,
+    /// State after the latest change of the attempt; `None` for directories and while the
+    /// change has not completed
+    pub post_image: Option<FileState>,
+}
+
+/// Outcome of restoring journaled changes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// Paths back at their pre-image
+    pub restored: Vec<PathBuf>,
+    /// Paths changed after the journaled change, left as they are
+    pub conflicts: Vec<PathBuf>,
+}
+
+impl JournalEntry {
+    fn to_record(&self, root: &Path) -> FileJournalRecord {
+        FileJournalRecord {
+            task_id: self.task_id.clone(),
+            attempt: self.attempt as i32,
+            project_root: root.to_string_lossy().to_string(),
+            path: self.path.to_string_lossy().to_string(),
+            kind: self.pre_image.kind().to_string(),
+            pre_image: self.pre_image.content(),
+            post_kind: self.post_image.as_ref().map(|post| post.kind().to_string()),
+            post_image: self.post_image.as_ref().and_then(FileState::content),
+        }
+    }
+
+    fn from_record(record: FileJournalRecord) -> Result<Self> {
+        let pre_image = FileState::from_parts(&record.kind, record.pre_image, &record.path)?;
+        let post_image = record.post_kind
+            .map(|kind| FileState::from_parts(&kind, record.post_image, &record.path))
+            .transpose()?;
+        Ok(Self {
+            task_id: record.task_id,
+            attempt: record.attempt.max(0) as usize,
+            path: PathBuf::from(record.path),
+            pre_image,
+            post_image,
+        })
+    }
+}
+
+/// Journal of all file changes of one execution
+pub struct FileJournal {
+    execution_id: String,
+    root: PathBuf,
+    shadow: Option<Arc<ShadowWorkspace>>,
+    store: Option<Arc<PostgresClient>>,
+    entries: Mutex<Vec<JournalEntry>>,
+}
+
+impl std::fmt::Debug for FileJournal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileJournal")
+            .field("execution_id", &self.execution_id)
+            .field("root", &self.root)
+            .field("shadow", &self.shadow)
+            .field("persisted", &self.store.is_some())
+            .finish()
+    }
+}
+
+impl FileJournal {
+    pub fn new(execution_id: impl Into<String>, root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let root = std::fs::canonicalize(&root).unwrap_or(root);
+        Self {
+            execution_id: execution_id.into(),
+            root,
+            shadow: None,
+            store: None,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Journal changes made through a shadow workspace instead of the project tree
+    pub fn with_shadow(mut self, shadow: Option<Arc<ShadowWorkspace>>) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    /// Persist entries in Postgres so the execution can be undone later
+    pub fn with_store(mut self, store: Arc<PostgresClient>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Restore the persisted journal of an execution; `None` if it made no recorded changes
+    pub async fn load(store: Arc<PostgresClient>, execution_id: &str) -> Result<Option<Self>> {
+        let records = store.load_file_journal(execution_id).await?;
+        let Some(root) = records.first().map(|record| PathBuf::from(&record.project_root)) else {
+            return Ok(None);
+        };
+        let entries = records.into_iter()
+            .map(JournalEntry::from_record)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(Self {
+            execution_id: execution_id.to_string(),
+            root,
+            shadow: None,
+            store: Some(store),
+            entries: Mutex::new(entries),
+        }))
+    }
+
+    pub fn execution_id(&self) -> &str {
+        &self.execution_id
+    }
+
+    /// Handle recording the changes of one task attempt
+    pub fn task(self: &Arc<Self>, task_id: impl Into<String>, attempt: usize) -> TaskJournal {
+        TaskJournal { journal: self.clone(), task_id: task_id.into(), attempt }
+    }
+
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn is_recorded(&self, task_id: &str, attempt: usize, path: &Path) -> bool {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).iter()
+            .any(|entry| entry.task_id == task_id && entry.attempt == attempt && entry.path == path)
+    }
+
+    /// Record a pre-image; a change that cannot be journaled must not be made
+    async fn record(&self, entry: JournalEntry) -> io::Result<()> {
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if entries.iter().any(|e| e.task_id == entry.task_id && e.attempt == entry.attempt && e.path == entry.path) {
+                return Ok(());
+            }
+            entries.push(entry.clone());
+        }
+        debug!("Journaled {} ({}) for task {} attempt {}", entry.path.display(), entry.pre_image.kind(), entry.task_id, entry.attempt);
+
+        if let Some(store) = &self.store {
+            if let Err(e) = store.record_file_change(&self.execution_id, &entry.to_record(&self.root)).await {
+                self.entries.lock().unwrap_or_else(|e| e.into_inner())
+                    .retain(|e| !(e.task_id == entry.task_id && e.attempt == entry.attempt && e.path == entry.path));
+                return Err(io::Error::other(format!("Failed to persist journal entry for {}: {}", entry.path.display(), e)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the state a change of an already journaled path left behind
+    async fn record_result(&self, task_id: &str, attempt: usize, path: &Path) -> io::Result<()> {
+        let post_image = self.current_state(path).await?;
+        let entry = {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(entry) = entries.iter_mut()
+                .find(|e| e.task_id == task_id && e.attempt == attempt && e.path == path)
+            else {
+                return Ok(());
+            };
+            entry.post_image = Some(post_image);
+            entry.clone()
+        };
+
+        if let Some(store) = &self.store {
+            store.update_file_result(&self.execution_id, &entry.to_record(&self.root)).await
+                .map_err(|e| io::Error::other(format!("Failed to persist journal entry for {}: {}", path.display(), e)))?;
+        }
+        Ok(())
+    }
+
+    async fn current_state(&self, path: &Path) -> io::Result<FileState> {
+        let content = match self.shadow.as_deref() {
+            Some(shadow) => shadow.read(path).await,
+            None => fs::read(self.root.join(path)).await,
+        };
+        match content {
+            Ok(content) => Ok(FileState::File(content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(FileState::Missing),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn restore(&self, entry: &JournalEntry) -> io::Result<()> {
+        let ignore_missing = |result: io::Result<()>| match result {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        };
+
+        match (&entry.pre_image, self.shadow.as_deref()) {
+            (FileState::File(content), Some(shadow)) => shadow.write(&entry.path, content).await,
+            (FileState::File(content), None) => {
+                let target = self.root.join(&entry.path);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(target, content).await
+            }
+            (FileState::Missing, Some(shadow)) => ignore_missing(shadow.remove_file(&entry.path).await),
+            (FileState::Missing, None) => ignore_missing(fs::remove_file(self.root.join(&entry.path)).await),
+            // Overlay directories never show up in the diff
+            (FileState::NoDirectory, Some(_)) => Ok(()),
+            (FileState::NoDirectory, None) => {
+                // Keep directories that got other content in the meantime
+                if let Err(e) = fs::remove_dir(self.root.join(&entry.path)).await {
+                    debug!("Keeping directory {}: {}", entry.path.display(), e);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Restore every path to its oldest pre-image among `entries`, newest entry first so
+    /// directories are removed after their files. A path that holds neither a post-image of
+    /// these entries nor that pre-image was changed since; it is a conflict and kept as is.
+    async fn restore_all(&self, entries: &[JournalEntry]) -> Result<RestoreReport> {
+        let mut report = RestoreReport::default();
+        for (index, entry) in entries.iter().enumerate().rev() {
+            if entry.pre_image != FileState::NoDirectory {
+                if entries[..index].iter().any(|older| older.path == entry.path) {
+                    continue;
+                }
+                let current = self.current_state(&entry.path).await
+                    .map_err(|e| anyhow!("Failed to read {}: {}", entry.path.display(), e))?;
+                let written = entries[index..].iter()
+                    .filter(|newer| newer.path == entry.path)
+                    .any(|newer| newer.post_image.as_ref() == Some(&current));
+                if !written && current != entry.pre_image {
+                    warn!("Not restoring {}: changed after task {} attempt {} wrote it", entry.path.display(), entry.task_id, entry.attempt);
+                    report.conflicts.push(entry.path.clone());
+                    continue;
+                }
+            }
+            self.restore(entry).await
+                .map_err(|e| anyhow!("Failed to restore {}: {}", entry.path.display(), e))?;
+            report.restored.push(entry.path.clone());
+        }
+        report.restored.reverse();
+        report.conflicts.reverse();
+        Ok(report)
+    }
+
+    /// Undo the changes of one task attempt
+    pub async fn rollback(&self, task_id: &str, attempt: usize) -> Result<RestoreReport> {
+        self.rollback_with_subtasks(task_id, attempt, &[]).await
+    }
+
+    /// Undo the changes of one task attempt together with all changes of the sub-tasks it
+    /// delegated
+    pub async fn rollback_with_subtasks(&self, task_id: &str, attempt: usize, subtasks: &[String]) -> Result<RestoreReport> {
+        let attempt_entries: Vec<JournalEntry> = {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            let (matching, rest) = entries.drain(..)
+                .partition(|entry| (entry.task_id == task_id && entry.attempt == attempt) || subtasks.contains(&entry.task_id));
+            *entries = rest;
+            matching
+        };
+        if attempt_entries.is_empty() {
+            return Ok(RestoreReport::default());
+        }
+
+        let report = self.restore_all(&attempt_entries).await?;
+        if let Some(store) = &self.store {
+            let mut attempts: Vec<(&str, usize)> = attempt_entries.iter()
+                .map(|entry| (entry.task_id.as_str(), entry.attempt))
+                .collect();
+            attempts.sort();
+            attempts.dedup();
+            for (task_id, attempt) in attempts {
+                store.delete_file_journal(&self.execution_id, Some((task_id, attempt as i32))).await?;
+            }
+        }
+        info!("Rolled back {} paths of task {} attempt {} ({} conflicts)", report.restored.len(), task_id, attempt, report.conflicts.len());
+        Ok(report)
+    }
+
+    /// Undo every journaled change of the execution
+    pub async fn undo(&self) -> Result<RestoreReport> {
+        let entries = std::mem::take(&mut *self.entries.lock().unwrap_or_else(|e| e.into_inner()));
+        let report = self.restore_all(&entries).await?;
+        if let Some(store) = &self.store {
+            store.delete_file_journal(&self.execution_id, None).await?;
+        }
+        info!("Undid {} paths of execution {} ({} conflicts)", report.restored.len(), self.execution_id, report.conflicts.len());
+        Ok(report)
+    }
+}
+
+/// Journal handle bound to one task attempt, given to the filesystem tools of that attempt
+#[derive(Debug, Clone)]
+pub struct TaskJournal {
+    journal: Arc<FileJournal>,
+    task_id: String,
+    attempt: usize,
+}
+
+impl TaskJournal {
+    /// Record the pre-image of a file (relative path) that is about to be written or deleted
+    pub async fn record_file(&self, path: &Path) -> io::Result<()> {
+        if self.journal.is_recorded(&self.task_id, self.attempt, path) {
+            return Ok(());
+        }
+        let pre_image = self.journal.current_state(path).await?;
+        self.journal.record(JournalEntry {
+            task_id: self.task_id.clone(),
+            attempt: self.attempt,
+            path: path.to_path_buf(),
+            pre_image,
+            post_image: None,
+        }).await
+    }
+
+    /// Record the state of a file (relative path) after it was written or deleted
+    pub async fn record_result(&self, path: &Path) -> io::Result<()> {
+        self.journal.record_result(&self.task_id, self.attempt, path).await
+    }
+
+    /// Record the directories that creating `path` (relative) will add, outermost first
+    pub async fn record_dir(&self, path: &Path) -> io::Result<()> {
+        if self.journal.shadow.is_some() {
+            return Ok(());
+        }
+        let mut missing = Vec::new();
+        let mut current = Some(path);
+        while let Some(dir) = current.filter(|dir| !dir.as_os_str().is_empty()) {
+            if fs::try_exists(self.journal.root.join(dir)).await? {
+                break;
+            }
+            missing.push(dir.to_path_buf());
+            current = dir.parent();
+        }
+
+        for dir in missing.into_iter().rev() {
+            self.journal.record(JournalEntry {
+                task_id: self.task_id.clone(),
+                attempt: self.attempt,
+                path: dir,
+                pre_image: FileState::NoDirectory,
+                post_image: None,
+            }).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("journal-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("lib.rs"), "original").unwrap();
+        root
+    }
+
+    /// Simulate a filesystem tool: journal, mutate, journal the result
+    async fn write(task: &TaskJournal, root: &Path, path: &str, content: &str) {
+        let path = Path::new(path);
+        if let Some(parent) = path.parent() {
+            task.record_dir(parent).await.unwrap();
+            std::fs::create_dir_all(root.join(parent)).unwrap();
+        }
+        task.record_file(path).await.unwrap();
+        std::fs::write(root.join(path), content).unwrap();
+        task.record_result(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rollback_restores_only_the_failed_attempt() {
+        let root = temp_root();
+        let journal = Arc::new(FileJournal::new("exec-1", &root));
+
+        let first = journal.task("task-1", 0);
+        write(&first, &root, "lib.rs", "first attempt").await;
+        write(&first, &root, "lib.rs", "first attempt, again").await;
+        write(&first, &root, "gen/out/new.rs", "generated").await;
+        write(&journal.task("task-2", 0), &root, "other.rs", "other task").await;
+
+        let restored = journal.rollback("task-1", 0).await.unwrap().restored;
+        assert_eq!(restored, vec![PathBuf::from("lib.rs"), PathBuf::from("gen"), PathBuf::from("gen/out"), PathBuf::from("gen/out/new.rs")]);
+        assert_eq!(std::fs::read_to_string(root.join("lib.rs")).unwrap(), "original");
+        assert!(!root.join("gen").exists());
+        assert_eq!(std::fs::read_to_string(root.join("other.rs")).unwrap(), "other task");
+        assert_eq!(journal.entries().len(), 1);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rollback_includes_delegated_subtasks() {
+        let root = temp_root();
+        let journal = Arc::new(FileJournal::new("exec-3", &root));
+
+        write(&journal.task("task-1", 0), &root, "lib.rs", "parent").await;
+        write(&journal.task("task-1-sub-1", 0), &root, "lib.rs", "sub-task").await;
+        write(&journal.task("task-1-sub-1", 1), &root, "README.md", "docs").await;
+        write(&journal.task("task-1", 0), &root, "lib.rs", "parent again").await;
+        write(&journal.task("task-1", 1), &root, "next.rs", "retry").await;
+
+        let subtasks = vec!["task-1-sub-1".to_string()];
+        let restored = journal.rollback_with_subtasks("task-1", 0, &subtasks).await.unwrap().restored;
+        assert_eq!(restored, vec![PathBuf::from("lib.rs"), PathBuf::from("README.md")]);
+        assert_eq!(std::fs::read_to_string(root.join("lib.rs")).unwrap(), "original");
+        assert!(!root.join("README.md").exists());
+        assert_eq!(std::fs::read_to_string(root.join("next.rs")).unwrap(), "retry");
+        assert_eq!(journal.entries().len(), 1);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undo_restores_oldest_pre_images() {
+        let root = temp_root();
+        let journal = Arc::new(FileJournal::new("exec-2", &root));
+
+        write(&journal.task("task-1", 0), &root, "lib.rs", "task 1").await;
+        write(&journal.task("task-2", 0), &root, "lib.rs", "task 2").await;
+        write(&journal.task("task-2", 0), &root, "new.rs", "new").await;
+
+        journal.undo().await.unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("lib.rs")).unwrap(), "original");
+        assert!(!root.join("new.rs").exists());
+        assert!(journal.entries().is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undo_keeps_files_changed_afterwards() {
+        let root = temp_root();
+        let journal = Arc::new(FileJournal::new("exec-4", &root));
+
+        write(&journal.task("task-1", 0), &root, "lib.rs", "task 1").await;
+        write(&journal.task("task-2", 0), &root, "lib.rs", "task 2").await;
+        write(&journal.task("task-2", 0), &root, "new.rs", "new").await;
+        write(&journal.task("task-2", 0), &root, "gone.rs", "deleted by the user").await;
+        std::fs::write(root.join("lib.rs"), "edited by the user").unwrap();
+        // Already back at its pre-image
+        std::fs::remove_file(root.join("gone.rs")).unwrap();
+
+        let report = journal.undo().await.unwrap();
+        assert_eq!(report.restored, vec![PathBuf::from("new.rs"), PathBuf::from("gone.rs")]);
+        assert_eq!(report.conflicts, vec![PathBuf::from("lib.rs")]);
+        assert_eq!(std::fs::read_to_string(root.join("lib.rs")).unwrap(), "edited by the user");
+        assert!(!root.join("new.rs").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
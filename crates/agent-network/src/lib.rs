@@ -18,6 +18,8 @@ pub mod tools;
 pub mod hitl;
 pub mod conflict;
 pub mod filelocks;
+pub mod shadow;
+pub mod journal;
 pub mod coordination;
 pub mod sharedcontext;
 pub mod token_budget;
@@ -21,19 +21,20 @@ use crate::execution_manager::BidirectionalEventChannel;
 use crate::sharedcontext::SharedContext;
 use crate::coordination::CoordinationManager;
 use crate::filelocks::FileLockManager;
+use crate::journal::FileJournal;
+use crate::shadow::ShadowWorkspace;
+use crate::token_budget::{count_tokens, TokenBudgetManager};
 use crate::hitl::{AuditLogger};
 use crate::workflow::{WorkflowExecutor, WorkflowGraph, TaskResult, WorkflowBuilder, TaskNode, DependencyType};
+use crate::workflow::quality::QualityGate;
 use schemars::JsonSchema;
 
 use ai_agent_common::{
     ConversationId, ProjectScope, SystemConfig, StatusEvent, EventSource, EventType,
-    AgentNetworkConfig, AgentType, ErrorRecoveryStrategy,
+    AgentNetworkConfig, AgentType, DelegationConfig, ErrorRecoveryStrategy,
     ExecutionPlan, WaveInfo, TaskInfo,
 };
 use chrono;
-use ai_agent_history::HistoryManager;
-use ai_agent_rag::SmartMultiSourceRag;
-use ai_agent_common::llm::EmbeddingClient;
 
 /// Core Orchestrator for multi-agent coordination (stateless)
 pub struct Orchestrator;
@@ -73,6 +74,7 @@ pub struct DecomposedTask {
 /// Agent capability information
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AgentCapability {
+    pub agent_id: String,
     pub agent_type: AgentType,
     pub description: String,
     pub capabilities: Vec<String>,
@@ -111,9 +113,9 @@ impl Orchestrator {
         coordination: Arc<CoordinationManager>,
         file_locks: Arc<FileLockManager>,
         audit_logger: Arc<AuditLogger>,
-        rag: Arc<SmartMultiSourceRag>,
-        history_manager: Arc<RwLock<HistoryManager>>,
-        embedding_client: Arc<EmbeddingClient>,
+        shadow: Option<Arc<ShadowWorkspace>>,
+        journal: Option<Arc<FileJournal>>,
+        token_budget: Option<Arc<TokenBudgetManager>>,
     ) -> Result<String> {
         info!("Processing query: {}", query);
 
@@ -158,6 +160,7 @@ impl Orchestrator {
                     &agent_pool,
                     &config.agent_network,
                     event_channel.clone(),
+                    token_budget.clone(),
                 ).await?
             }
         };
@@ -204,9 +207,12 @@ impl Orchestrator {
             coordination,
             file_locks,
             audit_logger,
-            rag,
-            history_manager,
             event_channel.clone(),
+            shadow,
+            journal,
+            token_budget,
+            Some(Arc::new(QualityGate::from_config(&config.agent_network))),
+            Some(config.agent_network.delegation.clone()),
         ).await?;
         info!("Workflow execution completed with {} results", results.len());
 
@@ -237,7 +243,7 @@ impl Orchestrator {
 
         // Heuristic analysis (can be enhanced with LLM later)
         let complexity = Self::estimate_complexity(query);
-        let estimated_tokens = (query.len() / 4) + 200; // Rough estimate
+        let estimated_tokens = count_tokens(query) + 200; // Rough estimate
 
         let requires_hitl = complexity >= Complexity::Complex;
 
@@ -333,6 +339,7 @@ impl Orchestrator {
                 description: task.description.clone(),
                 recovery_strategy: task.recovery_strategy.clone(),
                 requires_hitl: task.requires_hitl,
+                output_schema: None,
             };
 
             let idx = builder.add_task(node)?;
@@ -368,9 +375,12 @@ impl Orchestrator {
         coordination: Arc<CoordinationManager>,
         file_locks: Arc<FileLockManager>,
         audit_logger: Arc<AuditLogger>,
-        rag: Arc<SmartMultiSourceRag>,
-        history_manager: Arc<RwLock<HistoryManager>>,
         event_channel: BidirectionalEventChannel,
+        shadow: Option<Arc<ShadowWorkspace>>,
+        journal: Option<Arc<FileJournal>>,
+        token_budget: Option<Arc<TokenBudgetManager>>,
+        quality: Option<Arc<QualityGate>>,
+        delegation: Option<DelegationConfig>,
     ) -> Result<Vec<TaskResult>> {
         debug!("Executing workflow with {} nodes", workflow.node_count());
 
@@ -379,7 +389,12 @@ impl Orchestrator {
             agent_pool,
             coordination,
             file_locks,
-        );
+        )
+        .with_shadow(shadow)
+        .with_journal(journal)
+        .with_token_budget(token_budget)
+        .with_quality(quality)
+        .with_delegation(delegation);
 
         // Execute the workflow with HITL
         let results = executor.execute_with_hitl(
@@ -402,6 +417,7 @@ impl Orchestrator {
         agent_pool: &Arc<AgentPool>,
         config: &AgentNetworkConfig,
         event_channel: BidirectionalEventChannel,
+        token_budget: Option<Arc<TokenBudgetManager>>,
     ) -> Result<Vec<DecomposedTask>> {
         info!("Decomposing query using LLM planning agent: {}", analysis.query);
 
@@ -429,10 +445,15 @@ impl Orchestrator {
             .filter_map(|agent_id| {
                 let agent = agent_pool.get_agent(agent_id)?;
                 if agent.agent_type() != AgentType::Planning {
+                    let capabilities = config.agents.iter()
+                        .find(|a| a.id == *agent_id)
+                        .map(|a| a.capabilities.clone())
+                        .unwrap_or_default();
                     Some(AgentCapability {
+                        agent_id: agent_id.clone(),
                         agent_type: agent.agent_type(),
                         description: format!("{} agent", agent.system_prompt()),
-                        capabilities: vec![],
+                        capabilities,
                     })
                 } else {
                     None
@@ -456,11 +477,14 @@ impl Orchestrator {
         );
 
         // Build agent context for planning
-        let planning_context = AgentContext::new(
+        let mut planning_context = AgentContext::new(
             description.clone(),
             conversation_id.to_string(),
             None  // Planning agent has no task_id
         ).with_project_scope(project_scope.clone());
+        if let Some(token_budget) = token_budget {
+            planning_context = planning_context.with_token_budget(token_budget);
+        }
 
         info!("Planning Context: {}", description);
 
@@ -499,13 +523,14 @@ impl Orchestrator {
         let tasks: Result<Vec<DecomposedTask>> = task_specs_with_ids
             .into_iter()
             .map(|(actual_task_id, subtask)| {
-                let agent = config
-                    .get_agents_by_type(subtask.agent_type)
-                    .first()
+                // Prefer the agent the planner picked, falling back to the first of the type
+                let candidates = config.get_agents_by_type(subtask.agent_type);
+                let agent = subtask.agent_id.as_ref()
+                    .and_then(|id| candidates.iter().find(|a| a.id == *id))
+                    .or_else(|| candidates.first())
                     .ok_or_else(|| anyhow::anyhow!(
                         "No agent of type '{:?}' available", subtask.agent_type
-                    ))?
-                    .clone();
+                    ))?;
 
                 // Resolve dependencies: convert LLM IDs to actual UUIDs
                 let resolved_dependencies: Vec<String> = subtask.dependencies
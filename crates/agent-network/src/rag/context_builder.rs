@@ -3,12 +3,13 @@
 //! Converts ContextFragment streams and HistoryContext into formatted strings
 //! suitable for agent context injection.
 
-use ai_agent_common::{ContextFragment, ConversationId};
+use ai_agent_common::{ContextFragment, ContextSectionKind, ConversationId, Location};
 use futures::{Stream, StreamExt, FutureExt};
 use std::pin::Pin;
 use tracing::{debug, info, instrument, Instrument};
 
 use crate::error::AgentNetworkResult;
+use crate::token_budget::{count_tokens, prune_sections, ContextSection, PruningReport};
 
 /// Formatted RAG context ready for agent consumption
 #[derive(Debug, Clone)]
@@ -24,6 +25,12 @@ pub struct FormattedRagContext {
 
     /// Priority-ordered sources (workspace, personal, system, online)
     pub source_tiers: Vec<String>,
+
+    /// Locations of the included fragments, in output order
+    pub locations: Vec<Location>,
+
+    /// One section per fragment, with relevance relative to the best match
+    pub sections: Vec<ContextSection>,
 }
 
 impl FormattedRagContext {
@@ -34,6 +41,8 @@ impl FormattedRagContext {
             fragment_count: 0,
             estimated_tokens: 0,
             source_tiers: vec![],
+            locations: vec![],
+            sections: vec![],
         }
     }
 
@@ -63,6 +72,9 @@ pub struct FormattedHistoryContext {
 
     /// Approximate token count
     pub estimated_tokens: usize,
+
+    /// One section per message, summary and topic list
+    pub sections: Vec<ContextSection>,
 }
 
 impl FormattedHistoryContext {
@@ -75,6 +87,7 @@ impl FormattedHistoryContext {
             summary: None,
             topics: vec![],
             estimated_tokens: 0,
+            sections: vec![],
         }
     }
 
@@ -115,6 +128,9 @@ impl ContextBuilder {
         let mut token_count = 0;
         let mut source_tiers = std::collections::HashSet::new();
         let mut fragment_details = Vec::new();
+        let mut locations = Vec::new();
+        let mut sections = Vec::new();
+        let mut scores = Vec::new();
 
         let mut stream = std::pin::pin!(stream);
 
@@ -140,14 +156,15 @@ impl ContextBuilder {
             fragment_details.push(format!("source: {:?}, tokens: {}", location, fragment_tokens));
 
             // Format fragment with location info
-            formatted.push_str(&format!(
-                "## {}\n",
-                serde_json::to_string(location)?
-            ));
+            let label = serde_json::to_string(location)?;
+            formatted.push_str(&format!("## {}\n", label));
+            sections.push(ContextSection::new(ContextSectionKind::RagFragment, label, fragment.content.clone()));
+            scores.push(fragment.relevance_score);
 
             // Add content
             formatted.push_str(&fragment.content);
             formatted.push_str("\n\n");
+            locations.push(location.clone());
 
             token_count += fragment_tokens;
             fragment_count += 1;
@@ -168,6 +185,12 @@ impl ContextBuilder {
             return Ok(FormattedRagContext::empty());
         }
 
+        // Retriever scores have no fixed scale, so relevance is relative to the best fragment
+        let best_score = scores.iter().copied().max().unwrap_or(0).max(1) as f32;
+        for (section, score) in sections.iter_mut().zip(scores) {
+            section.relevance = score as f32 / best_score;
+        }
+
         info!(
             "RAG context built: {} fragments, ~{} tokens, sources: {:?}, embedding_time: {}ms",
             fragment_count, token_count, source_tiers_vec, embedding_time.as_millis()
@@ -178,6 +201,8 @@ impl ContextBuilder {
             fragment_count,
             estimated_tokens: token_count,
             source_tiers: source_tiers_vec,
+            locations,
+            sections,
         })
     }
 
@@ -193,14 +218,18 @@ impl ContextBuilder {
         let mut short_term_vec = Vec::new();
         let mut relevant_past_vec = Vec::new();
         let mut summary_vec = Vec::new();
+        let mut sections = Vec::new();
 
         // Add short-term memory
         if !history_result.short_term.is_empty() {
             formatted.push_str("## Recent Conversation\n");
-            for msg in &history_result.short_term {
+            // Short-term messages are the most recent, later ones more so
+            for (i, msg) in history_result.short_term.iter().enumerate() {
                 let message_string = serde_json::to_string(msg)?;
                 short_term_vec.push(message_string.clone());
                 formatted.push_str(&format!("- {}\n",message_string));
+                sections.push(ContextSection::new(ContextSectionKind::History, "Recent Conversation", format!("- {}", message_string))
+                    .with_recency(i + 1));
                 token_count += Self::estimate_tokens(&message_string);
             }
             formatted.push_str("\n");
@@ -213,6 +242,8 @@ impl ContextBuilder {
                 let message_string = serde_json::to_string(msg)?;
                 relevant_past_vec.push(message_string.clone());
                 formatted.push_str(&format!("- {}\n",message_string));
+                sections.push(ContextSection::new(ContextSectionKind::History, "Relevant Past Context", format!("- {}", message_string))
+                    .with_relevance(0.6));
                 token_count += Self::estimate_tokens(&message_string);
             }
             formatted.push_str("\n");
@@ -225,6 +256,7 @@ impl ContextBuilder {
             formatted.push_str("\n\n");
             token_count += Self::estimate_tokens(summary);
             summary_vec.push(summary);
+            sections.push(ContextSection::new(ContextSectionKind::History, "Conversation Summary", summary.clone()));
         }
 
         // Add topics
@@ -232,6 +264,8 @@ impl ContextBuilder {
             formatted.push_str("## Topics Discussed\n");
             formatted.push_str(&history_result.topics.join(", "));
             formatted.push_str("\n\n");
+            sections.push(ContextSection::new(ContextSectionKind::History, "Topics Discussed", history_result.topics.join(", "))
+                .with_relevance(0.3));
         }
 
         info!(
@@ -245,48 +279,65 @@ impl ContextBuilder {
             relevant_past: relevant_past_vec,            summary: history_result.summary.clone(),
             topics: history_result.topics.clone(),
             estimated_tokens: token_count,
+            sections,
         })
     }
 
-    /// Estimate token count (rough: 1 token ≈ 4 characters)
+    /// Estimate token count with the tokenizer
     pub fn estimate_tokens(text: &str) -> usize {
-        (text.len() / 4).max(1)
+        count_tokens(text).max(1)
     }
 
     /// Combine RAG and history contexts with token budget
+    ///
+    /// Fragments and history entries are pruned as individual sections, so the least
+    /// relevant and oldest ones are shortened first instead of dropping whole contexts.
     pub fn combine_contexts(
         rag: FormattedRagContext,
         history: FormattedHistoryContext,
         max_total_tokens: usize,
-    ) -> String {
+    ) -> (String, PruningReport) {
+        let rag_count = rag.sections.len();
+        let mut sections: Vec<ContextSection> = rag.sections.into_iter().chain(history.sections).collect();
+        let report = prune_sections(&mut sections, max_total_tokens);
+        if !report.is_empty() {
+            debug!(
+                "Pruned {} context sections to fit the token budget ({} -> {} of {} tokens)",
+                report.pruned.len(), report.tokens_before, report.tokens_after, max_total_tokens
+            );
+        }
+
+        let (rag_sections, history_sections) = sections.split_at(rag_count);
         let mut combined = String::new();
-        let mut current_tokens = 0;
-
-        // Prioritize RAG context
-        if current_tokens + rag.estimated_tokens <= max_total_tokens {
-            if !rag.content.is_empty() {
-                combined.push_str("# Retrieved Context\n\n");
-                combined.push_str(&rag.content);
-                current_tokens += rag.estimated_tokens;
-            }
+        if !rag_sections.is_empty() {
+            combined.push_str("# Retrieved Context\n\n");
+            combined.push_str(&Self::render_sections(rag_sections));
+        }
+        if !history_sections.is_empty() {
+            combined.push_str("# Conversation History\n\n");
+            combined.push_str(&Self::render_sections(history_sections));
         }
 
-        // Add history if space available
-        if current_tokens + history.estimated_tokens <= max_total_tokens {
-            if !history.content.is_empty() {
-                combined.push_str("# Conversation History\n\n");
-                combined.push_str(&history.content);
-                current_tokens += history.estimated_tokens;
+        (combined, report)
+    }
+
+    /// Render sections under their labels, consecutive sections sharing a label share the heading
+    fn render_sections(sections: &[ContextSection]) -> String {
+        let mut rendered = String::new();
+        let mut heading: Option<&str> = None;
+        for section in sections {
+            if heading != Some(section.label.as_str()) {
+                if heading.is_some() {
+                    rendered.push('\n');
+                }
+                rendered.push_str(&format!("## {}\n", section.label));
+                heading = Some(&section.label);
             }
-        } else {
-            debug!(
-                "History context truncated due to token budget (available: {}, needed: {})",
-                max_total_tokens - current_tokens,
-                history.estimated_tokens
-            );
+            rendered.push_str(&section.content);
+            rendered.push('\n');
         }
-
-        combined
+        rendered.push('\n');
+        rendered
     }
 }
 
@@ -313,4 +364,29 @@ mod tests {
         let ctx = FormattedHistoryContext::empty();
         assert!(ctx.is_empty());
     }
+
+    #[test]
+    fn test_combine_contexts_prunes_least_relevant_fragments() {
+        let fragment = |label: &str, relevance: f32| {
+            ContextSection::new(ContextSectionKind::RagFragment, label, format!("fn {}() {{}}\n", label).repeat(100))
+                .with_relevance(relevance)
+        };
+        let mut rag = FormattedRagContext::empty();
+        rag.sections = vec![fragment("best", 1.0), fragment("weak", 0.1)];
+        let mut history = FormattedHistoryContext::empty();
+        history.sections = vec![
+            ContextSection::new(ContextSectionKind::History, "Recent Conversation", "- hello").with_recency(1),
+            ContextSection::new(ContextSectionKind::History, "Recent Conversation", "- world").with_recency(2),
+        ];
+
+        let unpruned = ContextBuilder::combine_contexts(rag.clone(), history.clone(), usize::MAX).0;
+        assert!(unpruned.contains("## Recent Conversation\n- hello\n- world\n"));
+
+        let budget = count_tokens(&rag.sections[0].content) + 100;
+        let (combined, report) = ContextBuilder::combine_contexts(rag, history, budget);
+        assert_eq!(report.pruned.len(), 1);
+        assert_eq!(report.pruned[0].label, "weak");
+        assert!(combined.contains(&"fn best() {}\n".repeat(100)));
+        assert!(combined.contains("- world"));
+    }
 }
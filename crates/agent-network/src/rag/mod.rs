@@ -15,6 +15,7 @@ use tokio::sync::RwLock;
 use tracing::{debug, info, instrument, Instrument};
 
 use crate::error::AgentNetworkResult;
+use crate::token_budget::PruningReport;
 
 /// Context provider for agent execution
 ///
@@ -40,6 +41,11 @@ impl ContextProvider {
         }
     }
 
+    /// RAG pipeline backing this provider, for agents running their own searches
+    pub fn rag(&self) -> Arc<SmartMultiSourceRag> {
+        self.rag.clone()
+    }
+
     /// Retrieve context for a specific task using agent-specific refined query
     ///
     /// This implements Agentic RAG pattern:
@@ -52,13 +58,14 @@ impl ContextProvider {
     /// * `task_query` - Agent-specific refined query from task.description
     ///
     /// # Returns
-    /// Combined formatted context string ready for AgentContext injection
+    /// Combined formatted context string ready for AgentContext injection, and what
+    /// was pruned to fit it into the token budget
     #[instrument(name = "context_retrieval", skip(self), fields(query_len = %task_query.len(), token_budget = self.token_budget))]
     pub async fn retrieve_context(&self,
         task_query: String,
         project_scope: ProjectScope,
         conversation_id: ConversationId,
-        ) -> AgentNetworkResult<String> {
+        ) -> AgentNetworkResult<(String, PruningReport)> {
         info!("Retrieving context for task query: {}", task_query);
 
         // Retrieve RAG context - RetrieverSource-level spans are created within the RAG system
@@ -70,7 +77,7 @@ impl ContextProvider {
         let history_context = history_future.await?;
 
         // Combine contexts with token budget
-        let combined = ContextBuilder::combine_contexts(
+        let (combined, pruning) = ContextBuilder::combine_contexts(
             rag_context,
             history_context,
             self.token_budget,
@@ -85,7 +92,7 @@ impl ContextProvider {
             );
         }
 
-        Ok(combined)
+        Ok((combined, pruning))
     }
 
     /// Retrieve RAG context from SmartMultiSourceRag
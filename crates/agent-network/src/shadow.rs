@@ -0,0 +1,423 @@
+//! Shadow workspaces for dry-run executions
+//!
+//! In shadow mode the file-mutating tools write into an overlay directory keyed by
+//! conversation id instead of the project tree, and reads look at the overlay first.
+//! Deleted files are recorded in the overlay's manifest. At the end of an execution the
+//! combined diff is emitted and stored next to the overlay; `apply` commits the changes
+//! to the real tree.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs;
+use tracing::{debug, info};
+use crate::tools::filesystem::unified_diff;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const FILES_DIR: &str = "files";
+const DIFF_FILE: &str = "changes.diff";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    root: PathBuf,
+    deleted: BTreeSet<PathBuf>,
+}
+
+/// A file changed in a shadow workspace; `None` means the file does not exist on that side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowChange {
+    pub path: PathBuf,
+    pub before: Option<Vec<u8>>,
+    pub after: Option<Vec<u8>>,
+}
+
+/// Overlay of one conversation over a project tree; all paths are relative to the project root
+#[derive(Debug)]
+pub struct ShadowWorkspace {
+    conversation_id: String,
+    root: PathBuf,
+    dir: PathBuf,
+    deleted: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl ShadowWorkspace {
+    /// Overlay directory of a conversation; ids come from API paths, so they must be a single component
+    fn workspace_dir(shadow_dir: &Path, conversation_id: &str) -> Result<PathBuf> {
+        let valid = !conversation_id.is_empty()
+            && conversation_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(anyhow!("Invalid conversation id: {}", conversation_id));
+        }
+        Ok(shadow_dir.join(conversation_id))
+    }
+
+    /// Create the overlay for a conversation under `shadow_dir`, or reopen an existing one
+    pub fn create(shadow_dir: &Path, conversation_id: &str, root: &Path) -> Result<Self> {
+        let dir = Self::workspace_dir(shadow_dir, conversation_id)?;
+        if dir.join(MANIFEST_FILE).exists() {
+            return Self::open(shadow_dir, conversation_id);
+        }
+
+        let root = std::fs::canonicalize(root)
+            .with_context(|| format!("Project root {} does not exist", root.display()))?;
+        std::fs::create_dir_all(dir.join(FILES_DIR))
+            .with_context(|| format!("Failed to create shadow workspace in {}", dir.display()))?;
+
+        let workspace = Self {
+            conversation_id: conversation_id.to_string(),
+            root,
+            dir,
+            deleted: Mutex::new(BTreeSet::new()),
+        };
+        std::fs::write(workspace.dir.join(MANIFEST_FILE), workspace.manifest_json()?)?;
+        info!("Created shadow workspace {} for {}", workspace.dir.display(), workspace.root.display());
+        Ok(workspace)
+    }
+
+    /// Whether a conversation has an overlay under `shadow_dir`
+    pub fn exists(shadow_dir: &Path, conversation_id: &str) -> bool {
+        Self::workspace_dir(shadow_dir, conversation_id)
+            .map(|dir| dir.join(MANIFEST_FILE).exists())
+            .unwrap_or(false)
+    }
+
+    /// Open the existing overlay of a conversation
+    pub fn open(shadow_dir: &Path, conversation_id: &str) -> Result<Self> {
+        let dir = Self::workspace_dir(shadow_dir, conversation_id)?;
+        let manifest = std::fs::read_to_string(dir.join(MANIFEST_FILE))
+            .map_err(|_| anyhow!("No shadow workspace for conversation {}", conversation_id))?;
+        let manifest: Manifest = serde_json::from_str(&manifest)
+            .with_context(|| format!("Corrupt shadow manifest in {}", dir.display()))?;
+
+        Ok(Self {
+            conversation_id: conversation_id.to_string(),
+            root: manifest.root,
+            dir,
+            deleted: Mutex::new(manifest.deleted),
+        })
+    }
+
+    pub fn conversation_id(&self) -> &str {
+        &self.conversation_id
+    }
+
+    /// Real project root the overlay applies to
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn overlay_path(&self, path: &Path) -> PathBuf {
+        self.dir.join(FILES_DIR).join(path)
+    }
+
+    fn is_deleted(&self, path: &Path) -> bool {
+        self.deleted.lock().unwrap_or_else(|e| e.into_inner()).contains(path)
+    }
+
+    fn set_deleted(&self, path: &Path, deleted: bool) -> bool {
+        let mut set = self.deleted.lock().unwrap_or_else(|e| e.into_inner());
+        if deleted { set.insert(path.to_path_buf()) } else { set.remove(path) }
+    }
+
+    fn manifest_json(&self) -> Result<String> {
+        let manifest = Manifest {
+            root: self.root.clone(),
+            deleted: self.deleted.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        };
+        Ok(serde_json::to_string_pretty(&manifest)?)
+    }
+
+    async fn save_manifest(&self) -> io::Result<()> {
+        let json = self.manifest_json().map_err(io::Error::other)?;
+        fs::write(self.dir.join(MANIFEST_FILE), json).await
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, format!("{} was deleted in this execution", path.display()))
+    }
+
+    /// Path to read a file from: the overlay copy if there is one, otherwise the real file
+    async fn effective_path(&self, path: &Path) -> io::Result<PathBuf> {
+        if self.is_deleted(path) {
+            return Err(Self::not_found(path));
+        }
+        let overlay = self.overlay_path(path);
+        if fs::try_exists(&overlay).await? {
+            Ok(overlay)
+        } else {
+            Ok(self.root.join(path))
+        }
+    }
+
+    /// Blocking `read_to_string`, for callers outside async code
+    pub fn read_to_string_blocking(&self, path: &Path) -> io::Result<String> {
+        if self.is_deleted(path) {
+            return Err(Self::not_found(path));
+        }
+        let overlay = self.overlay_path(path);
+        if overlay.try_exists()? {
+            std::fs::read_to_string(overlay)
+        } else {
+            std::fs::read_to_string(self.root.join(path))
+        }
+    }
+
+    pub async fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(self.effective_path(path).await?).await
+    }
+
+    pub async fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(self.effective_path(path).await?).await
+    }
+
+    pub async fn metadata(&self, path: &Path) -> io::Result<std::fs::Metadata> {
+        fs::metadata(self.effective_path(path).await?).await
+    }
+
+    pub async fn write(&self, path: &Path, content: impl AsRef<[u8]>) -> io::Result<()> {
+        let overlay = self.overlay_path(path);
+        if let Some(parent) = overlay.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&overlay, content).await?;
+        if self.set_deleted(path, false) {
+            self.save_manifest().await?;
+        }
+        debug!("Shadow write {}", path.display());
+        Ok(())
+    }
+
+    pub async fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(self.overlay_path(path)).await
+    }
+
+    pub async fn remove_file(&self, path: &Path) -> io::Result<()> {
+        if self.is_deleted(path) {
+            return Err(Self::not_found(path));
+        }
+        let overlay = self.overlay_path(path);
+        let in_overlay = fs::try_exists(&overlay).await?;
+        let in_root = fs::metadata(self.root.join(path)).await.map(|m| m.is_file()).unwrap_or(false);
+        if !in_overlay && !in_root {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())));
+        }
+
+        if in_overlay {
+            fs::remove_file(&overlay).await?;
+        }
+        if in_root {
+            self.set_deleted(path, true);
+            self.save_manifest().await?;
+        }
+        debug!("Shadow delete {}", path.display());
+        Ok(())
+    }
+
+    /// Entries of a directory as (name, is_dir), merging the real tree and the overlay
+    pub async fn read_dir(&self, path: &Path) -> io::Result<Vec<(String, bool)>> {
+        let mut entries = BTreeSet::new();
+        let mut found = false;
+
+        for (dir, is_overlay) in [(self.root.join(path), false), (self.overlay_path(path), true)] {
+            let Ok(mut reader) = fs::read_dir(&dir).await else { continue };
+            found = true;
+            while let Some(entry) = reader.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !is_overlay && self.is_deleted(&path.join(&name)) {
+                    continue;
+                }
+                entries.insert((name, entry.file_type().await?.is_dir()));
+            }
+        }
+
+        if !found {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())));
+        }
+        Ok(entries.into_iter().collect())
+    }
+
+    /// Files written into the overlay, relative to the project root
+    async fn overlay_files(&self) -> Result<Vec<PathBuf>> {
+        let base = self.dir.join(FILES_DIR);
+        let mut files = Vec::new();
+        let mut pending = vec![base.clone()];
+        while let Some(dir) = pending.pop() {
+            let mut reader = fs::read_dir(&dir).await?;
+            while let Some(entry) = reader.next_entry().await? {
+                if entry.file_type().await?.is_dir() {
+                    pending.push(entry.path());
+                } else {
+                    files.push(entry.path().strip_prefix(&base)?.to_path_buf());
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// All changes of the overlay against the real tree, sorted by path
+    pub async fn changes(&self) -> Result<Vec<ShadowChange>> {
+        let mut changes = Vec::new();
+        for path in self.overlay_files().await? {
+            let before = fs::read(self.root.join(&path)).await.ok();
+            let after = fs::read(self.overlay_path(&path)).await
+                .with_context(|| format!("Failed to read shadow copy of {}", path.display()))?;
+            if before.as_deref() != Some(after.as_slice()) {
+                changes.push(ShadowChange { path, before, after: Some(after) });
+            }
+        }
+
+        let deleted = self.deleted.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        for path in deleted {
+            let before = fs::read(self.root.join(&path)).await.ok();
+            changes.push(ShadowChange { path, before, after: None });
+        }
+
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(changes)
+    }
+
+    /// Combined unified diff of all changes; binary files are only listed
+    pub async fn diff(&self) -> Result<String> {
+        Ok(self.changes().await?.iter().map(change_diff).collect())
+    }
+
+    /// Compute the combined diff and store it next to the overlay
+    pub async fn store_diff(&self) -> Result<String> {
+        let diff = self.diff().await?;
+        fs::write(self.dir.join(DIFF_FILE), &diff).await?;
+        Ok(diff)
+    }
+
+    /// Commit all changes to the real tree and remove the overlay; returns the changed paths
+    pub async fn apply(&self) -> Result<Vec<PathBuf>> {
+        let changes = self.changes().await?;
+        for change in &changes {
+            let target = self.root.join(&change.path);
+            match &change.after {
+                Some(content) => {
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent).await?;
+                    }
+                    fs::write(&target, content).await
+                        .with_context(|| format!("Failed to apply {}", change.path.display()))?;
+                }
+                None => {
+                    if let Err(e) = fs::remove_file(&target).await {
+                        if e.kind() != io::ErrorKind::NotFound {
+                            return Err(e).with_context(|| format!("Failed to delete {}", change.path.display()));
+                        }
+                    }
+                }
+            }
+        }
+        info!("Applied {} shadow changes of conversation {}", changes.len(), self.conversation_id);
+
+        self.discard().await?;
+        Ok(changes.into_iter().map(|change| change.path).collect())
+    }
+
+    /// Drop the overlay without touching the real tree
+    pub async fn discard(&self) -> Result<()> {
+        fs::remove_dir_all(&self.dir).await
+            .with_context(|| format!("Failed to remove shadow workspace {}", self.dir.display()))?;
+        self.deleted.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        Ok(())
+    }
+}
+
+/// Unified diff of one change, or a `Binary files ... differ` line if either side is not UTF-8
+fn change_diff(change: &ShadowChange) -> String {
+    let path = change.path.to_string_lossy();
+    fn text(content: &Option<Vec<u8>>) -> Option<&str> {
+        match content {
+            Some(bytes) => std::str::from_utf8(bytes).ok(),
+            None => Some(""),
+        }
+    }
+    match (text(&change.before), text(&change.after)) {
+        (Some(before), Some(after)) => unified_diff(&path, before, after),
+        _ => format!("Binary files a/{} and b/{} differ\n", path, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shadow-test-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_overlay_reads_diff_and_apply() {
+        let root = temp_dir("root");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(root.join("old.txt"), "old\n").unwrap();
+
+        let shadow_dir = temp_dir("overlay");
+        let shadow = ShadowWorkspace::create(&shadow_dir, "conv-1", &root).unwrap();
+
+        shadow.write(Path::new("src/lib.rs"), "fn b() {}\n").await.unwrap();
+        shadow.write(Path::new("src/new.rs"), "fn c() {}\n").await.unwrap();
+        shadow.remove_file(Path::new("old.txt")).await.unwrap();
+
+        // The real tree is untouched, reads see the overlay
+        assert_eq!(std::fs::read_to_string(root.join("src/lib.rs")).unwrap(), "fn a() {}\n");
+        assert_eq!(shadow.read_to_string(Path::new("src/lib.rs")).await.unwrap(), "fn b() {}\n");
+        assert!(shadow.read_to_string(Path::new("old.txt")).await.is_err());
+        assert_eq!(shadow.read_to_string_blocking(Path::new("src/lib.rs")).unwrap(), "fn b() {}\n");
+        assert!(shadow.read_to_string_blocking(Path::new("old.txt")).is_err());
+        let listing = shadow.read_dir(Path::new("src")).await.unwrap();
+        assert_eq!(listing, vec![("lib.rs".to_string(), false), ("new.rs".to_string(), false)]);
+        assert!(!shadow.read_dir(Path::new("")).await.unwrap().iter().any(|(name, _)| name == "old.txt"));
+
+        // Deletions survive reopening
+        let reopened = ShadowWorkspace::open(&shadow_dir, "conv-1").unwrap();
+        let paths: Vec<PathBuf> = reopened.changes().await.unwrap().into_iter().map(|c| c.path).collect();
+        assert_eq!(paths, vec![PathBuf::from("old.txt"), PathBuf::from("src/lib.rs"), PathBuf::from("src/new.rs")]);
+
+        let diff = reopened.store_diff().await.unwrap();
+        assert!(diff.contains("-fn a() {}\n+fn b() {}\n"));
+        assert!(diff.contains("+++ b/src/new.rs"));
+        assert!(diff.contains("-old\n"));
+
+        reopened.apply().await.unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("src/lib.rs")).unwrap(), "fn b() {}\n");
+        assert_eq!(std::fs::read_to_string(root.join("src/new.rs")).unwrap(), "fn c() {}\n");
+        assert!(!root.join("old.txt").exists());
+        assert!(!ShadowWorkspace::exists(&shadow_dir, "conv-1"));
+        assert!(ShadowWorkspace::open(&shadow_dir, "../conv-1").is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_dir_all(shadow_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_binary_changes() {
+        let root = temp_dir("binary-root");
+        std::fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G', 0xff]).unwrap();
+        std::fs::write(root.join("same.bin"), [0xfe, 0x00]).unwrap();
+
+        let shadow_dir = temp_dir("binary-overlay");
+        let shadow = ShadowWorkspace::create(&shadow_dir, "conv-2", &root).unwrap();
+        shadow.write(Path::new("logo.png"), [0x89, b'P', b'N', b'G', 0x00]).await.unwrap();
+        shadow.write(Path::new("same.bin"), [0xfe, 0x00]).await.unwrap();
+
+        let changes = shadow.changes().await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, PathBuf::from("logo.png"));
+        assert_eq!(shadow.diff().await.unwrap(), "Binary files a/logo.png and b/logo.png differ\n");
+
+        shadow.apply().await.unwrap();
+        assert_eq!(std::fs::read(root.join("logo.png")).unwrap(), [0x89, b'P', b'N', b'G', 0x00]);
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_dir_all(shadow_dir).unwrap();
+    }
+}
@@ -1,11 +1,195 @@
 //! Token budget management
 
-use crate::error::AgentNetworkResult;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
 
+use ai_agent_common::{ContextSectionKind, EventType, PruneAction, PrunedSection, TokenBudgetConfig, TokenUsage};
+
+use crate::error::{AgentNetworkError, AgentNetworkResult};
+
+/// Count tokens with the cl100k tokenizer, used when a provider reports no usage
+pub fn count_tokens(text: &str) -> usize {
+    tiktoken_rs::cl100k_base_singleton().encode_ordinary(text).len()
+}
+
+/// Tokens an excerpt of a summarized section is cut to
+const SUMMARY_TOKENS: usize = 64;
+
+/// A part of a prompt that can be pruned on its own
+#[derive(Debug, Clone)]
+pub struct ContextSection {
+    pub kind: ContextSectionKind,
+    pub label: String,
+    pub content: String,
+    /// Relevance to the task, 0.0 to 1.0
+    pub relevance: f32,
+    /// Position in time; higher is more recent
+    pub recency: usize,
+    /// Pinned sections are never pruned
+    pub pinned: bool,
+}
+
+impl ContextSection {
+    /// The task description is pinned, everything else starts with neutral relevance
+    pub fn new(kind: ContextSectionKind, label: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            kind,
+            label: label.into(),
+            content: content.into(),
+            relevance: 0.5,
+            recency: 0,
+            pinned: kind == ContextSectionKind::Task,
+        }
+    }
+
+    pub fn with_relevance(mut self, relevance: f32) -> Self {
+        self.relevance = relevance.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_recency(mut self, recency: usize) -> Self {
+        self.recency = recency;
+        self
+    }
+
+    pub fn pinned(mut self) -> Self {
+        self.pinned = true;
+        self
+    }
+}
+
+/// Outcome of pruning a set of sections
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PruningReport {
+    pub budget: usize,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+    pub pruned: Vec<PrunedSection>,
+}
+
+impl PruningReport {
+    /// Whether nothing had to be pruned
+    pub fn is_empty(&self) -> bool {
+        self.pruned.is_empty()
+    }
+
+    pub fn into_event(self) -> EventType {
+        EventType::ContextPruned {
+            budget: self.budget,
+            tokens_before: self.tokens_before,
+            tokens_after: self.tokens_after,
+            sections: self.pruned,
+        }
+    }
+}
+
+/// Shorten sections until they fit into `budget` tokens
+///
+/// Unpinned sections are ranked by relevance and recency. The lowest-ranked ones are
+/// first condensed to an excerpt, and elided entirely if that is not enough.
+pub fn prune_sections(sections: &mut [ContextSection], budget: usize) -> PruningReport {
+    let tokens_before: Vec<usize> = sections.iter().map(|section| count_tokens(&section.content)).collect();
+    let mut total: usize = tokens_before.iter().sum();
+    let mut report = PruningReport { budget, tokens_before: total, tokens_after: total, pruned: Vec::new() };
+    if total <= budget {
+        return report;
+    }
+
+    let newest = sections.iter().map(|section| section.recency).max().unwrap_or(0).max(1) as f32;
+    let value = |section: &ContextSection| 0.7 * section.relevance + 0.3 * section.recency as f32 / newest;
+    let mut order: Vec<usize> = (0..sections.len()).filter(|&i| !sections[i].pinned).collect();
+    // Stable sort keeps earlier sections first among equally valuable ones
+    order.sort_by(|&a, &b| value(&sections[a]).total_cmp(&value(&sections[b])));
+
+    let mut tokens = tokens_before.clone();
+    for action in [PruneAction::Summarized, PruneAction::Elided] {
+        for &i in &order {
+            if total <= budget {
+                break;
+            }
+            let section = &mut sections[i];
+            let content = match action {
+                PruneAction::Summarized if tokens[i] > SUMMARY_TOKENS * 2 => excerpt(&section.content, SUMMARY_TOKENS, tokens[i]),
+                PruneAction::Summarized => continue,
+                PruneAction::Elided => format!("[{} elided: {} tokens]", section.label, tokens_before[i]),
+            };
+            let pruned_tokens = count_tokens(&content);
+            if pruned_tokens >= tokens[i] {
+                continue;
+            }
+            total = total - tokens[i] + pruned_tokens;
+            tokens[i] = pruned_tokens;
+            section.content = content;
+
+            report.pruned.retain(|pruned| pruned.label != section.label || pruned.kind != section.kind);
+            report.pruned.push(PrunedSection {
+                label: section.label.clone(),
+                kind: section.kind,
+                action,
+                tokens_before: tokens_before[i],
+                tokens_after: pruned_tokens,
+            });
+        }
+    }
+    report.tokens_after = total;
+    report
+}
+
+/// Leading lines of `content` within `max_tokens`, noting how much was omitted
+fn excerpt(content: &str, max_tokens: usize, total_tokens: usize) -> String {
+    let mut kept = String::new();
+    let mut kept_tokens = 0;
+    for line in content.lines() {
+        let line_tokens = count_tokens(line) + 1;
+        if kept_tokens + line_tokens > max_tokens {
+            if kept.is_empty() {
+                // A single long line: cut it on a character boundary
+                kept = line.chars().take(max_tokens * 4).collect();
+                kept_tokens = count_tokens(&kept);
+            }
+            break;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+        kept_tokens += line_tokens;
+    }
+    format!("{}[... {} more tokens omitted]", kept, total_tokens.saturating_sub(kept_tokens))
+}
+
+/// A soft budget that was crossed by a recorded usage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetWarning {
+    pub scope: String,
+    pub used: usize,
+    pub limit: usize,
+}
+
+/// Tokens consumed so far within one execution
+#[derive(Debug, Default)]
+struct TokenLedger {
+    execution: TokenUsage,
+    by_agent: HashMap<String, TokenUsage>,
+    by_task: HashMap<String, TokenUsage>,
+    /// Scopes whose soft limit was already reported
+    warned: HashSet<String>,
+}
+
+/// Tracks the token usage of one execution and enforces its budgets
+///
+/// Hard limits are checked before each LLM call, so the call that crosses a limit
+/// completes and the next one fails. Soft limits are reported once per scope.
+#[derive(Debug)]
 pub struct TokenBudgetManager {
     max_tokens_per_agent: usize,
     enable_context_pruning: bool,
     enable_prompt_caching: bool,
+    /// Share of `max_tokens_per_agent` a context is pruned down to once it exceeds the limit
+    pruning_threshold: f32,
+    soft_tokens_per_agent: Option<usize>,
+    hard_tokens_per_agent: Option<usize>,
+    soft_tokens_per_execution: Option<usize>,
+    hard_tokens_per_execution: Option<usize>,
+    ledger: Mutex<TokenLedger>,
 }
 
 impl TokenBudgetManager {
@@ -18,14 +202,102 @@ impl TokenBudgetManager {
             max_tokens_per_agent,
             enable_context_pruning,
             enable_prompt_caching,
+            pruning_threshold: 0.8,
+            soft_tokens_per_agent: None,
+            hard_tokens_per_agent: None,
+            soft_tokens_per_execution: None,
+            hard_tokens_per_execution: None,
+            ledger: Mutex::new(TokenLedger::default()),
+        }
+    }
+
+    /// Create a manager with the budgets of the configuration
+    pub fn from_config(config: &TokenBudgetConfig) -> Self {
+        Self {
+            pruning_threshold: config.pruning_threshold,
+            soft_tokens_per_agent: config.soft_tokens_per_agent,
+            hard_tokens_per_agent: config.hard_tokens_per_agent,
+            soft_tokens_per_execution: config.soft_tokens_per_execution,
+            hard_tokens_per_execution: config.hard_tokens_per_execution,
+            ..Self::new(config.max_tokens_per_agent, config.enable_context_pruning, config.enable_prompt_caching)
         }
     }
 
     /// Estimate token count for text
     pub fn estimate_tokens(&self, text: &str) -> usize {
-        // TODO: Week 6 - Integrate tiktoken-rs for accurate token counting
-        // Rough approximation: 1 token ≈ 4 characters
-        (text.len() + 3) / 4
+        count_tokens(text)
+    }
+
+    /// Fail if the execution, or the given agent, has used up its hard budget
+    pub fn check(&self, agent_id: Option<&str>) -> AgentNetworkResult<()> {
+        let ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(limit) = self.hard_tokens_per_execution {
+            let used = ledger.execution.total();
+            if used >= limit {
+                return Err(AgentNetworkError::TokenBudgetExceeded { scope: "execution".to_string(), used, limit });
+            }
+        }
+        if let (Some(limit), Some(agent_id)) = (self.hard_tokens_per_agent, agent_id) {
+            let used = ledger.by_agent.get(agent_id).map(TokenUsage::total).unwrap_or(0);
+            if used >= limit {
+                return Err(AgentNetworkError::TokenBudgetExceeded { scope: format!("agent:{}", agent_id), used, limit });
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the usage of an LLM call, returning the soft budgets it crossed
+    pub fn record(&self, agent_id: &str, task_id: Option<&str>, usage: TokenUsage) -> Vec<BudgetWarning> {
+        let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        ledger.execution += usage;
+        *ledger.by_agent.entry(agent_id.to_string()).or_default() += usage;
+        if let Some(task_id) = task_id {
+            *ledger.by_task.entry(task_id.to_string()).or_default() += usage;
+        }
+
+        let agent_used = ledger.by_agent[agent_id].total();
+        let execution_used = ledger.execution.total();
+        let crossed = [
+            (format!("agent:{}", agent_id), agent_used, self.soft_tokens_per_agent),
+            ("execution".to_string(), execution_used, self.soft_tokens_per_execution),
+        ];
+
+        let mut warnings = Vec::new();
+        for (scope, used, limit) in crossed {
+            let Some(limit) = limit else { continue };
+            if used >= limit && ledger.warned.insert(scope.clone()) {
+                warnings.push(BudgetWarning { scope, used, limit });
+            }
+        }
+        warnings
+    }
+
+    /// Tokens used by the whole execution
+    pub fn total(&self) -> TokenUsage {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner()).execution
+    }
+
+    /// Tokens used by one agent
+    pub fn agent_usage(&self, agent_id: &str) -> TokenUsage {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner()).by_agent.get(agent_id).copied().unwrap_or_default()
+    }
+
+    /// Tokens used by one task, including failed attempts
+    pub fn task_usage(&self, task_id: &str) -> TokenUsage {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner()).by_task.get(task_id).copied().unwrap_or_default()
+    }
+
+    /// Prune sections once they exceed the per-agent context limit
+    ///
+    /// Pruning goes below the limit, to the pruning threshold, so that a growing
+    /// transcript is not pruned again on every call.
+    pub fn prune(&self, sections: &mut [ContextSection]) -> PruningReport {
+        let total: usize = sections.iter().map(|section| count_tokens(&section.content)).sum();
+        if !self.enable_context_pruning || total <= self.max_tokens_per_agent {
+            return PruningReport { budget: self.max_tokens_per_agent, tokens_before: total, tokens_after: total, pruned: Vec::new() };
+        }
+        let target = (self.max_tokens_per_agent as f32 * self.pruning_threshold) as usize;
+        prune_sections(sections, target)
     }
 
     /// Optimize context to fit within token budget
@@ -37,9 +309,6 @@ impl TokenBudgetManager {
         }
 
         if self.enable_context_pruning {
-            // TODO: Week 6 - Implement smart context pruning
-            // - Keep most relevant parts
-            // - Summarize or truncate less important parts
             let pruned = self.prune_context(context)?;
             Ok(pruned)
         } else {
@@ -47,15 +316,21 @@ impl TokenBudgetManager {
         }
     }
 
+    /// Prune a markdown context section by section, preferring to keep its later sections
     fn prune_context(&self, context: &str) -> AgentNetworkResult<String> {
-        // TODO: Week 6 - Implement smart pruning logic
-        // For now, simple truncation
-        let max_chars = self.max_tokens_per_agent * 4;
-        if context.len() > max_chars {
-            Ok(context[..max_chars].to_string())
-        } else {
-            Ok(context.to_string())
+        let mut sections: Vec<ContextSection> = Vec::new();
+        for line in context.split_inclusive('\n') {
+            match sections.last_mut() {
+                Some(section) if !line.starts_with("## ") => section.content.push_str(line),
+                _ => {
+                    let label = line.trim_start_matches('#').trim();
+                    let label = if label.is_empty() { format!("section {}", sections.len() + 1) } else { label.to_string() };
+                    sections.push(ContextSection::new(ContextSectionKind::RagFragment, label, line).with_recency(sections.len()));
+                }
+            }
         }
+        prune_sections(&mut sections, self.max_tokens_per_agent);
+        Ok(sections.into_iter().map(|section| section.content).collect())
     }
 
     /// Check if context fits within budget
@@ -63,3 +338,122 @@ impl TokenBudgetManager {
         self.estimate_tokens(context) <= self.max_tokens_per_agent
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budgets(soft_agent: Option<usize>, hard_agent: Option<usize>, soft_execution: Option<usize>, hard_execution: Option<usize>) -> TokenBudgetManager {
+        TokenBudgetManager::from_config(&TokenBudgetConfig {
+            soft_tokens_per_agent: soft_agent,
+            hard_tokens_per_agent: hard_agent,
+            soft_tokens_per_execution: soft_execution,
+            hard_tokens_per_execution: hard_execution,
+            ..TokenBudgetConfig::default()
+        })
+    }
+
+    fn section(kind: ContextSectionKind, label: &str, lines: usize) -> ContextSection {
+        let content: Vec<String> = (0..lines).map(|i| format!("line {} of {} with some words", i, label)).collect();
+        ContextSection::new(kind, label, content.join("\n"))
+    }
+
+    #[test]
+    fn test_low_value_sections_are_pruned_first() {
+        let mut sections = vec![
+            section(ContextSectionKind::Task, "task", 40),
+            section(ContextSectionKind::History, "old history", 40).with_relevance(0.2),
+            section(ContextSectionKind::RagFragment, "parser.rs", 40).with_relevance(0.9),
+            section(ContextSectionKind::ToolResult, "read_file", 40).with_recency(5).pinned(),
+        ];
+        let before: Vec<String> = sections.iter().map(|section| section.content.clone()).collect();
+        let total: usize = before.iter().map(|content| count_tokens(content)).sum();
+
+        let report = prune_sections(&mut sections, total - 100);
+        assert!(report.tokens_after <= total - 100);
+        assert_eq!(report.pruned.len(), 1);
+        assert_eq!(report.pruned[0].label, "old history");
+        assert_eq!(report.pruned[0].action, PruneAction::Summarized);
+        assert!(sections[1].content.contains("more tokens omitted"));
+        assert_eq!(sections[0].content, before[0]);
+        assert_eq!(sections[2].content, before[2]);
+        assert_eq!(sections[3].content, before[3]);
+    }
+
+    #[test]
+    fn test_sections_are_elided_when_excerpts_do_not_fit() {
+        let mut sections = vec![
+            section(ContextSectionKind::Task, "task", 10),
+            section(ContextSectionKind::DependencyOutput, "task-1", 60).with_recency(1),
+            section(ContextSectionKind::DependencyOutput, "task-2", 60).with_recency(2),
+        ];
+        let task_tokens = count_tokens(&sections[0].content);
+
+        let report = prune_sections(&mut sections, task_tokens + 100);
+        assert!(report.tokens_after <= task_tokens + 100);
+        // The older dependency goes first
+        assert_eq!(sections[1].content, format!("[task-1 elided: {} tokens]", report.pruned[0].tokens_before));
+        assert!(report.pruned.iter().all(|pruned| pruned.label != "task"));
+    }
+
+    #[test]
+    fn test_prune_context_is_utf8_safe() {
+        let manager = TokenBudgetManager::new(50, true, false);
+        let context = format!("## a\n{}\n## b\n{}\n", "größe ü ".repeat(200), "日本語のテキスト".repeat(100));
+        let pruned = manager.optimize_context(&context).unwrap();
+        assert!(count_tokens(&pruned) <= 50);
+        assert!(pruned.starts_with("## a") || pruned.starts_with("[a"));
+
+        assert_eq!(manager.optimize_context("short").unwrap(), "short");
+    }
+
+    #[test]
+    fn test_count_tokens_uses_tokenizer() {
+        assert_eq!(count_tokens(""), 0);
+        assert_eq!(count_tokens("hello world"), 2);
+        // Non-ASCII text is not counted by bytes
+        assert!(count_tokens("größenwahn") < "größenwahn".len());
+    }
+
+    #[test]
+    fn test_usage_is_aggregated_per_agent_task_and_execution() {
+        let manager = budgets(None, None, None, None);
+        manager.record("coding-1", Some("task-1"), TokenUsage::new(100, 20));
+        manager.record("coding-1", Some("task-2"), TokenUsage::new(50, 5));
+        manager.record("planning-1", None, TokenUsage { prompt_tokens: 10, completion_tokens: 1, estimated: true });
+
+        assert_eq!(manager.agent_usage("coding-1"), TokenUsage::new(150, 25));
+        assert_eq!(manager.task_usage("task-1").total(), 120);
+        assert_eq!(manager.total().total(), 186);
+        assert!(manager.total().estimated);
+        assert!(manager.check(Some("coding-1")).is_ok());
+    }
+
+    #[test]
+    fn test_soft_limits_warn_once() {
+        let manager = budgets(Some(100), None, Some(150), None);
+        assert!(manager.record("coding-1", None, TokenUsage::new(60, 0)).is_empty());
+
+        let warnings = manager.record("coding-1", None, TokenUsage::new(60, 0));
+        assert_eq!(warnings, vec![BudgetWarning { scope: "agent:coding-1".to_string(), used: 120, limit: 100 }]);
+
+        let warnings = manager.record("coding-1", None, TokenUsage::new(60, 0));
+        assert_eq!(warnings, vec![BudgetWarning { scope: "execution".to_string(), used: 180, limit: 150 }]);
+        assert!(manager.record("writing-1", None, TokenUsage::new(10, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_hard_limits_abort() {
+        let manager = budgets(None, Some(100), None, Some(250));
+        manager.record("coding-1", None, TokenUsage::new(90, 10));
+        assert!(matches!(
+            manager.check(Some("coding-1")),
+            Err(AgentNetworkError::TokenBudgetExceeded { used: 100, limit: 100, .. })
+        ));
+        assert!(manager.check(Some("writing-1")).is_ok());
+
+        manager.record("writing-1", None, TokenUsage::new(150, 0));
+        let error = manager.check(None).unwrap_err();
+        assert!(error.to_string().contains("execution"));
+    }
+}
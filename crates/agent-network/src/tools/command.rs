@@ -0,0 +1,439 @@
+//! Command execution tools (`run_command`, `run_tests`) confined to the project root
+//!
+//! Commands are spawned directly (no shell), with a scrubbed environment, a wall-clock
+//! timeout and a head/tail-truncated output cap. Programs on the configured allow-list
+//! are classified as medium risk; anything else is critical and always goes through HITL.
+
+use async_trait::async_trait;
+use anyhow::{anyhow, Result};
+use ai_agent_common::{CommandConfig, RiskLevel};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tracing::{debug, instrument, warn};
+use crate::tools::{ToolResult, TypedTool};
+
+/// Output of a finished (or killed) command
+#[derive(Debug, Clone)]
+struct CommandOutput {
+    exit_code: Option<i32>,
+    timed_out: bool,
+    duration: Duration,
+    stdout: String,
+    stderr: String,
+}
+
+impl CommandOutput {
+    fn success(&self) -> bool {
+        !self.timed_out && self.exit_code == Some(0)
+    }
+
+    fn render(&self, command_line: &str) -> String {
+        let status = match (self.timed_out, self.exit_code) {
+            (true, _) => "timed out (killed)".to_string(),
+            (false, Some(code)) => format!("exit code {}", code),
+            (false, None) => "terminated by signal".to_string(),
+        };
+        let mut output = format!("$ {}\n{} after {:.1}s\n", command_line, status, self.duration.as_secs_f64());
+        if !self.stdout.is_empty() {
+            output.push_str("--- stdout ---\n");
+            output.push_str(&self.stdout);
+            if !self.stdout.ends_with('\n') {
+                output.push('\n');
+            }
+        }
+        if !self.stderr.is_empty() {
+            output.push_str("--- stderr ---\n");
+            output.push_str(&self.stderr);
+        }
+        output
+    }
+}
+
+/// Keeps the first and last `limit / 2` bytes of a stream, counting what was dropped
+#[derive(Debug, Default)]
+struct HeadTailBuffer {
+    limit: usize,
+    head: Vec<u8>,
+    tail: std::collections::VecDeque<u8>,
+    dropped: usize,
+}
+
+impl HeadTailBuffer {
+    fn new(limit: usize) -> Self {
+        Self { limit, ..Default::default() }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        let head_limit = self.limit / 2;
+        let tail_limit = self.limit - head_limit;
+        let mut bytes = bytes;
+
+        if self.head.len() < head_limit {
+            let take = (head_limit - self.head.len()).min(bytes.len());
+            self.head.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+        }
+
+        self.tail.extend(bytes);
+        while self.tail.len() > tail_limit {
+            self.tail.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    fn into_string(self) -> String {
+        let head = String::from_utf8_lossy(&self.head).into_owned();
+        let tail: Vec<u8> = self.tail.into_iter().collect();
+        let tail = String::from_utf8_lossy(&tail);
+        if self.dropped == 0 {
+            head + &tail
+        } else {
+            format!("{}\n... [{} bytes truncated] ...\n{}", head, self.dropped, tail)
+        }
+    }
+}
+
+// Shared process runner for the command tools
+#[derive(Debug, Clone)]
+struct CommandRunner {
+    base_path: PathBuf,
+    config: CommandConfig,
+}
+
+impl CommandRunner {
+    fn new(base_path: &str, config: CommandConfig) -> Self {
+        let base_path = PathBuf::from(base_path);
+        let base_path = std::fs::canonicalize(&base_path).unwrap_or(base_path);
+        Self { base_path, config }
+    }
+
+    fn resolve_working_dir(&self, relative: Option<&str>) -> Result<PathBuf> {
+        let Some(relative) = relative.filter(|r| !r.is_empty()) else {
+            return Ok(self.base_path.clone());
+        };
+        let target = std::fs::canonicalize(self.base_path.join(relative.trim_start_matches('/')))
+            .map_err(|e| anyhow!("Invalid working directory {}: {}", relative, e))?;
+        if !target.starts_with(&self.base_path) {
+            return Err(anyhow!("Access denied: {} is outside the workspace", relative));
+        }
+        Ok(target)
+    }
+
+    fn classify(&self, program: &str) -> RiskLevel {
+        if self.config.is_allowed(program) {
+            RiskLevel::Medium
+        } else {
+            RiskLevel::Critical
+        }
+    }
+
+    async fn run(&self, program: &str, args: &[String], working_dir: Option<&str>) -> Result<CommandOutput> {
+        self.run_with_env(program, args, working_dir, &std::env::vars().collect()).await
+    }
+
+    /// Run a program seeing only the `env_passthrough` variables of `env`
+    async fn run_with_env(&self, program: &str, args: &[String], working_dir: Option<&str>, env: &HashMap<String, String>) -> Result<CommandOutput> {
+        let cwd = self.resolve_working_dir(working_dir)?;
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .current_dir(&cwd)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        for name in &self.config.env_passthrough {
+            if let Some(value) = env.get(name) {
+                command.env(name, value);
+            }
+        }
+
+        let start = Instant::now();
+        let mut child = command.spawn()
+            .map_err(|e| anyhow!("Failed to start '{}': {}", program, e))?;
+
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("stdout unavailable"))?;
+        let stderr = child.stderr.take().ok_or_else(|| anyhow!("stderr unavailable"))?;
+        let limit = self.config.max_output_bytes;
+        let stdout_task = tokio::spawn(Self::collect(stdout, limit));
+        let stderr_task = tokio::spawn(Self::collect(stderr, limit));
+
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        let (exit_code, timed_out) = match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => (status?.code(), false),
+            Err(_) => {
+                warn!("Command '{}' exceeded {:?}, killing it", program, timeout);
+                let _ = child.kill().await;
+                (None, true)
+            }
+        };
+
+        // Grandchildren may keep the pipes open; don't wait on them forever
+        let grace = Duration::from_secs(2);
+        let stdout = tokio::time::timeout(grace, stdout_task).await.ok().and_then(|r| r.ok()).unwrap_or_default();
+        let stderr = tokio::time::timeout(grace, stderr_task).await.ok().and_then(|r| r.ok()).unwrap_or_default();
+
+        Ok(CommandOutput { exit_code, timed_out, duration: start.elapsed(), stdout, stderr })
+    }
+
+    async fn collect(mut stream: impl AsyncRead + Unpin, limit: usize) -> String {
+        let mut buffer = HeadTailBuffer::new(limit);
+        let mut chunk = [0u8; 8192];
+        loop {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => buffer.push(&chunk[..n]),
+            }
+        }
+        buffer.into_string()
+    }
+
+    fn to_tool_result(result: Result<CommandOutput>, command_line: &str) -> ToolResult {
+        let current_span = tracing::Span::current();
+        match result {
+            Ok(output) => {
+                current_span.record("success", output.success());
+                if let Some(code) = output.exit_code {
+                    current_span.record("exit_code", code);
+                }
+                ToolResult { success: output.success(), output: output.render(command_line) }
+            }
+            Err(e) => {
+                let error_msg = format!("Command error: {}", e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                ToolResult { success: false, output: error_msg }
+            }
+        }
+    }
+}
+
+fn command_line(program: &str, args: &[String]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RunCommandParams {
+    #[schemars(description = "The program to run (e.g. \"cargo\"). No shell is involved, so pipes and redirects are not supported.")]
+    pub program: String,
+    #[schemars(description = "Arguments passed to the program, one per element.")]
+    pub args: Vec<String>,
+    #[schemars(description = "Optional working directory relative to the project root.")]
+    pub working_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RunTestsParams {
+    #[schemars(description = "Optional test name filter appended to the configured test command.")]
+    pub filter: Option<String>,
+    #[schemars(description = "Optional working directory relative to the project root.")]
+    pub working_dir: Option<String>,
+}
+
+// RunCommandTool - Run a program inside the project root
+#[derive(Debug, Clone)]
+pub struct RunCommandTool {
+    runner: CommandRunner,
+}
+
+impl RunCommandTool {
+    pub fn new(base_path: &str) -> Self {
+        Self::with_config(base_path, CommandConfig::default())
+    }
+
+    pub fn with_config(base_path: &str, config: CommandConfig) -> Self {
+        Self { runner: CommandRunner::new(base_path, config) }
+    }
+}
+
+#[async_trait]
+impl TypedTool for RunCommandTool {
+    type Params = RunCommandParams;
+
+    fn name(&self) -> &str {
+        "run_command"
+    }
+
+    fn description(&self) -> &str {
+        "Run a program with arguments inside the project root (e.g. build or lint commands). Returns exit code, stdout and stderr. Commands outside the allow-list require human approval."
+    }
+
+    fn risk_level(&self, params: &Self::Params) -> Option<RiskLevel> {
+        Some(self.runner.classify(&params.program))
+    }
+
+    #[instrument(name = "run_command_tool", skip(self), fields(
+        tool_name = "run_command",
+        program = %parameters.program,
+        success = tracing::field::Empty,
+        exit_code = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(
+        &self,
+        parameters: Self::Params
+    ) -> Result<ToolResult> {
+        let line = command_line(&parameters.program, &parameters.args);
+        debug!("Running command: {}", line);
+        let result = self.runner.run(&parameters.program, &parameters.args, parameters.working_dir.as_deref()).await;
+        Ok(CommandRunner::to_tool_result(result, &line))
+    }
+}
+
+// RunTestsTool - Run the configured test command inside the project root
+#[derive(Debug, Clone)]
+pub struct RunTestsTool {
+    runner: CommandRunner,
+}
+
+impl RunTestsTool {
+    pub fn new(base_path: &str) -> Self {
+        Self::with_config(base_path, CommandConfig::default())
+    }
+
+    pub fn with_config(base_path: &str, config: CommandConfig) -> Self {
+        Self { runner: CommandRunner::new(base_path, config) }
+    }
+}
+
+#[async_trait]
+impl TypedTool for RunTestsTool {
+    type Params = RunTestsParams;
+
+    fn name(&self) -> &str {
+        "run_tests"
+    }
+
+    fn description(&self) -> &str {
+        "Run the project's test suite, optionally filtered by test name. Returns exit code and (truncated) test output."
+    }
+
+    fn risk_level(&self, _params: &Self::Params) -> Option<RiskLevel> {
+        let program = self.runner.config.test_command.first().map(String::as_str).unwrap_or_default();
+        Some(self.runner.classify(program))
+    }
+
+    #[instrument(name = "run_tests_tool", skip(self), fields(
+        tool_name = "run_tests",
+        filter = ?parameters.filter,
+        success = tracing::field::Empty,
+        exit_code = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(
+        &self,
+        parameters: Self::Params
+    ) -> Result<ToolResult> {
+        let Some((program, args)) = self.runner.config.test_command.split_first() else {
+            return Ok(CommandRunner::to_tool_result(Err(anyhow!("No test command configured")), ""));
+        };
+        let mut args = args.to_vec();
+        if let Some(filter) = parameters.filter.filter(|f| !f.is_empty()) {
+            // An option here could reconfigure the runner (e.g. cargo's --config) to run arbitrary code
+            if filter.starts_with('-') {
+                return Ok(CommandRunner::to_tool_result(Err(anyhow!("Test filter must be a test name, not an option: {}", filter)), ""));
+            }
+            args.push(filter);
+        }
+
+        let line = command_line(program, &args);
+        debug!("Running tests: {}", line);
+        let result = self.runner.run(program, &args, parameters.working_dir.as_deref()).await;
+        Ok(CommandRunner::to_tool_result(result, &line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    fn config(timeout_secs: u64, max_output_bytes: usize) -> CommandConfig {
+        CommandConfig {
+            allowed_commands: vec!["echo".to_string()],
+            test_command: vec!["sh".to_string(), "-c".to_string(), "echo tests ok".to_string()],
+            timeout_secs,
+            max_output_bytes,
+            env_passthrough: vec!["PATH".to_string()],
+        }
+    }
+
+    fn run(program: &str, args: &[&str]) -> RunCommandParams {
+        RunCommandParams {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            working_dir: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_command_and_scrubs_environment() {
+        let tool = RunCommandTool::with_config(".", config(10, 10_000));
+        let env = HashMap::from([
+            ("PATH".to_string(), std::env::var("PATH").unwrap_or_default()),
+            ("Q_SECRET_TOKEN".to_string(), "hunter2".to_string()),
+        ]);
+
+        let args = ["-c".to_string(), "echo hello; env".to_string()];
+        let output = tool.runner.run_with_env("sh", &args, None, &env).await.unwrap();
+        assert!(output.success(), "{}", output.stderr);
+        assert!(output.stdout.contains("hello"));
+        assert!(output.stdout.contains("PATH="));
+        assert!(!output.stdout.contains("hunter2"));
+
+        let result = TypedTool::call(&tool, run("sh", &["-c", "echo hello"])).await.unwrap();
+        assert!(result.success, "{}", result.output);
+        assert!(result.output.contains("exit code 0"));
+
+        let failed = TypedTool::call(&tool, run("sh", &["-c", "exit 3"])).await.unwrap();
+        assert!(!failed.success);
+        assert!(failed.output.contains("exit code 3"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_truncation() {
+        let tool = RunCommandTool::with_config(".", config(1, 100));
+
+        let slow = TypedTool::call(&tool, run("sleep", &["5"])).await.unwrap();
+        assert!(!slow.success);
+        assert!(slow.output.contains("timed out"));
+
+        let tool = RunCommandTool::with_config(".", config(10, 100));
+        let noisy = TypedTool::call(&tool, run("sh", &["-c", "echo START; seq 1 2000; echo END"])).await.unwrap();
+        assert!(noisy.output.contains("START"));
+        assert!(noisy.output.contains("END"));
+        assert!(noisy.output.contains("bytes truncated"));
+    }
+
+    #[tokio::test]
+    async fn test_run_tests_uses_configured_command() {
+        let tool = RunTestsTool::with_config(".", config(10, 10_000));
+        let result = TypedTool::call(&tool, RunTestsParams { filter: None, working_dir: None }).await.unwrap();
+        assert!(result.success, "{}", result.output);
+        assert!(result.output.contains("tests ok"));
+
+        let escape = TypedTool::call(&tool, RunTestsParams { filter: None, working_dir: Some("../..".into()) }).await.unwrap();
+        assert!(!escape.success);
+
+        let option = TypedTool::call(&tool, RunTestsParams { filter: Some("--config=build.rustc-wrapper=/tmp/x".into()), working_dir: None }).await.unwrap();
+        assert!(!option.success);
+        assert!(option.output.contains("not an option"), "{}", option.output);
+    }
+
+    #[test]
+    fn test_risk_follows_allow_list() {
+        let tool = RunCommandTool::with_config(".", config(10, 10_000));
+        assert_eq!(Tool::risk_level(&tool, r#"{"program": "echo", "args": [], "working_dir": null}"#), Some(RiskLevel::Medium));
+        assert_eq!(Tool::risk_level(&tool, r#"{"program": "rm", "args": ["-rf", "/"], "working_dir": null}"#), Some(RiskLevel::Critical));
+    }
+}
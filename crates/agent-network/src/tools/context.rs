@@ -0,0 +1,190 @@
+//! Context search tool exposing the RAG pipeline to agents
+//!
+//! Wraps `SmartMultiSourceRag::retrieve_stream_for_tiers` so agents can run their own
+//! retrieval mid-ReAct loop, instead of relying only on the context injected before the task.
+
+use async_trait::async_trait;
+use anyhow::Result;
+use ai_agent_common::{CollectionTier, ConversationId, Location, ProjectScope, RiskLevel};
+use ai_agent_rag::SmartMultiSourceRag;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::instrument;
+use crate::rag::{ContextBuilder, FormattedRagContext};
+use crate::tools::{ToolResult, TypedTool};
+
+/// Default token cap for a single search when the agent does not ask for one
+pub const DEFAULT_SEARCH_TOKENS: usize = 2000;
+
+/// Upper bound for `max_tokens` regardless of what the agent asks for
+pub const MAX_SEARCH_TOKENS: usize = 8000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchContextParams {
+    #[schemars(description = "Natural language query describing the information you need.")]
+    pub query: String,
+    #[schemars(description = "Optional list of sources to search: Workspace (project code), Personal (documents), System (man pages, /etc), Online (web docs). Defaults to automatic routing.")]
+    pub tiers: Option<Vec<CollectionTier>>,
+    #[schemars(description = "Maximum number of tokens of context to return (default 2000, max 8000).")]
+    pub max_tokens: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchContextTool {
+    rag: Arc<SmartMultiSourceRag>,
+    project_scope: ProjectScope,
+    conversation_id: ConversationId,
+}
+
+impl SearchContextTool {
+    pub fn new(rag: Arc<SmartMultiSourceRag>, project_scope: ProjectScope, conversation_id: ConversationId) -> Self {
+        Self { rag, project_scope, conversation_id }
+    }
+}
+
+#[async_trait]
+impl TypedTool for SearchContextTool {
+    type Params = SearchContextParams;
+
+    fn name(&self) -> &str {
+        "search_context"
+    }
+
+    fn description(&self) -> &str {
+        "Semantic search over indexed project code, personal documents, system docs and the web. Returns relevant fragments with their locations; use read_file on a location to see the full file."
+    }
+
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        true
+    }
+
+    fn risk_level(&self, _params: &Self::Params) -> Option<RiskLevel> {
+        Some(RiskLevel::Low)
+    }
+
+    #[instrument(name = "search_context_tool", skip(self), fields(
+        tool_name = "search_context",
+        fragments = tracing::field::Empty,
+        success = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(&self, params: Self::Params) -> Result<ToolResult> {
+        let current_span = tracing::Span::current();
+        let max_tokens = params.max_tokens.unwrap_or(DEFAULT_SEARCH_TOKENS).clamp(1, MAX_SEARCH_TOKENS);
+
+        let stream = match self.rag.clone().retrieve_stream_for_tiers(
+            params.query.clone(),
+            self.project_scope.clone(),
+            self.conversation_id.clone(),
+            params.tiers.clone(),
+        ).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                let error_msg = format!("Context search failed: {:#}", e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                return Ok(ToolResult { success: false, output: error_msg });
+            }
+        };
+
+        let context = match ContextBuilder::build_rag_context(stream, max_tokens).await {
+            Ok(context) => context,
+            Err(e) => {
+                let error_msg = format!("Failed to collect context: {}", e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                return Ok(ToolResult { success: false, output: error_msg });
+            }
+        };
+
+        current_span.record("fragments", context.fragment_count);
+        current_span.record("success", true);
+        Ok(ToolResult {
+            success: true,
+            output: format_search_results(&params.query, &context, &self.project_scope.root),
+        })
+    }
+}
+
+/// Render a location as something the agent can act on, with file paths relative to the project root
+fn format_location(location: &Location, root: &str) -> String {
+    match location {
+        Location::File { path, line_start, line_end, .. } => {
+            let path = Path::new(path).strip_prefix(root)
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_else(|_| path.clone());
+            match (line_start, line_end) {
+                (Some(start), Some(end)) if end > start => format!("{}:{}-{}", path, start, end),
+                (Some(start), _) => format!("{}:{}", path, start),
+                _ => path,
+            }
+        }
+        Location::URI { uri } => uri.clone(),
+        Location::WebContent { url, title, .. } => match title {
+            Some(title) => format!("{} ({})", url, title),
+            None => url.clone(),
+        },
+    }
+}
+
+fn format_search_results(query: &str, context: &FormattedRagContext, root: &str) -> String {
+    if context.is_empty() {
+        return format!("No context found for '{}'. Try rephrasing the query or searching other tiers.", query);
+    }
+
+    let mut output = format!(
+        "Found {} fragments (~{} tokens) for '{}'\n\nLocations:\n",
+        context.fragment_count, context.estimated_tokens, query
+    );
+    for (index, location) in context.locations.iter().enumerate() {
+        output.push_str(&format!("{}. {}\n", index + 1, format_location(location, root)));
+    }
+    output.push('\n');
+    output.push_str(&context.content);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_agent_common::{ContextFragment, MetadataContextFragment};
+
+    fn fragment(location: Location, content: &str) -> anyhow::Result<ContextFragment> {
+        Ok(ContextFragment {
+            content: content.to_string(),
+            metadata: MetadataContextFragment { location, structures: vec![], annotations: None },
+            relevance_score: 1,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_results_list_locations_within_token_cap() {
+        let fragments = vec![
+            fragment(Location::File {
+                path: "/project/src/lib.rs".into(),
+                line_start: Some(10),
+                line_end: Some(24),
+                project_root: Some("/project".into()),
+            }, "pub fn retrieve() {}"),
+            fragment(Location::URI { uri: "man:ls(1)".into() }, "list directory contents"),
+            fragment(Location::URI { uri: "https://example.com".into() }, &"x".repeat(4000)),
+        ];
+        let stream = Box::pin(futures::stream::iter(fragments));
+
+        let context = ContextBuilder::build_rag_context(stream, 100).await.unwrap();
+        assert_eq!(context.fragment_count, 2);
+
+        let output = format_search_results("retrieve", &context, "/project");
+        assert!(output.contains("1. src/lib.rs:10-24\n2. man:ls(1)\n"));
+        assert!(output.contains("pub fn retrieve() {}"));
+        assert!(!output.contains("example.com"));
+    }
+
+    #[test]
+    fn test_empty_results_suggest_rephrasing() {
+        let output = format_search_results("nothing", &FormattedRagContext::empty(), "/project");
+        assert!(output.starts_with("No context found for 'nothing'"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Delegation tool handing a sub-task to an agent of another type
+//!
+//! The sub-task runs as a new node of the live workflow (see `workflow::delegation`) while
+//! the delegating agent waits; its output comes back as the tool result.
+
+use async_trait::async_trait;
+use anyhow::Result;
+use ai_agent_common::llm::structured::parse_structured;
+use ai_agent_common::{AgentType, RiskLevel};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::instrument;
+use crate::tools::{ToolResult, TypedTool};
+use crate::workflow::delegation::{Delegation, DelegationRequest};
+use crate::workflow::TaskResult;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DelegateParams {
+    #[schemars(description = "Type of agent to hand the sub-task to, e.g. Writing for documentation or Evaluator for a review.")]
+    pub agent_type: AgentType,
+    #[schemars(description = "Self-contained description of the sub-task. The other agent does not see your conversation, so include every detail it needs.")]
+    pub description: String,
+    #[schemars(description = "Optional JSON schema the sub-task's output must match.")]
+    pub output_schema: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DelegateTool {
+    delegation: Delegation,
+    /// Task of the agent using the tool
+    task_id: String,
+}
+
+impl DelegateTool {
+    pub fn new(delegation: Delegation, task_id: String) -> Self {
+        Self { delegation, task_id }
+    }
+}
+
+#[async_trait]
+impl TypedTool for DelegateTool {
+    type Params = DelegateParams;
+
+    fn name(&self) -> &str {
+        "delegate"
+    }
+
+    fn description(&self) -> &str {
+        "Hand a sub-task to another agent (e.g. have docs written or your changes reviewed) and wait for its result. Use it for work outside your specialty, not for steps you can do yourself."
+    }
+
+    fn risk_level(&self, _params: &Self::Params) -> Option<RiskLevel> {
+        // The sub-task's own tool calls go through approval
+        Some(RiskLevel::Low)
+    }
+
+    #[instrument(name = "delegate_tool", skip(self), fields(
+        tool_name = "delegate",
+        parent_task_id = %self.task_id,
+        depth = self.delegation.depth(),
+        success = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(&self, params: Self::Params) -> Result<ToolResult> {
+        let current_span = tracing::Span::current();
+        let output_schema = params.output_schema.clone();
+        let request = DelegationRequest {
+            agent_type: params.agent_type,
+            description: params.description,
+            output_schema: params.output_schema,
+        };
+
+        let result = match self.delegation.delegate(&self.task_id, request).await {
+            Ok(result) => delegation_result(&result, output_schema.as_ref()),
+            Err(e) => ToolResult { success: false, output: format!("Delegation failed: {}", e) },
+        };
+        current_span.record("success", result.success);
+        if !result.success {
+            current_span.record("error", result.output.as_str());
+        }
+        Ok(result)
+    }
+}
+
+/// Tool result for a finished sub-task; output not matching the requested schema is a failure
+fn delegation_result(result: &TaskResult, output_schema: Option<&Value>) -> ToolResult {
+    if !result.success {
+        return ToolResult {
+            success: false,
+            output: format!("Delegated task {} failed: {}", result.task_id, result.error.as_deref().unwrap_or("unknown error")),
+        };
+    }
+
+    let output = result.output.as_deref().unwrap_or_default();
+    let agent_id = result.agent_id.as_deref().unwrap_or("unknown agent");
+    if let Some(Err(errors)) = output_schema.map(|schema| parse_structured(output, schema)) {
+        return ToolResult {
+            success: false,
+            output: format!(
+                "Delegated task {} ({}) finished, but its output does not match the requested schema:\n- {}\n\nOutput:\n{}",
+                result.task_id, agent_id, errors.join("\n- "), output,
+            ),
+        };
+    }
+    ToolResult {
+        success: true,
+        output: format!("Delegated task {} completed by {}:\n{}", result.task_id, agent_id, output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::confidence::ConfidenceSignals;
+    use ai_agent_common::TokenUsage;
+    use serde_json::json;
+
+    fn result(success: bool, output: &str) -> TaskResult {
+        TaskResult {
+            task_id: "task-1-sub-1".to_string(),
+            success,
+            output: Some(output.to_string()),
+            error: (!success).then(|| "agent crashed".to_string()),
+            tool_executions: vec![],
+            token_usage: TokenUsage::default(),
+            confidence: ConfidenceSignals::default(),
+            agent_id: Some("writing-1".to_string()),
+            task_description: None,
+            completed_at: None,
+        }
+    }
+
+    #[test]
+    fn test_sub_task_output_is_checked_against_schema() {
+        let schema = json!({"type": "object", "properties": {"summary": {"type": "string"}}, "required": ["summary"]});
+
+        let done = delegation_result(&result(true, r#"{"summary": "Documented"}"#), Some(&schema));
+        assert!(done.success);
+        assert_eq!(done.output, "Delegated task task-1-sub-1 completed by writing-1:\n{\"summary\": \"Documented\"}");
+
+        let mismatch = delegation_result(&result(true, r#"{"content": "Documented"}"#), Some(&schema));
+        assert!(!mismatch.success);
+        assert!(mismatch.output.contains("- /: missing required property `summary`\n"), "{}", mismatch.output);
+        assert!(delegation_result(&result(true, r#"{"content": "Documented"}"#), None).success);
+
+        let failed = delegation_result(&result(false, ""), Some(&schema));
+        assert_eq!(failed.output, "Delegated task task-1-sub-1 failed: agent crashed");
+    }
+}
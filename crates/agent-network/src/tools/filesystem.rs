@@ -3,6 +3,7 @@
 use async_trait::async_trait;
 use schemars::JsonSchema;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
 use serde_json::{Value, json};
 use anyhow::Result;
@@ -10,6 +11,8 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{debug, info, warn, instrument};
 use anyhow::anyhow;
 use async_openai::types::{ChatCompletionTool, ChatCompletionToolType, FunctionObject};
+use crate::journal::TaskJournal;
+use crate::shadow::ShadowWorkspace;
 use crate::tools::{Tool,ToolResult, TypedTool};
 use serde::{Deserialize, Serialize};
 
@@ -24,10 +27,25 @@ You are operating within a restricted project workspace.
 ### CRITICAL: When passing code content in JSON, do NOT double-escape newlines. Use standard JSON string escaping (e.g. use \n for a newline, not \\n).
 "#;
 
+/// Per-execution context of the filesystem tools: the shadow overlay of a dry run and the
+/// journal recording pre-images of the current task attempt
+#[derive(Debug, Clone, Default)]
+pub struct FileScope {
+    pub shadow: Option<Arc<ShadowWorkspace>>,
+    pub journal: Option<TaskJournal>,
+}
+
+impl FileScope {
+    pub fn is_empty(&self) -> bool {
+        self.shadow.is_none() && self.journal.is_none()
+    }
+}
+
 // Shared base functionality for all filesystem tools
 #[derive(Debug, Clone)]
 struct FilesystemBase {
     base_path: PathBuf,
+    scope: FileScope,
 }
 
 impl FilesystemBase {
@@ -35,7 +53,86 @@ impl FilesystemBase {
         let base_path = PathBuf::from(base_path);
         let base_path = std::fs::canonicalize(&base_path)
             .unwrap_or_else(|_| PathBuf::from(base_path));
-        Self { base_path }
+        Self { base_path, scope: FileScope::default() }
+    }
+
+    fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self { scope, ..Self::new(base_path) }
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.base_path).unwrap_or(path)
+    }
+
+    async fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        match self.scope.shadow.as_deref() {
+            Some(shadow) => shadow.read_to_string(self.relative(path)).await,
+            None => fs::read_to_string(path).await,
+        }
+    }
+
+    async fn write(&self, path: &Path, content: &str) -> std::io::Result<()> {
+        if let Some(journal) = &self.scope.journal {
+            journal.record_file(self.relative(path)).await?;
+        }
+        let result = match self.scope.shadow.as_deref() {
+            Some(shadow) => shadow.write(self.relative(path), content).await,
+            None => fs::write(path, content).await,
+        };
+        self.record_result(path).await?;
+        result
+    }
+
+    /// Journal what a write or delete left behind, also when it failed halfway
+    async fn record_result(&self, path: &Path) -> std::io::Result<()> {
+        match &self.scope.journal {
+            Some(journal) => journal.record_result(self.relative(path)).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(journal) = &self.scope.journal {
+            journal.record_dir(self.relative(path)).await?;
+        }
+        match self.scope.shadow.as_deref() {
+            Some(shadow) => shadow.create_dir_all(self.relative(path)).await,
+            None => fs::create_dir_all(path).await,
+        }
+    }
+
+    async fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(journal) = &self.scope.journal {
+            journal.record_file(self.relative(path)).await?;
+        }
+        let result = match self.scope.shadow.as_deref() {
+            Some(shadow) => shadow.remove_file(self.relative(path)).await,
+            None => fs::remove_file(path).await,
+        };
+        self.record_result(path).await?;
+        result
+    }
+
+    async fn metadata(&self, path: &Path) -> std::io::Result<std::fs::Metadata> {
+        match self.scope.shadow.as_deref() {
+            Some(shadow) => shadow.metadata(self.relative(path)).await,
+            None => fs::metadata(path).await,
+        }
+    }
+
+    /// Directory entries as (name, is_dir)
+    async fn read_dir(&self, path: &Path) -> std::io::Result<Vec<(String, bool)>> {
+        if let Some(shadow) = self.scope.shadow.as_deref() {
+            return shadow.read_dir(self.relative(path)).await;
+        }
+        let mut entries = fs::read_dir(path).await?;
+        let mut listing = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let (Ok(file_type), Some(name)) = (entry.file_type().await, entry.file_name().to_str()) {
+                listing.push((name.to_string(), file_type.is_dir()));
+            }
+        }
+        Ok(listing)
     }
 
     fn resolve_secure_path(&self, relative_path: &str) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
@@ -124,6 +221,24 @@ pub struct WriteParam {
     pub content: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchReplaceEdit {
+    #[schemars(description = "Exact text to find, including indentation. Must occur exactly once in the file.")]
+    pub search: String,
+    #[schemars(description = "The text that replaces the search text.")]
+    pub replace: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EditFileParam {
+    #[schemars(description = "The path of the file to edit.")]
+    pub path: String,
+    #[schemars(description = "Search/replace blocks applied in order. Use either this or 'patch'.")]
+    pub edits: Option<Vec<SearchReplaceEdit>>,
+    #[schemars(description = "A unified diff (with @@ hunk headers) to apply. Use either this or 'edits'.")]
+    pub patch: Option<String>,
+}
+
 // ReadFileTool - Read file contents
 #[derive(Debug, Clone)]
 pub struct ReadFileTool {
@@ -136,6 +251,13 @@ impl ReadFileTool {
             base: FilesystemBase::new(base_path),
         }
     }
+
+    /// Variant bound to an execution's shadow workspace and change journal
+    pub fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self {
+            base: FilesystemBase::scoped(base_path, scope),
+        }
+    }
 }
 
 #[async_trait]
@@ -150,6 +272,10 @@ impl TypedTool for ReadFileTool {
         "Read the contents of a file. Provide a relative path."
     }
 
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        true
+    }
+
     #[instrument(name = "read_file_tool", skip(self), fields(
         tool_name = "read_file",
         path = tracing::field::Empty,
@@ -177,7 +303,7 @@ impl TypedTool for ReadFileTool {
                 }
             };
 
-            let contents = match fs::read_to_string(&target_path).await {
+            let contents = match self.base.read_to_string(&target_path).await {
                 Ok(contents) => contents,
                 Err(e) => {
                     let error_msg = format!("Error reading file: {}", e);
@@ -211,6 +337,13 @@ impl WriteFileTool {
             base: FilesystemBase::new(base_path),
         }
     }
+
+    /// Variant bound to an execution's shadow workspace and change journal
+    pub fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self {
+            base: FilesystemBase::scoped(base_path, scope),
+        }
+    }
 }
 
 #[async_trait]
@@ -224,6 +357,12 @@ impl TypedTool for WriteFileTool {
         "Write content to a file. Creates parent directories if needed. Provide relative path and content."
     }
 
+    async fn preview(&self, params: &Self::Params) -> Option<String> {
+        let target_path = self.base.resolve_secure_path(&params.path).ok()?;
+        let existing = self.base.read_to_string(&target_path).await.unwrap_or_default();
+        Some(unified_diff(&params.path, &existing, &params.content))
+    }
+
     #[instrument(name = "write_file_tool", skip(self), fields(
         tool_name = "write_file",
         path = tracing::field::Empty,
@@ -253,7 +392,7 @@ impl TypedTool for WriteFileTool {
             };
 
             if let Some(parent) = target_path.parent() {
-                if let Err(e) = fs::create_dir_all(parent).await {
+                if let Err(e) = self.base.create_dir_all(parent).await {
                     let error_msg = format!("Error creating parent directories: {}", e);
                     current_span.record("success", false);
                     current_span.record("error", error_msg.as_str());
@@ -264,7 +403,7 @@ impl TypedTool for WriteFileTool {
                 }
             }
 
-            if let Err(e) = fs::write(&target_path, &parameters.content).await {
+            if let Err(e) = self.base.write(&target_path, &parameters.content).await {
                 let error_msg = format!("Error writing file: {}", e);
                 current_span.record("success", false);
                 current_span.record("error", error_msg.as_str());
@@ -282,6 +421,275 @@ impl TypedTool for WriteFileTool {
     }
 }
 
+// EditFileTool - Apply search/replace edits or a unified diff to a file
+#[derive(Debug, Clone)]
+pub struct EditFileTool {
+    base: FilesystemBase,
+}
+
+impl EditFileTool {
+    pub fn new(base_path: &str) -> Self {
+        Self {
+            base: FilesystemBase::new(base_path),
+        }
+    }
+
+    /// Variant bound to an execution's shadow workspace and change journal
+    pub fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self {
+            base: FilesystemBase::scoped(base_path, scope),
+        }
+    }
+
+    /// Read the target file and compute its edited content without writing it
+    async fn compute_edit(&self, parameters: &EditFileParam) -> Result<(PathBuf, String, String)> {
+        let target_path = self.base.resolve_secure_path(&parameters.path)
+            .map_err(|e| anyhow!("Path access error: {}", e))?;
+        let original = self.base.read_to_string(&target_path).await
+            .map_err(|e| anyhow!("Error reading file: {}", e))?;
+
+        // Edit CRLF files as LF (models write '\n') and restore the endings afterwards
+        let crlf = uses_crlf(&original);
+        let to_lf = |text: &str| if crlf { text.replace("\r\n", "\n") } else { text.to_string() };
+        let content = to_lf(&original);
+
+        let edited = match (&parameters.edits, &parameters.patch) {
+            (Some(edits), None) if !edits.is_empty() => {
+                let edits: Vec<SearchReplaceEdit> = edits.iter()
+                    .map(|edit| SearchReplaceEdit { search: to_lf(&edit.search), replace: to_lf(&edit.replace) })
+                    .collect();
+                apply_search_replace(&content, &edits)?
+            }
+            (None, Some(patch)) if !patch.trim().is_empty() => apply_unified_diff(&content, patch)?,
+            _ => return Err(anyhow!("Provide exactly one of 'edits' (search/replace blocks) or 'patch' (unified diff)")),
+        };
+        let edited = if crlf { edited.replace('\n', "\r\n") } else { edited };
+        Ok((target_path, original, edited))
+    }
+}
+
+#[async_trait]
+impl TypedTool for EditFileTool {
+    type Params = EditFileParam;
+
+    fn name(&self) -> &str {
+        "edit_file"
+    }
+
+    fn description(&self) -> &str {
+        "Edit an existing file without rewriting it. Provide either 'edits' (exact search/replace blocks, each search text must match exactly once) or 'patch' (a unified diff). All changes apply atomically; the resulting diff is returned."
+    }
+
+    async fn preview(&self, params: &Self::Params) -> Option<String> {
+        match self.compute_edit(params).await {
+            Ok((_, original, edited)) => Some(unified_diff(&params.path, &original, &edited)),
+            Err(e) => Some(format!("Edit to {} will fail: {}", params.path, e)),
+        }
+    }
+
+    #[instrument(name = "edit_file_tool", skip(self), fields(
+        tool_name = "edit_file",
+        path = tracing::field::Empty,
+        success = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(
+        &self,
+        parameters: Self::Params
+    ) -> Result<ToolResult> {
+            let current_span = tracing::Span::current();
+            current_span.record("path", parameters.path.as_str());
+
+            let (target_path, original, edited) = match self.compute_edit(&parameters).await {
+                Ok(result) => result,
+                Err(e) => {
+                    let error_msg = format!("Error: {}", e);
+                    current_span.record("success", false);
+                    current_span.record("error", error_msg.as_str());
+                    return Ok(ToolResult {
+                        success: false,
+                        output: error_msg,
+                    });
+                }
+            };
+
+            if original == edited {
+                current_span.record("success", true);
+                return Ok(ToolResult {
+                    success: true,
+                    output: format!("No changes: {} already matches the requested edit", parameters.path),
+                });
+            }
+
+            if let Err(e) = self.base.write(&target_path, &edited).await {
+                let error_msg = format!("Error writing file: {}", e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                return Ok(ToolResult {
+                    success: false,
+                    output: error_msg,
+                });
+            }
+            current_span.record("success", true);
+
+            Ok(ToolResult {
+                success: true,
+                output: format!("Edited {}\n{}", parameters.path, unified_diff(&parameters.path, &original, &edited)),
+            })
+    }
+}
+
+/// Render a unified diff between two versions of a file
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+/// Apply search/replace blocks in order; every search text must match exactly once
+fn apply_search_replace(content: &str, edits: &[SearchReplaceEdit]) -> Result<String> {
+    let mut content = content.to_string();
+    for (index, edit) in edits.iter().enumerate() {
+        if edit.search.is_empty() {
+            return Err(anyhow!("Edit {}: search text must not be empty", index + 1));
+        }
+        match content.matches(edit.search.as_str()).count() {
+            1 => content = content.replacen(edit.search.as_str(), &edit.replace, 1),
+            0 => {
+                let hint = if normalize_whitespace(&content).contains(&normalize_whitespace(&edit.search)) {
+                    " The text exists with different whitespace/indentation; copy it exactly."
+                } else {
+                    " Re-read the file; it may differ from what you expect or an earlier edit changed it."
+                };
+                return Err(anyhow!("Edit {}: search text not found.{}", index + 1, hint));
+            }
+            count => {
+                return Err(anyhow!(
+                    "Edit {}: search text is ambiguous ({} matches). Include more surrounding lines so it matches exactly once.",
+                    index + 1, count
+                ));
+            }
+        }
+    }
+    Ok(content)
+}
+
+/// Whether a file mostly uses CRLF line endings
+fn uses_crlf(content: &str) -> bool {
+    let crlf = content.matches("\r\n").count();
+    crlf > 0 && crlf * 2 >= content.matches('\n').count()
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A parsed unified-diff hunk
+#[derive(Debug, Default)]
+struct Hunk {
+    header: String,
+    old_start: usize,
+    old_lines: Vec<String>,
+    new_lines: Vec<String>,
+}
+
+fn parse_unified_diff(patch: &str) -> Result<Vec<Hunk>> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for line in patch.lines() {
+        if let Some(rest) = line.strip_prefix("@@") {
+            // @@ -old_start[,old_count] +new_start[,new_count] @@
+            let old_start = rest.trim_start()
+                .strip_prefix('-')
+                .and_then(|r| r.split([',', ' ']).next())
+                .and_then(|n| n.parse::<usize>().ok())
+                .ok_or_else(|| anyhow!("Malformed hunk header: {}", line))?;
+            hunks.push(Hunk { header: line.to_string(), old_start, ..Default::default() });
+            continue;
+        }
+        let Some(hunk) = hunks.last_mut() else {
+            // File headers (diff/index/---/+++) before the first hunk
+            continue;
+        };
+        if line.starts_with("--- ") || line.starts_with("+++ ") || line.starts_with("diff ") {
+            continue;
+        }
+        match line.chars().next() {
+            Some('+') => hunk.new_lines.push(line[1..].to_string()),
+            Some('-') => hunk.old_lines.push(line[1..].to_string()),
+            Some(' ') => {
+                hunk.old_lines.push(line[1..].to_string());
+                hunk.new_lines.push(line[1..].to_string());
+            }
+            Some('\\') => {} // "\ No newline at end of file"
+            // Some models drop the leading space of empty context lines
+            None => {
+                hunk.old_lines.push(String::new());
+                hunk.new_lines.push(String::new());
+            }
+            Some(_) => return Err(anyhow!("Unexpected line in {}: {:?}", hunk.header, line)),
+        }
+    }
+    if hunks.is_empty() {
+        return Err(anyhow!("Patch contains no hunks (expected lines starting with '@@')"));
+    }
+    Ok(hunks)
+}
+
+/// Apply a unified diff, locating each hunk by its context. Hunks may drift from their
+/// stated line numbers, but a hunk whose context matches in several places is rejected
+/// unless one of them is at the stated position.
+fn apply_unified_diff(content: &str, patch: &str) -> Result<String> {
+    let hunks = parse_unified_diff(patch)?;
+    let trailing_newline = content.ends_with('\n');
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut offset: isize = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let expected = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+
+        let position = if hunk.old_lines.is_empty() {
+            expected.min(lines.len())
+        } else {
+            let find = |eq: &dyn Fn(&str, &str) -> bool| -> Vec<usize> {
+                (0..=lines.len().saturating_sub(hunk.old_lines.len()))
+                    .filter(|&start| {
+                        start + hunk.old_lines.len() <= lines.len()
+                            && hunk.old_lines.iter().zip(&lines[start..]).all(|(a, b)| eq(a, b))
+                    })
+                    .collect()
+            };
+            let mut candidates = find(&|a, b| a == b);
+            if candidates.is_empty() {
+                candidates = find(&|a, b| a.trim_end() == b.trim_end());
+            }
+            match candidates.as_slice() {
+                [] => return Err(anyhow!(
+                    "Hunk {} ({}) does not apply: its context/removed lines were not found in the file. Re-read the file and regenerate the patch.",
+                    index + 1, hunk.header
+                )),
+                [single] => *single,
+                many if many.contains(&expected) => expected,
+                many => return Err(anyhow!(
+                    "Hunk {} ({}) is ambiguous: its context matches at lines {}. Add more context lines.",
+                    index + 1, hunk.header,
+                    many.iter().map(|p| (p + 1).to_string()).collect::<Vec<_>>().join(", ")
+                )),
+            }
+        };
+
+        lines.splice(position..position + hunk.old_lines.len(), hunk.new_lines.iter().cloned());
+        offset += hunk.new_lines.len() as isize - hunk.old_lines.len() as isize;
+    }
+
+    let mut result = lines.join("\n");
+    if trailing_newline && !result.is_empty() {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
 // ListDirectoryTool - List directory contents
 #[derive(Debug, Clone)]
 pub struct ListDirectoryTool {
@@ -294,6 +702,13 @@ impl ListDirectoryTool {
             base: FilesystemBase::new(base_path),
         }
     }
+
+    /// Variant bound to an execution's shadow workspace and change journal
+    pub fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self {
+            base: FilesystemBase::scoped(base_path, scope),
+        }
+    }
 }
 
 #[async_trait]
@@ -308,6 +723,10 @@ impl TypedTool for ListDirectoryTool {
         "List the contents of a directory. Provide a relative path to a directory."
     }
 
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        true
+    }
+
     #[instrument(name = "list_directory_tool", skip(self), fields(
         tool_name = "list_directory",
         path = tracing::field::Empty,
@@ -335,7 +754,7 @@ impl TypedTool for ListDirectoryTool {
                 }
             };
 
-            let mut entries = match fs::read_dir(&target_path).await {
+            let entries = match self.base.read_dir(&target_path).await {
                 Ok(entries) => entries,
                 Err(e) => {
                     let error_msg = format!("Error reading directory: {}", e);
@@ -347,16 +766,9 @@ impl TypedTool for ListDirectoryTool {
                 });
                 }
             };
-            let mut listing = Vec::new();
-
-            while let Ok(Some(entry)) = entries.next_entry().await {
-                if let Ok(file_type) = entry.file_type().await {
-                    let typ = if file_type.is_dir() { "[DIR]" } else { "[FILE]" };
-                    if let Some(name) = entry.file_name().to_str() {
-                        listing.push(format!("{} {}", typ, name));
-                    }
-                }
-            }
+            let listing: Vec<String> = entries.into_iter()
+                .map(|(name, is_dir)| format!("{} {}", if is_dir { "[DIR]" } else { "[FILE]" }, name))
+                .collect();
 
             current_span.record("success", true);
             current_span.record("entry_count", listing.len());
@@ -380,6 +792,13 @@ impl CreateDirectoryTool {
             base: FilesystemBase::new(base_path),
         }
     }
+
+    /// Variant bound to an execution's shadow workspace and change journal
+    pub fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self {
+            base: FilesystemBase::scoped(base_path, scope),
+        }
+    }
 }
 
 #[async_trait]
@@ -420,7 +839,7 @@ impl TypedTool for CreateDirectoryTool {
                 }
             };
 
-            if let Err(e) = fs::create_dir_all(&target_path).await {
+            if let Err(e) = self.base.create_dir_all(&target_path).await {
                 let error_msg = format!("Error creating directory: {}", e);
                 current_span.record("success", false);
                 current_span.record("error", error_msg.as_str());
@@ -450,6 +869,13 @@ impl DeleteFileTool {
             base: FilesystemBase::new(base_path),
         }
     }
+
+    /// Variant bound to an execution's shadow workspace and change journal
+    pub fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self {
+            base: FilesystemBase::scoped(base_path, scope),
+        }
+    }
 }
 
 #[async_trait]
@@ -490,7 +916,7 @@ impl TypedTool for DeleteFileTool {
                 }
             };
 
-            if let Err(e) = fs::remove_file(&target_path).await {
+            if let Err(e) = self.base.remove_file(&target_path).await {
                 let error_msg = format!("Error deleting file: {}", e);
                 current_span.record("success", false);
                 current_span.record("error", error_msg.as_str());
@@ -520,6 +946,13 @@ impl FileExistsTool {
             base: FilesystemBase::new(base_path),
         }
     }
+
+    /// Variant bound to an execution's shadow workspace and change journal
+    pub fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self {
+            base: FilesystemBase::scoped(base_path, scope),
+        }
+    }
 }
 
 #[async_trait]
@@ -534,6 +967,10 @@ impl TypedTool for FileExistsTool {
         "Check if a file or directory exists. Provide a relative path."
     }
 
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        true
+    }
+
     #[instrument(name = "file_exists_tool", skip(self), fields(
         tool_name = "file_exists",
         path = tracing::field::Empty,
@@ -561,7 +998,7 @@ impl TypedTool for FileExistsTool {
                 }
             };
 
-            let exists = fs::metadata(&target_path).await.is_ok();
+            let exists = self.base.metadata(&target_path).await.is_ok();
             current_span.record("success", true);
             current_span.record("exists", exists);
 
@@ -584,6 +1021,13 @@ impl FileMetadataTool {
             base: FilesystemBase::new(base_path),
         }
     }
+
+    /// Variant bound to an execution's shadow workspace and change journal
+    pub fn scoped(base_path: &str, scope: FileScope) -> Self {
+        Self {
+            base: FilesystemBase::scoped(base_path, scope),
+        }
+    }
 }
 
 #[async_trait]
@@ -598,6 +1042,10 @@ impl TypedTool for FileMetadataTool {
         "Get metadata information about a file or directory (size, type, permissions). Provide a relative path."
     }
 
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        true
+    }
+
     #[instrument(name = "file_metadata_tool", skip(self), fields(
         tool_name = "file_metadata",
         path = tracing::field::Empty,
@@ -626,7 +1074,7 @@ impl TypedTool for FileMetadataTool {
                 }
             };
 
-            let metadata = match fs::metadata(&target_path).await {
+            let metadata = match self.base.metadata(&target_path).await {
                 Ok(metadata) => metadata,
                 Err(e) => {
                     let error_msg = format!("Error getting file metadata: {}", e);
@@ -654,3 +1102,97 @@ impl TypedTool for FileMetadataTool {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n\nfn helper() {\n    let x = 1;\n}\n";
+
+    fn edit(search: &str, replace: &str) -> SearchReplaceEdit {
+        SearchReplaceEdit { search: search.to_string(), replace: replace.to_string() }
+    }
+
+    #[test]
+    fn test_search_replace_requires_unique_match() {
+        let edited = apply_search_replace(SOURCE, &[edit("println!(\"{}\", x);", "println!(\"x = {}\", x);")]).unwrap();
+        assert!(edited.contains("x = {}"));
+
+        let ambiguous = apply_search_replace(SOURCE, &[edit("    let x = 1;", "    let x = 2;")]).unwrap_err();
+        assert!(ambiguous.to_string().contains("ambiguous (2 matches)"));
+
+        let whitespace = apply_search_replace(SOURCE, &[edit("let  x = 1;\n  println", "")]).unwrap_err();
+        assert!(whitespace.to_string().contains("different whitespace"));
+    }
+
+    #[test]
+    fn test_unified_diff_applies_with_drift_and_rejects_bad_context() {
+        let patch = "--- a/main.rs\n+++ b/main.rs\n@@ -8,3 +8,3 @@\n fn helper() {\n-    let x = 1;\n+    let y = 2;\n }\n";
+        let edited = apply_unified_diff(SOURCE, patch).unwrap();
+        assert!(edited.contains("fn helper() {\n    let y = 2;\n}\n"));
+        assert!(edited.starts_with("fn main() {\n    let x = 1;"));
+
+        let ambiguous = "@@ -20,1 +20,1 @@\n-    let x = 1;\n+    let x = 3;\n";
+        assert!(apply_unified_diff(SOURCE, ambiguous).unwrap_err().to_string().contains("ambiguous"));
+
+        let missing = "@@ -1,1 +1,1 @@\n-fn nope() {\n+fn yes() {\n";
+        assert!(apply_unified_diff(SOURCE, missing).unwrap_err().to_string().contains("does not apply"));
+    }
+
+    #[tokio::test]
+    async fn test_edit_file_reports_diff_and_is_atomic() {
+        let dir = std::env::temp_dir().join(format!("q_edit_file_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), SOURCE).unwrap();
+        let tool = EditFileTool::new(dir.to_str().unwrap());
+
+        let params = |edits: Vec<SearchReplaceEdit>| EditFileParam { path: "main.rs".into(), edits: Some(edits), patch: None };
+
+        let failed = TypedTool::call(&tool, params(vec![edit("fn main()", "fn start()"), edit("missing", "")])).await.unwrap();
+        assert!(!failed.success);
+        assert_eq!(std::fs::read_to_string(dir.join("main.rs")).unwrap(), SOURCE);
+
+        let preview = TypedTool::preview(&tool, &params(vec![edit("fn main()", "fn start()")])).await.unwrap();
+        assert!(preview.contains("-fn main() {\n+fn start() {"));
+        assert_eq!(std::fs::read_to_string(dir.join("main.rs")).unwrap(), SOURCE);
+
+        let applied = TypedTool::call(&tool, params(vec![edit("fn main()", "fn start()")])).await.unwrap();
+        assert!(applied.success, "{}", applied.output);
+        assert!(applied.output.contains("+++ b/main.rs"));
+        assert!(std::fs::read_to_string(dir.join("main.rs")).unwrap().starts_with("fn start()"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_edit_file_keeps_crlf_line_endings() {
+        let dir = std::env::temp_dir().join(format!("q_edit_file_crlf_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), SOURCE.replace('\n', "\r\n")).unwrap();
+        let tool = EditFileTool::new(dir.to_str().unwrap());
+
+        let edits = EditFileParam {
+            path: "main.rs".into(),
+            edits: Some(vec![edit("fn main() {\n    let x = 1;", "fn main() {\n    let x = 5;")]),
+            patch: None,
+        };
+        let applied = TypedTool::call(&tool, edits).await.unwrap();
+        assert!(applied.success, "{}", applied.output);
+
+        let patch = EditFileParam {
+            path: "main.rs".into(),
+            edits: None,
+            patch: Some("@@ -6,3 +6,3 @@\n fn helper() {\n-    let x = 1;\n+    let y = 2;\n }\n".into()),
+        };
+        let applied = TypedTool::call(&tool, patch).await.unwrap();
+        assert!(applied.success, "{}", applied.output);
+
+        let expected = SOURCE.replace("let x = 1;\n    println", "let x = 5;\n    println")
+            .replace("fn helper() {\n    let x = 1;", "fn helper() {\n    let y = 2;")
+            .replace('\n', "\r\n");
+        assert_eq!(std::fs::read_to_string(dir.join("main.rs")).unwrap(), expected);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
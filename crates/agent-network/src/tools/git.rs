@@ -1,147 +1,613 @@
-// use async_trait::async_trait;
-// use anyhow::{anyhow, Result};
-// use git2::{Repository, Signature};
-// use serde_json::{Value, json};
-// use std::path::PathBuf;
-// use tokio::task;
-// use tracing::{debug, info};
-//
-// #[derive(Debug)]
-// pub struct GitTool {
-//     repo_path: PathBuf,
-// }
-//
-// impl GitTool {
-//     pub fn new(repo_path: PathBuf) -> Result<Self> {
-//         // Just check repo existence synchronously here
-//         Repository::open(&repo_path).map_err(|e| anyhow!("Failed to open git repo: {}", e))?;
-//         info!("GitTool initialized for: {}", repo_path.display());
-//         Ok(Self { repo_path })
-//     }
-//
-//     // Helper to do blocking git operations on a dedicated thread pool
-//     async fn open_repo(&self) -> Result<Repository> {
-//         let path = self.repo_path.clone();
-//         task::spawn_blocking(move || Repository::open(path))
-//             .await?
-//             .map_err(|e| anyhow!("Failed to open git repo: {}", e))
-//     }
-//
-//     // Example: status command
-//     async fn status(&self) -> Result<String> {
-//         let repo_path = self.repo_path.clone();
-//         let statuses = tokio::task::spawn_blocking(move || -> Result<git2::Statuses, anyhow::Error> {
-//             let repo = Repository::open(repo_path)?;
-//             Ok(repo.statuses(None)?)
-//         })
-//         .await??;
-//
-//         let mut output = String::new();
-//         for entry in statuses.iter() {
-//             let path = entry.path().unwrap_or("unknown");
-//             let status_str = if entry.status().is_wt_new() {
-//                 "new"
-//             } else if entry.status().is_wt_modified() {
-//                 "modified"
-//             } else if entry.status().is_wt_deleted() {
-//                 "deleted"
-//             } else if entry.status().is_index_modified() {
-//                 "staged-modified"
-//             } else if entry.status().is_index_new() {
-//                 "staged-new"
-//             } else {
-//                 "unknown"
-//             };
-//             output.push_str(&format!("{}: {}\n", path, status_str));
-//         }
-//         Ok(output)
-//     }
-//
-//     // Similarly implement async `log`, `add`, `commit`, `branch`, `diff` here,
-//     // running all blocking operations inside `task::spawn_blocking`.
-//
-//     // To save space, omitted here - implement following pattern above.
-// }
-//
-// #[async_trait]
-// impl crate::tools::ToolExecutor for GitTool {
-//     fn name(&self) -> &'static str {
-//         "git"
-//     }
-//
-//     fn description(&self) -> &'static str {
-//         "Git version control tool for commits, branches, history, and diffs"
-//     }
-//
-//     fn provide_tool_info(&self) -> ollama_rs::generation::tools::ToolInfo {
-//         // Simple JSON schema for command and parameters
-//         let parameters = json!({
-//             "type": "object",
-//             "properties": {
-//                 "command": {
-//                     "type": "string",
-//                     "enum": ["status", "log", "add", "commit", "branch", "diff"]
-//                 },
-//                 "message": {
-//                     "type": "string"
-//                 },
-//                 "patterns": {
-//                     "type": "string",
-//                     "description": "Comma separated list of file patterns"
-//                 },
-//                 "limit": {
-//                     "type": "integer"
-//                 },
-//                 "file": {
-//                     "type": "string"
-//                 },
-//                 "action": {
-//                     "type": "string"
-//                 }
-//             },
-//             "required": ["command"]
-//         });
-//
-//         ollama_rs::generation::tools::ToolInfo {
-//             tool_type: ollama_rs::generation::tools::ToolType::Function,
-//             function: ollama_rs::generation::tools::ToolFunctionInfo {
-//                 name: self.name().to_string(),
-//                 description: self.description().to_string(),
-//                 parameters: serde_json::from_value(parameters).unwrap(),
-//             },
-//         }
-//     }
-//
-//     async fn call(&mut self, parameters: Value) -> Result<String> {
-//         let command = parameters.get("command")
-//             .and_then(Value::as_str)
-//             .ok_or_else(|| anyhow!("Missing 'command' field"))?;
-//
-//         match command {
-//             "status" => self.status().await,
-//
-//             // For "log", parse limit param:
-//             "log" => {
-//                 let limit = parameters.get("limit")
-//                     .and_then(Value::as_u64)
-//                     .unwrap_or(10) as usize;
-//                 // Implement log following same spawn_blocking pattern as status
-//                 Err(anyhow!("log command not yet implemented"))
-//             }
-//
-//             // For "add", parse patterns:
-//             "add" => {
-//                 let patterns = parameters.get("patterns")
-//                     .and_then(Value::as_str)
-//                     .map(|s| s.split(',').map(|v| v.trim().to_string()).collect())
-//                     .unwrap_or_default();
-//                 // Implement add similar to status
-//                 Err(anyhow!("add command not yet implemented"))
-//             }
-//
-//             // Similarly implement commit, branch, diff...
-//
-//             _ => Err(anyhow!("Unknown git command: {}", command)),
-//         }
-//     }
-// }
+//! Git tool for inspecting and modifying repository state using git2
+
+use async_trait::async_trait;
+use anyhow::{anyhow, Result};
+use git2::{
+    BlameOptions, BranchType, DiffFormat, DiffOptions, Index, IndexEntry, IndexTime, Repository, Signature, StatusOptions,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, instrument};
+use ai_agent_common::RiskLevel;
+use crate::tools::{ToolResult, TypedTool};
+
+/// Maximum number of characters returned to the agent for a single git command
+const MAX_OUTPUT_CHARS: usize = 50_000;
+
+/// Default number of commits returned by `log`
+const DEFAULT_LOG_COUNT: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GitCommand {
+    /// Show modified, staged and untracked files
+    Status,
+    /// Show a unified diff (unstaged by default, staged with `staged`, or against `reference`)
+    Diff,
+    /// Show the commit history
+    Log,
+    /// Show per-line authorship of a file
+    Blame,
+    /// List local and remote branches
+    Branch,
+    /// Stage the listed paths and create a commit (requires approval)
+    Commit,
+    /// Check out a branch, tag or commit (requires approval)
+    Checkout,
+}
+
+impl GitCommand {
+    /// Whether the command modifies the repository or working tree
+    pub fn is_mutating(&self) -> bool {
+        matches!(self, GitCommand::Commit | GitCommand::Checkout)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitParams {
+    #[schemars(description = "The git command to run: status, diff, log, blame, branch, commit or checkout.")]
+    pub command: GitCommand,
+    #[schemars(description = "Relative file path. Required for blame, optional filter for diff and log.")]
+    pub path: Option<String>,
+    #[schemars(description = "For diff: show staged changes (index against HEAD) instead of unstaged ones.")]
+    pub staged: Option<bool>,
+    #[schemars(description = "A branch, tag or commit. For diff: compare the working tree against it. For log: start from it. For checkout: the target.")]
+    pub reference: Option<String>,
+    #[schemars(description = "The commit message. Required for commit.")]
+    pub message: Option<String>,
+    #[schemars(description = "For commit: relative paths of the changed or deleted files to stage. Required for commit; other changes are left unstaged.")]
+    pub paths: Option<Vec<String>>,
+    #[schemars(description = "For log: maximum number of commits to show (default 20).")]
+    pub max_count: Option<usize>,
+}
+
+// GitTool - Inspect and modify the git repository at the project root
+#[derive(Debug, Clone)]
+pub struct GitTool {
+    base_path: PathBuf,
+}
+
+impl GitTool {
+    pub fn new(base_path: &str) -> Self {
+        let base_path = PathBuf::from(base_path);
+        let base_path = std::fs::canonicalize(&base_path).unwrap_or(base_path);
+        Self { base_path }
+    }
+
+    /// Check whether raw tool arguments describe a mutating git command.
+    /// Unparseable arguments are treated as mutating so they never bypass approval.
+    pub fn is_mutating_call(arguments: &str) -> bool {
+        serde_json::from_str::<GitParams>(arguments)
+            .map(|params| params.command.is_mutating())
+            .unwrap_or(true)
+    }
+
+    fn run(base_path: &Path, params: GitParams) -> Result<String> {
+        // Opened rather than discovered, so a repository enclosing the project root stays out of reach
+        let repo = Repository::open(base_path)
+            .map_err(|e| anyhow!("The project root is not a git repository ({}): {}", base_path.display(), e.message()))?;
+        if repo.workdir().is_none() {
+            return Err(anyhow!("Bare repositories are not supported"));
+        }
+        let repo_path = |path: &str| -> Result<String> {
+            let path = Path::new(path.trim_start_matches('/'));
+            if path.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+                return Err(anyhow!("Access denied: {} is outside the workspace", path.display()));
+            }
+            Ok(path.to_string_lossy().replace('\\', "/"))
+        };
+
+        match params.command {
+            GitCommand::Status => Self::status(&repo),
+            GitCommand::Diff => {
+                let path = params.path.as_deref().map(repo_path).transpose()?;
+                Self::diff(&repo, params.staged.unwrap_or(false), params.reference.as_deref(), path.as_deref())
+            }
+            GitCommand::Log => {
+                let path = params.path.as_deref().map(repo_path).transpose()?;
+                Self::log(&repo, params.reference.as_deref(), path.as_deref(), params.max_count.unwrap_or(DEFAULT_LOG_COUNT))
+            }
+            GitCommand::Blame => {
+                let path = params.path.as_deref()
+                    .ok_or_else(|| anyhow!("blame requires a 'path'"))?;
+                Self::blame(&repo, &repo_path(path)?)
+            }
+            GitCommand::Branch => Self::branches(&repo),
+            GitCommand::Commit => {
+                let message = params.message.as_deref()
+                    .filter(|m| !m.trim().is_empty())
+                    .ok_or_else(|| anyhow!("commit requires a non-empty 'message'"))?;
+                let paths = params.paths.as_deref()
+                    .filter(|paths| !paths.is_empty())
+                    .ok_or_else(|| anyhow!("commit requires the 'paths' to stage"))?
+                    .iter()
+                    .map(|path| repo_path(path))
+                    .collect::<Result<Vec<_>>>()?;
+                Self::commit(&repo, message, &paths)
+            }
+            GitCommand::Checkout => {
+                let reference = params.reference.as_deref()
+                    .ok_or_else(|| anyhow!("checkout requires a 'reference'"))?;
+                Self::checkout(&repo, reference)
+            }
+        }
+    }
+
+    fn status(repo: &Repository) -> Result<String> {
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true).recurse_untracked_dirs(true);
+        let statuses = repo.statuses(Some(&mut opts))?;
+
+        let mut output = String::new();
+        for entry in statuses.iter() {
+            let status = entry.status();
+            let path = entry.path().unwrap_or("unknown");
+            let label = if status.is_conflicted() {
+                "conflicted"
+            } else if status.is_index_new() {
+                "staged-new"
+            } else if status.is_index_modified() {
+                "staged-modified"
+            } else if status.is_index_deleted() {
+                "staged-deleted"
+            } else if status.is_index_renamed() {
+                "staged-renamed"
+            } else if status.is_wt_new() {
+                "untracked"
+            } else if status.is_wt_modified() {
+                "modified"
+            } else if status.is_wt_deleted() {
+                "deleted"
+            } else if status.is_wt_renamed() {
+                "renamed"
+            } else {
+                continue;
+            };
+            output.push_str(&format!("{}: {}\n", label, path));
+        }
+
+        let branch = repo.head().ok()
+            .and_then(|h| h.shorthand().map(str::to_string))
+            .unwrap_or_else(|| "(no commits)".to_string());
+        if output.is_empty() {
+            Ok(format!("On branch {}\nWorking tree clean", branch))
+        } else {
+            Ok(format!("On branch {}\n{}", branch, output))
+        }
+    }
+
+    fn diff(repo: &Repository, staged: bool, reference: Option<&str>, path: Option<&str>) -> Result<String> {
+        let mut opts = DiffOptions::new();
+        opts.include_untracked(!staged && reference.is_none())
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true);
+        if let Some(path) = path {
+            opts.pathspec(path);
+        }
+
+        let diff = match reference {
+            Some(reference) => {
+                let tree = repo.revparse_single(reference)
+                    .map_err(|e| anyhow!("Unknown reference '{}': {}", reference, e.message()))?
+                    .peel_to_tree()?;
+                repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut opts))?
+            }
+            None if staged => {
+                let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+                repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut opts))?
+            }
+            None => repo.diff_index_to_workdir(None, Some(&mut opts))?,
+        };
+
+        let mut output = String::new();
+        diff.print(DiffFormat::Patch, |_delta, _hunk, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                output.push(line.origin());
+            }
+            output.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+
+        if output.is_empty() {
+            Ok("No changes".to_string())
+        } else {
+            Ok(output)
+        }
+    }
+
+    fn log(repo: &Repository, reference: Option<&str>, path: Option<&str>, max_count: usize) -> Result<String> {
+        let mut revwalk = repo.revwalk()?;
+        match reference {
+            Some(reference) => {
+                let commit = repo.revparse_single(reference)
+                    .map_err(|e| anyhow!("Unknown reference '{}': {}", reference, e.message()))?
+                    .peel_to_commit()?;
+                revwalk.push(commit.id())?;
+            }
+            None => revwalk.push_head()
+                .map_err(|e| anyhow!("Repository has no commits: {}", e.message()))?,
+        }
+
+        let mut output = String::new();
+        let mut shown = 0;
+        for oid in revwalk {
+            if shown >= max_count {
+                break;
+            }
+            let commit = repo.find_commit(oid?)?;
+
+            if let Some(path) = path {
+                if !Self::commit_touches_path(repo, &commit, path)? {
+                    continue;
+                }
+            }
+
+            let author = commit.author();
+            let time = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            output.push_str(&format!(
+                "{} {} {} <{}> {}\n",
+                &commit.id().to_string()[..8],
+                time,
+                author.name().unwrap_or("unknown"),
+                author.email().unwrap_or(""),
+                commit.summary().unwrap_or(""),
+            ));
+            shown += 1;
+        }
+
+        if output.is_empty() {
+            Ok("No commits found".to_string())
+        } else {
+            Ok(output)
+        }
+    }
+
+    fn commit_touches_path(repo: &Repository, commit: &git2::Commit, path: &str) -> Result<bool> {
+        let tree = commit.tree()?;
+        let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+        let mut opts = DiffOptions::new();
+        opts.pathspec(path);
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut opts))?;
+        Ok(diff.deltas().len() > 0)
+    }
+
+    fn blame(repo: &Repository, path: &str) -> Result<String> {
+        let blame = repo.blame_file(Path::new(path), Some(&mut BlameOptions::new()))
+            .map_err(|e| anyhow!("Cannot blame {}: {}", path, e.message()))?;
+
+        let head_tree = repo.head()?.peel_to_tree()?;
+        let blob = head_tree.get_path(Path::new(path))?.to_object(repo)?.peel_to_blob()?;
+        let content = String::from_utf8_lossy(blob.content());
+
+        let mut output = String::new();
+        for (index, line) in content.lines().enumerate() {
+            let line_no = index + 1;
+            match blame.get_line(line_no) {
+                Some(hunk) => {
+                    let signature = hunk.final_signature();
+                    let time = chrono::DateTime::from_timestamp(signature.when().seconds(), 0)
+                        .map(|t| t.format("%Y-%m-%d").to_string())
+                        .unwrap_or_default();
+                    output.push_str(&format!(
+                        "{} ({} {}) {:>4}: {}\n",
+                        &hunk.final_commit_id().to_string()[..8],
+                        signature.name().unwrap_or("unknown"),
+                        time,
+                        line_no,
+                        line,
+                    ));
+                }
+                None => output.push_str(&format!("???????? {:>4}: {}\n", line_no, line)),
+            }
+        }
+        Ok(output)
+    }
+
+    fn branches(repo: &Repository) -> Result<String> {
+        let mut output = String::new();
+        for branch in repo.branches(None)? {
+            let (branch, branch_type) = branch?;
+            let name = branch.name()?.unwrap_or("invalid-utf8").to_string();
+            let marker = if branch.is_head() { "*" } else { " " };
+            let kind = match branch_type {
+                BranchType::Local => "",
+                BranchType::Remote => " (remote)",
+            };
+            output.push_str(&format!("{} {}{}\n", marker, name, kind));
+        }
+
+        if output.is_empty() {
+            Ok("No branches".to_string())
+        } else {
+            Ok(output)
+        }
+    }
+
+    /// Commit the working-tree state of `paths` on top of HEAD; anything else the user has
+    /// staged stays staged and out of the commit
+    fn commit(repo: &Repository, message: &str, paths: &[String]) -> Result<String> {
+        let workdir = repo.workdir().ok_or_else(|| anyhow!("Bare repositories are not supported"))?;
+        let mut index = repo.index()?;
+        index.read(false)?;
+
+        // The commit's tree is HEAD's tree plus the listed paths, built in a detached index
+        let mut tree_index = Index::new()?;
+        if let Ok(head_tree) = repo.head().and_then(|head| head.peel_to_tree()) {
+            tree_index.read_tree(&head_tree)?;
+        }
+        for path in paths {
+            let file = workdir.join(path);
+            if file.is_file() {
+                let mode = tree_index.get_path(Path::new(path), 0)
+                    .or_else(|| index.get_path(Path::new(path), 0))
+                    .map_or(0o100644, |entry| entry.mode);
+                tree_index.add(&IndexEntry {
+                    ctime: IndexTime::new(0, 0),
+                    mtime: IndexTime::new(0, 0),
+                    dev: 0,
+                    ino: 0,
+                    mode,
+                    uid: 0,
+                    gid: 0,
+                    file_size: std::fs::metadata(&file)?.len() as u32,
+                    id: repo.blob_path(&file)?,
+                    flags: path.len().min(0xfff) as u16,
+                    flags_extended: 0,
+                    path: path.as_bytes().to_vec(),
+                })?;
+            } else if tree_index.get_path(Path::new(path), 0).is_some() || index.get_path(Path::new(path), 0).is_some() {
+                let _ = tree_index.remove_path(Path::new(path));
+            } else {
+                return Err(anyhow!("Cannot stage {}: no such file", path));
+            }
+        }
+        let tree_id = tree_index.write_tree_to(repo)?;
+        let tree = repo.find_tree(tree_id)?;
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+
+        if let Some(parent) = &parent {
+            if parent.tree_id() == tree_id {
+                return Err(anyhow!("Nothing to commit, working tree clean"));
+            }
+        }
+
+        let signature = repo.signature()
+            .or_else(|_| Signature::now("q-agent", "agent@localhost"))?;
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        let oid = repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+
+        // Only the committed entries change in the real index
+        for path in paths {
+            if workdir.join(path).is_file() {
+                index.add_path(Path::new(path))?;
+            } else if index.get_path(Path::new(path), 0).is_some() {
+                index.remove_path(Path::new(path))?;
+            }
+        }
+        index.write()?;
+
+        Ok(format!("Created commit {}: {}", &oid.to_string()[..8], message.lines().next().unwrap_or("")))
+    }
+
+    fn checkout(repo: &Repository, reference: &str) -> Result<String> {
+        let (object, git_ref) = repo.revparse_ext(reference)
+            .map_err(|e| anyhow!("Unknown reference '{}': {}", reference, e.message()))?;
+
+        repo.checkout_tree(&object, Some(git2::build::CheckoutBuilder::new().safe()))
+            .map_err(|e| anyhow!("Checkout failed (uncommitted changes?): {}", e.message()))?;
+
+        match git_ref.as_ref().and_then(|r| r.name()) {
+            Some(name) => repo.set_head(name)?,
+            None => repo.set_head_detached(object.id())?,
+        }
+
+        Ok(format!("Checked out {}", reference))
+    }
+}
+
+#[async_trait]
+impl TypedTool for GitTool {
+    type Params = GitParams;
+
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn description(&self) -> &str {
+        "Inspect and modify the project's git repository. Commands: status, diff (unstaged, staged, or against a reference), log, blame, branch, commit (of the listed paths) and checkout. Paths are relative to the project root."
+    }
+
+    /// Commit/checkout rewrite repository state; inspection is read-only
+    fn risk_level(&self, params: &Self::Params) -> Option<RiskLevel> {
+        if params.command.is_mutating() {
+            Some(RiskLevel::Critical)
+        } else {
+            Some(RiskLevel::Low)
+        }
+    }
+
+    fn is_read_only(&self, params: &Self::Params) -> bool {
+        !params.command.is_mutating()
+    }
+
+    #[instrument(name = "git_tool", skip(self), fields(
+        tool_name = "git",
+        command = ?parameters.command,
+        success = tracing::field::Empty,
+        output_size = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(
+        &self,
+        parameters: Self::Params
+    ) -> Result<ToolResult> {
+        let current_span = tracing::Span::current();
+        let base_path = self.base_path.clone();
+
+        let result = tokio::task::spawn_blocking(move || Self::run(&base_path, parameters)).await?;
+
+        match result {
+            Ok(mut output) => {
+                if output.len() > MAX_OUTPUT_CHARS {
+                    let mut cut = MAX_OUTPUT_CHARS;
+                    while !output.is_char_boundary(cut) {
+                        cut -= 1;
+                    }
+                    output.truncate(cut);
+                    output.push_str("\n... (output truncated)");
+                }
+                debug!("git command produced {} bytes", output.len());
+                current_span.record("success", true);
+                current_span.record("output_size", output.len());
+                Ok(ToolResult {
+                    success: true,
+                    output,
+                })
+            }
+            Err(e) => {
+                let error_msg = format!("Git error: {}", e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                Ok(ToolResult {
+                    success: false,
+                    output: error_msg,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    fn init_repo(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("q_git_tool_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        dir
+    }
+
+    fn params(command: GitCommand) -> GitParams {
+        GitParams { command, path: None, staged: None, reference: None, message: None, paths: None, max_count: None }
+    }
+
+    #[tokio::test]
+    async fn test_status_commit_log_and_diff() {
+        let dir = init_repo("flow");
+        let tool = GitTool::new(dir.to_str().unwrap());
+        std::fs::write(dir.join("a.txt"), "hello\n").unwrap();
+
+        let status = TypedTool::call(&tool, params(GitCommand::Status)).await.unwrap();
+        assert!(status.success);
+        assert!(status.output.contains("untracked: a.txt"));
+
+        let commit = TypedTool::call(&tool, GitParams {
+            message: Some("initial".into()),
+            paths: Some(vec!["a.txt".into()]),
+            ..params(GitCommand::Commit)
+        }).await.unwrap();
+        assert!(commit.success, "{}", commit.output);
+
+        std::fs::write(dir.join("a.txt"), "hello\nworld\n").unwrap();
+        let diff = TypedTool::call(&tool, params(GitCommand::Diff)).await.unwrap();
+        assert!(diff.output.contains("+world"));
+
+        let log = TypedTool::call(&tool, params(GitCommand::Log)).await.unwrap();
+        assert!(log.output.contains("initial"));
+
+        let blame = TypedTool::call(&tool, GitParams { path: Some("a.txt".into()), ..params(GitCommand::Blame) }).await.unwrap();
+        assert!(blame.output.contains("hello"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_reported_as_failed_results() {
+        let dir = init_repo("errors");
+        let tool = GitTool::new(dir.to_str().unwrap());
+
+        let commit = TypedTool::call(&tool, params(GitCommand::Commit)).await.unwrap();
+        assert!(!commit.success);
+
+        let blame = TypedTool::call(&tool, GitParams { path: Some("../etc/passwd".into()), ..params(GitCommand::Blame) }).await.unwrap();
+        assert!(!blame.success);
+        assert!(blame.output.contains("outside the workspace"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_commit_stages_only_the_listed_paths() {
+        let dir = init_repo("paths");
+        let tool = GitTool::new(dir.to_str().unwrap());
+        std::fs::write(dir.join("a.txt"), "a\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "b\n").unwrap();
+        let commit = |message: &str, paths: Option<Vec<String>>| TypedTool::call(&tool, GitParams {
+            message: Some(message.into()),
+            paths,
+            ..params(GitCommand::Commit)
+        });
+
+        assert!(commit("everything", None).await.unwrap().output.contains("requires the 'paths'"));
+        assert!(commit("a", Some(vec!["a.txt".into()])).await.unwrap().success);
+        let status = TypedTool::call(&tool, params(GitCommand::Status)).await.unwrap();
+        assert!(status.output.contains("untracked: b.txt"), "{}", status.output);
+        assert!(!status.output.contains("a.txt"), "{}", status.output);
+
+        std::fs::remove_file(dir.join("a.txt")).unwrap();
+        assert!(commit("remove a", Some(vec!["a.txt".into()])).await.unwrap().success);
+        assert!(!commit("missing", Some(vec!["c.txt".into()])).await.unwrap().success);
+
+        // What the user staged themselves is neither committed nor unstaged
+        let repo = Repository::open(&dir).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("b.txt")).unwrap();
+        index.write().unwrap();
+        std::fs::write(dir.join("c.txt"), "c\n").unwrap();
+        assert!(commit("c", Some(vec!["c.txt".into()])).await.unwrap().success);
+        let head = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(head.get_path(Path::new("c.txt")).is_ok());
+        assert!(head.get_path(Path::new("b.txt")).is_err());
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        assert!(index.get_path(Path::new("b.txt"), 0).is_some());
+        assert!(index.get_path(Path::new("c.txt"), 0).is_some());
+        let status = TypedTool::call(&tool, params(GitCommand::Status)).await.unwrap();
+        assert!(!status.output.contains("c.txt"), "{}", status.output);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_enclosing_repository_is_not_used() {
+        let dir = init_repo("enclosing");
+        std::fs::create_dir_all(dir.join("project")).unwrap();
+        let tool = GitTool::new(dir.join("project").to_str().unwrap());
+
+        let status = TypedTool::call(&tool, params(GitCommand::Status)).await.unwrap();
+        assert!(!status.success);
+        assert!(status.output.contains("not a git repository"), "{}", status.output);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_risk_level_by_command() {
+        let tool = GitTool::new(".");
+        assert_eq!(Tool::risk_level(&tool, r#"{"command": "commit", "message": "x"}"#), Some(RiskLevel::Critical));
+        assert_eq!(Tool::risk_level(&tool, r#"{"command": "checkout", "reference": "main"}"#), Some(RiskLevel::Critical));
+        assert_eq!(Tool::risk_level(&tool, r#"{"command": "status"}"#), Some(RiskLevel::Low));
+        assert!(GitTool::is_mutating_call(r#"{"command": "commit", "message": "x"}"#));
+        assert!(!GitTool::is_mutating_call(r#"{"command": "status"}"#));
+        assert!(GitTool::is_mutating_call("not json"));
+    }
+}
@@ -1,49 +1,591 @@
-use anyhow::{anyhow, Result};
+//! Language Server Protocol tool backed by real language servers over stdio
+//!
+//! One server is started lazily per language (as configured in `[agent_network.lsp]`)
+//! and rooted at the project root. Before every request the target document is
+//! re-read from disk, so edits made through `write_file` are pushed to the server
+//! via `didOpen`/`didChange` before diagnostics, hover or navigation is queried.
+//! Columns are counted in characters; they are converted to and from the position
+//! encoding negotiated with the server (UTF-16 unless it picks another).
+
+use anyhow::{anyhow, Context, Result};
+use ai_agent_common::{LspConfig, LspServerConfig};
 use serde_json::{json, Value};
-use tracing::debug;
+use tracing::{debug, info, warn, instrument};
 use serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
-use crate::tools::{Tool, ToolResult, TypedTool};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Mutex, Notify};
+use crate::tools::{ToolResult, TypedTool};
 
-#[derive(Debug, Clone)]
-pub struct LspTool {
+type PendingRequests = Arc<std::sync::Mutex<HashMap<i64, oneshot::Sender<Result<Value>>>>>;
+type SharedWriter = Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+
+/// Diagnostics published by the server, keyed by document URI
+#[derive(Debug, Default)]
+struct DiagnosticsStore {
+    by_uri: HashMap<String, Vec<Value>>,
+    /// Incremented every time diagnostics for a URI are published
+    generation: HashMap<String, u64>,
 }
 
-impl LspTool {
-    pub fn new() -> Self {
-        debug!("LspTool initialized for language");
-        Self {}
+/// Document state as last sent to the server
+#[derive(Debug)]
+struct OpenDocument {
+    version: i32,
+    text: String,
+}
+
+/// Unit in which a server counts the `character` of a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PositionEncoding {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// The encoding a server chose in its capabilities; LSP's default is UTF-16
+    fn negotiated(capabilities: &Value) -> Self {
+        match capabilities["positionEncoding"].as_str() {
+            Some("utf-8") => Self::Utf8,
+            Some("utf-32") => Self::Utf32,
+            _ => Self::Utf16,
+        }
+    }
+
+    fn units(self, c: char) -> u32 {
+        match self {
+            Self::Utf8 => c.len_utf8() as u32,
+            Self::Utf16 => c.len_utf16() as u32,
+            Self::Utf32 => 1,
+        }
+    }
+
+    /// Server offset of a 0-based character column of a line
+    fn offset(self, line: &str, column: u32) -> u32 {
+        let chars = line.chars().count() as u32;
+        line.chars().take(column as usize).map(|c| self.units(c)).sum::<u32>() + column.saturating_sub(chars)
+    }
+
+    /// 0-based character column of a server offset into a line
+    fn column(self, line: &str, offset: u32) -> u32 {
+        let mut units = 0;
+        for (column, c) in line.chars().enumerate() {
+            if units >= offset {
+                return column as u32;
+            }
+            units += self.units(c);
+        }
+        line.chars().count() as u32 + offset.saturating_sub(units)
+    }
+}
+
+/// JSON-RPC client for a single language server process
+pub struct LspClient {
+    language: String,
+    writer: SharedWriter,
+    pending: PendingRequests,
+    diagnostics: Arc<std::sync::Mutex<DiagnosticsStore>>,
+    diagnostics_updated: Arc<Notify>,
+    documents: Mutex<HashMap<String, OpenDocument>>,
+    next_id: AtomicI64,
+    request_timeout: Duration,
+    position_encoding: PositionEncoding,
+    reader_task: tokio::task::JoinHandle<()>,
+    _child: Option<Child>,
+}
+
+impl std::fmt::Debug for LspClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LspClient")
+            .field("language", &self.language)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        self.reader_task.abort();
+    }
+}
+
+impl LspClient {
+    /// Launch a language server process and perform the initialize handshake
+    pub async fn spawn(server: &LspServerConfig, root: &Path, request_timeout: Duration) -> Result<Self> {
+        let mut child = Command::new(&server.command)
+            .args(&server.args)
+            .current_dir(root)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start language server '{}'", server.command))?;
+
+        let stdin = child.stdin.take().ok_or_else(|| anyhow!("Language server stdin unavailable"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("Language server stdout unavailable"))?;
+
+        info!("Started {} language server: {}", server.language, server.command);
+        Self::connect(
+            stdout,
+            stdin,
+            Some(child),
+            &server.language,
+            root,
+            server.initialization_options.clone(),
+            request_timeout,
+        ).await
+    }
+
+    /// Connect to a server over arbitrary streams and perform the initialize handshake
+    pub async fn connect<R, W>(
+        reader: R,
+        writer: W,
+        child: Option<Child>,
+        language: &str,
+        root: &Path,
+        initialization_options: Option<Value>,
+        request_timeout: Duration,
+    ) -> Result<Self>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let writer: SharedWriter = Arc::new(Mutex::new(Box::new(writer)));
+        let pending: PendingRequests = Arc::default();
+        let diagnostics: Arc<std::sync::Mutex<DiagnosticsStore>> = Arc::default();
+        let diagnostics_updated = Arc::new(Notify::new());
+
+        let reader_task = tokio::spawn(Self::read_loop(
+            BufReader::new(reader),
+            writer.clone(),
+            pending.clone(),
+            diagnostics.clone(),
+            diagnostics_updated.clone(),
+        ));
+
+        let mut client = Self {
+            language: language.to_string(),
+            writer,
+            pending,
+            diagnostics,
+            diagnostics_updated,
+            documents: Mutex::new(HashMap::new()),
+            next_id: AtomicI64::new(1),
+            request_timeout,
+            position_encoding: PositionEncoding::Utf16,
+            reader_task,
+            _child: child,
+        };
+
+        let root_uri = path_to_uri(root);
+        let initialized = client.request("initialize", json!({
+            "processId": std::process::id(),
+            "rootUri": root_uri,
+            "workspaceFolders": [{ "uri": root_uri, "name": root.file_name().and_then(|n| n.to_str()).unwrap_or("workspace") }],
+            "initializationOptions": initialization_options,
+            "capabilities": {
+                "general": { "positionEncodings": ["utf-32", "utf-8", "utf-16"] },
+                "textDocument": {
+                    "synchronization": { "didSave": true, "dynamicRegistration": false },
+                    "publishDiagnostics": { "relatedInformation": false },
+                    "hover": { "contentFormat": ["plaintext", "markdown"] },
+                    "definition": { "linkSupport": true },
+                    "references": {},
+                    "documentSymbol": { "hierarchicalDocumentSymbolSupport": true }
+                },
+                "workspace": { "workspaceFolders": true, "configuration": true }
+            }
+        })).await.context("Language server initialization failed")?;
+        client.position_encoding = PositionEncoding::negotiated(&initialized["capabilities"]);
+        client.notify("initialized", json!({})).await?;
+
+        Ok(client)
+    }
+
+    async fn read_loop(
+        mut reader: BufReader<impl AsyncRead + Unpin>,
+        writer: SharedWriter,
+        pending: PendingRequests,
+        diagnostics: Arc<std::sync::Mutex<DiagnosticsStore>>,
+        diagnostics_updated: Arc<Notify>,
+    ) {
+        loop {
+            let message = match read_message(&mut reader).await {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Failed to read language server message: {}", e);
+                    break;
+                }
+            };
+
+            let method = message.get("method").and_then(Value::as_str);
+            let id = message.get("id").cloned();
+
+            match (method, id) {
+                // Response to one of our requests
+                (None, Some(id)) => {
+                    let Some(id) = id.as_i64() else { continue };
+                    let sender = pending.lock().unwrap().remove(&id);
+                    if let Some(sender) = sender {
+                        let result = match message.get("error") {
+                            Some(error) => Err(anyhow!("Language server error: {}", error)),
+                            None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                        };
+                        let _ = sender.send(result);
+                    }
+                }
+                // Request from the server; answer with neutral defaults
+                (Some(method), Some(id)) => {
+                    let result = match method {
+                        "workspace/configuration" => {
+                            let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                            Value::Array(vec![Value::Null; items])
+                        }
+                        _ => Value::Null,
+                    };
+                    let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                    if let Err(e) = write_message(&mut **writer.lock().await, &response).await {
+                        warn!("Failed to answer language server request {}: {}", method, e);
+                    }
+                }
+                (Some("textDocument/publishDiagnostics"), None) => {
+                    let uri = message["params"]["uri"].as_str().unwrap_or_default().to_string();
+                    let items = message["params"]["diagnostics"].as_array().cloned().unwrap_or_default();
+                    {
+                        let mut store = diagnostics.lock().unwrap();
+                        *store.generation.entry(uri.clone()).or_default() += 1;
+                        store.by_uri.insert(uri, items);
+                    }
+                    diagnostics_updated.notify_waiters();
+                }
+                (Some(method), None) => debug!("Ignoring language server notification {}", method),
+                (None, None) => {}
+            }
+        }
+
+        // Fail all outstanding requests once the server goes away
+        for (_, sender) in pending.lock().unwrap().drain() {
+            let _ = sender.send(Err(anyhow!("Language server exited")));
+        }
+    }
+
+    /// Send a request and wait for its response
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = write_message(&mut **self.writer.lock().await, &message).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(self.request_timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(anyhow!("Language server closed before answering {}", method)),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(anyhow!("Language server request {} timed out", method))
+            }
+        }
+    }
+
+    /// Send a notification (no response expected)
+    pub async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut **self.writer.lock().await, &message).await
+    }
+
+    /// Push the current on-disk contents of a file to the server.
+    /// Returns the document URI and whether the server saw new content.
+    pub async fn sync_document(&self, path: &Path) -> Result<(String, bool)> {
+        let text = tokio::fs::read_to_string(path).await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let uri = path_to_uri(path);
+
+        let mut documents = self.documents.lock().await;
+        match documents.get_mut(&uri) {
+            Some(document) if document.text == text => Ok((uri, false)),
+            Some(document) => {
+                document.version += 1;
+                document.text = text;
+                self.notify("textDocument/didChange", json!({
+                    "textDocument": { "uri": uri, "version": document.version },
+                    "contentChanges": [{ "text": document.text }]
+                })).await?;
+                self.notify("textDocument/didSave", json!({ "textDocument": { "uri": uri } })).await?;
+                Ok((uri, true))
+            }
+            None => {
+                self.notify("textDocument/didOpen", json!({
+                    "textDocument": { "uri": uri, "languageId": self.language, "version": 1, "text": text }
+                })).await?;
+                documents.insert(uri.clone(), OpenDocument { version: 1, text });
+                Ok((uri, true))
+            }
+        }
+    }
+
+    /// Sync a document and return its diagnostics, waiting up to `wait` for a fresh publish
+    pub async fn diagnostics(&self, path: &Path, wait: Duration) -> Result<Vec<Value>> {
+        let uri = path_to_uri(path);
+        let generation_before = self.diagnostics_generation(&uri);
+        let (uri, changed) = self.sync_document(path).await?;
+
+        let needs_fresh = changed || generation_before == 0;
+        if needs_fresh {
+            let deadline = tokio::time::Instant::now() + wait;
+            loop {
+                let notified = self.diagnostics_updated.notified();
+                if self.diagnostics_generation(&uri) > generation_before {
+                    break;
+                }
+                if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                    debug!("No fresh diagnostics for {} within {:?}", uri, wait);
+                    break;
+                }
+            }
+        }
+
+        let mut diagnostics = self.diagnostics.lock().unwrap().by_uri.get(&uri).cloned().unwrap_or_default();
+        for diagnostic in &mut diagnostics {
+            self.range_to_columns(&uri, &mut diagnostic["range"]).await;
+        }
+        Ok(diagnostics)
+    }
+
+    fn diagnostics_generation(&self, uri: &str) -> u64 {
+        self.diagnostics.lock().unwrap().generation.get(uri).copied().unwrap_or(0)
+    }
+
+    /// Run a position-based request (hover, definition, references) against a synced document
+    ///
+    /// `character` is a 0-based character column, sent in the server's position encoding.
+    pub async fn position_request(&self, method: &str, path: &Path, line: u32, character: u32, extra: Value) -> Result<Value> {
+        let (uri, _) = self.sync_document(path).await?;
+        let character = match self.line_text(&uri, line).await {
+            Some(text) => self.position_encoding.offset(&text, character),
+            None => character,
+        };
+        let mut params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+        if let (Some(params), Some(extra)) = (params.as_object_mut(), extra.as_object()) {
+            params.extend(extra.clone());
+        }
+        self.request(method, params).await
+    }
+
+    /// Convert the ranges of a definition or references result into character columns
+    pub async fn locations_to_columns(&self, mut result: Value) -> Value {
+        if result.is_object() {
+            result = Value::Array(vec![result]);
+        }
+        if let Value::Array(locations) = &mut result {
+            for location in locations {
+                // Location uses uri/range, LocationLink uses targetUri/targetSelectionRange
+                let uri = location["uri"].as_str().or(location["targetUri"].as_str()).unwrap_or_default().to_string();
+                for key in ["range", "targetSelectionRange"] {
+                    if location[key].is_object() {
+                        self.range_to_columns(&uri, &mut location[key]).await;
+                    }
+                }
+            }
+        }
+        result
     }
 
-    // Placeholder implementations, replace with actual tower-lsp async client logic
+    /// Rewrite the server's offsets in a range into character columns
+    async fn range_to_columns(&self, uri: &str, range: &mut Value) {
+        if self.position_encoding == PositionEncoding::Utf32 || !range.is_object() {
+            return;
+        }
+        for end in ["start", "end"] {
+            let (Some(line), Some(offset)) = (range[end]["line"].as_u64(), range[end]["character"].as_u64()) else {
+                continue;
+            };
+            if let Some(text) = self.line_text(uri, line as u32).await {
+                range[end]["character"] = json!(self.position_encoding.column(&text, offset as u32));
+            }
+        }
+    }
 
-    async fn completions(&self, context: &str) -> Result<String> {
-        // TODO: integrate tower-lsp completion request
-        Ok(format!("Completions for context: {}", &context[..context.len().min(100)]))
+    /// A line of a document as last synced, or as on disk for documents never opened
+    async fn line_text(&self, uri: &str, line: u32) -> Option<String> {
+        let synced = self.documents.lock().await.get(uri).map(|document| document.text.clone());
+        let text = match synced {
+            Some(text) => text,
+            None => tokio::fs::read_to_string(uri_to_path(uri)).await.ok()?,
+        };
+        text.lines().nth(line as usize).map(str::to_string)
     }
 
-    async fn diagnostics(&self, code: &str) -> Result<String> {
-        // TODO: get diagnostics from language server
-        Ok("No diagnostics".to_string())
+    /// Request the symbol outline of a synced document
+    pub async fn document_symbols(&self, path: &Path) -> Result<Value> {
+        let (uri, _) = self.sync_document(path).await?;
+        self.request("textDocument/documentSymbol", json!({ "textDocument": { "uri": uri } })).await
     }
+}
+
+/// Lazily started language servers for a single project root
+#[derive(Debug)]
+pub struct LspManager {
+    root: PathBuf,
+    config: LspConfig,
+    clients: Mutex<HashMap<String, Arc<LspClient>>>,
+}
+
+impl LspManager {
+    pub fn new(root: PathBuf, config: LspConfig) -> Self {
+        Self { root, config, clients: Mutex::new(HashMap::new()) }
+    }
+
+    /// Get (starting if needed) the client responsible for a file
+    pub async fn client_for(&self, path: &Path) -> Result<Arc<LspClient>> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let server = self.config.server_for_extension(extension)
+            .ok_or_else(|| anyhow!("No language server configured for '.{}' files", extension))?;
 
-    async fn symbols(&self, code: &str) -> Result<String> {
-        // TODO: analyze symbols via language server
-        Ok("Symbols: [placeholder]".to_string())
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(&server.language) {
+            if !client.reader_task.is_finished() {
+                return Ok(client.clone());
+            }
+            warn!("{} language server exited, restarting", server.language);
+        }
+
+        let timeout = Duration::from_secs(self.config.request_timeout_secs);
+        let client = Arc::new(LspClient::spawn(server, &self.root, timeout).await?);
+        clients.insert(server.language.clone(), client.clone());
+        Ok(client)
     }
 
-    async fn type_info(&self, code: &str, position: usize) -> Result<String> {
-        // TODO: get type info at position
-        Ok(format!("Type info at position {}: unknown", position))
+    /// Register an already connected client for a language
+    pub async fn insert_client(&self, language: &str, client: LspClient) {
+        self.clients.lock().await.insert(language.to_string(), Arc::new(client));
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LspCommand {
+    /// Compiler/linter diagnostics for a file
+    Diagnostics,
+    /// Type information and documentation at a position
+    Hover,
+    /// Location(s) where the symbol at a position is defined
+    Definition,
+    /// All references to the symbol at a position
+    References,
+    /// Outline of symbols declared in a file
+    Symbols,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LspParams {
-    pub command: String,
-    pub context: Option<String>,
-    pub code: Option<String>,
-    pub position: Option<usize>,
+    #[schemars(description = "The LSP command: diagnostics, hover, definition, references or symbols.")]
+    pub command: LspCommand,
+    #[schemars(description = "Relative path of the file to analyze.")]
+    pub path: String,
+    #[schemars(description = "1-based line number. Required for hover, definition and references.")]
+    pub line: Option<u32>,
+    #[schemars(description = "1-based column number. Required for hover, definition and references.")]
+    pub column: Option<u32>,
+}
+
+// LspTool - Query language servers for diagnostics, hover, definitions and references
+#[derive(Debug, Clone)]
+pub struct LspTool {
+    base_path: PathBuf,
+    diagnostics_wait: Duration,
+    manager: Arc<LspManager>,
+}
+
+impl LspTool {
+    pub fn new(base_path: &str) -> Self {
+        Self::with_config(base_path, LspConfig::default())
+    }
+
+    pub fn with_config(base_path: &str, config: LspConfig) -> Self {
+        let base_path = PathBuf::from(base_path);
+        let base_path = std::fs::canonicalize(&base_path).unwrap_or(base_path);
+        debug!("LspTool initialized for {}", base_path.display());
+        Self {
+            diagnostics_wait: Duration::from_millis(config.diagnostics_wait_ms),
+            manager: Arc::new(LspManager::new(base_path.clone(), config)),
+            base_path,
+        }
+    }
+
+    /// Use an existing manager (e.g., one shared between tool sets or with injected clients)
+    pub fn with_manager(base_path: &str, diagnostics_wait: Duration, manager: Arc<LspManager>) -> Self {
+        let base_path = PathBuf::from(base_path);
+        let base_path = std::fs::canonicalize(&base_path).unwrap_or(base_path);
+        Self { base_path, diagnostics_wait, manager }
+    }
+
+    fn resolve_path(&self, relative_path: &str) -> Result<PathBuf> {
+        let target = self.base_path.join(relative_path.trim_start_matches('/'));
+        let target = std::fs::canonicalize(&target)
+            .with_context(|| format!("File not found: {}", relative_path))?;
+        if !target.starts_with(&self.base_path) {
+            return Err(anyhow!("Access denied: {} is outside the workspace", relative_path));
+        }
+        Ok(target)
+    }
+
+    fn position(params: &LspParams) -> Result<(u32, u32)> {
+        match (params.line, params.column) {
+            (Some(line), Some(column)) if line > 0 && column > 0 => Ok((line - 1, column - 1)),
+            _ => Err(anyhow!("{:?} requires 1-based 'line' and 'column'", params.command)),
+        }
+    }
+
+    async fn run(&self, params: &LspParams) -> Result<String> {
+        let path = self.resolve_path(&params.path)?;
+        let client = self.manager.client_for(&path).await?;
+
+        match params.command {
+            LspCommand::Diagnostics => {
+                let diagnostics = client.diagnostics(&path, self.diagnostics_wait).await?;
+                Ok(format_diagnostics(&params.path, &diagnostics))
+            }
+            LspCommand::Hover => {
+                let (line, character) = Self::position(params)?;
+                let hover = client.position_request("textDocument/hover", &path, line, character, json!({})).await?;
+                Ok(format_hover(&hover))
+            }
+            LspCommand::Definition => {
+                let (line, character) = Self::position(params)?;
+                let result = client.position_request("textDocument/definition", &path, line, character, json!({})).await?;
+                let result = client.locations_to_columns(result).await;
+                Ok(format_locations(&self.base_path, &result, "No definition found"))
+            }
+            LspCommand::References => {
+                let (line, character) = Self::position(params)?;
+                let result = client.position_request(
+                    "textDocument/references", &path, line, character,
+                    json!({ "context": { "includeDeclaration": true } }),
+                ).await?;
+                let result = client.locations_to_columns(result).await;
+                Ok(format_locations(&self.base_path, &result, "No references found"))
+            }
+            LspCommand::Symbols => {
+                let result = client.document_symbols(&path).await?;
+                Ok(format_symbols(&result))
+            }
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -54,53 +596,391 @@ impl TypedTool for LspTool {
     }
 
     fn description(&self) -> &str {
-        "Language Server Protocol tool for code analysis and completion"
+        "Language Server Protocol tool for code analysis. Commands: diagnostics (errors/warnings for a file), hover (type info at line/column), definition, references and symbols. Paths are relative; line and column are 1-based."
     }
 
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        true
+    }
+
+    #[instrument(name = "lsp_tool", skip(self), fields(
+        tool_name = "lsp",
+        path = %params.path,
+        success = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
     async fn call(&self, params: Self::Params) -> Result<ToolResult> {
-        let result = match params.command.as_str() {
-                "completions" => {
-                    let context = params.context.unwrap_or_default();
-                    self.completions(&context).await?
-                }
-                "diagnostics" => {
-                    let code = params.code.unwrap_or_default();
-                    self.diagnostics(&code).await?
-                }
-                "symbols" => {
-                    let code = params.code.unwrap_or_default();
-                    self.symbols(&code).await?
-                }
-                "type_info" => {
-                    let code = params.code.unwrap_or_default();
-                    let position = params.position.unwrap_or(0);
-                    self.type_info(&code, position).await?
-                }
-                _ => return Err(anyhow!("Unknown LSP command: {}", params.command)),
+        let current_span = tracing::Span::current();
+        match self.run(&params).await {
+            Ok(output) => {
+                current_span.record("success", true);
+                Ok(ToolResult { success: true, output })
+            }
+            Err(e) => {
+                let error_msg = format!("LSP error: {:#}", e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                Ok(ToolResult { success: false, output: error_msg })
+            }
+        }
+    }
+}
+
+// ============== Wire format ==============
+
+async fn write_message<W: AsyncWrite + Unpin + ?Sized>(writer: &mut W, message: &Value) -> Result<()> {
+    let body = serde_json::to_vec(message)?;
+    writer.write_all(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes()).await?;
+    writer.write_all(&body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let mut body = vec![0u8; content_length.unwrap_or(0)];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let raw = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'%' && i + 2 < raw.len() {
+            if let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(&raw[i + 1..i + 3]), 16) {
+                bytes.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        bytes.push(raw[i]);
+        i += 1;
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// ============== Output formatting ==============
+
+fn format_diagnostics(path: &str, diagnostics: &[Value]) -> String {
+    if diagnostics.is_empty() {
+        return format!("No diagnostics for {}", path);
+    }
+    diagnostics
+        .iter()
+        .map(|d| {
+            let severity = match d["severity"].as_u64() {
+                Some(1) => "error",
+                Some(2) => "warning",
+                Some(3) => "info",
+                Some(4) => "hint",
+                _ => "diagnostic",
             };
+            let source = d["source"].as_str().map(|s| format!(" [{}]", s)).unwrap_or_default();
+            format!(
+                "{}:{}:{}: {}: {}{}",
+                path,
+                d["range"]["start"]["line"].as_u64().unwrap_or(0) + 1,
+                d["range"]["start"]["character"].as_u64().unwrap_or(0) + 1,
+                severity,
+                d["message"].as_str().unwrap_or_default(),
+                source,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-        Ok(ToolResult {
-            success: true,
-            output: result,
+fn format_hover(hover: &Value) -> String {
+    fn marked(value: &Value) -> String {
+        match value {
+            Value::String(s) => s.clone(),
+            Value::Object(o) => o.get("value").and_then(Value::as_str).unwrap_or_default().to_string(),
+            Value::Array(items) => items.iter().map(marked).collect::<Vec<_>>().join("\n\n"),
+            _ => String::new(),
+        }
+    }
+    let text = marked(&hover["contents"]);
+    if text.trim().is_empty() {
+        "No hover information".to_string()
+    } else {
+        text
+    }
+}
+
+fn format_locations(root: &Path, result: &Value, empty: &str) -> String {
+    let locations: Vec<&Value> = match result {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(_) => vec![result],
+        _ => vec![],
+    };
+    if locations.is_empty() {
+        return empty.to_string();
+    }
+    locations
+        .iter()
+        .map(|location| {
+            // Location uses uri/range, LocationLink uses targetUri/targetSelectionRange
+            let uri = location["uri"].as_str().or(location["targetUri"].as_str()).unwrap_or_default();
+            let range = if location["range"].is_object() { &location["range"] } else { &location["targetSelectionRange"] };
+            let path = uri_to_path(uri);
+            let display = path.strip_prefix(root).unwrap_or(&path);
+            format!(
+                "{}:{}:{}",
+                display.display(),
+                range["start"]["line"].as_u64().unwrap_or(0) + 1,
+                range["start"]["character"].as_u64().unwrap_or(0) + 1,
+            )
         })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_symbols(result: &Value) -> String {
+    fn kind_name(kind: u64) -> &'static str {
+        match kind {
+            2 => "module",
+            5 => "class",
+            6 => "method",
+            8 => "field",
+            10 => "enum",
+            11 => "interface",
+            12 => "function",
+            13 => "variable",
+            14 => "constant",
+            22 => "enum_member",
+            23 => "struct",
+            26 => "type_parameter",
+            _ => "symbol",
+        }
+    }
+    fn walk(symbols: &[Value], depth: usize, out: &mut Vec<String>) {
+        for symbol in symbols {
+            let range = if symbol["range"].is_object() { &symbol["range"] } else { &symbol["location"]["range"] };
+            out.push(format!(
+                "{}{} {} (line {})",
+                "  ".repeat(depth),
+                kind_name(symbol["kind"].as_u64().unwrap_or(0)),
+                symbol["name"].as_str().unwrap_or_default(),
+                range["start"]["line"].as_u64().unwrap_or(0) + 1,
+            ));
+            if let Some(children) = symbol["children"].as_array() {
+                walk(children, depth + 1, out);
+            }
+        }
+    }
+    let mut out = Vec::new();
+    walk(result.as_array().map(Vec::as_slice).unwrap_or_default(), 0, &mut out);
+    if out.is_empty() {
+        "No symbols found".to_string()
+    } else {
+        out.join("\n")
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tokio::io::DuplexStream;
+
+    /// UTF-16 span of `answer` on the first line of a document
+    fn answer_span(text: &str) -> (usize, usize) {
+        let line = text.lines().next().unwrap_or_default();
+        let start = line.find("answer").map_or(0, |byte| line[..byte].encode_utf16().count());
+        (start, start + "answer".len())
+    }
+
+    /// Minimal scripted language server: answers initialize/hover/definition/references
+    /// in UTF-16 positions, locating `answer` on the document's first line, and publishes
+    /// one diagnostic per line containing "TODO" whenever a document changes.
+    async fn fake_server(stream: DuplexStream) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+        let mut documents: HashMap<String, String> = HashMap::new();
+        while let Ok(Some(message)) = read_message(&mut reader).await {
+            let method = message["method"].as_str().unwrap_or_default();
+            let params = &message["params"];
+            let reply = |result: Value| json!({ "jsonrpc": "2.0", "id": message["id"], "result": result });
+            let outgoing = match method {
+                "initialize" => reply(json!({ "capabilities": {} })),
+                "textDocument/didOpen" | "textDocument/didChange" => {
+                    let text = params["textDocument"]["text"].as_str()
+                        .or(params["contentChanges"][0]["text"].as_str())
+                        .unwrap_or_default();
+                    documents.insert(params["textDocument"]["uri"].as_str().unwrap_or_default().to_string(), text.to_string());
+                    let diagnostics: Vec<Value> = text.lines().enumerate()
+                        .filter(|(_, l)| l.contains("TODO"))
+                        .map(|(i, _)| json!({
+                            "range": { "start": { "line": i, "character": 0 }, "end": { "line": i, "character": 4 } },
+                            "severity": 2, "message": "unfinished work", "source": "fake"
+                        }))
+                        .collect();
+                    json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics",
+                            "params": { "uri": params["textDocument"]["uri"], "diagnostics": diagnostics } })
+                }
+                "textDocument/hover" => {
+                    let (start, end) = answer_span(&documents[params["textDocument"]["uri"].as_str().unwrap()]);
+                    let character = params["position"]["character"].as_u64().unwrap() as usize;
+                    if (start..end).contains(&character) {
+                        reply(json!({ "contents": { "kind": "markdown", "value": "fn answer() -> u32" } }))
+                    } else {
+                        reply(Value::Null)
+                    }
+                }
+                "textDocument/definition" | "textDocument/references" => {
+                    let (start, end) = answer_span(&documents[params["textDocument"]["uri"].as_str().unwrap()]);
+                    reply(json!([{
+                        "uri": params["textDocument"]["uri"],
+                        "range": { "start": { "line": 0, "character": start }, "end": { "line": 0, "character": end } }
+                    }]))
+                }
+                _ if message.get("id").is_some() => reply(Value::Null),
+                _ => continue,
+            };
+            write_message(&mut writer, &outgoing).await.unwrap();
+        }
+    }
+
+    fn temp_project(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("q_lsp_tool_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::canonicalize(dir).unwrap()
+    }
+
+    async fn tool_with_fake_server(root: &Path) -> LspTool {
+        let (client_stream, server_stream) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(fake_server(server_stream));
+        let (reader, writer) = tokio::io::split(client_stream);
+        let client = LspClient::connect(reader, writer, None, "rust", root, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+        let manager = Arc::new(LspManager::new(root.to_path_buf(), LspConfig::default()));
+        manager.insert_client("rust", client).await;
+        LspTool::with_manager(root.to_str().unwrap(), Duration::from_secs(2), manager)
+    }
+
+    fn params(command: LspCommand, path: &str, position: Option<(u32, u32)>) -> LspParams {
+        LspParams { command, path: path.to_string(), line: position.map(|p| p.0), column: position.map(|p| p.1) }
+    }
 
     #[tokio::test]
-    async fn test_lsp_tool_call() {
-        let tool = LspTool::new();
-        let params = LspParams {
-            command: "completions".to_string(),
-            context: Some("fn main() { pri".to_string()),
-            code: None,
-            position: None,
-        };
-        let result = tool.call(params).await.unwrap();
-        assert!(result.success);
-        assert!(result.output.contains("Completions"));
+    async fn test_diagnostics_follow_file_edits() {
+        let root = temp_project("diagnostics");
+        std::fs::write(root.join("main.rs"), "fn answer() -> u32 { 42 }\n").unwrap();
+        let tool = tool_with_fake_server(&root).await;
+
+        let result = TypedTool::call(&tool, params(LspCommand::Diagnostics, "main.rs", None)).await.unwrap();
+        assert!(result.success, "{}", result.output);
+        assert!(result.output.contains("No diagnostics"));
+
+        // Simulate a write_file edit; the next query must see the new content
+        std::fs::write(root.join("main.rs"), "fn answer() -> u32 { 42 }\n// TODO\n").unwrap();
+        let result = TypedTool::call(&tool, params(LspCommand::Diagnostics, "main.rs", None)).await.unwrap();
+        assert!(result.output.contains("main.rs:2:1: warning: unfinished work [fake]"), "{}", result.output);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_hover_definition_and_references() {
+        let root = temp_project("navigation");
+        std::fs::write(root.join("lib.rs"), "fn answer() -> u32 { 42 }\n").unwrap();
+        let tool = tool_with_fake_server(&root).await;
+
+        let hover = TypedTool::call(&tool, params(LspCommand::Hover, "lib.rs", Some((1, 4)))).await.unwrap();
+        assert_eq!(hover.output, "fn answer() -> u32");
+
+        let definition = TypedTool::call(&tool, params(LspCommand::Definition, "lib.rs", Some((1, 4)))).await.unwrap();
+        assert_eq!(definition.output, "lib.rs:1:4");
+
+        let references = TypedTool::call(&tool, params(LspCommand::References, "lib.rs", Some((1, 4)))).await.unwrap();
+        assert_eq!(references.output, "lib.rs:1:4");
+
+        let missing_position = TypedTool::call(&tool, params(LspCommand::Hover, "lib.rs", None)).await.unwrap();
+        assert!(!missing_position.success);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_columns_count_characters_on_multibyte_lines() {
+        let root = temp_project("multibyte");
+        // The emoji is one character but two UTF-16 units (and four UTF-8 bytes)
+        std::fs::write(root.join("lib.rs"), "/*😀*/fn answer() -> u32 { 42 }\n").unwrap();
+        let tool = tool_with_fake_server(&root).await;
+
+        let hover = TypedTool::call(&tool, params(LspCommand::Hover, "lib.rs", Some((1, 9)))).await.unwrap();
+        assert_eq!(hover.output, "fn answer() -> u32");
+
+        let definition = TypedTool::call(&tool, params(LspCommand::Definition, "lib.rs", Some((1, 9)))).await.unwrap();
+        assert_eq!(definition.output, "lib.rs:1:9");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_position_encodings_convert_columns() {
+        let line = "é😀x";
+        assert_eq!(PositionEncoding::Utf16.offset(line, 2), 3);
+        assert_eq!(PositionEncoding::Utf8.offset(line, 2), 6);
+        assert_eq!(PositionEncoding::Utf32.offset(line, 2), 2);
+        assert_eq!(PositionEncoding::Utf16.column(line, 3), 2);
+        assert_eq!(PositionEncoding::Utf8.column(line, 6), 2);
+        // Past the end of the line columns and offsets stay one to one
+        assert_eq!(PositionEncoding::Utf16.offset(line, 5), 6);
+        assert_eq!(PositionEncoding::Utf16.column(line, 6), 5);
+        assert_eq!(PositionEncoding::negotiated(&json!({ "positionEncoding": "utf-8" })), PositionEncoding::Utf8);
+        assert_eq!(PositionEncoding::negotiated(&json!({})), PositionEncoding::Utf16);
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_language_is_an_error_result() {
+        let root = temp_project("unconfigured");
+        std::fs::write(root.join("notes.xyz"), "hello").unwrap();
+        let tool = LspTool::new(root.to_str().unwrap());
+
+        let result = TypedTool::call(&tool, params(LspCommand::Diagnostics, "notes.xyz", None)).await.unwrap();
+        assert!(!result.success);
+        assert!(result.output.contains("No language server configured"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_uri_round_trip() {
+        let path = Path::new("/tmp/my project/src/ü.rs");
+        assert_eq!(uri_to_path(&path_to_uri(path)), path);
     }
 }
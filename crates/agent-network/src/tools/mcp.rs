@@ -0,0 +1,373 @@
+//! Model Context Protocol client: mounts tools of external MCP servers as agent tools
+//!
+//! Each configured server (stdio child process or streamable HTTP endpoint) is connected
+//! once at startup; its tools are listed and wrapped as `McpTool`s named `<server>__<tool>`,
+//! with the server's JSON schemas passed through unchanged. A server that cannot be reached
+//! is skipped so the agent still starts without its tools.
+
+use async_openai::types::{ChatCompletionTool, ChatCompletionToolType, FunctionObject};
+use async_trait::async_trait;
+use anyhow::{anyhow, Context, Result};
+use ai_agent_common::{McpServerConfig, RiskLevel};
+use rmcp::model::{CallToolRequestParams, CallToolResult, RawContent, ResourceContents};
+use rmcp::service::{RoleClient, RunningService};
+use rmcp::transport::{ConfigureCommandExt, StreamableHttpClientTransport, TokioChildProcess};
+use rmcp::ServiceExt;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::{debug, info, instrument, warn};
+use crate::tools::{Tool, ToolResult};
+
+/// Separator between server name and tool name in exposed tool names
+const NAME_SEPARATOR: &str = "__";
+
+/// Exposed name of a server's tool; characters LLM APIs reject in function names become `_`
+fn exposed_name(server: &str, tool: &str) -> String {
+    let tool: String = tool.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    format!("{}{}{}", server, NAME_SEPARATOR, tool)
+}
+
+/// A connected MCP server; the connection is closed when the last tool using it is dropped
+pub struct McpClient {
+    name: String,
+    service: RunningService<RoleClient, ()>,
+}
+
+impl std::fmt::Debug for McpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpClient").field("name", &self.name).finish()
+    }
+}
+
+impl McpClient {
+    /// Connect to a server as described by its configuration
+    #[instrument(name = "mcp_connect", skip(config), fields(server = %config.name))]
+    pub async fn connect(config: &McpServerConfig) -> Result<Self> {
+        let service = match (&config.command, &config.url) {
+            (Some(command), _) => {
+                let command = tokio::process::Command::new(command).configure(|cmd| {
+                    cmd.args(&config.args).envs(&config.env);
+                });
+                let transport = TokioChildProcess::new(command)
+                    .with_context(|| format!("Failed to launch MCP server '{}'", config.name))?;
+                ().serve(transport).await
+            }
+            (None, Some(url)) => ().serve(StreamableHttpClientTransport::from_uri(url.as_str())).await,
+            (None, None) => return Err(anyhow!("MCP server '{}' has neither command nor url", config.name)),
+        }
+        .with_context(|| format!("Failed to initialize MCP server '{}'", config.name))?;
+
+        info!("Connected to MCP server {}", config.name);
+        Ok(Self { name: config.name.clone(), service })
+    }
+
+    /// Wrap an already running client session (e.g., over an in-process transport)
+    pub fn from_service(name: &str, service: RunningService<RoleClient, ()>) -> Self {
+        Self { name: name.to_string(), service }
+    }
+
+    /// List the server's tools and wrap them, keeping only `allowed` ones when non-empty
+    ///
+    /// Tools are treated as read-only only when `trust_read_only_hints` is set and the
+    /// server annotates them so; any server could claim it otherwise.
+    pub async fn tools(self: Arc<Self>, allowed: &[String], risk_level: Option<RiskLevel>, trust_read_only_hints: bool) -> Result<Vec<McpTool>> {
+        let tools = self.service.list_all_tools().await
+            .with_context(|| format!("Failed to list tools of MCP server '{}'", self.name))?;
+
+        let mut mounted: Vec<McpTool> = Vec::new();
+        for tool in tools.into_iter()
+            .filter(|tool| allowed.is_empty() || allowed.iter().any(|name| name == tool.name.as_ref()))
+        {
+            let name = exposed_name(&self.name, &tool.name);
+            if let Some(other) = mounted.iter().find(|t| t.name == name) {
+                warn!("Skipping tool '{}' of MCP server {}: its name clashes with '{}' as {}", tool.name, self.name, other.remote_name, name);
+                continue;
+            }
+            mounted.push(McpTool {
+                name,
+                remote_name: tool.name.to_string(),
+                description: tool.description.map(|d| d.to_string()).unwrap_or_default(),
+                schema: Value::Object((*tool.input_schema).clone()),
+                risk_level,
+                read_only: trust_read_only_hints && tool.annotations.as_ref().and_then(|a| a.read_only_hint).unwrap_or(false),
+                client: self.clone(),
+            });
+        }
+
+        if let Some(missing) = allowed.iter().find(|name| !mounted.iter().any(|t| &t.remote_name == *name)) {
+            return Err(anyhow!("MCP server '{}' has no tool named '{}'", self.name, missing));
+        }
+        debug!("Mounted {} tools from MCP server {}", mounted.len(), self.name);
+        Ok(mounted)
+    }
+}
+
+/// Connect to all configured servers and return their tools; failing servers are logged and skipped
+pub async fn connect_servers(configs: &[McpServerConfig]) -> Vec<Arc<dyn Tool>> {
+    let mut tools: Vec<Arc<dyn Tool>> = Vec::new();
+    for config in configs {
+        let mounted = match McpClient::connect(config).await {
+            Ok(client) => Arc::new(client).tools(&config.tools, config.risk_level, config.trust_read_only_hints).await,
+            Err(e) => Err(e),
+        };
+        match mounted {
+            Ok(mounted) => tools.extend(mounted.into_iter().map(|tool| Arc::new(tool) as Arc<dyn Tool>)),
+            Err(e) => warn!("Skipping MCP server {}: {:#}", config.name, e),
+        }
+    }
+    tools
+}
+
+/// A tool provided by an MCP server
+#[derive(Debug, Clone)]
+pub struct McpTool {
+    name: String,
+    remote_name: String,
+    description: String,
+    schema: Value,
+    risk_level: Option<RiskLevel>,
+    /// The server's `readOnlyHint` annotation, when the server is trusted with it
+    read_only: bool,
+    client: Arc<McpClient>,
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    #[instrument(name = "mcp_tool", skip(self, arguments), fields(
+        tool_name = %self.name,
+        success = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(&self, arguments: &str) -> Result<ToolResult> {
+        let current_span = tracing::Span::current();
+        let arguments = match serde_json::from_str::<Value>(arguments) {
+            Ok(Value::Object(map)) => Some(map),
+            Ok(Value::Null) => None,
+            Ok(_) | Err(_) => {
+                let error_msg = format!("Arguments for {} must be a JSON object", self.name);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                return Ok(ToolResult { success: false, output: error_msg });
+            }
+        };
+
+        let request = CallToolRequestParams {
+            meta: None,
+            name: self.remote_name.clone().into(),
+            arguments,
+            task: None,
+        };
+        match self.client.service.call_tool(request).await {
+            Ok(result) => {
+                let success = !result.is_error.unwrap_or(false);
+                current_span.record("success", success);
+                Ok(ToolResult { success, output: format_result(&result) })
+            }
+            Err(e) => {
+                let error_msg = format!("MCP server '{}' failed to run {}: {}", self.client.name, self.remote_name, e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                Ok(ToolResult { success: false, output: error_msg })
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        self.schema.clone()
+    }
+
+    fn risk_level(&self, _arguments: &str) -> Option<RiskLevel> {
+        Some(self.risk_level.unwrap_or(RiskLevel::High))
+    }
+
+    fn is_read_only(&self, _arguments: &str) -> bool {
+        self.read_only
+    }
+
+    fn is_always_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// External schemas are not guaranteed to satisfy strict mode, so it is not requested
+    fn to_openai_tool(&self) -> ChatCompletionTool {
+        ChatCompletionTool {
+            r#type: ChatCompletionToolType::Function,
+            function: FunctionObject {
+                name: self.name.clone(),
+                description: Some(self.description.clone()),
+                parameters: Some(self.schema.clone()),
+                strict: None,
+            },
+        }
+    }
+}
+
+/// Flatten an MCP tool result into text for the agent
+fn format_result(result: &CallToolResult) -> String {
+    let mut parts: Vec<String> = result.content.iter().map(|content| match &content.raw {
+        RawContent::Text(text) => text.text.clone(),
+        RawContent::Resource(resource) => match &resource.resource {
+            ResourceContents::TextResourceContents { uri, text, .. } => format!("[{}]\n{}", uri, text),
+            ResourceContents::BlobResourceContents { uri, mime_type, .. } => {
+                format!("[binary resource {} ({})]", uri, mime_type.as_deref().unwrap_or("unknown type"))
+            }
+        },
+        RawContent::ResourceLink(link) => format!("[resource {}: {}]", link.name, link.uri),
+        RawContent::Image(image) => format!("[image ({})]", image.mime_type),
+        RawContent::Audio(audio) => format!("[audio ({})]", audio.mime_type),
+    }).collect();
+
+    if let Some(structured) = &result.structured_content {
+        if parts.is_empty() {
+            parts.push(structured.to_string());
+        }
+    }
+    parts.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::handler::server::{router::tool::ToolRouter, wrapper::Parameters};
+    use rmcp::model::Content;
+    use rmcp::{tool, tool_handler, tool_router, ServerHandler};
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct AddParams {
+        a: i64,
+        b: i64,
+    }
+
+    #[derive(Clone)]
+    struct TestServer {
+        tool_router: ToolRouter<Self>,
+    }
+
+    #[tool_router(router = tool_router)]
+    impl TestServer {
+        #[tool(name = "add", description = "Add two numbers", annotations(read_only_hint = true))]
+        async fn add(&self, Parameters(params): Parameters<AddParams>) -> String {
+            (params.a + params.b).to_string()
+        }
+
+        #[tool(name = "fail", description = "Always fails")]
+        async fn fail(&self) -> Result<CallToolResult, rmcp::ErrorData> {
+            Ok(CallToolResult::error(vec![Content::text("boom")]))
+        }
+    }
+
+    #[tool_handler(router = self.tool_router)]
+    impl ServerHandler for TestServer {}
+
+    async fn connect_test_server() -> Arc<McpClient> {
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let server = TestServer { tool_router: TestServer::tool_router() };
+        tokio::spawn(async move {
+            if let Ok(running) = server.serve(server_io).await {
+                let _ = running.waiting().await;
+            }
+        });
+        let service = ().serve(client_io).await.unwrap();
+        Arc::new(McpClient::from_service("calc", service))
+    }
+
+    #[tokio::test]
+    async fn test_server_tools_are_mounted_and_called() {
+        let client = connect_test_server().await;
+        let tools = client.tools(&[], None, false).await.unwrap();
+
+        let mut names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+        names.sort();
+        assert_eq!(names, vec!["calc__add", "calc__fail"]);
+
+        let add = tools.iter().find(|t| t.name() == "calc__add").unwrap();
+        assert!(add.parameters()["properties"].get("a").is_some());
+        assert_eq!(add.risk_level("{}"), Some(RiskLevel::High));
+
+        let result = add.call(r#"{"a": 2, "b": 3}"#).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, "5");
+
+        let fail = tools.iter().find(|t| t.name() == "calc__fail").unwrap();
+        let result = fail.call("{}").await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.output, "boom");
+
+        assert!(!add.call("[1, 2]").await.unwrap().success);
+    }
+
+    #[tokio::test]
+    async fn test_allowed_tools_filter_and_risk_override() {
+        let client = connect_test_server().await;
+
+        let tools = client.clone().tools(&["add".to_string()], Some(RiskLevel::Low), false).await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].risk_level("{}"), Some(RiskLevel::Low));
+
+        let err = client.tools(&["missing".to_string()], None, false).await.unwrap_err();
+        assert!(err.to_string().contains("no tool named 'missing'"));
+    }
+
+    #[tokio::test]
+    async fn test_read_only_hints_need_the_servers_opt_in() {
+        use crate::shadow::ShadowWorkspace;
+        use crate::tools::{AgentTools, FileScope, ToolRegistry};
+
+        let root = std::env::temp_dir().join(format!("mcp-shadow-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let root_str = root.to_string_lossy().to_string();
+        let shadow = Arc::new(ShadowWorkspace::create(&root.join(".shadow"), "conv", &root).unwrap());
+        let scope = FileScope { shadow: Some(shadow), journal: None };
+        let registry = Arc::new(ToolRegistry::default());
+
+        // `add` is annotated read-only, which only a trusted server may claim
+        for trusted in [false, true] {
+            let client = connect_test_server().await;
+            let add = client.tools(&["add".to_string()], None, trusted).await.unwrap().remove(0);
+            assert_eq!(add.is_read_only("{}"), trusted);
+
+            let tools = AgentTools::new(registry.clone(), vec!["read_file".to_string()]).unwrap()
+                .with_external_tools(vec![Arc::new(add)]);
+            let toolset = tools.toolset(&root_str).unwrap();
+            let shadowed = registry.scoped_toolset(&toolset, &root_str, &scope);
+            assert_eq!(shadowed.get("calc__add").is_some(), trusted);
+        }
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_exposed_names_are_valid_function_names() {
+        assert_eq!(exposed_name("calc", "add"), "calc__add");
+        assert_eq!(exposed_name("docs", "search.pages/v2"), "docs__search_pages_v2");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_servers_are_skipped() {
+        let config = |name: &str, command: &str| McpServerConfig {
+            name: name.to_string(),
+            command: Some(command.to_string()),
+            args: vec![],
+            env: Default::default(),
+            url: None,
+            tools: vec![],
+            risk_level: None,
+            trust_read_only_hints: false,
+        };
+
+        let tools = connect_servers(&[config("missing", "/nonexistent/mcp-server"), config("broken", "true")]).await;
+        assert!(tools.is_empty());
+    }
+}
@@ -3,19 +3,36 @@ use serde_json::Value;
 use schemars::JsonSchema;
 use anyhow::{Result, anyhow};
 use async_openai::types::{ChatCompletionTool, ChatCompletionToolType, FunctionObject};
+pub mod command;
+pub mod context;
+pub mod delegate;
 pub mod filesystem;
-// pub mod git;
+pub mod git;
 pub mod lsp;
+pub mod mcp;
+pub mod registry;
+pub mod search;
+pub mod treesitter;
 
 use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use ai_agent_common::RiskLevel;
 
 // Re-export the tool implementations
 pub use filesystem::{
-    WriteFileTool, ReadFileTool, ListDirectoryTool,
+    WriteFileTool, EditFileTool, ReadFileTool, ListDirectoryTool,
     CreateDirectoryTool, FileExistsTool, FileMetadataTool,
-    DeleteFileTool
+    DeleteFileTool, FileScope
 };
-pub use lsp::LspTool;
+pub use command::{RunCommandTool, RunTestsTool};
+pub use context::SearchContextTool;
+pub use delegate::DelegateTool;
+pub use git::GitTool;
+pub use lsp::{LspTool, LspManager};
+pub use mcp::{McpClient, McpTool};
+pub use registry::{AgentTools, ToolLimits, ToolRegistry, ToolSettings};
+pub use search::SearchCodeTool;
+pub use treesitter::CodeNavigationTool;
 
 use crate::tools::filesystem::FILESYSTEM_PREAMBLE;
 // Simple result type for tool execution
@@ -50,6 +67,26 @@ pub trait Tool: Send + Sync + std::fmt::Debug{
     /// Get the JSON schema for the tool's parameters
     fn parameters(&self) -> Value;
 
+    /// Tool-specific risk classification for a concrete call, if the tool provides one
+    fn risk_level(&self, _arguments: &str) -> Option<RiskLevel> {
+        None
+    }
+
+    /// Human-readable preview of a call's effect (e.g. a diff), shown in HITL requests
+    async fn preview(&self, _arguments: &str) -> Option<String> {
+        None
+    }
+
+    /// Whether a concrete call only reads state; read-only calls of one turn run concurrently
+    fn is_read_only(&self, _arguments: &str) -> bool {
+        false
+    }
+
+    /// Whether every call only reads state, whatever its arguments
+    fn is_always_read_only(&self) -> bool {
+        false
+    }
+
     /// Get the ChatCompletionTool definition for this tool (default implementation)
     fn to_openai_tool(&self) -> ChatCompletionTool {
         ChatCompletionTool {
@@ -69,7 +106,7 @@ pub trait Tool: Send + Sync + std::fmt::Debug{
 #[async_trait::async_trait]
 pub trait TypedTool: Send + Sync {
     /// The parameter struct for this tool
-    type Params: JsonSchema + for<'de> Deserialize<'de> + Send;
+    type Params: JsonSchema + for<'de> Deserialize<'de> + Send + Sync;
 
     /// Execute with typed parameters
     async fn call(&self, params: Self::Params) -> Result<ToolResult>;
@@ -80,6 +117,21 @@ pub trait TypedTool: Send + Sync {
     /// Get the tool description
     fn description(&self) -> &str;
 
+    /// Risk of a concrete call; `None` falls back to the agent's default classification
+    fn risk_level(&self, _params: &Self::Params) -> Option<RiskLevel> {
+        None
+    }
+
+    /// Preview of a call's effect without performing it; `None` shows the raw arguments
+    async fn preview(&self, _params: &Self::Params) -> Option<String> {
+        None
+    }
+
+    /// Whether a call only reads state; unparsable calls are treated as mutating
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        false
+    }
+
     /// Helper to generate schema (only available on concrete types)
     fn schema_for_params() -> Value where Self: Sized {
         let schema = schemars::schema_for!(Self::Params);
@@ -111,37 +163,78 @@ where
         let schema = schemars::schema_for!(T::Params);
         serde_json::to_value(schema).unwrap_or_default()
     }
+
+    fn risk_level(&self, arguments: &str) -> Option<RiskLevel> {
+        let params: T::Params = serde_json::from_str(arguments).ok()?;
+        TypedTool::risk_level(self, &params)
+    }
+
+    async fn preview(&self, arguments: &str) -> Option<String> {
+        let params: T::Params = serde_json::from_str(arguments).ok()?;
+        TypedTool::preview(self, &params).await
+    }
+
+    fn is_read_only(&self, arguments: &str) -> bool {
+        serde_json::from_str::<T::Params>(arguments)
+            .map(|params| TypedTool::is_read_only(self, &params))
+            .unwrap_or(false)
+    }
 }
 
 /// Collection of available tools
-#[derive(Debug)]
+///
+/// Tools are shared (`Arc`), so cloning a set to add execution-specific tools is cheap.
+/// Agents get their sets from `ToolRegistry` based on their configuration.
+#[derive(Debug, Clone, Default)]
 pub struct ToolSet {
-    tools: std::collections::HashMap<String, Box<dyn Tool>>,
+    tools: std::collections::HashMap<String, Arc<dyn Tool>>,
 }
 
 impl ToolSet {
-    pub fn new(base_path: &str) -> Self {
-        let mut tools = std::collections::HashMap::new();
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Add a (possibly shared) tool under the given name
+    pub fn insert(&mut self, name: String, tool: Arc<dyn Tool>) {
+        self.tools.insert(name, tool);
+    }
 
-        // Register available tools
-        tools.insert(
-            "write_file".to_string(),
-            Box::new(WriteFileTool::new(base_path)) as Box<dyn Tool>,
-        );
-        // ... other tools
+    pub fn remove(&mut self, tool_name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.remove(tool_name)
+    }
 
-        Self { tools }
+    pub fn get(&self, tool_name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.get(tool_name).cloned()
     }
 
     pub fn register_tool<T: Tool + 'static>(&mut self, tool: T) {
         let name = tool.name().to_string();
-        self.tools.insert(name, Box::new(tool));
+        self.tools.insert(name, Arc::new(tool));
     }
 
     pub fn available_tools(&self) -> Vec<String> {
         self.tools.keys().cloned().collect()
     }
 
+    /// Risk classification provided by the tool itself for a concrete call
+    pub fn risk_level(&self, tool_name: &str, arguments: &str) -> Option<RiskLevel> {
+        self.tools.get(tool_name).and_then(|t| t.risk_level(arguments))
+    }
+
+    /// Whether a call only reads state and may run concurrently with other read-only calls
+    pub fn is_read_only(&self, tool_name: &str, arguments: &str) -> bool {
+        self.tools.get(tool_name).is_some_and(|t| t.is_read_only(arguments))
+    }
+
+    /// Preview of a call's effect provided by the tool (e.g. the diff of a file edit)
+    pub async fn preview(&self, tool_name: &str, arguments: &str) -> Option<String> {
+        match self.tools.get(tool_name) {
+            Some(tool) => tool.preview(arguments).await,
+            None => None,
+        }
+    }
+
     pub fn to_openai_tools(&self, required_tools: &[String]) -> Result<Vec<ChatCompletionTool>> {
         let tools = required_tools
             .iter()
@@ -151,11 +244,26 @@ impl ToolSet {
     }
 
     pub async fn execute_tool(&self, tool_name: &str, arguments: &str) -> Result<ToolExecution> {
+        self.execute_tool_with_timeout(tool_name, arguments, None).await
+    }
+
+    /// Execute a tool; a call exceeding the timeout yields a failed result instead of an error,
+    /// so the agent can react to it
+    pub async fn execute_tool_with_timeout(&self, tool_name: &str, arguments: &str, timeout: Option<std::time::Duration>) -> Result<ToolExecution> {
         if let Some(tool) = self.tools.get(tool_name) {
             let start_time = std::time::Instant::now();
             let timestamp = chrono::Utc::now();
 
-            let result = tool.call(arguments).await?;
+            let result = match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, tool.call(arguments)).await {
+                    Ok(result) => result?,
+                    Err(_) => ToolResult {
+                        success: false,
+                        output: format!("Error: {} timed out after {:?}", tool_name, timeout),
+                    },
+                },
+                None => tool.call(arguments).await?,
+            };
             let execution_time_ms = start_time.elapsed().as_millis() as u64;
 
             Ok(ToolExecution {
@@ -175,3 +283,58 @@ impl ToolSet {
         return Some(FILESYSTEM_PREAMBLE.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    struct SlowTool;
+
+    #[async_trait::async_trait]
+    impl Tool for SlowTool {
+        async fn call(&self, _arguments: &str) -> Result<ToolResult> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(ToolResult { success: true, output: "done".to_string() })
+        }
+
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn description(&self) -> &str {
+            "Never finishes in time"
+        }
+
+        fn parameters(&self) -> Value {
+            serde_json::json!({"type": "object"})
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_tool_returns_failed_result() {
+        let mut toolset = ToolSet::empty();
+        toolset.register_tool(SlowTool);
+
+        let execution = toolset.execute_tool_with_timeout("slow", "{}", Some(Duration::from_millis(20))).await.unwrap();
+        assert!(!execution.result.success);
+        assert!(execution.result.output.contains("timed out after 20ms"));
+        assert!(!toolset.is_read_only("slow", "{}"));
+    }
+
+    #[test]
+    fn test_read_only_classification() {
+        let mut toolset = ToolSet::empty();
+        toolset.register_tool(ReadFileTool::new("."));
+        toolset.register_tool(WriteFileTool::new("."));
+        toolset.register_tool(GitTool::new("."));
+
+        assert!(toolset.is_read_only("read_file", r#"{"path": "a.txt"}"#));
+        assert!(!toolset.is_read_only("write_file", r#"{"path": "a.txt", "content": "x"}"#));
+        assert!(toolset.is_read_only("git", r#"{"command": "status"}"#));
+        assert!(!toolset.is_read_only("git", r#"{"command": "commit", "message": "x"}"#));
+        assert!(!toolset.is_read_only("git", "not json"));
+        assert!(!toolset.is_read_only("unknown", "{}"));
+    }
+}
@@ -0,0 +1,434 @@
+//! Tool registry mapping tool names to constructors
+//!
+//! Agents get a `ToolSet` built from their `AgentConfig.available_tools`/`required_tools`
+//! instead of a hardcoded list. Tool instances are created once per project root and shared
+//! between agents, so stateful tools (e.g. language servers behind `lsp`) are not respawned
+//! on every execution.
+
+use anyhow::{anyhow, Result};
+use ai_agent_common::{AgentConfig, CommandConfig, IndexingFilters, LspConfig, SystemConfig};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::debug;
+use crate::agents::AgentContext;
+use crate::hitl::ToolPolicy;
+use crate::tools::{
+    CodeNavigationTool, CreateDirectoryTool, DelegateTool, DeleteFileTool, EditFileTool, FileExistsTool,
+    FileMetadataTool, FileScope, GitTool, ListDirectoryTool, LspTool, ReadFileTool, RunCommandTool,
+    RunTestsTool, SearchCodeTool, SearchContextTool, Tool, ToolSet, WriteFileTool,
+};
+
+/// Tools that depend on the execution (conversation, RAG pipeline) rather than only on
+/// the project root; they are attached per execution in `AgentTools::toolset_for`
+const CONTEXT_TOOLS: &[&str] = &["search_context", "delegate"];
+
+/// Tools bounded by their own timeouts; delegated sub-tasks run under the task timeout
+const UNTIMED_TOOLS: &[&str] = &["delegate"];
+
+/// Configuration handed to tool constructors
+#[derive(Debug, Clone, Default)]
+pub struct ToolSettings {
+    pub lsp: LspConfig,
+    pub commands: CommandConfig,
+    pub filters: IndexingFilters,
+}
+
+impl ToolSettings {
+    pub fn from_config(config: &SystemConfig) -> Self {
+        Self {
+            lsp: config.agent_network.lsp.clone(),
+            commands: config.agent_network.commands.clone(),
+            filters: config.indexing.filters.clone(),
+        }
+    }
+}
+
+/// Builds a tool for a project root
+pub type ToolConstructor = fn(&ToolSettings, &str) -> Arc<dyn Tool>;
+
+/// Builds a filesystem tool bound to an execution's file scope (shadow overlay, journal)
+pub type ScopedConstructor = fn(&str, FileScope) -> Arc<dyn Tool>;
+
+/// Registry of all tools agents can be configured with
+#[derive(Debug)]
+pub struct ToolRegistry {
+    settings: ToolSettings,
+    constructors: HashMap<&'static str, ToolConstructor>,
+    scoped_constructors: HashMap<&'static str, ScopedConstructor>,
+    /// Shared instances keyed by (project root, tool name)
+    instances: Mutex<HashMap<(String, String), Arc<dyn Tool>>>,
+}
+
+impl ToolRegistry {
+    pub fn new(settings: ToolSettings) -> Self {
+        let mut constructors: HashMap<&'static str, ToolConstructor> = HashMap::new();
+        constructors.insert("read_file", |_, root| Arc::new(ReadFileTool::new(root)));
+        constructors.insert("write_file", |_, root| Arc::new(WriteFileTool::new(root)));
+        constructors.insert("edit_file", |_, root| Arc::new(EditFileTool::new(root)));
+        constructors.insert("list_directory", |_, root| Arc::new(ListDirectoryTool::new(root)));
+        constructors.insert("create_directory", |_, root| Arc::new(CreateDirectoryTool::new(root)));
+        constructors.insert("file_exists", |_, root| Arc::new(FileExistsTool::new(root)));
+        constructors.insert("file_metadata", |_, root| Arc::new(FileMetadataTool::new(root)));
+        constructors.insert("delete_file", |_, root| Arc::new(DeleteFileTool::new(root)));
+        constructors.insert("git", |_, root| Arc::new(GitTool::new(root)));
+        constructors.insert("run_command", |s, root| Arc::new(RunCommandTool::with_config(root, s.commands.clone())));
+        constructors.insert("run_tests", |s, root| Arc::new(RunTestsTool::with_config(root, s.commands.clone())));
+        constructors.insert("search_code", |s, root| Arc::new(SearchCodeTool::with_filters(root, s.filters.clone())));
+        constructors.insert("code_navigation", |s, root| Arc::new(CodeNavigationTool::with_filters(root, s.filters.clone())));
+        constructors.insert("lsp", |s, root| Arc::new(LspTool::with_config(root, s.lsp.clone())));
+
+        let mut scoped_constructors: HashMap<&'static str, ScopedConstructor> = HashMap::new();
+        scoped_constructors.insert("read_file", |root, scope| Arc::new(ReadFileTool::scoped(root, scope)));
+        scoped_constructors.insert("write_file", |root, scope| Arc::new(WriteFileTool::scoped(root, scope)));
+        scoped_constructors.insert("edit_file", |root, scope| Arc::new(EditFileTool::scoped(root, scope)));
+        scoped_constructors.insert("list_directory", |root, scope| Arc::new(ListDirectoryTool::scoped(root, scope)));
+        scoped_constructors.insert("create_directory", |root, scope| Arc::new(CreateDirectoryTool::scoped(root, scope)));
+        scoped_constructors.insert("file_exists", |root, scope| Arc::new(FileExistsTool::scoped(root, scope)));
+        scoped_constructors.insert("file_metadata", |root, scope| Arc::new(FileMetadataTool::scoped(root, scope)));
+        scoped_constructors.insert("delete_file", |root, scope| Arc::new(DeleteFileTool::scoped(root, scope)));
+
+        Self { settings, constructors, scoped_constructors, instances: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn from_config(config: &SystemConfig) -> Self {
+        Self::new(ToolSettings::from_config(config))
+    }
+
+    /// Whether a tool name can be configured for an agent
+    pub fn is_known(&self, name: &str) -> bool {
+        self.constructors.contains_key(name) || CONTEXT_TOOLS.contains(&name)
+    }
+
+    /// Names of all tools agents can be configured with, sorted
+    pub fn known_tools(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.constructors.keys().copied().chain(CONTEXT_TOOLS.iter().copied()).collect();
+        names.sort();
+        names
+    }
+
+    /// Build a tool set with the given tools for a project root, reusing shared instances
+    pub fn toolset(&self, names: &[String], root: &str) -> Result<ToolSet> {
+        let mut toolset = ToolSet::empty();
+        let mut instances = self.instances.lock().unwrap_or_else(|e| e.into_inner());
+        for name in names {
+            if CONTEXT_TOOLS.contains(&name.as_str()) {
+                continue;
+            }
+            let constructor = self.constructors.get(name.as_str())
+                .ok_or_else(|| anyhow!("Unknown tool: {}", name))?;
+            let tool = instances
+                .entry((root.to_string(), name.clone()))
+                .or_insert_with(|| {
+                    debug!("Constructing tool {} for {}", name, root);
+                    constructor(&self.settings, root)
+                })
+                .clone();
+            toolset.insert(name.clone(), tool);
+        }
+        Ok(toolset)
+    }
+
+    /// Rebind a tool set's filesystem tools to an execution's file scope
+    ///
+    /// In shadow mode only tools that go through the overlay are kept: the other built-ins
+    /// either change the real tree (`git`, `run_command`, `run_tests`) or read it past the
+    /// overlay (`search_code`, `code_navigation`, `lsp`), and external tools (MCP) stay only
+    /// when they are read-only, which a server is trusted to declare only by opting in.
+    pub fn scoped_toolset(&self, toolset: &ToolSet, root: &str, scope: &FileScope) -> ToolSet {
+        let mut scoped = toolset.clone();
+        for name in toolset.available_tools() {
+            if let Some(constructor) = self.scoped_constructors.get(name.as_str()) {
+                scoped.insert(name, constructor(root, scope.clone()));
+            } else if scope.shadow.is_some() && !self.is_shadow_safe(&name, toolset) {
+                debug!("Withholding tool {} in shadow mode", name);
+                scoped.remove(&name);
+            }
+        }
+        scoped
+    }
+
+    /// Whether a tool without an overlay-bound variant may run in shadow mode
+    fn is_shadow_safe(&self, name: &str, toolset: &ToolSet) -> bool {
+        !self.constructors.contains_key(name)
+            && toolset.get(name).is_some_and(|tool| tool.is_always_read_only())
+    }
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new(ToolSettings::default())
+    }
+}
+
+/// Concurrency and timeout limits for one agent's tool calls
+#[derive(Debug, Clone)]
+pub struct ToolLimits {
+    /// Maximum number of read-only calls of one turn running at once
+    pub max_parallel: usize,
+    pub default_timeout: Duration,
+    /// Per-tool overrides of `default_timeout`
+    pub timeouts: HashMap<String, Duration>,
+}
+
+impl ToolLimits {
+    pub fn from_agent_config(config: &AgentConfig) -> Self {
+        Self {
+            max_parallel: config.max_parallel_tools.max(1),
+            default_timeout: Duration::from_secs(config.tool_timeout_secs),
+            timeouts: config.tool_timeouts.iter()
+                .map(|(name, secs)| (name.clone(), Duration::from_secs(*secs)))
+                .collect(),
+        }
+    }
+
+    /// Timeout of a call; `None` for tools bounded by their own timeouts unless configured
+    pub fn timeout_for(&self, tool_name: &str) -> Option<Duration> {
+        match self.timeouts.get(tool_name) {
+            Some(timeout) => Some(*timeout),
+            None if UNTIMED_TOOLS.contains(&tool_name) => None,
+            None => Some(self.default_timeout),
+        }
+    }
+}
+
+impl Default for ToolLimits {
+    fn default() -> Self {
+        Self {
+            max_parallel: 4,
+            default_timeout: Duration::from_secs(300),
+            timeouts: HashMap::new(),
+        }
+    }
+}
+
+/// The tools configured for one agent, resolved per project root on first use
+#[derive(Debug)]
+pub struct AgentTools {
+    registry: Arc<ToolRegistry>,
+    names: Vec<String>,
+    /// Root-independent tools from outside the registry (e.g., mounted MCP server tools)
+    external: Vec<Arc<dyn Tool>>,
+    /// Risk policy deciding which of this agent's tool calls need approval
+    policy: Arc<ToolPolicy>,
+    limits: ToolLimits,
+    toolsets: Mutex<HashMap<String, Arc<ToolSet>>>,
+}
+
+impl AgentTools {
+    pub fn new(registry: Arc<ToolRegistry>, names: Vec<String>) -> Result<Self> {
+        if let Some(unknown) = names.iter().find(|name| !registry.is_known(name)) {
+            return Err(anyhow!("Unknown tool '{}'. Known tools: {}", unknown, registry.known_tools().join(", ")));
+        }
+        Ok(Self {
+            registry,
+            names,
+            external: Vec::new(),
+            policy: Arc::new(ToolPolicy::default()),
+            limits: ToolLimits::default(),
+            toolsets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn with_policy(mut self, policy: Arc<ToolPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> &ToolPolicy {
+        &self.policy
+    }
+
+    pub fn with_limits(mut self, limits: ToolLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &ToolLimits {
+        &self.limits
+    }
+
+    /// Add tools that are not constructed by the registry
+    pub fn with_external_tools(mut self, tools: Vec<Arc<dyn Tool>>) -> Self {
+        self.external.extend(tools);
+        self
+    }
+
+    pub fn from_agent_config(registry: Arc<ToolRegistry>, config: &AgentConfig) -> Result<Self> {
+        Ok(Self::new(registry, config.tool_names())?.with_limits(ToolLimits::from_agent_config(config)))
+    }
+
+    /// Names of all tools of this agent, including external ones
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self.names.clone();
+        names.extend(self.external.iter().map(|tool| tool.name().to_string()));
+        names
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name) || self.external.iter().any(|tool| tool.name() == name)
+    }
+
+    /// Tool set for a project root, built once and cached
+    pub fn toolset(&self, root: &str) -> Result<Arc<ToolSet>> {
+        let mut toolsets = self.toolsets.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(toolset) = toolsets.get(root) {
+            return Ok(toolset.clone());
+        }
+        let mut toolset = self.registry.toolset(&self.names, root)?;
+        for tool in &self.external {
+            toolset.insert(tool.name().to_string(), tool.clone());
+        }
+        let toolset = Arc::new(toolset);
+        toolsets.insert(root.to_string(), toolset.clone());
+        Ok(toolset)
+    }
+
+    /// Tool set for an execution, adding execution-bound tools (search_context, delegate) when
+    /// configured and binding the filesystem tools to the execution's shadow overlay and change journal
+    pub fn toolset_for(&self, context: &AgentContext) -> Result<Arc<ToolSet>> {
+        let project_scope = context.project_scope.clone()
+            .ok_or_else(|| anyhow!("Agent context has no project scope"))?;
+        let mut toolset = self.toolset(&project_scope.root)?;
+
+        let scope = FileScope { shadow: context.shadow.clone(), journal: context.journal.clone() };
+        if !scope.is_empty() {
+            toolset = Arc::new(self.registry.scoped_toolset(&toolset, &project_scope.root, &scope));
+        }
+
+        let search = match (self.contains("search_context"), context.rag.clone(), context.conversation_id.clone()) {
+            (true, Some(rag), Some(conversation_id)) => Some(SearchContextTool::new(rag, project_scope, conversation_id)),
+            _ => None,
+        };
+        let delegate = match (self.contains("delegate"), context.delegation.clone(), context.task_id.clone()) {
+            (true, Some(delegation), Some(task_id)) => Some(DelegateTool::new(delegation, task_id)),
+            _ => None,
+        };
+        if search.is_none() && delegate.is_none() {
+            return Ok(toolset);
+        }
+
+        let mut toolset = (*toolset).clone();
+        if let Some(search) = search {
+            toolset.register_tool(search);
+        }
+        if let Some(delegate) = delegate {
+            toolset.register_tool(delegate);
+        }
+        Ok(Arc::new(toolset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shadow::ShadowWorkspace;
+    use crate::tools::ToolResult;
+    use serde_json::Value;
+
+    /// Stand-in for a mounted MCP tool
+    #[derive(Debug)]
+    struct RemoteTool {
+        name: &'static str,
+        read_only: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Tool for RemoteTool {
+        async fn call(&self, _arguments: &str) -> Result<ToolResult> {
+            Ok(ToolResult { success: true, output: String::new() })
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn description(&self) -> &str {
+            "Remote tool"
+        }
+
+        fn parameters(&self) -> Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        fn is_always_read_only(&self) -> bool {
+            self.read_only
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_registry_knows_every_configurable_tool() {
+        let registry = ToolRegistry::default();
+        let known = registry.known_tools();
+        assert_eq!(known.len(), 16);
+        assert!(known.iter().all(|name| registry.is_known(name)));
+        assert!(known.contains(&"delegate") && known.contains(&"git"));
+        assert!(!registry.is_known("list_files"));
+
+        let err = AgentTools::new(Arc::new(registry), names(&["read_file", "list_files"])).unwrap_err();
+        assert!(err.to_string().starts_with("Unknown tool 'list_files'. Known tools: code_navigation, create_directory"), "{}", err);
+    }
+
+    #[test]
+    fn test_toolsets_follow_config_and_share_instances() {
+        let root = std::env::temp_dir().to_string_lossy().to_string();
+        let registry = Arc::new(ToolRegistry::default());
+
+        let coder = AgentTools::new(registry.clone(), names(&["read_file", "write_file", "lsp"])).unwrap();
+        let reviewer = AgentTools::new(registry.clone(), names(&["read_file"])).unwrap();
+
+        let coder_set = coder.toolset(&root).unwrap();
+        let mut available = coder_set.available_tools();
+        available.sort();
+        assert_eq!(available, names(&["lsp", "read_file", "write_file"]));
+        assert!(Arc::ptr_eq(&coder_set, &coder.toolset(&root).unwrap()));
+
+        let reviewer_set = reviewer.toolset(&root).unwrap();
+        assert_eq!(reviewer_set.available_tools(), names(&["read_file"]));
+        assert!(Arc::ptr_eq(&coder_set.get("read_file").unwrap(), &reviewer_set.get("read_file").unwrap()));
+
+        assert!(AgentTools::new(registry, names(&["list_files"])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shadow_toolset_writes_to_overlay() {
+        let root = std::env::temp_dir().join(format!("registry-shadow-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let root_str = root.to_string_lossy().to_string();
+        let shadow = Arc::new(ShadowWorkspace::create(&root.join(".shadow"), "conv", &root).unwrap());
+
+        let registry = Arc::new(ToolRegistry::default());
+        let tools = AgentTools::new(registry.clone(), names(&["read_file", "write_file", "git", "run_command", "search_code", "lsp"]))
+            .unwrap()
+            .with_external_tools(vec![
+                Arc::new(RemoteTool { name: "github__get_issue", read_only: true }),
+                Arc::new(RemoteTool { name: "github__create_issue", read_only: false }),
+            ]);
+        let scope = FileScope { shadow: Some(shadow.clone()), journal: None };
+        let toolset = tools.toolset(&root_str).unwrap();
+        let shadowed = registry.scoped_toolset(&toolset, &root_str, &scope);
+
+        let mut available = shadowed.available_tools();
+        available.sort();
+        assert_eq!(available, names(&["github__get_issue", "read_file", "write_file"]));
+
+        // Without an overlay only the filesystem tools are rebound
+        let journaled = registry.scoped_toolset(&toolset, &root_str, &FileScope { shadow: None, journal: None });
+        assert_eq!(journaled.available_tools().len(), 8);
+
+        let write = shadowed.get("write_file").unwrap();
+        let result = write.call(r#"{"path": "notes.md", "content": "draft"}"#).await.unwrap();
+        assert!(result.success);
+        assert!(!root.join("notes.md").exists());
+
+        let read = shadowed.get("read_file").unwrap();
+        assert_eq!(read.call(r#"{"path": "notes.md"}"#).await.unwrap().output, "draft");
+        assert!(shadow.diff().await.unwrap().contains("+draft"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,448 @@
+//! Code search tool built on the ripgrep crates (`ignore` + `grep`)
+//!
+//! Walks the project tree with the same filters the indexer uses (`IndexingFilters`)
+//! and returns `file:line:column` matches with surrounding context lines.
+
+use async_trait::async_trait;
+use anyhow::{anyhow, Result};
+use ai_agent_common::{IndexingFilters, RiskLevel};
+use grep_matcher::Matcher;
+use grep_regex::{RegexMatcher, RegexMatcherBuilder};
+use grep_searcher::{BinaryDetection, Searcher, SearcherBuilder, Sink, SinkContext, SinkContextKind, SinkMatch};
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, instrument};
+use crate::tools::{ToolResult, TypedTool};
+
+/// Default number of matches returned per page
+const DEFAULT_MAX_RESULTS: usize = 50;
+
+/// Upper bound for `max_results` regardless of what the agent asks for
+const MAX_RESULTS_CAP: usize = 200;
+
+/// Default and maximum number of context lines around each match
+const DEFAULT_CONTEXT_LINES: usize = 2;
+const MAX_CONTEXT_LINES: usize = 10;
+
+/// Longest line echoed back; longer lines (minified code, data) are cut
+const MAX_LINE_CHARS: usize = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchCodeParams {
+    #[schemars(description = "The pattern to search for (a regular expression unless 'literal' is true).")]
+    pub pattern: String,
+    #[schemars(description = "Treat the pattern as a literal string instead of a regular expression.")]
+    pub literal: Option<bool>,
+    #[schemars(description = "Match case-insensitively.")]
+    pub case_insensitive: Option<bool>,
+    #[schemars(description = "Optional relative directory or file to search in (defaults to the project root).")]
+    pub path: Option<String>,
+    #[schemars(description = "Optional glob restricting which files are searched, e.g. \"*.rs\" or \"src/**/*.py\".")]
+    pub glob: Option<String>,
+    #[schemars(description = "Number of context lines before and after each match (default 2, max 10).")]
+    pub context_lines: Option<usize>,
+    #[schemars(description = "Number of matches to skip, for paging through results (default 0).")]
+    pub offset: Option<usize>,
+    #[schemars(description = "Maximum number of matches to return (default 50, max 200).")]
+    pub max_results: Option<usize>,
+}
+
+/// A single line of output, either a match or surrounding context
+#[derive(Debug, Clone)]
+struct SearchLine {
+    line_number: u64,
+    column: Option<usize>,
+    text: String,
+}
+
+/// A match together with its context lines
+#[derive(Debug, Clone)]
+struct SearchHit {
+    path: String,
+    lines: Vec<SearchLine>,
+}
+
+/// Collects hits for one file, honouring the global offset/limit window
+struct HitSink<'a> {
+    matcher: &'a RegexMatcher,
+    path: String,
+    seen: &'a mut usize,
+    window: std::ops::Range<usize>,
+    hits: &'a mut Vec<SearchHit>,
+    before: Vec<SearchLine>,
+    current: Option<SearchHit>,
+}
+
+impl HitSink<'_> {
+    fn line(&self, line_number: Option<u64>, bytes: &[u8], with_column: bool) -> SearchLine {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches(['\n', '\r']);
+        let column = if with_column {
+            self.matcher.find(bytes).ok().flatten()
+                .map(|m| String::from_utf8_lossy(&bytes[..m.start()]).chars().count() + 1)
+        } else {
+            None
+        };
+        let text = if text.chars().count() > MAX_LINE_CHARS {
+            format!("{}…", text.chars().take(MAX_LINE_CHARS).collect::<String>())
+        } else {
+            text.to_string()
+        };
+        SearchLine { line_number: line_number.unwrap_or(0), column, text }
+    }
+
+    fn flush(&mut self) {
+        if let Some(hit) = self.current.take() {
+            self.hits.push(hit);
+        }
+    }
+}
+
+impl Sink for HitSink<'_> {
+    type Error = std::io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, Self::Error> {
+        let index = *self.seen;
+        *self.seen += 1;
+        if index >= self.window.end {
+            // One past the window: we only needed to know that more results exist
+            self.flush();
+            return Ok(false);
+        }
+        if index < self.window.start {
+            self.before.clear();
+            return Ok(true);
+        }
+
+        self.flush();
+        let mut lines = std::mem::take(&mut self.before);
+        lines.push(self.line(mat.line_number(), mat.bytes(), true));
+        self.current = Some(SearchHit { path: self.path.clone(), lines });
+        Ok(true)
+    }
+
+    fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, Self::Error> {
+        let line = self.line(context.line_number(), context.bytes(), false);
+        match context.kind() {
+            SinkContextKind::Before => self.before.push(line),
+            SinkContextKind::After => {
+                if let Some(hit) = self.current.as_mut() {
+                    hit.lines.push(line);
+                }
+            }
+            SinkContextKind::Other => {}
+        }
+        Ok(true)
+    }
+
+    fn context_break(&mut self, _searcher: &Searcher) -> Result<bool, Self::Error> {
+        self.before.clear();
+        Ok(true)
+    }
+
+    fn finish(&mut self, _searcher: &Searcher, _finish: &grep_searcher::SinkFinish) -> Result<(), Self::Error> {
+        self.flush();
+        Ok(())
+    }
+}
+
+// SearchCodeTool - Regex/literal search across the project tree
+#[derive(Debug, Clone)]
+pub struct SearchCodeTool {
+    base_path: PathBuf,
+    filters: IndexingFilters,
+}
+
+impl SearchCodeTool {
+    pub fn new(base_path: &str) -> Self {
+        Self::with_filters(base_path, IndexingFilters::default())
+    }
+
+    pub fn with_filters(base_path: &str, filters: IndexingFilters) -> Self {
+        let base_path = PathBuf::from(base_path);
+        let base_path = std::fs::canonicalize(&base_path).unwrap_or(base_path);
+        Self { base_path, filters }
+    }
+
+    fn resolve_search_root(&self, relative: Option<&str>) -> Result<PathBuf> {
+        let Some(relative) = relative.filter(|r| !r.is_empty() && *r != ".") else {
+            return Ok(self.base_path.clone());
+        };
+        let target = std::fs::canonicalize(self.base_path.join(relative.trim_start_matches('/')))
+            .map_err(|e| anyhow!("Invalid search path {}: {}", relative, e))?;
+        if !target.starts_with(&self.base_path) {
+            return Err(anyhow!("Access denied: {} is outside the workspace", relative));
+        }
+        Ok(target)
+    }
+
+    fn search(&self, params: &SearchCodeParams) -> Result<String> {
+        if params.pattern.is_empty() {
+            return Err(anyhow!("pattern must not be empty"));
+        }
+        let pattern = if params.literal.unwrap_or(false) {
+            regex::escape(&params.pattern)
+        } else {
+            params.pattern.clone()
+        };
+        let matcher = RegexMatcherBuilder::new()
+            .case_insensitive(params.case_insensitive.unwrap_or(false))
+            .build(&pattern)
+            .map_err(|e| anyhow!("Invalid pattern: {}", e))?;
+
+        let context_lines = params.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES).min(MAX_CONTEXT_LINES);
+        let offset = params.offset.unwrap_or(0);
+        let limit = params.max_results.unwrap_or(DEFAULT_MAX_RESULTS).clamp(1, MAX_RESULTS_CAP);
+        let window = offset..offset + limit;
+
+        let mut searcher = SearcherBuilder::new()
+            .line_number(true)
+            .before_context(context_lines)
+            .after_context(context_lines)
+            .binary_detection(BinaryDetection::quit(b'\x00'))
+            .build();
+
+        let root = self.resolve_search_root(params.path.as_deref())?;
+        let mut seen = 0usize;
+        let mut hits = Vec::new();
+        let mut files_searched = 0usize;
+
+        for entry in walk_project(&self.base_path, &root, &self.filters, params.glob.as_deref())? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    debug!("Skipping unreadable entry: {}", e);
+                    continue;
+                }
+            };
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            files_searched += 1;
+
+            let relative = entry.path().strip_prefix(&self.base_path).unwrap_or(entry.path());
+            let mut sink = HitSink {
+                matcher: &matcher,
+                path: relative.display().to_string(),
+                seen: &mut seen,
+                window: window.clone(),
+                hits: &mut hits,
+                before: Vec::new(),
+                current: None,
+            };
+            if let Err(e) = searcher.search_path(&matcher, entry.path(), &mut sink) {
+                debug!("Failed to search {}: {}", entry.path().display(), e);
+            }
+            if seen > window.end {
+                break;
+            }
+        }
+
+        Ok(render_hits(&hits, &window, seen, files_searched))
+    }
+}
+
+/// Walk a project directory applying the indexing filters (gitignore, ignore_dirs,
+/// ignore_extensions, custom_ignores, max_file_size) plus an optional file glob
+pub(crate) fn walk_project(base_path: &Path, root: &Path, filters: &IndexingFilters, glob: Option<&str>) -> Result<ignore::Walk> {
+    let mut overrides = OverrideBuilder::new(base_path);
+    if let Some(glob) = glob.filter(|g| !g.is_empty()) {
+        overrides.add(glob)?;
+    }
+    for pattern in &filters.custom_ignores {
+        overrides.add(&format!("!{}", pattern))?;
+    }
+
+    let ignore_dirs = filters.ignore_dirs.clone();
+    let ignore_extensions = filters.ignore_extensions.clone();
+
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(!filters.include_hidden)
+        .git_ignore(filters.respect_gitignore)
+        .git_exclude(filters.respect_gitignore)
+        .git_global(filters.respect_gitignore)
+        .ignore(filters.respect_gitignore)
+        .parents(filters.respect_gitignore)
+        .require_git(false)
+        .max_filesize(filters.max_file_size)
+        .overrides(overrides.build()?)
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            let name = entry.file_name().to_string_lossy();
+            if entry.file_type().is_some_and(|t| t.is_dir()) {
+                return !ignore_dirs.iter().any(|d| *d == name);
+            }
+            let extension = entry.path().extension().and_then(|e| e.to_str()).unwrap_or_default();
+            !ignore_extensions.iter().any(|e| e.trim_start_matches('.') == extension)
+        });
+    Ok(builder.build())
+}
+
+fn render_hits(hits: &[SearchHit], window: &std::ops::Range<usize>, seen: usize, files_searched: usize) -> String {
+    if hits.is_empty() {
+        return if seen > 0 {
+            format!("No matches in range (only {} matches found)", seen)
+        } else {
+            format!("No matches found ({} files searched)", files_searched)
+        };
+    }
+
+    let mut output = String::new();
+    for (index, hit) in hits.iter().enumerate() {
+        if index > 0 {
+            output.push_str("--\n");
+        }
+        for line in &hit.lines {
+            match line.column {
+                Some(column) => output.push_str(&format!("{}:{}:{}: {}\n", hit.path, line.line_number, column, line.text)),
+                None => output.push_str(&format!("{}-{}-  {}\n", hit.path, line.line_number, line.text)),
+            }
+        }
+    }
+
+    let shown_end = window.start + hits.len();
+    if seen > window.end {
+        output.push_str(&format!(
+            "\nShowing matches {}-{}; more results available (use offset={})",
+            window.start + 1, shown_end, window.end
+        ));
+    } else {
+        output.push_str(&format!("\nShowing matches {}-{} of {}", window.start + 1, shown_end, seen));
+    }
+    output
+}
+
+#[async_trait]
+impl TypedTool for SearchCodeTool {
+    type Params = SearchCodeParams;
+
+    fn name(&self) -> &str {
+        "search_code"
+    }
+
+    fn description(&self) -> &str {
+        "Search file contents across the project with a regex or literal pattern. Returns path:line:column matches with context lines. Respects .gitignore and indexing filters; use offset/max_results to page."
+    }
+
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        true
+    }
+
+    fn risk_level(&self, _params: &Self::Params) -> Option<RiskLevel> {
+        Some(RiskLevel::Low)
+    }
+
+    #[instrument(name = "search_code_tool", skip(self), fields(
+        tool_name = "search_code",
+        pattern = %parameters.pattern,
+        success = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(
+        &self,
+        parameters: Self::Params
+    ) -> Result<ToolResult> {
+        let current_span = tracing::Span::current();
+        let tool = self.clone();
+        let result = tokio::task::spawn_blocking(move || tool.search(&parameters)).await?;
+
+        match result {
+            Ok(output) => {
+                current_span.record("success", true);
+                Ok(ToolResult { success: true, output })
+            }
+            Err(e) => {
+                let error_msg = format!("Search error: {}", e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                Ok(ToolResult { success: false, output: error_msg })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_project(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("q_search_tool_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "fn alpha() {}\n\nfn beta() {\n    alpha();\n}\n").unwrap();
+        std::fs::write(dir.join("src/util.py"), "def alpha():\n    pass\n").unwrap();
+        std::fs::write(dir.join("target/out.rs"), "fn alpha() {}\n").unwrap();
+        std::fs::write(dir.join(".gitignore"), "ignored.rs\n").unwrap();
+        std::fs::write(dir.join("ignored.rs"), "fn alpha() {}\n").unwrap();
+        dir
+    }
+
+    fn params(pattern: &str) -> SearchCodeParams {
+        SearchCodeParams {
+            pattern: pattern.to_string(),
+            literal: None,
+            case_insensitive: None,
+            path: None,
+            glob: None,
+            context_lines: Some(0),
+            offset: None,
+            max_results: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_respects_filters_and_reports_columns() {
+        let dir = temp_project("filters");
+        let tool = SearchCodeTool::new(dir.to_str().unwrap());
+
+        let result = TypedTool::call(&tool, params("alpha")).await.unwrap();
+        assert!(result.success, "{}", result.output);
+        assert!(result.output.contains("src/lib.rs:1:4: fn alpha() {}"), "{}", result.output);
+        assert!(result.output.contains("src/lib.rs:4:5:     alpha();"));
+        assert!(result.output.contains("src/util.py:1:5:"));
+        assert!(!result.output.contains("target/out.rs"));
+        assert!(!result.output.contains("ignored.rs"));
+
+        let rust_only = TypedTool::call(&tool, SearchCodeParams { glob: Some("*.rs".into()), ..params("alpha") }).await.unwrap();
+        assert!(!rust_only.output.contains("util.py"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_search_context_and_paging() {
+        let dir = temp_project("paging");
+        let tool = SearchCodeTool::new(dir.to_str().unwrap());
+
+        let with_context = TypedTool::call(&tool, SearchCodeParams { context_lines: Some(1), glob: Some("*.rs".into()), ..params("beta") }).await.unwrap();
+        assert!(with_context.output.contains("src/lib.rs-2-"));
+        assert!(with_context.output.contains("src/lib.rs-4-      alpha();"));
+
+        let first_page = TypedTool::call(&tool, SearchCodeParams { max_results: Some(1), ..params("alpha") }).await.unwrap();
+        assert!(first_page.output.contains("more results available (use offset=1)"), "{}", first_page.output);
+
+        let second_page = TypedTool::call(&tool, SearchCodeParams { max_results: Some(1), offset: Some(1), ..params("alpha") }).await.unwrap();
+        assert!(second_page.output.contains("src/lib.rs:4:5:"), "{}", second_page.output);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_literal_and_invalid_patterns() {
+        let dir = temp_project("literal");
+        let tool = SearchCodeTool::new(dir.to_str().unwrap());
+
+        let literal = TypedTool::call(&tool, SearchCodeParams { literal: Some(true), ..params("alpha()") }).await.unwrap();
+        assert!(literal.output.contains("src/lib.rs:4:5:"));
+
+        let invalid = TypedTool::call(&tool, params("alpha(")).await.unwrap();
+        assert!(!invalid.success);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,488 @@
+//! Tree-sitter code navigation tool (outline, definitions, usages)
+//!
+//! Reuses the grammars of the indexing pipeline
+//! (`ExtractMetadataTransformer::get_language_from_extension`) with its own definition
+//! queries, so agents get cheap structural navigation without a running language server.
+//! Files whose definitions cannot be extracted are skipped by searches.
+
+use async_trait::async_trait;
+use anyhow::{anyhow, Context, Result};
+use ai_agent_common::{IndexingFilters, RiskLevel};
+use ai_agent_indexing::metadata_transformer::ExtractMetadataTransformer;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, instrument, warn};
+use tree_sitter::{Node as TsNode, Parser, Query, QueryCursor, StreamingIterator, Tree};
+use crate::tools::search::walk_project;
+use crate::tools::{ToolResult, TypedTool};
+
+/// Maximum number of definitions/usages returned by a workspace-wide lookup
+const MAX_RESULTS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeNavCommand {
+    /// List the functions, types, traits and classes declared in a file with line ranges
+    Outline,
+    /// Find where a symbol is defined across the workspace
+    FindDefinitions,
+    /// Find where a symbol is used (excluding its definitions) across the workspace
+    FindUsages,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CodeNavParams {
+    #[schemars(description = "The command: outline, find_definitions or find_usages.")]
+    pub command: CodeNavCommand,
+    #[schemars(description = "Relative path. Required for outline (a file); optional directory or file to restrict find_definitions/find_usages.")]
+    pub path: Option<String>,
+    #[schemars(description = "The symbol name to look up. Required for find_definitions and find_usages.")]
+    pub symbol: Option<String>,
+}
+
+/// A definition found by the indexing queries
+#[derive(Debug, Clone, PartialEq)]
+struct SymbolDefinition {
+    kind: String,
+    name: String,
+    start_line: usize,
+    end_line: usize,
+    start_byte: usize,
+    end_byte: usize,
+    /// Byte range of the name node, used to tell definitions from usages
+    name_range: Option<std::ops::Range<usize>>,
+}
+
+/// Map a file extension to the language name understood by `get_query_for_language`
+fn query_language(extension: &str) -> Option<&'static str> {
+    match extension.to_lowercase().as_str() {
+        "rs" => Some("rust"),
+        "py" => Some("python"),
+        "js" | "jsx" | "mjs" => Some("javascript"),
+        "ts" | "tsx" => Some("typescript"),
+        "java" => Some("java"),
+        "go" => Some("go"),
+        _ => None,
+    }
+}
+
+/// Tree-sitter query capturing the definitions of a language returned by `query_language`
+fn definition_query(language: &str) -> &'static str {
+    match language {
+        "rust" => r#"
+            (function_item name: (identifier) @function.name)
+            (impl_item) @impl
+            (mod_item name: (identifier) @mod.name)
+            (struct_item name: (type_identifier) @struct.name)
+            (trait_item name: (type_identifier) @trait.name)
+            (enum_item name: (type_identifier) @enum.name)
+        "#,
+        "python" => r#"
+            (function_definition name: (identifier) @function.name)
+            (class_definition name: (identifier) @class.name)
+        "#,
+        "javascript" => r#"
+            (function_declaration name: (identifier) @function.name)
+            (function_expression name: (identifier) @function.name)
+            (class_declaration name: (identifier) @class.name)
+            (method_definition name: (property_identifier) @method.name)
+        "#,
+        "typescript" => r#"
+            (function_declaration name: (identifier) @function.name)
+            (function_expression name: (identifier) @function.name)
+            (class_declaration name: (type_identifier) @class.name)
+            (method_definition name: (property_identifier) @method.name)
+            (interface_declaration name: (type_identifier) @interface.name)
+        "#,
+        "java" => r#"
+            (method_declaration name: (identifier) @method.name)
+            (class_declaration name: (identifier) @class.name)
+            (interface_declaration name: (identifier) @interface.name)
+        "#,
+        "go" => r#"
+            (function_declaration name: (identifier) @function.name)
+            (method_declaration name: (field_identifier) @method.name)
+            (type_spec name: (type_identifier) @type.name)
+        "#,
+        _ => "",
+    }
+}
+
+/// A parsed source file
+struct ParsedFile {
+    language: &'static str,
+    ts_language: tree_sitter::Language,
+    tree: Tree,
+    source: String,
+}
+
+impl ParsedFile {
+    fn parse(path: &Path, source: String) -> Result<Self> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let language = query_language(extension)
+            .ok_or_else(|| anyhow!("No definition queries for '.{}' files", extension))?;
+        let ts_language = ExtractMetadataTransformer::get_language_from_extension(extension)
+            .ok_or_else(|| anyhow!("No tree-sitter grammar for '.{}' files", extension))?;
+
+        let mut parser = Parser::new();
+        parser.set_language(&ts_language)?;
+        let tree = parser.parse(&source, None).context("Failed to parse file")?;
+        Ok(Self { language, ts_language, tree, source })
+    }
+
+    fn definitions(&self) -> Result<Vec<SymbolDefinition>> {
+        let query_source = definition_query(self.language);
+        let query = Query::new(&self.ts_language, query_source)
+            .map_err(|e| anyhow!("Invalid {} definition query: {}", self.language, e))?;
+        let source = self.source.as_bytes();
+
+        let mut definitions: Vec<SymbolDefinition> = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&query, self.tree.root_node(), source);
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let node = capture.node;
+                let capture_name = query.capture_names()[capture.index as usize];
+
+                // `@function.name` captures the identifier; the definition is its parent.
+                // Bare captures such as `@impl` capture the whole definition node.
+                let (kind, definition, name, name_range) = match capture_name.strip_suffix(".name") {
+                    Some(kind) => (
+                        kind,
+                        node.parent().unwrap_or(node),
+                        node.utf8_text(source).unwrap_or_default().to_string(),
+                        Some(node.byte_range()),
+                    ),
+                    None => {
+                        let header = header_line(node, source);
+                        let name = header.strip_prefix(capture_name).unwrap_or(&header).trim().to_string();
+                        (capture_name, node, name, None)
+                    }
+                };
+
+                let entry = SymbolDefinition {
+                    kind: kind.to_string(),
+                    name,
+                    start_line: definition.start_position().row + 1,
+                    end_line: definition.end_position().row + 1,
+                    start_byte: definition.start_byte(),
+                    end_byte: definition.end_byte(),
+                    name_range,
+                };
+                if !definitions.contains(&entry) {
+                    definitions.push(entry);
+                }
+            }
+        }
+
+        definitions.sort_by_key(|d| (d.start_byte, std::cmp::Reverse(d.end_byte)));
+        Ok(definitions)
+    }
+
+    /// Identifier nodes whose text equals `symbol`
+    fn identifiers(&self, symbol: &str) -> Vec<TsNode<'_>> {
+        let source = self.source.as_bytes();
+        let mut found = Vec::new();
+        let mut stack = vec![self.tree.root_node()];
+        while let Some(node) = stack.pop() {
+            if node.kind().ends_with("identifier") && node.utf8_text(source) == Ok(symbol) {
+                found.push(node);
+            }
+            let mut cursor = node.walk();
+            stack.extend(node.children(&mut cursor));
+        }
+        found.sort_by_key(|n| n.start_byte());
+        found
+    }
+
+    fn line(&self, row: usize) -> &str {
+        self.source.lines().nth(row).unwrap_or_default().trim()
+    }
+}
+
+/// First line of a definition without its opening brace, e.g. `impl Display for Foo {` -> `impl Display for Foo`
+fn header_line(node: TsNode, source: &[u8]) -> String {
+    node.utf8_text(source)
+        .unwrap_or_default()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('{')
+        .trim()
+        .to_string()
+}
+
+// CodeNavigationTool - Structural code navigation using tree-sitter
+#[derive(Debug, Clone)]
+pub struct CodeNavigationTool {
+    base_path: PathBuf,
+    filters: IndexingFilters,
+}
+
+impl CodeNavigationTool {
+    pub fn new(base_path: &str) -> Self {
+        Self::with_filters(base_path, IndexingFilters::default())
+    }
+
+    pub fn with_filters(base_path: &str, filters: IndexingFilters) -> Self {
+        let base_path = PathBuf::from(base_path);
+        let base_path = std::fs::canonicalize(&base_path).unwrap_or(base_path);
+        Self { base_path, filters }
+    }
+
+    fn resolve_path(&self, relative: Option<&str>) -> Result<PathBuf> {
+        let Some(relative) = relative.filter(|r| !r.is_empty() && *r != ".") else {
+            return Ok(self.base_path.clone());
+        };
+        let target = std::fs::canonicalize(self.base_path.join(relative.trim_start_matches('/')))
+            .map_err(|e| anyhow!("Invalid path {}: {}", relative, e))?;
+        if !target.starts_with(&self.base_path) {
+            return Err(anyhow!("Access denied: {} is outside the workspace", relative));
+        }
+        Ok(target)
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> std::path::Display<'a> {
+        path.strip_prefix(&self.base_path).unwrap_or(path).display()
+    }
+
+    fn run(&self, params: &CodeNavParams) -> Result<String> {
+        match params.command {
+            CodeNavCommand::Outline => {
+                let path = params.path.as_deref().ok_or_else(|| anyhow!("outline requires a 'path'"))?;
+                self.outline(&self.resolve_path(Some(path))?)
+            }
+            CodeNavCommand::FindDefinitions | CodeNavCommand::FindUsages => {
+                let symbol = params.symbol.as_deref()
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .ok_or_else(|| anyhow!("{:?} requires a 'symbol'", params.command))?;
+                let root = self.resolve_path(params.path.as_deref())?;
+                self.find(&root, symbol, params.command == CodeNavCommand::FindUsages)
+            }
+        }
+    }
+
+    fn outline(&self, path: &Path) -> Result<String> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", self.relative(path)))?;
+        let parsed = ParsedFile::parse(path, source)?;
+        let definitions = parsed.definitions().inspect_err(|e| {
+            warn!("Failed to extract definitions of {}: {:#}", path.display(), e);
+        })?;
+        if definitions.is_empty() {
+            return Ok(format!("{} ({}): no definitions found", self.relative(path), parsed.language));
+        }
+
+        let mut output = format!("{} ({})\n", self.relative(path), parsed.language);
+        for (index, definition) in definitions.iter().enumerate() {
+            let depth = definitions[..index]
+                .iter()
+                .filter(|outer| outer.start_byte <= definition.start_byte && definition.end_byte <= outer.end_byte)
+                .count();
+            output.push_str(&format!(
+                "{}{} {} [{}-{}]\n",
+                "  ".repeat(depth),
+                definition.kind,
+                definition.name,
+                definition.start_line,
+                definition.end_line,
+            ));
+        }
+        Ok(output)
+    }
+
+    fn find(&self, root: &Path, symbol: &str, usages: bool) -> Result<String> {
+        let mut results = Vec::new();
+        let mut truncated = false;
+        let mut files_parsed = 0usize;
+
+        'files: for entry in walk_project(&self.base_path, root, &self.filters, None)? {
+            let Ok(entry) = entry else { continue };
+            let path = entry.path();
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+            if !entry.file_type().is_some_and(|t| t.is_file()) || query_language(extension).is_none() {
+                continue;
+            }
+            // Cheap pre-filter before parsing
+            let Ok(source) = std::fs::read_to_string(path) else { continue };
+            if !source.contains(symbol) {
+                continue;
+            }
+            let parsed = match ParsedFile::parse(path, source) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    debug!("Skipping {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let definitions = match parsed.definitions() {
+                Ok(definitions) => definitions,
+                Err(e) => {
+                    warn!("Skipping {}: {:#}", path.display(), e);
+                    continue;
+                }
+            };
+            files_parsed += 1;
+
+            if usages {
+                let definition_names: Vec<_> = definitions.iter().filter_map(|d| d.name_range.clone()).collect();
+                for node in parsed.identifiers(symbol) {
+                    if definition_names.contains(&node.byte_range()) {
+                        continue;
+                    }
+                    if results.len() >= MAX_RESULTS {
+                        truncated = true;
+                        break 'files;
+                    }
+                    let position = node.start_position();
+                    results.push(format!(
+                        "{}:{}:{}: {}",
+                        self.relative(path),
+                        position.row + 1,
+                        position.column + 1,
+                        parsed.line(position.row),
+                    ));
+                }
+            } else {
+                for definition in definitions.iter().filter(|d| d.name == symbol) {
+                    if results.len() >= MAX_RESULTS {
+                        truncated = true;
+                        break 'files;
+                    }
+                    results.push(format!(
+                        "{}:{}: {} {} (lines {}-{})",
+                        self.relative(path),
+                        definition.start_line,
+                        definition.kind,
+                        definition.name,
+                        definition.start_line,
+                        definition.end_line,
+                    ));
+                }
+            }
+        }
+
+        let what = if usages { "usages" } else { "definitions" };
+        if results.is_empty() {
+            return Ok(format!("No {} of '{}' found ({} candidate files parsed)", what, symbol, files_parsed));
+        }
+        let mut output = results.join("\n");
+        if truncated {
+            output.push_str(&format!("\n... more {} omitted (limit {}); narrow the search with 'path'", what, MAX_RESULTS));
+        }
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl TypedTool for CodeNavigationTool {
+    type Params = CodeNavParams;
+
+    fn name(&self) -> &str {
+        "code_navigation"
+    }
+
+    fn description(&self) -> &str {
+        "Structural code navigation with tree-sitter (Rust, Python, JavaScript, TypeScript, Java, Go). Commands: outline (definitions in a file with line ranges), find_definitions and find_usages (of a symbol across the workspace)."
+    }
+
+    fn is_read_only(&self, _params: &Self::Params) -> bool {
+        true
+    }
+
+    fn risk_level(&self, _params: &Self::Params) -> Option<RiskLevel> {
+        Some(RiskLevel::Low)
+    }
+
+    #[instrument(name = "code_navigation_tool", skip(self), fields(
+        tool_name = "code_navigation",
+        command = ?parameters.command,
+        success = tracing::field::Empty,
+        error = tracing::field::Empty
+    ))]
+    async fn call(
+        &self,
+        parameters: Self::Params
+    ) -> Result<ToolResult> {
+        let current_span = tracing::Span::current();
+        let tool = self.clone();
+        let result = tokio::task::spawn_blocking(move || tool.run(&parameters)).await?;
+
+        match result {
+            Ok(output) => {
+                current_span.record("success", true);
+                Ok(ToolResult { success: true, output })
+            }
+            Err(e) => {
+                let error_msg = format!("Code navigation error: {}", e);
+                current_span.record("success", false);
+                current_span.record("error", error_msg.as_str());
+                Ok(ToolResult { success: false, output: error_msg })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST_SOURCE: &str = "struct Counter {\n    value: u32,\n}\n\nimpl Counter {\n    fn increment(&mut self) {\n        self.value += 1;\n    }\n}\n\nfn main() {\n    let mut c = Counter { value: 0 };\n    c.increment();\n}\n";
+
+    fn temp_project(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("q_treesitter_tool_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/main.rs"), RUST_SOURCE).unwrap();
+        std::fs::write(dir.join("src/helper.py"), "class Counter:\n    def increment(self):\n        pass\n").unwrap();
+        dir
+    }
+
+    fn params(command: CodeNavCommand, path: Option<&str>, symbol: Option<&str>) -> CodeNavParams {
+        CodeNavParams { command, path: path.map(str::to_string), symbol: symbol.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_definition_queries_compile_for_supported_languages() {
+        for extension in ["rs", "py", "js", "ts", "java", "go"] {
+            let path = PathBuf::from(format!("sample.{}", extension));
+            let parsed = ParsedFile::parse(&path, String::new()).unwrap();
+            assert!(parsed.definitions().is_ok(), "query for .{} failed: {:?}", extension, parsed.definitions());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outline_lists_nested_definitions() {
+        let dir = temp_project("outline");
+        let tool = CodeNavigationTool::new(dir.to_str().unwrap());
+
+        let result = TypedTool::call(&tool, params(CodeNavCommand::Outline, Some("src/main.rs"), None)).await.unwrap();
+        assert!(result.success, "{}", result.output);
+        assert!(result.output.contains("struct Counter [1-3]"), "{}", result.output);
+        assert!(result.output.contains("impl Counter [5-9]"), "{}", result.output);
+        assert!(result.output.contains("  function increment [6-8]"), "{}", result.output);
+        assert!(result.output.contains("function main [11-14]"), "{}", result.output);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_find_definitions_and_usages_across_languages() {
+        let dir = temp_project("find");
+        let tool = CodeNavigationTool::new(dir.to_str().unwrap());
+
+        let definitions = TypedTool::call(&tool, params(CodeNavCommand::FindDefinitions, None, Some("increment"))).await.unwrap();
+        assert!(definitions.output.contains("src/main.rs:6: function increment"), "{}", definitions.output);
+        assert!(definitions.output.contains("src/helper.py:2: function increment"), "{}", definitions.output);
+
+        let usages = TypedTool::call(&tool, params(CodeNavCommand::FindUsages, Some("src"), Some("increment"))).await.unwrap();
+        assert_eq!(usages.output, "src/main.rs:13:7: c.increment();");
+
+        let missing = TypedTool::call(&tool, params(CodeNavCommand::FindUsages, None, None)).await.unwrap();
+        assert!(!missing.success);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
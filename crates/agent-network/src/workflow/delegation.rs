@@ -0,0 +1,470 @@
+//! In-execution delegation of sub-tasks
+//!
+//! The DAG built from the plan is fixed, but an agent may find mid-task that it needs help
+//! from an agent of another type (docs written, a review). The `delegate` tool submits such a
+//! sub-task through the agent's [`Delegation`] handle: the execution's [`DelegationManager`]
+//! adds it to the live workflow graph as a dependency of the delegating task, runs it like a
+//! planned task (retries, rollback, quality review) and hands its result back as the tool
+//! result. `[agent_network.delegation]` bounds the nesting depth and the number of sub-tasks.
+//!
+//! A sub-task runs under its own task timeout, so the time the delegating task waits for it
+//! does not count against the delegating task's timeout. When an attempt of the delegating task
+//! fails or times out, the sub-tasks it spawned are discarded with it: their file changes are
+//! rolled back together with the attempt's and their slots become free for the retry.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use ai_agent_common::{AgentType, ConversationId, DelegationConfig, ErrorRecoveryStrategy, EventSource, EventType, ProjectScope, StatusEvent};
+use petgraph::graph::NodeIndex;
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::error::{AgentNetworkError, AgentNetworkResult};
+use crate::execution_manager::BidirectionalEventChannel;
+use crate::hitl::AuditLogger;
+use crate::workflow::{DependencyEdge, DependencyType, TaskNode, TaskResult, WorkflowExecutor, WorkflowGraph};
+
+/// A sub-task an agent asks another agent to do
+#[derive(Debug, Clone)]
+pub struct DelegationRequest {
+    pub agent_type: AgentType,
+    pub description: String,
+    /// JSON schema the sub-task's output must match
+    pub output_schema: Option<Value>,
+}
+
+/// Spawns and tracks the delegated sub-tasks of one execution
+pub struct DelegationManager {
+    executor: WorkflowExecutor,
+    limits: DelegationConfig,
+    /// The workflow graph including the delegated tasks
+    graph: Mutex<WorkflowGraph>,
+    /// Results of delegated tasks not yet collected by the executor
+    results: Mutex<Vec<TaskResult>>,
+    /// Sub-tasks holding a slot
+    spawned: AtomicUsize,
+    /// Sequence number of the last sub-task, used in task ids
+    sequence: AtomicUsize,
+    /// Sub-tasks by the task attempt that delegated them
+    subtasks: Mutex<Vec<SubTask>>,
+    audit_logger: Arc<AuditLogger>,
+    project_scope: ProjectScope,
+    conversation_id: ConversationId,
+    event_channel: BidirectionalEventChannel,
+}
+
+impl DelegationManager {
+    pub fn new(
+        executor: WorkflowExecutor,
+        limits: DelegationConfig,
+        graph: WorkflowGraph,
+        audit_logger: Arc<AuditLogger>,
+        project_scope: ProjectScope,
+        conversation_id: ConversationId,
+        event_channel: BidirectionalEventChannel,
+    ) -> Self {
+        Self {
+            executor,
+            limits,
+            graph: Mutex::new(graph),
+            results: Mutex::new(Vec::new()),
+            spawned: AtomicUsize::new(0),
+            sequence: AtomicUsize::new(0),
+            subtasks: Mutex::new(Vec::new()),
+            audit_logger,
+            project_scope,
+            conversation_id,
+            event_channel,
+        }
+    }
+
+    /// The workflow graph with all tasks delegated so far
+    pub fn graph(&self) -> WorkflowGraph {
+        self.graph.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Results of the sub-tasks finished since the last call
+    pub fn take_results(&self) -> Vec<TaskResult> {
+        std::mem::take(&mut *self.results.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Reserve a sub-task slot for a task at `depth`, returning the sub-task's sequence number
+    fn admit(&self, depth: usize) -> AgentNetworkResult<usize> {
+        check_depth(&self.limits, depth)?;
+        self.spawned
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |spawned| (spawned < self.limits.max_tasks).then_some(spawned + 1))
+            .map_err(|_| AgentNetworkError::DelegationRefused(format!(
+                "the execution already delegated the maximum of {} sub-tasks", self.limits.max_tasks
+            )))?;
+        Ok(self.sequence.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    /// Forget the sub-tasks an attempt of `task_id` delegated, including nested ones, and free
+    /// their slots; returns their ids
+    fn discard_subtasks(&self, task_id: &str, attempt: usize) -> Vec<String> {
+        let discarded = {
+            let mut subtasks = self.subtasks.lock().unwrap_or_else(|e| e.into_inner());
+            let (discarded, kept) = discard(std::mem::take(&mut *subtasks), task_id, attempt);
+            *subtasks = kept;
+            discarded
+        };
+        if discarded.is_empty() {
+            return discarded;
+        }
+
+        self.spawned.fetch_sub(discarded.len(), Ordering::SeqCst);
+        self.results.lock().unwrap_or_else(|e| e.into_inner()).retain(|result| !discarded.contains(&result.task_id));
+        info!(task_id, attempt, subtasks = ?discarded, "Discarded sub-tasks of failed attempt");
+        discarded
+    }
+
+    /// Run a sub-task for the task holding `delegation` and wait for its result
+    async fn delegate(&self, delegation: &Delegation, parent_task_id: &str, request: DelegationRequest) -> AgentNetworkResult<TaskResult> {
+        let agent = self.executor.agent_pool()
+            .get_agent_by_type(request.agent_type)
+            .ok_or_else(|| AgentNetworkError::DelegationRefused(format!("no {} agent is configured", request.agent_type)))?;
+        let sequence = self.admit(delegation.depth)?;
+
+        let task = {
+            let mut graph = self.graph.lock().unwrap_or_else(|e| e.into_inner());
+            let task = TaskNode {
+                task_id: format!("{}-sub-{}", parent_task_id, sequence),
+                agent_id: agent.id().to_string(),
+                description: subtask_description(&request),
+                recovery_strategy: parent_index(&graph, parent_task_id)
+                    .map(|index| graph[index].recovery_strategy.clone())
+                    .unwrap_or(ErrorRecoveryStrategy::Skip),
+                requires_hitl: false,
+                output_schema: request.output_schema.clone(),
+            };
+            insert_subtask(&mut graph, parent_task_id, task.clone());
+            task
+        };
+        self.subtasks.lock().unwrap_or_else(|e| e.into_inner()).push(SubTask {
+            parent_task_id: parent_task_id.to_string(),
+            parent_attempt: delegation.attempt,
+            task_id: task.task_id.clone(),
+        });
+
+        let child = delegation.child();
+        info!(parent_task_id, task_id = %task.task_id, agent_id = %task.agent_id, depth = child.depth, "Delegating sub-task");
+        let delegated_event = StatusEvent {
+            id: self.conversation_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            source: EventSource::Orchestrator,
+            event: EventType::TaskDelegated {
+                parent_task_id: parent_task_id.to_string(),
+                task_id: task.task_id.clone(),
+                agent_id: task.agent_id.clone(),
+                wave_index: delegation.wave_index,
+                depth: child.depth,
+                description: request.description,
+            },
+        };
+        if self.event_channel.send(delegated_event).await.is_err() {
+            debug!("Failed to send task delegated event");
+        }
+
+        let result = Box::pin(self.executor.execute_delegated(
+            task,
+            child,
+            Arc::clone(&self.audit_logger),
+            self.project_scope.clone(),
+            self.conversation_id.clone(),
+            self.event_channel.clone(),
+        )).await?;
+        self.results.lock().unwrap_or_else(|e| e.into_inner()).push(result.clone());
+        Ok(result)
+    }
+}
+
+/// A delegated sub-task and the task attempt it belongs to
+#[derive(Debug, Clone)]
+struct SubTask {
+    parent_task_id: String,
+    parent_attempt: usize,
+    task_id: String,
+}
+
+/// Time a task spent waiting for its sub-tasks, shared by all handles of the task
+#[derive(Debug, Default)]
+struct WaitClock {
+    state: Mutex<WaitState>,
+}
+
+#[derive(Debug, Default)]
+struct WaitState {
+    /// Delegations in flight
+    waiting: usize,
+    /// Start of the current wait, while `waiting > 0`
+    since: Option<Instant>,
+    /// Finished waits
+    total: Duration,
+}
+
+impl WaitClock {
+    fn start(self: &Arc<Self>) -> WaitGuard {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.waiting == 0 {
+            state.since = Some(Instant::now());
+        }
+        state.waiting += 1;
+        WaitGuard { clock: Arc::clone(self) }
+    }
+
+    fn waited(&self) -> Duration {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.total + state.since.map(|since| since.elapsed()).unwrap_or_default()
+    }
+
+    fn is_waiting(&self) -> bool {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).waiting > 0
+    }
+}
+
+/// Ends a wait when dropped, also when the delegating future is cancelled
+struct WaitGuard {
+    clock: Arc<WaitClock>,
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        let mut state = self.clock.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.waiting -= 1;
+        if state.waiting == 0 {
+            if let Some(since) = state.since.take() {
+                state.total += since.elapsed();
+            }
+        }
+    }
+}
+
+/// A task's access to delegation, carrying how deeply the task itself is nested
+#[derive(Clone)]
+pub struct Delegation {
+    manager: Arc<DelegationManager>,
+    /// 0 for planned tasks, 1 for their sub-tasks, ...
+    depth: usize,
+    /// Wave of the planned task the delegation chain started from
+    wave_index: usize,
+    /// Attempt of the task the handle was given to
+    attempt: usize,
+    clock: Arc<WaitClock>,
+}
+
+impl Delegation {
+    /// Handle for a planned task of the given wave
+    pub fn new(manager: Arc<DelegationManager>, wave_index: usize) -> Self {
+        Self { manager, depth: 0, wave_index, attempt: 0, clock: Arc::default() }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn wave_index(&self) -> usize {
+        self.wave_index
+    }
+
+    /// Handle for one attempt of this task
+    pub fn for_attempt(&self, attempt: usize) -> Self {
+        Self { attempt, ..self.clone() }
+    }
+
+    /// Handle for a sub-task of this task
+    fn child(&self) -> Self {
+        Self {
+            manager: Arc::clone(&self.manager),
+            depth: self.depth + 1,
+            wave_index: self.wave_index,
+            attempt: 0,
+            clock: Arc::default(),
+        }
+    }
+
+    /// Time this task spent waiting for its sub-tasks so far
+    pub fn waited(&self) -> Duration {
+        self.clock.waited()
+    }
+
+    /// Whether this task is waiting for a sub-task right now
+    pub fn is_waiting(&self) -> bool {
+        self.clock.is_waiting()
+    }
+
+    /// Insert a sub-task of `parent_task_id` into the workflow, run it and return its result
+    pub async fn delegate(&self, parent_task_id: &str, request: DelegationRequest) -> AgentNetworkResult<TaskResult> {
+        let _waiting = self.clock.start();
+        self.manager.delegate(self, parent_task_id, request).await
+    }
+
+    /// Discard the sub-tasks an attempt of `task_id` delegated, returning their ids (nested
+    /// ones included) so their file changes can be rolled back with the attempt
+    pub fn discard_subtasks(&self, task_id: &str, attempt: usize) -> Vec<String> {
+        self.manager.discard_subtasks(task_id, attempt)
+    }
+}
+
+impl std::fmt::Debug for Delegation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Delegation")
+            .field("depth", &self.depth)
+            .field("wave_index", &self.wave_index)
+            .field("attempt", &self.attempt)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Refuse delegation from a task nested `depth` levels deep once sub-tasks would exceed `max_depth`
+fn check_depth(limits: &DelegationConfig, depth: usize) -> AgentNetworkResult<()> {
+    if depth >= limits.max_depth {
+        return Err(AgentNetworkError::DelegationRefused(if limits.max_depth == 0 {
+            "delegation is disabled".to_string()
+        } else {
+            format!("sub-tasks may be nested at most {} level(s) deep", limits.max_depth)
+        }));
+    }
+    Ok(())
+}
+
+/// Split `subtasks` into the ids of those delegated by an attempt of `task_id`, transitively,
+/// and the rest
+fn discard(subtasks: Vec<SubTask>, task_id: &str, attempt: usize) -> (Vec<String>, Vec<SubTask>) {
+    let (mut pending, mut kept): (Vec<SubTask>, Vec<SubTask>) = subtasks.into_iter()
+        .partition(|sub| sub.parent_task_id == task_id && sub.parent_attempt == attempt);
+    let mut discarded = Vec::new();
+    while let Some(sub) = pending.pop() {
+        let (nested, rest): (Vec<SubTask>, Vec<SubTask>) = kept.into_iter()
+            .partition(|nested| nested.parent_task_id == sub.task_id);
+        kept = rest;
+        pending.extend(nested);
+        discarded.push(sub.task_id);
+    }
+    discarded.sort();
+    (discarded, kept)
+}
+
+fn parent_index(graph: &WorkflowGraph, parent_task_id: &str) -> Option<NodeIndex> {
+    graph.node_indices().find(|index| graph[*index].task_id == parent_task_id)
+}
+
+/// Add a sub-task to the graph as a dependency of the task that delegated it
+fn insert_subtask(graph: &mut WorkflowGraph, parent_task_id: &str, task: TaskNode) -> NodeIndex {
+    let parent = parent_index(graph, parent_task_id);
+    let index = graph.add_node(task);
+    if let Some(parent) = parent {
+        graph.add_edge(index, parent, DependencyEdge { dependency_type: DependencyType::Sequential });
+    }
+    index
+}
+
+/// Task description of a sub-task, spelling out the expected output when a schema is given
+pub fn subtask_description(request: &DelegationRequest) -> String {
+    match &request.output_schema {
+        Some(schema) => format!(
+            "{}\n\n## Expected Output\nAnswer with JSON matching this schema:\n```json\n{}\n```",
+            request.description,
+            serde_json::to_string_pretty(schema).unwrap_or_else(|_| schema.to_string()),
+        ),
+        None => request.description.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::algo::toposort;
+    use serde_json::json;
+
+    fn task(task_id: &str) -> TaskNode {
+        TaskNode {
+            task_id: task_id.to_string(),
+            agent_id: "coding-1".to_string(),
+            description: "Add a parser".to_string(),
+            recovery_strategy: ErrorRecoveryStrategy::Skip,
+            requires_hitl: false,
+            output_schema: None,
+        }
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let limits = DelegationConfig { max_depth: 2, max_tasks: 8 };
+        assert!(check_depth(&limits, 0).is_ok());
+        assert!(check_depth(&limits, 1).is_ok());
+        assert!(matches!(check_depth(&limits, 2), Err(AgentNetworkError::DelegationRefused(_))));
+
+        let disabled = DelegationConfig { max_depth: 0, ..limits };
+        assert_eq!(check_depth(&disabled, 0).unwrap_err().to_string(), "Delegation refused: delegation is disabled");
+    }
+
+    #[test]
+    fn test_subtask_runs_before_its_parent() {
+        let mut graph = WorkflowGraph::new();
+        let first = graph.add_node(task("task-1"));
+        let second = graph.add_node(task("task-2"));
+        graph.add_edge(first, second, DependencyEdge { dependency_type: DependencyType::Sequential });
+
+        let sub = insert_subtask(&mut graph, "task-2", task("task-2-sub-1"));
+        let nested = insert_subtask(&mut graph, "task-2-sub-1", task("task-2-sub-1-sub-2"));
+        let order = toposort(&graph, None).unwrap();
+        let position = |index| order.iter().position(|i| *i == index).unwrap();
+        assert!(position(nested) < position(sub));
+        assert!(position(sub) < position(second));
+    }
+
+    #[test]
+    fn test_failed_attempt_discards_its_subtasks() {
+        let sub = |parent: &str, attempt, task_id: &str| SubTask {
+            parent_task_id: parent.to_string(),
+            parent_attempt: attempt,
+            task_id: task_id.to_string(),
+        };
+        let subtasks = vec![
+            sub("task-1", 0, "task-1-sub-1"),
+            sub("task-1-sub-1", 0, "task-1-sub-1-sub-2"),
+            sub("task-1-sub-1", 1, "task-1-sub-1-sub-3"),
+            sub("task-1", 1, "task-1-sub-4"),
+            sub("task-2", 0, "task-2-sub-5"),
+        ];
+
+        let (discarded, kept) = discard(subtasks, "task-1", 0);
+        assert_eq!(discarded, vec!["task-1-sub-1", "task-1-sub-1-sub-2", "task-1-sub-1-sub-3"]);
+        let kept: Vec<&str> = kept.iter().map(|sub| sub.task_id.as_str()).collect();
+        assert_eq!(kept, vec!["task-1-sub-4", "task-2-sub-5"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_clock_counts_overlapping_waits_once() {
+        let clock = Arc::new(WaitClock::default());
+        let first = clock.start();
+        tokio::time::advance(Duration::from_secs(2)).await;
+        let second = clock.start();
+        drop(first);
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert!(clock.is_waiting());
+        drop(second);
+        tokio::time::advance(Duration::from_secs(7)).await;
+
+        assert!(!clock.is_waiting());
+        assert_eq!(clock.waited(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_subtask_description_includes_schema() {
+        let request = DelegationRequest {
+            agent_type: AgentType::Writing,
+            description: "Document the parser API".to_string(),
+            output_schema: None,
+        };
+        assert_eq!(subtask_description(&request), "Document the parser API");
+
+        let request = DelegationRequest { output_schema: Some(json!({"type": "object", "required": ["summary"]})), ..request };
+        let description = subtask_description(&request);
+        assert!(description.starts_with("Document the parser API\n\n## Expected Output\n"));
+        assert!(description.contains("\"required\": [\n"));
+    }
+}
@@ -7,23 +7,35 @@ use tracing::{info, debug, warn, error, instrument, span, Level, Instrument};
 use crate::error::{AgentNetworkError, AgentNetworkResult};
 use crate::hitl::{ApprovalRequest, AuditEvent, AuditLogger, RiskAssessment};
 use crate::workflow::{TaskNode, TaskResult, WorkflowGraph, DependencyType};
-use crate::agents::{AgentPool, AgentContext};
+use crate::workflow::quality::{self, QualityGate};
+use crate::workflow::delegation::{Delegation, DelegationManager};
+use crate::agents::{Agent, AgentPool, AgentContext, AgentResult};
+use crate::agents::evaluator::EvaluatorOutput;
+use crate::agents::confidence::ConfidenceSignals;
 use crate::tools::ToolSet;
 use crate::coordination::CoordinationManager;
 use crate::filelocks::FileLockManager;
+use crate::journal::{FileJournal, TaskJournal};
+use crate::shadow::ShadowWorkspace;
+use crate::token_budget::TokenBudgetManager;
 use crate::execution_manager::BidirectionalEventChannel;
-use ai_agent_common::{ConversationId, ProjectScope, StatusEvent, EventSource, EventType, ExecutionPlan, WaveInfo, TaskInfo};
+use ai_agent_common::{AgentType, ConversationId, DelegationConfig, ProjectScope, StatusEvent, EventSource, EventType, ExecutionPlan, RiskLevel, WaveInfo, TaskInfo, TokenUsage};
 use petgraph::algo::toposort;
 use petgraph::graph::NodeIndex;
 use petgraph::visit::EdgeRef;
 use std::collections::{HashMap, VecDeque, BTreeMap};
 use std::sync::Arc;
+use std::future::Future;
 use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, RwLock, broadcast};
 use tokio::task::JoinHandle;
 use uuid::Uuid;
 
+/// How often a task waiting for a delegated sub-task re-checks its deadline
+const DELEGATION_WAIT_POLL: Duration = Duration::from_secs(1);
+
 /// Wave-based workflow executor for parallel task execution
+#[derive(Clone)]
 pub struct WorkflowExecutor {
     /// Agent pool for task execution
     agent_pool: Arc<AgentPool>,
@@ -39,6 +51,16 @@ pub struct WorkflowExecutor {
     config: ExecutorConfig,
     /// Context provider for RAG and history
     context_provider: Option<Arc<crate::rag::ContextProvider>>,
+    /// Shadow workspace receiving all file changes in dry-run mode
+    shadow: Option<Arc<ShadowWorkspace>>,
+    /// Journal of file changes, used to roll back failed task attempts
+    journal: Option<Arc<FileJournal>>,
+    /// Token budget of the execution; exhausting it aborts the workflow
+    token_budget: Option<Arc<TokenBudgetManager>>,
+    /// When to score task outputs with the Evaluator and revise them
+    quality: Option<Arc<QualityGate>>,
+    /// Limits for sub-tasks agents delegate; delegation is unavailable when unset
+    delegation: Option<DelegationConfig>,
 }
 
 /// Executor configuration
@@ -139,10 +161,45 @@ impl WorkflowExecutor {
             coordination,
             file_locks,
             config,
-            context_provider: None
+            context_provider: None,
+            shadow: None,
+            journal: None,
+            token_budget: None,
+            quality: None,
+            delegation: None,
         }
     }
 
+    /// Run all tasks in shadow (dry-run) mode against the given workspace
+    pub fn with_shadow(mut self, shadow: Option<Arc<ShadowWorkspace>>) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    /// Journal file changes so failed task attempts are rolled back before retrying
+    pub fn with_journal(mut self, journal: Option<Arc<FileJournal>>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Charge all agent LLM calls to the execution's token budget
+    pub fn with_token_budget(mut self, token_budget: Option<Arc<TokenBudgetManager>>) -> Self {
+        self.token_budget = token_budget;
+        self
+    }
+
+    /// Score task outputs with the Evaluator agent and revise them while below the quality bar
+    pub fn with_quality(mut self, quality: Option<Arc<QualityGate>>) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Let agents with the delegate tool insert sub-tasks into the running workflow
+    pub fn with_delegation(mut self, delegation: Option<DelegationConfig>) -> Self {
+        self.delegation = delegation;
+        self
+    }
+
     /// Execute workflow with wave-based parallel execution
     #[instrument(name = "workflow_execution", skip(self, graph, event_channel), fields(task_count = %graph.node_count()))]
     pub async fn execute_with_hitl(&self,
@@ -185,6 +242,19 @@ impl WorkflowExecutor {
             debug!("Failed to send execution plan event");
         }
 
+        // Sub-tasks delegated while the waves run are added to the manager's copy of the graph
+        let delegation = self.delegation.clone()
+            .filter(|limits| limits.max_depth > 0 && limits.max_tasks > 0)
+            .map(|limits| Arc::new(DelegationManager::new(
+                self.clone(),
+                limits,
+                graph.clone(),
+                Arc::clone(&audit_logger),
+                project_scope.clone(),
+                conversation_id.clone(),
+                event_channel.clone(),
+            )));
+
         // Execute waves sequentially, tasks within waves in parallel
         let mut all_results: HashMap<String, TaskResult> = HashMap::new();
 
@@ -198,13 +268,32 @@ impl WorkflowExecutor {
                 project_scope.clone(),
                 conversation_id.clone(),
                 event_channel.clone(),
+                delegation.clone(),
             ).await?;
 
-            for result in wave_results {
+            let delegated_results = delegation.as_ref().map(|delegation| delegation.take_results()).unwrap_or_default();
+            for result in wave_results.into_iter().chain(delegated_results) {
                 all_results.insert(result.task_id.clone(), result);
             }
+
+            // Later waves would fail on their first LLM call anyway
+            if let Some(budget) = &self.token_budget {
+                budget.check(None)?;
+            }
         }
 
+        // Delegated tasks are ordered before the tasks that spawned them
+        let (graph, sorted_nodes) = match &delegation {
+            Some(delegation) => {
+                let graph = delegation.graph();
+                let sorted_nodes = toposort(&graph, None).map_err(|_| {
+                    AgentNetworkError::dag_construction("Workflow graph contains cycles after delegation")
+                })?;
+                (graph, sorted_nodes)
+            }
+            None => (graph, sorted_nodes),
+        };
+
         // Collect results in original order
         let results: Vec<TaskResult> = sorted_nodes
             .iter()
@@ -226,7 +315,7 @@ impl WorkflowExecutor {
     }
 
     /// Execute a single wave of tasks in parallel
-    #[instrument(name = "wave_execution", skip(self, graph, wave, previous_results), fields(
+    #[instrument(name = "wave_execution", skip(self, graph, wave, previous_results, delegation), fields(
         wave_index = wave.wave_index,
         tasks = wave.task_indices.len()
     ))]
@@ -239,6 +328,7 @@ impl WorkflowExecutor {
         project_scope: ProjectScope,
         conversation_id: ConversationId,
         event_channel: BidirectionalEventChannel,
+        delegation: Option<Arc<DelegationManager>>,
     ) -> AgentNetworkResult<Vec<TaskResult>> {
         debug!("Executing wave {}: {} parallel tasks", wave.wave_index, wave.task_indices.len());
         // Log wave information with structured fields
@@ -281,6 +371,11 @@ impl WorkflowExecutor {
             let wave_index = wave.wave_index;
             let audit_logger_clone = Arc::clone(&audit_logger);
             let context_provider = self.context_provider.clone();
+            let shadow = self.shadow.clone();
+            let journal = self.journal.clone();
+            let token_budget = self.token_budget.clone();
+            let quality = self.quality.clone();
+            let delegation = delegation.clone().map(|manager| Delegation::new(manager, wave.wave_index));
 
             let project_scope = project_scope.clone();
             let conversation_id = conversation_id.clone();
@@ -305,6 +400,11 @@ impl WorkflowExecutor {
                         file_locks,
                         audit_logger_clone,
                         context_provider,
+                        shadow,
+                        journal,
+                        token_budget,
+                        quality,
+                        delegation,
                         timeout,
                         max_retries,
                         wave_index,
@@ -336,6 +436,8 @@ impl WorkflowExecutor {
                         output: None,
                         error: Some(e.to_string()),
                         tool_executions: vec![],
+                        token_usage: TokenUsage::default(),
+                        confidence: ConfidenceSignals::default(),
                         agent_id: None,
                         task_description: None,
                         completed_at: Some(chrono::Utc::now()),
@@ -349,6 +451,8 @@ impl WorkflowExecutor {
                         output: None,
                         error: Some(format!("Join error: {}", e)),
                         tool_executions: vec![],
+                        token_usage: TokenUsage::default(),
+                        confidence: ConfidenceSignals::default(),
                         agent_id: None,
                         task_description: None,
                         completed_at: Some(chrono::Utc::now()),
@@ -506,12 +610,50 @@ impl WorkflowExecutor {
         &self.config
     }
 
+    pub fn agent_pool(&self) -> &Arc<AgentPool> {
+        &self.agent_pool
+    }
+
+    /// Run a delegated sub-task with the retry, rollback and review handling of planned tasks.
+    /// It sees no dependency outputs; its description has to carry the context it needs.
+    pub(crate) async fn execute_delegated(
+        &self,
+        task: TaskNode,
+        delegation: Delegation,
+        audit_logger: Arc<AuditLogger>,
+        project_scope: ProjectScope,
+        conversation_id: ConversationId,
+        event_channel: BidirectionalEventChannel,
+    ) -> AgentNetworkResult<TaskResult> {
+        let wave_index = delegation.wave_index();
+        execute_task_with_retry(
+            task,
+            Arc::clone(&self.agent_pool),
+            Arc::clone(&self.coordination),
+            Arc::clone(&self.file_locks),
+            audit_logger,
+            self.context_provider.clone(),
+            self.shadow.clone(),
+            self.journal.clone(),
+            self.token_budget.clone(),
+            self.quality.clone(),
+            Some(delegation),
+            self.config.task_timeout,
+            self.config.max_retries,
+            wave_index,
+            project_scope,
+            conversation_id,
+            event_channel,
+            &HashMap::new(),
+        ).await
+    }
+
 
 }
 
 
 /// Execute a single task
-#[instrument(name = "task_execution", skip(agent_pool, audit_logger, context_provider, file_locks, previous_results), fields(
+#[instrument(name = "task_execution", skip(agent_pool, audit_logger, context_provider, shadow, journal, token_budget, delegation, file_locks, previous_results), fields(
     task_id = %task.task_id,
     agent_id = %task.agent_id,
     description = %task.description
@@ -521,6 +663,10 @@ async fn execute_single_task(
     agent_pool: Arc<AgentPool>,
     audit_logger: Arc<AuditLogger>,
     context_provider: Option<Arc<crate::rag::ContextProvider>>,
+    shadow: Option<Arc<ShadowWorkspace>>,
+    journal: Option<TaskJournal>,
+    token_budget: Option<Arc<TokenBudgetManager>>,
+    delegation: Option<Delegation>,
     file_locks: Arc<FileLockManager>,
     project_scope: ProjectScope,
     conversation_id: ConversationId,
@@ -542,36 +688,27 @@ async fn execute_single_task(
         Some(task.task_id.clone())
     ).with_project_scope(project_scope.clone());
 
+    if let Some(shadow) = shadow {
+        agent_context = agent_context.with_shadow(shadow);
+    }
+    if let Some(journal) = journal {
+        agent_context = agent_context.with_journal(journal);
+    }
+    if let Some(token_budget) = token_budget {
+        agent_context = agent_context.with_token_budget(token_budget);
+    }
+    if let Some(delegation) = delegation {
+        agent_context = agent_context.with_delegation(delegation);
+    }
+    if let Some(output_schema) = &task.output_schema {
+        agent_context = agent_context.with_output_schema(output_schema.clone());
+    }
+
     // Build dependency outputs from previous results
     let mut dependency_outputs = HashMap::new();
     for (task_id, task_result) in previous_results.iter() {
         if task_result.success {
-            // Create a structured dependency output including both the agent output and tool executions
-            let mut dep_output = serde_json::Map::new();
-
-            // Add the agent's structured output
-            if let Some(output) = &task_result.output {
-                if let Ok(parsed_output) = serde_json::from_str::<serde_json::Value>(output) {
-                    dep_output.insert("agent_output".to_string(), parsed_output);
-                }
-            }
-
-            // Add tool executions
-            let tool_executions_json = serde_json::to_value(&task_result.tool_executions)?;
-            dep_output.insert("tool_executions".to_string(), tool_executions_json);
-
-            // Add attribution metadata
-            if let Some(agent_id) = &task_result.agent_id {
-                dep_output.insert("agent_id".to_string(), serde_json::Value::String(agent_id.clone()));
-            }
-            if let Some(task_description) = &task_result.task_description {
-                dep_output.insert("task_description".to_string(), serde_json::Value::String(task_description.clone()));
-            }
-            if let Some(completed_at) = &task_result.completed_at {
-                dep_output.insert("completed_at".to_string(), serde_json::Value::String(completed_at.to_rfc3339()));
-            }
-
-            dependency_outputs.insert(task_id.clone(), serde_json::Value::Object(dep_output));
+            dependency_outputs.insert(task_id.clone(), dependency_output(task_result)?);
         }
     }
 
@@ -583,10 +720,28 @@ async fn execute_single_task(
 
     // Retrieve and inject context if provider available
     if let Some(provider) = context_provider.as_ref() {
+        // Give the agent the RAG pipeline for its own searches (search_context tool)
+        agent_context = agent_context.with_rag(provider.rag());
+
         let context_retrieval_future = async {
             // Use task.description as refined query for RAG/History
             match provider.retrieve_context(task.description.clone(), project_scope, conversation_id.clone()).await {
-                Ok(context) => {
+                Ok((context, pruning)) => {
+                    if !pruning.is_empty() {
+                        let pruned_event = StatusEvent {
+                            id: conversation_id.to_string(),
+                            timestamp: chrono::Utc::now(),
+                            source: EventSource::Agent {
+                                agent_id: agent.id().to_string(),
+                                agent_type: agent.agent_type(),
+                                task_id: Some(task.task_id.clone()),
+                            },
+                            event: pruning.into_event(),
+                        };
+                        if event_channel.send(pruned_event).await.is_err() {
+                            debug!("Failed to send context pruned event");
+                        }
+                    }
                     if !context.is_empty() {
                         let context_length = context.len();
                         info!("Retrieved RAG+History context (length: {} chars)", context_length);
@@ -638,6 +793,8 @@ async fn execute_single_task(
                 output: Some(serde_json::to_string(&result.output)?),
                 error: None,
                 tool_executions: result.tool_executions,
+                token_usage: result.tokens_used.unwrap_or_default(),
+                confidence: result.confidence_signals,
                 agent_id: Some(agent.id().to_string()),
                 task_description: Some(task.description),
                 completed_at: Some(chrono::Utc::now()),
@@ -649,7 +806,256 @@ async fn execute_single_task(
     }
 }
 
-#[instrument(name = "task_retry_execution", skip(task, agent_pool, coordination, file_locks, audit_logger, context_provider, event_channel, previous_results), fields(
+/// Structured output of a finished task for the agents depending on it,
+/// including both the agent output and tool executions
+fn dependency_output(task_result: &TaskResult) -> AgentNetworkResult<serde_json::Value> {
+    let mut dep_output = serde_json::Map::new();
+
+    // Add the agent's structured output
+    if let Some(output) = &task_result.output {
+        if let Ok(parsed_output) = serde_json::from_str::<serde_json::Value>(output) {
+            dep_output.insert("agent_output".to_string(), parsed_output);
+        }
+    }
+
+    // Add tool executions
+    let tool_executions_json = serde_json::to_value(&task_result.tool_executions)?;
+    dep_output.insert("tool_executions".to_string(), tool_executions_json);
+
+    // Add attribution metadata
+    if let Some(agent_id) = &task_result.agent_id {
+        dep_output.insert("agent_id".to_string(), serde_json::Value::String(agent_id.clone()));
+    }
+    if let Some(task_description) = &task_result.task_description {
+        dep_output.insert("task_description".to_string(), serde_json::Value::String(task_description.clone()));
+    }
+    if let Some(completed_at) = &task_result.completed_at {
+        dep_output.insert("completed_at".to_string(), serde_json::Value::String(completed_at.to_rfc3339()));
+    }
+
+    Ok(serde_json::Value::Object(dep_output))
+}
+
+/// Score a task's output with the Evaluator agent and re-attempt the task with the critique
+/// while the score is below the quality bar, for at most `max_rounds` revisions. The last
+/// revision is kept, since its file changes are the ones in the workspace; a revision that
+/// fails is rolled back and the previous output kept.
+async fn review_task(
+    task: &TaskNode,
+    mut task_result: TaskResult,
+    attempts: usize,
+    quality: &QualityGate,
+    evaluator: Arc<dyn Agent>,
+    agent_pool: Arc<AgentPool>,
+    audit_logger: Arc<AuditLogger>,
+    context_provider: Option<Arc<crate::rag::ContextProvider>>,
+    shadow: Option<Arc<ShadowWorkspace>>,
+    journal: Option<Arc<FileJournal>>,
+    token_budget: Option<Arc<TokenBudgetManager>>,
+    delegation: Option<Delegation>,
+    file_locks: Arc<FileLockManager>,
+    timeout: Duration,
+    wave_index: usize,
+    project_scope: ProjectScope,
+    conversation_id: ConversationId,
+    event_channel: BidirectionalEventChannel,
+    previous_results: &HashMap<String, TaskResult>,
+) -> TaskResult {
+    let max_rounds = quality.max_rounds();
+    let mut round = 0;
+
+    loop {
+        let evaluation = match evaluate_task(
+            &evaluator,
+            task,
+            &task_result,
+            Arc::clone(&audit_logger),
+            shadow.clone(),
+            token_budget.clone(),
+            project_scope.clone(),
+            &conversation_id,
+            event_channel.clone(),
+        ).await {
+            Ok(evaluation) => {
+                // The review is part of what the task cost
+                task_result.token_usage += evaluation.tokens_used.unwrap_or_default();
+                evaluation.extract::<EvaluatorOutput>()
+            }
+            Err(e) => Err(e.into()),
+        };
+        let evaluation = match evaluation {
+            Ok(evaluation) => evaluation,
+            Err(e) => {
+                warn!("Evaluation of task {} failed, keeping its output: {}", task.task_id, e);
+                return task_result;
+            }
+        };
+        task_result.confidence.record_evaluation(evaluation.score);
+
+        let passed = quality.passes(&evaluation);
+        info!(task_id = %task.task_id, round, score = evaluation.score, passed, "Task evaluated");
+        let evaluated_event = StatusEvent {
+            id: conversation_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            source: EventSource::Orchestrator,
+            event: EventType::TaskEvaluated {
+                task_id: task.task_id.clone(),
+                evaluator_id: evaluator.id().to_string(),
+                wave_index,
+                round,
+                score: evaluation.score,
+                min_score: quality.min_score(),
+                passed,
+                summary: evaluation.summary.clone(),
+                issues: evaluation.issues.clone(),
+            },
+        };
+        if event_channel.send(evaluated_event).await.is_err() {
+            debug!("Failed to send task evaluated event");
+        }
+
+        if passed {
+            return task_result;
+        }
+        let budget_exhausted = token_budget.as_ref().is_some_and(|budget| budget.check(Some(&task.agent_id)).is_err());
+        if round >= max_rounds || budget_exhausted {
+            warn!("Task {} stays below the quality bar ({:.2} < {:.2}) after {} revision(s)",
+                task.task_id, evaluation.score, quality.min_score(), round);
+            return task_result;
+        }
+        round += 1;
+
+        let revision_event = StatusEvent {
+            id: conversation_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            source: EventSource::Orchestrator,
+            event: EventType::TaskRevisionStarted {
+                task_id: task.task_id.clone(),
+                wave_index,
+                round,
+                max_rounds,
+                score: evaluation.score,
+            },
+        };
+        if event_channel.send(revision_event).await.is_err() {
+            debug!("Failed to send task revision started event");
+        }
+
+        let revision = TaskNode {
+            description: quality::revision_request(task, &evaluation, task_result.output.as_deref()),
+            ..task.clone()
+        };
+        let attempt = attempts + round - 1;
+        let revised = with_task_timeout(timeout, delegation.as_ref(), execute_single_task(
+            revision,
+            Arc::clone(&agent_pool),
+            Arc::clone(&audit_logger),
+            context_provider.clone(),
+            shadow.clone(),
+            journal.as_ref().map(|journal| journal.task(task.task_id.clone(), attempt)),
+            token_budget.clone(),
+            delegation.as_ref().map(|delegation| delegation.for_attempt(attempt)),
+            Arc::clone(&file_locks),
+            project_scope.clone(),
+            conversation_id.clone(),
+            event_channel.clone(),
+            previous_results,
+        ))
+        .await;
+
+        match revised {
+            Some(Ok(mut revised)) => {
+                revised.token_usage += task_result.token_usage;
+                revised.task_description = task_result.task_description.take();
+                task_result = revised;
+            }
+            Some(Err(e)) => {
+                rollback_attempt(journal.as_deref(), delegation.as_ref(), &task.task_id, attempt).await;
+                warn!("Revision {} of task {} failed, keeping the previous output: {}", round, task.task_id, e);
+                return task_result;
+            }
+            None => {
+                rollback_attempt(journal.as_deref(), delegation.as_ref(), &task.task_id, attempt).await;
+                warn!("Revision {} of task {} timed out, keeping the previous output", round, task.task_id);
+                return task_result;
+            }
+        }
+    }
+}
+
+/// Have the Evaluator agent score a task's output; the result carries an `EvaluatorOutput`
+async fn evaluate_task(
+    evaluator: &Arc<dyn Agent>,
+    task: &TaskNode,
+    task_result: &TaskResult,
+    audit_logger: Arc<AuditLogger>,
+    shadow: Option<Arc<ShadowWorkspace>>,
+    token_budget: Option<Arc<TokenBudgetManager>>,
+    project_scope: ProjectScope,
+    conversation_id: &ConversationId,
+    event_channel: BidirectionalEventChannel,
+) -> AgentNetworkResult<AgentResult> {
+    let mut context = AgentContext::new(
+        quality::evaluation_request(task, task_result),
+        conversation_id.to_string(),
+        Some(task.task_id.clone()),
+    )
+    .with_project_scope(project_scope)
+    .with_dependency_outputs(HashMap::from([(task.task_id.clone(), dependency_output(task_result)?)]));
+
+    if let Some(shadow) = shadow {
+        context = context.with_shadow(shadow);
+    }
+    if let Some(token_budget) = token_budget {
+        context = context.with_token_budget(token_budget);
+    }
+
+    evaluator.execute(context, event_channel, Some(audit_logger)).await
+        .map_err(|e| AgentNetworkError::AgentExecutionFailed { agent_id: evaluator.id().to_string(), reason: e.to_string() })
+}
+
+/// Undo a failed or timed-out attempt, including the sub-tasks it delegated
+async fn rollback_attempt(journal: Option<&FileJournal>, delegation: Option<&Delegation>, task_id: &str, attempt: usize) {
+    let subtasks = delegation.map(|delegation| delegation.discard_subtasks(task_id, attempt)).unwrap_or_default();
+    let Some(journal) = journal else { return };
+    match journal.rollback_with_subtasks(task_id, attempt, &subtasks).await {
+        Ok(report) => {
+            if !report.restored.is_empty() {
+                info!(task_id, attempt, files = report.restored.len(), "Rolled back file changes of failed attempt");
+            }
+            if !report.conflicts.is_empty() {
+                warn!(task_id, attempt, conflicts = ?report.conflicts, "Kept files changed after the failed attempt wrote them");
+            }
+        }
+        Err(e) => error!(task_id, attempt, "Failed to roll back file changes: {}", e),
+    }
+}
+
+/// Run a task attempt under `timeout`, or `None` once it expired. Time spent waiting for
+/// delegated sub-tasks, which run under their own timeout, is not counted.
+async fn with_task_timeout<F: Future>(timeout: Duration, delegation: Option<&Delegation>, attempt: F) -> Option<F::Output> {
+    let Some(delegation) = delegation else {
+        return tokio::time::timeout(timeout, attempt).await.ok();
+    };
+
+    let start = tokio::time::Instant::now();
+    let waited_before = delegation.waited();
+    tokio::pin!(attempt);
+    loop {
+        let deadline = start + timeout + delegation.waited().saturating_sub(waited_before);
+        let waiting = delegation.is_waiting();
+        if !waiting && tokio::time::Instant::now() >= deadline {
+            return None;
+        }
+        // While waiting the deadline moves, so check again a little later at the latest
+        let wake = if waiting { deadline.max(tokio::time::Instant::now() + DELEGATION_WAIT_POLL) } else { deadline };
+        if let Ok(output) = tokio::time::timeout_at(wake, &mut attempt).await {
+            return Some(output);
+        }
+    }
+}
+
+#[instrument(name = "task_retry_execution", skip(task, agent_pool, coordination, file_locks, audit_logger, context_provider, shadow, journal, token_budget, quality, delegation, event_channel, previous_results), fields(
     task_id = %task.task_id,
     agent_id = %task.agent_id,
 ))]
@@ -661,6 +1067,11 @@ async fn execute_task_with_retry(
     file_locks: Arc<FileLockManager>,
     audit_logger: Arc<AuditLogger>,
     context_provider: Option<Arc<crate::rag::ContextProvider>>,
+    shadow: Option<Arc<ShadowWorkspace>>,
+    journal: Option<Arc<FileJournal>>,
+    token_budget: Option<Arc<TokenBudgetManager>>,
+    quality: Option<Arc<QualityGate>>,
+    delegation: Option<Delegation>,
     timeout: Duration,
     max_retries: usize,
     wave_index: usize,
@@ -705,11 +1116,15 @@ async fn execute_task_with_retry(
 
     loop {
         // Execute task with timeout
-        let result = tokio::time::timeout(timeout, execute_single_task(
+        let result = with_task_timeout(timeout, delegation.as_ref(), execute_single_task(
             task.clone(),
             Arc::clone(&agent_pool),
             audit_logger.clone(),
             context_provider.clone(),
+            shadow.clone(),
+            journal.as_ref().map(|journal| journal.task(task_id.clone(), retries)),
+            token_budget.clone(),
+            delegation.as_ref().map(|delegation| delegation.for_attempt(retries)),
             Arc::clone(&file_locks),
             project_scope.clone(),
             conversation_id.clone(),
@@ -719,29 +1134,81 @@ async fn execute_task_with_retry(
         .await;
 
         match result {
-            Ok(Ok(task_result)) => {
+            Some(Ok(task_result)) => {
+                let evaluator = quality.as_deref()
+                    .and_then(|quality| quality.evaluator_id())
+                    .and_then(|evaluator_id| agent_pool.get_agent(evaluator_id));
+                let agent_type = agent_pool.get_agent(&task.agent_id).map(|agent| agent.agent_type());
+                let mut task_result = match (quality.as_deref(), evaluator, agent_type) {
+                    (Some(quality), Some(evaluator), Some(agent_type))
+                        if quality.should_evaluate(&task, agent_type, &task_result, retries + 1) =>
+                    {
+                        review_task(
+                            &task,
+                            task_result,
+                            retries + 1,
+                            quality,
+                            evaluator,
+                            Arc::clone(&agent_pool),
+                            Arc::clone(&audit_logger),
+                            context_provider.clone(),
+                            shadow.clone(),
+                            journal.clone(),
+                            token_budget.clone(),
+                            delegation.clone(),
+                            Arc::clone(&file_locks),
+                            timeout,
+                            wave_index,
+                            project_scope.clone(),
+                            conversation_id.clone(),
+                            event_channel.clone(),
+                            previous_results,
+                        ).await
+                    }
+                    _ => task_result,
+                };
+                // Failed attempts, revisions and the review were charged to the task too
+                if let Some(budget) = &token_budget {
+                    task_result.token_usage = budget.task_usage(&task_id);
+                }
+
                 // Success
                 coordination
                     .update_task_status(&task_id, crate::coordination::TaskStatus::Completed)
                     .await?;
 
-                // ADD HITL CHECK HERE:
-                if let (logger) = (audit_logger.as_ref()) {
-                    if task_result.success {
-                        // Get agent for type information
-                        if let Some(agent) = agent_pool.get_agent(&task.agent_id) {
-
-                            let agent_result = crate::agents::AgentResult::from_string(
-                                &task.agent_id.clone(),
-                                task_result.clone().output.unwrap_or_default().as_ref(),
-                            )?
-                            .with_confidence(0.8); // Should come from actual agent result
-
-                            let assessment = RiskAssessment::new(
-                                &agent_result,
-                                agent.agent_type(),
-                                Some(format!("Task {} completed", task.task_id)),
-                            );
+                // Flag results the agent is unsure of for review
+                if task_result.success {
+                    // Get agent for type information
+                    if let Some(agent) = agent_pool.get_agent(&task.agent_id) {
+                        let agent_result = crate::agents::AgentResult::from_string(
+                            &task.agent_id.clone(),
+                            task_result.clone().output.unwrap_or_default().as_ref(),
+                        )?
+                        .with_confidence_signals(task_result.confidence.clone());
+
+                        let signals = serde_json::to_string(&task_result.confidence).unwrap_or_default();
+                        let assessment = RiskAssessment::new(
+                            &agent_result,
+                            agent.agent_type(),
+                            Some(format!("Task {} completed", task.task_id)),
+                        )
+                        .with_metadata("confidence_signals", &signals);
+
+                        if assessment.needs_hitl(RiskLevel::High) {
+                            AuditLogger::warn(AuditEvent {
+                                event_id: format!("low_confidence_{}", task_id),
+                                timestamp: chrono::Utc::now(),
+                                agent_id: agent_id.clone(),
+                                task_id: task_id.clone(),
+                                action: "TASK_COMPLETED".to_string(),
+                                risk_level: format!("{:?}", assessment.risk_level),
+                                decision: "REVIEW".to_string(),
+                                metadata: [
+                                    ("confidence".to_string(), format!("{:.2}", assessment.confidence)),
+                                    ("confidence_signals".to_string(), signals),
+                                ].into(),
+                            });
                         }
                     }
                 }
@@ -765,10 +1232,13 @@ async fn execute_task_with_retry(
 
                 return Ok(task_result);
             }
-            Ok(Err(e)) => {
+            Some(Err(e)) => {
                 // Execution error
+                rollback_attempt(journal.as_deref(), delegation.as_ref(), &task_id, retries).await;
                 last_error = Some(e);
-                if retries < max_retries && task.recovery_strategy.is_retryable() {
+                // A retry cannot succeed once the budget is used up
+                let budget_exhausted = token_budget.as_ref().is_some_and(|budget| budget.check(Some(&agent_id)).is_err());
+                if retries < max_retries && task.recovery_strategy.is_retryable() && !budget_exhausted {
                     retries += 1;
                     warn!("Task {} failed, retry {} of {}", task_id, retries, max_retries);
 
@@ -779,8 +1249,9 @@ async fn execute_task_with_retry(
                     break;
                 }
             }
-            Err(_) => {
+            None => {
                 // Timeout
+                rollback_attempt(journal.as_deref(), delegation.as_ref(), &task_id, retries).await;
                 last_error = Some(AgentNetworkError::Timeout {
                     operation: format!("Task {}", task_id),
                 });
@@ -820,12 +1291,16 @@ async fn execute_task_with_retry(
     }
 
     debug!("Task completed with failure");
+    // Failed attempts still consumed tokens
+    let token_usage = token_budget.as_ref().map(|budget| budget.task_usage(&task_id)).unwrap_or_default();
     Ok(TaskResult {
         task_id,
         success: false,
         output: None,
         error: Some(error_msg),
         tool_executions: vec![],
+        token_usage,
+        confidence: ConfidenceSignals::default(),
         agent_id: Some(agent_id),
         task_description: Some(task.description.clone()),
         completed_at: Some(chrono::Utc::now()),
@@ -837,6 +1312,7 @@ async fn execute_task_with_retry(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ai_agent_common::llm::MockProvider;
 
     #[test]
     fn test_executor_config_defaults() {
@@ -856,4 +1332,133 @@ mod tests {
         assert_eq!(wave.wave_index, 0);
         assert_eq!(wave.parallel_degree, 4);
     }
+
+    #[tokio::test]
+    async fn test_delegated_subtask_runs_before_its_parent() {
+        let coder: ai_agent_common::AgentConfig = toml::from_str(r#"
+            id = "coding-1"
+            agent_type = "Coding"
+            model = "mock"
+            system_prompt = "You write code."
+            configurable = true
+            available_tools = ["delegate"]
+
+            [[steps]]
+            id = "implement"
+            description = "Implement the task."
+            mode = "ReAct"
+            max_iterations = 3
+        "#).unwrap();
+        let writer: ai_agent_common::AgentConfig = toml::from_str(r#"
+            id = "writing-1"
+            agent_type = "Writing"
+            model = "mock"
+            system_prompt = "You write documentation."
+            configurable = true
+
+            [[steps]]
+            id = "write"
+            description = "Write the documentation."
+        "#).unwrap();
+        let mut config = ai_agent_common::SystemConfig::default();
+        config.agent_network.agents = vec![coder, writer];
+        config.agent_network.hitl.enabled = false;
+
+        let schema = serde_json::json!({"type": "object", "required": ["summary"]});
+        let provider = Arc::new(MockProvider::new()
+            .respond_tool_call("delegate", serde_json::json!({"agent_type": "Writing", "description": "Document the parser", "output_schema": schema}))
+            .respond_json(serde_json::json!({"summary": "The parser reads TOML."}))
+            .respond_json(serde_json::json!({"summary": "Added the parser and its docs."})));
+        let pool = Arc::new(AgentPool::with_provider(&config, provider.clone()).await.unwrap());
+        let executor = WorkflowExecutor::new(pool, Arc::new(CoordinationManager::new()), Arc::new(FileLockManager::new(30)))
+            .with_delegation(Some(DelegationConfig::default()));
+
+        let mut graph = WorkflowGraph::new();
+        graph.add_node(TaskNode {
+            task_id: "task-1".to_string(),
+            agent_id: "coding-1".to_string(),
+            description: "Add a parser".to_string(),
+            recovery_strategy: ai_agent_common::ErrorRecoveryStrategy::Skip,
+            requires_hitl: false,
+            output_schema: None,
+        });
+        let root = std::env::temp_dir().to_string_lossy().to_string();
+        let results = executor.execute_with_hitl(
+            graph,
+            Arc::new(AuditLogger),
+            ProjectScope::new(root, None, HashMap::new()),
+            ConversationId::new(),
+            BidirectionalEventChannel::new("delegation-test".to_string()),
+        ).await.unwrap();
+
+        let ids: Vec<&str> = results.iter().map(|result| result.task_id.as_str()).collect();
+        assert_eq!(ids, vec!["task-1-sub-1", "task-1"]);
+        assert!(results.iter().all(|result| result.success));
+        assert_eq!(results[0].agent_id.as_deref(), Some("writing-1"));
+        assert_eq!(results[0].output.as_deref(), Some(r#"{"summary":"The parser reads TOML."}"#));
+
+        // The sub-task is told the expected output, and its output comes back to the coder as the tool result
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(serde_json::to_string(&requests[1].messages).unwrap().contains("## Expected Output"));
+        let last = serde_json::to_string(&requests[2].messages).unwrap();
+        assert!(last.contains("Delegated task task-1-sub-1 completed by writing-1"), "{}", last);
+        assert_eq!(provider.remaining(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_task_usage_includes_failed_attempts() {
+        let coder: ai_agent_common::AgentConfig = toml::from_str(r#"
+            id = "coding-1"
+            agent_type = "Coding"
+            model = "mock"
+            system_prompt = "You write code."
+            configurable = true
+
+            [[steps]]
+            id = "plan"
+            description = "Plan the change."
+
+            [[steps]]
+            id = "implement"
+            description = "Implement the change."
+        "#).unwrap();
+        let mut config = ai_agent_common::SystemConfig::default();
+        config.agent_network.agents = vec![coder];
+        config.agent_network.hitl.enabled = false;
+
+        // The first attempt fails in its second step, after the first one was paid for
+        let provider = Arc::new(MockProvider::new()
+            .respond_json(serde_json::json!({"plan": "Add a parser"}))
+            .respond_error("connection reset")
+            .respond_json(serde_json::json!({"plan": "Add a parser"}))
+            .respond_json(serde_json::json!({"summary": "Added the parser."})));
+        let pool = Arc::new(AgentPool::with_provider(&config, provider.clone()).await.unwrap());
+        let budget = Arc::new(TokenBudgetManager::from_config(&config.agent_network.token_budget));
+        let executor = WorkflowExecutor::new(pool, Arc::new(CoordinationManager::new()), Arc::new(FileLockManager::new(30)))
+            .with_token_budget(Some(budget.clone()));
+
+        let mut graph = WorkflowGraph::new();
+        graph.add_node(TaskNode {
+            task_id: "task-1".to_string(),
+            agent_id: "coding-1".to_string(),
+            description: "Add a parser".to_string(),
+            recovery_strategy: ai_agent_common::ErrorRecoveryStrategy::Retry { max_attempts: 2, backoff_ms: 0 },
+            requires_hitl: false,
+            output_schema: None,
+        });
+        let root = std::env::temp_dir().to_string_lossy().to_string();
+        let results = executor.execute_with_hitl(
+            graph,
+            Arc::new(AuditLogger),
+            ProjectScope::new(root, None, HashMap::new()),
+            ConversationId::new(),
+            BidirectionalEventChannel::new("usage-test".to_string()),
+        ).await.unwrap();
+
+        assert!(results[0].success);
+        assert_eq!(provider.remaining(), 0);
+        assert!(results[0].token_usage.total() > 0);
+        assert_eq!(results[0].token_usage, budget.total());
+    }
 }
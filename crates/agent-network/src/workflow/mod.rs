@@ -3,6 +3,8 @@
 pub mod builder;
 pub mod executor;
 pub mod analyzer;
+pub mod quality;
+pub mod delegation;
 
 use std::fmt::Display;
 
@@ -27,6 +29,9 @@ pub struct TaskNode {
     pub description: String,
     pub recovery_strategy: ErrorRecoveryStrategy,
     pub requires_hitl: bool,
+    /// JSON schema the task's output must match instead of its agent's own (delegated tasks)
+    #[serde(default)]
+    pub output_schema: Option<serde_json::Value>,
 }
 
 /// Dependency edge between tasks
@@ -50,6 +55,10 @@ pub struct TaskResult {
     pub output: Option<String>,
     pub error: Option<String>,
     pub tool_executions: Vec<crate::tools::ToolExecution>,
+    /// Tokens consumed by the agent that ran the task
+    pub token_usage: ai_agent_common::TokenUsage,
+    /// Signals the agent's confidence in the output was estimated from
+    pub confidence: crate::agents::confidence::ConfidenceSignals,
     
     // Attribution metadata
     pub agent_id: Option<String>,
@@ -0,0 +1,215 @@
+//! Evaluator-driven quality feedback loop
+//!
+//! Decides which task outputs the Evaluator agent scores, following the `QualityStrategy`
+//! of the agent that produced them (or `[agent_network.quality] default_strategy`), and
+//! turns a failing evaluation into a revision request for that agent. The executor runs
+//! the loop; the decisions live here so they can be tested without agents.
+
+use std::collections::HashMap;
+
+use ai_agent_common::{AgentNetworkConfig, AgentType, QualityConfig, QualityStrategy, RiskLevel};
+
+use crate::agents::evaluator::EvaluatorOutput;
+use crate::workflow::{TaskNode, TaskResult};
+
+/// Quality settings resolved for an execution
+#[derive(Debug, Clone)]
+pub struct QualityGate {
+    config: QualityConfig,
+    /// Strategies configured on individual agents
+    strategies: HashMap<String, QualityStrategy>,
+    /// Agent scoring the outputs
+    evaluator_id: Option<String>,
+}
+
+impl QualityGate {
+    pub fn from_config(config: &AgentNetworkConfig) -> Self {
+        let strategies = config.agents.iter()
+            .filter_map(|agent| agent.effective_quality_strategy().map(|strategy| (agent.id.clone(), strategy)))
+            .collect();
+        // Configurable agents only claim a type; their workflow does not produce scores
+        let evaluator_id = config.quality.evaluator.clone().or_else(|| {
+            config.agents.iter()
+                .find(|agent| agent.agent_type == AgentType::Evaluator && !agent.configurable)
+                .map(|agent| agent.id.clone())
+        });
+        Self { config: config.quality.clone(), strategies, evaluator_id }
+    }
+
+    /// Agent scoring task outputs: `[agent_network.quality] evaluator`, or the first built-in
+    /// Evaluator agent in configuration order
+    pub fn evaluator_id(&self) -> Option<&str> {
+        self.evaluator_id.as_deref()
+    }
+
+    /// Strategy for outputs of an agent; nothing is evaluated unless configured
+    pub fn strategy(&self, agent_id: &str) -> QualityStrategy {
+        self.strategies.get(agent_id).copied()
+            .or(self.config.default_strategy)
+            .unwrap_or(QualityStrategy::Never)
+    }
+
+    /// Whether a task's output should be scored; `attempts` counts the runs it took to succeed
+    pub fn should_evaluate(&self, task: &TaskNode, agent_type: AgentType, result: &TaskResult, attempts: usize) -> bool {
+        if !result.success || agent_type == AgentType::Evaluator {
+            return false;
+        }
+        match self.strategy(&task.agent_id) {
+            QualityStrategy::Always => true,
+            QualityStrategy::OnlyForCritical => {
+                task.requires_hitl || RiskLevel::from_confidence(result.confidence.confidence()) >= RiskLevel::High
+            }
+            QualityStrategy::AfterNIterations(n) => attempts >= n,
+            QualityStrategy::Never => false,
+        }
+    }
+
+    pub fn min_score(&self) -> f32 {
+        self.config.min_quality_score
+    }
+
+    pub fn passes(&self, evaluation: &EvaluatorOutput) -> bool {
+        evaluation.score >= self.config.min_quality_score
+    }
+
+    /// Revisions allowed after failing evaluations
+    pub fn max_rounds(&self) -> usize {
+        if self.config.enable_feedback_loop { self.config.max_feedback_rounds } else { 0 }
+    }
+}
+
+/// Task description for the evaluator reviewing a task's output
+pub fn evaluation_request(task: &TaskNode, result: &TaskResult) -> String {
+    format!(
+        "Evaluate the output of task {} against its description. Score it from 0.0 to 1.0 for \
+         correctness, completeness and quality, and list concrete issues and suggestions.\n\n\
+         ## Task\n{}\n\n## Output\n{}",
+        task.task_id,
+        task.description,
+        result.output.as_deref().unwrap_or("(no output)"),
+    )
+}
+
+/// Description of the task's next attempt, carrying the evaluator's critique of `output`
+pub fn revision_request(task: &TaskNode, evaluation: &EvaluatorOutput, output: Option<&str>) -> String {
+    let mut request = format!(
+        "{}\n\n## Evaluator Feedback\nYour previous attempt scored {:.2}: {}\n",
+        task.description, evaluation.score, evaluation.summary,
+    );
+    if !evaluation.issues.is_empty() {
+        request.push_str("\nIssues:\n");
+        for issue in &evaluation.issues {
+            request.push_str(&format!("- {}\n", issue));
+        }
+    }
+    if !evaluation.suggestions.is_empty() {
+        request.push_str("\nSuggestions:\n");
+        for suggestion in &evaluation.suggestions {
+            request.push_str(&format!("- {}\n", suggestion));
+        }
+    }
+    if let Some(output) = output {
+        request.push_str(&format!("\n## Previous Output\n{}\n", output));
+    }
+    request.push_str("\nAddress the feedback and complete the task again.");
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::confidence::ConfidenceSignals;
+    use ai_agent_common::{ErrorRecoveryStrategy, TokenUsage};
+
+    fn network(quality: &str) -> AgentNetworkConfig {
+        AgentNetworkConfig { quality: toml::from_str(quality).unwrap(), ..Default::default() }
+    }
+
+    fn task(agent_id: &str) -> TaskNode {
+        TaskNode {
+            task_id: "task-1".to_string(),
+            agent_id: agent_id.to_string(),
+            description: "Add a parser".to_string(),
+            recovery_strategy: ErrorRecoveryStrategy::Skip,
+            requires_hitl: false,
+            output_schema: None,
+        }
+    }
+
+    fn result(confidence: f32) -> TaskResult {
+        let mut signals = ConfidenceSignals::default();
+        signals.record_self_assessment(confidence);
+        TaskResult {
+            task_id: "task-1".to_string(),
+            success: true,
+            output: Some("{}".to_string()),
+            error: None,
+            tool_executions: vec![],
+            token_usage: TokenUsage::default(),
+            confidence: signals,
+            agent_id: None,
+            task_description: None,
+            completed_at: None,
+        }
+    }
+
+    #[test]
+    fn test_strategies_select_outputs_to_evaluate() {
+        let gate = QualityGate::from_config(&network("default_strategy = \"only_for_critical\""));
+        assert!(!gate.should_evaluate(&task("coding-1"), AgentType::Coding, &result(0.95), 1));
+        assert!(gate.should_evaluate(&task("coding-1"), AgentType::Coding, &result(0.5), 1));
+        assert!(gate.should_evaluate(&TaskNode { requires_hitl: true, ..task("coding-1") }, AgentType::Coding, &result(0.95), 1));
+        assert!(!gate.should_evaluate(&task("evaluator-1"), AgentType::Evaluator, &result(0.5), 1));
+        let failed = TaskResult { success: false, ..result(0.5) };
+        assert!(!gate.should_evaluate(&task("coding-1"), AgentType::Coding, &failed, 1));
+
+        let gate = QualityGate::from_config(&network("default_strategy = { after_n_iterations = 2 }"));
+        assert!(!gate.should_evaluate(&task("coding-1"), AgentType::Coding, &result(0.95), 1));
+        assert!(gate.should_evaluate(&task("coding-1"), AgentType::Coding, &result(0.95), 2));
+
+        // Without a configured strategy nothing is evaluated
+        let gate = QualityGate::from_config(&network(""));
+        assert_eq!(gate.strategy("coding-1"), QualityStrategy::Never);
+        assert_eq!(gate.max_rounds(), 2);
+        assert_eq!(QualityGate::from_config(&network("enable_feedback_loop = false")).max_rounds(), 0);
+    }
+
+    #[test]
+    fn test_evaluator_is_chosen_explicitly() {
+        let agent = |id: &str, agent_type: &str, configurable: bool| -> ai_agent_common::AgentConfig {
+            toml::from_str(&format!(
+                "id = \"{}\"\nagent_type = \"{}\"\nmodel = \"mock\"\nsystem_prompt = \"\"\nconfigurable = {}",
+                id, agent_type, configurable,
+            )).unwrap()
+        };
+        let mut config = network("");
+        config.agents = vec![
+            agent("reviewer", "Evaluator", true),
+            agent("evaluator-1", "Evaluator", false),
+            agent("evaluator-2", "Evaluator", false),
+        ];
+        assert_eq!(QualityGate::from_config(&config).evaluator_id(), Some("evaluator-1"));
+
+        config.quality.evaluator = Some("evaluator-2".to_string());
+        assert_eq!(QualityGate::from_config(&config).evaluator_id(), Some("evaluator-2"));
+
+        config.agents.truncate(1);
+        config.quality.evaluator = None;
+        assert_eq!(QualityGate::from_config(&config).evaluator_id(), None);
+    }
+
+    #[test]
+    fn test_revision_request_carries_critique() {
+        let evaluation = EvaluatorOutput {
+            score: 0.4,
+            issues: vec!["Errors are not handled".to_string()],
+            suggestions: vec!["Return a Result".to_string()],
+            summary: "Incomplete".to_string(),
+        };
+        let request = revision_request(&task("coding-1"), &evaluation, Some("{\"done\": true}"));
+        assert!(request.starts_with("Add a parser\n\n## Evaluator Feedback\nYour previous attempt scored 0.40: Incomplete"));
+        assert!(request.contains("- Errors are not handled\n"));
+        assert!(request.contains("- Return a Result\n"));
+        assert!(request.contains("## Previous Output\n{\"done\": true}"));
+    }
+}
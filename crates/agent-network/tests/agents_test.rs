@@ -1,6 +1,21 @@
 //! Integration tests for agents
 
-use agent_network::agents::{AgentPool, AgentContext};
+use std::collections::HashMap;
+use std::path::Path;
+
+use ai_agent_common::config::{CassetteConfig, CassetteMode};
+use ai_agent_common::{AgentConfig, AgentType, ProjectScope, SystemConfig};
+use ai_agent_network::agents::planning::TaskDecompositionPlan;
+use ai_agent_network::agents::{AgentContext, AgentPool};
+use ai_agent_network::execution_manager::BidirectionalEventChannel;
+
+/// Cassette under `tests/cassettes`; set `RECORD_CASSETTES=1` to re-record it against the configured LLM
+fn cassette(name: &str) -> CassetteConfig {
+    CassetteConfig {
+        path: Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cassettes").join(name),
+        mode: if std::env::var_os("RECORD_CASSETTES").is_some() { CassetteMode::Record } else { CassetteMode::Replay },
+    }
+}
 
 #[tokio::test]
 async fn test_agent_pool() {
@@ -20,10 +35,31 @@ async fn test_coding_agent() {
 
 #[tokio::test]
 async fn test_planning_agent() {
-    // TODO: Week 8 - Add planning agent test
-    // - Execute planning task
-    // - Verify task decomposition
-    // - Check dependencies
+    let mut config = SystemConfig::default();
+    config.llm.cassette = Some(cassette("planning_agent.json"));
+    config.agent_network.agents = vec![toml::from_str::<AgentConfig>(r#"
+        id = "planning-1"
+        agent_type = "Planning"
+        model = "qwen3:8b"
+        system_prompt = "You plan."
+    "#).unwrap()];
+    let pool = AgentPool::new(&config).await.unwrap();
+
+    let context = AgentContext::new(
+        "Add a `--json` flag to the CLI's `status` command and document it in the README".to_string(),
+        "conversation-1".to_string(),
+        None,
+    ).with_project_scope(ProjectScope::new(std::env::temp_dir().to_string_lossy().to_string(), None, HashMap::new()));
+    let result = pool.get_agent("planning-1").unwrap()
+        .execute(context, BidirectionalEventChannel::new("agents-test".to_string()), None)
+        .await.unwrap();
+
+    let plan: TaskDecompositionPlan = result.extract().unwrap();
+    assert_eq!(plan.subtasks.len(), 2);
+    assert_eq!(plan.subtasks[0].agent_type, AgentType::Coding);
+    assert_eq!(plan.subtasks[1].agent_type, AgentType::Writing);
+    assert_eq!(plan.subtasks[1].dependencies, vec![plan.subtasks[0].id.clone()]);
+    assert!(result.tokens_used.is_some_and(|usage| usage.total() > 0));
 }
 
 #[tokio::test]
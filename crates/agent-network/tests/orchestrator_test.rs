@@ -1,6 +1,72 @@
 //! Integration tests for orchestrator
 
-use agent_network::{AgentNetworkConfig, Orchestrator};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use ai_agent_common::config::{CassetteConfig, CassetteMode};
+use ai_agent_common::{AgentConfig, ConversationId, ProjectScope, SystemConfig};
+use ai_agent_network::agents::AgentPool;
+use ai_agent_network::coordination::CoordinationManager;
+use ai_agent_network::execution_manager::BidirectionalEventChannel;
+use ai_agent_network::filelocks::FileLockManager;
+use ai_agent_network::hitl::AuditLogger;
+use ai_agent_network::sharedcontext::SharedContext;
+use ai_agent_network::token_budget::TokenBudgetManager;
+use ai_agent_network::Orchestrator;
+use tokio::sync::RwLock;
+
+fn agent(toml: &str) -> AgentConfig {
+    toml::from_str(toml).unwrap()
+}
+
+/// Cassette under `tests/cassettes`; set `RECORD_CASSETTES=1` to re-record it against the configured LLM
+fn cassette(name: &str) -> CassetteConfig {
+    CassetteConfig {
+        path: Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cassettes").join(name),
+        mode: if std::env::var_os("RECORD_CASSETTES").is_some() { CassetteMode::Record } else { CassetteMode::Replay },
+    }
+}
+
+/// Planner, coder and writer, all answering from the cassette `name`
+async fn network(name: &str) -> (Arc<SystemConfig>, Arc<AgentPool>) {
+    let mut config = SystemConfig::default();
+    config.llm.cassette = Some(cassette(name));
+    config.agent_network.agents = vec![
+        agent(r#"
+            id = "planning-1"
+            agent_type = "Planning"
+            model = "qwen3:8b"
+            system_prompt = "You plan."
+        "#),
+        agent(r#"
+            id = "coding-1"
+            agent_type = "Coding"
+            model = "qwen3:8b"
+            system_prompt = "You write code."
+            configurable = true
+
+            [[steps]]
+            id = "implement"
+            description = "Implement the task."
+        "#),
+        agent(r#"
+            id = "writing-1"
+            agent_type = "Writing"
+            model = "qwen3:8b"
+            system_prompt = "You write documentation."
+            configurable = true
+
+            [[steps]]
+            id = "write"
+            description = "Write the documentation."
+        "#),
+    ];
+    config.agent_network.hitl.enabled = false;
+
+    let pool = AgentPool::new(&config).await.unwrap();
+    (Arc::new(config), Arc::new(pool))
+}
 
 #[tokio::test]
 async fn test_orchestrator_initialization() {
@@ -12,10 +78,30 @@ async fn test_orchestrator_initialization() {
 
 #[tokio::test]
 async fn test_query_execution() {
-    // TODO: Week 8 - Add query execution test
-    // - Execute sample query
-    // - Verify workflow generation
-    // - Check results
+    let (config, pool) = network("query_execution.json").await;
+    let token_budget = Arc::new(TokenBudgetManager::from_config(&config.agent_network.token_budget));
+
+    // Long enough to be planned instead of routed to a single agent
+    let query = "Add a parser for the TOML configuration files of the project, with error reporting, and document how it is used";
+    let output = Orchestrator::execute_query(
+        query,
+        ProjectScope::new(std::env::temp_dir().to_string_lossy().to_string(), None, HashMap::new()),
+        ConversationId::new(),
+        BidirectionalEventChannel::new("orchestrator-test".to_string()),
+        config,
+        pool,
+        Arc::new(RwLock::new(SharedContext::new())),
+        Arc::new(CoordinationManager::new()),
+        Arc::new(FileLockManager::new(30)),
+        Arc::new(AuditLogger),
+        None,
+        None,
+        Some(token_budget.clone()),
+    ).await.unwrap();
+
+    // The plan's dependency orders the tasks; every request had to match the cassette
+    assert_eq!(output, "{\"summary\":\"Added the parser.\"}\n{\"summary\":\"Documented the parser.\"}\n");
+    assert!(token_budget.total().total() > 0);
 }
 
 #[tokio::test]
@@ -1,7 +1,7 @@
 //! Agent-Network binary entry point
 //!
 //! Initializes the orchestrator, loads configuration, and starts the ACP server.
-//! Supports both interactive and one-shot modes.
+//! Supports both interactive and one-shot modes, and serving the network over MCP (stdio).
 
 use ai_agent_common::SystemConfig;
 use anyhow::Result;
@@ -52,6 +52,12 @@ enum Commands {
         #[arg(long, default_value = "http://localhost:8080")]
         server_url: String,
     },
+    /// Serve the agent network as an MCP server over stdio
+    Mcp {
+        /// Project root the exposed tools operate on
+        #[arg(long, default_value = ".")]
+        root: String,
+    },
     /// Validate configuration
     ValidateConfig,
 }
@@ -72,9 +78,13 @@ struct ExecuteResponse {
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Initialize tracing with OpenTelemetry
+    // Initialize tracing with OpenTelemetry; in MCP mode stdout carries the protocol
     let log_level = cli.log_level.as_deref().unwrap_or("info");
-    ai_agent_common::init_tracing_with_level(log_level)?;
+    if matches!(cli.command, Some(Commands::Mcp { .. })) {
+        ai_agent_common::init_tracing_to_stderr(log_level)?;
+    } else {
+        ai_agent_common::init_tracing_with_level(log_level)?;
+    }
 
     info!("ACP Server v0.1.0 starting");
 
@@ -83,6 +93,10 @@ async fn main() -> Result<()> {
         error!("Failed to load configuration: {}", e);
         e
     })?;
+    config.validate().map_err(|e| {
+        error!("Invalid configuration: {}", e);
+        e
+    })?;
 
     info!("Configuration loaded successfully");
     info!("Available agents: {}", config.agent_network.agents.len());
@@ -101,6 +115,9 @@ async fn main() -> Result<()> {
         Some(Commands::Execute { query, cwd, server_url }) => {
             execute_via_acp(&query, &cwd, &server_url).await
         }
+        Some(Commands::Mcp { root }) => {
+            start_mcp_server(config, &root).await
+        }
         Some(Commands::Server { host, port }) => {
             let mut config = config;
             if let Some(h) = host {
@@ -163,3 +180,13 @@ async fn start_server(config: SystemConfig) -> Result<()> {
 
     Ok(())
 }
+
+/// Serve the agent network over MCP on stdin/stdout
+async fn start_mcp_server(config: SystemConfig, root: &str) -> Result<()> {
+    info!("Starting MCP server for {}", root);
+
+    let server = ai_agent_api::McpServer::new(config, root).await?;
+    server.serve_stdio().await?;
+
+    Ok(())
+}
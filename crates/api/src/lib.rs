@@ -71,6 +71,12 @@
 //! - **WebSocket events** for execution-time errors
 //! - **Machine-readable codes** for programmatic handling
 //!
+//! ## MCP Server Mode
+//!
+//! The same agent network can be served over the Model Context Protocol (stdio) with
+//! `acp-server mcp`, exposing `run_task`, `search_context` and read-only file and code search
+//! tools to editors and other assistants (see [`mcp`]).
+//!
 //! ## Standards Compliance
 //!
 //! This implementation follows the [Agent Communication Protocol](https://agentcommunicationprotocol.dev)
@@ -81,6 +87,8 @@ pub mod routes;
 pub mod middleware;
 pub mod types;
 pub mod openapi;
+pub mod mcp;
 
 pub use server::AcpServer;
+pub use mcp::McpServer;
 pub use types::*;
\ No newline at end of file
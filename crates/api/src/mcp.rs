@@ -0,0 +1,370 @@
+//! # Model Context Protocol (MCP) Server Mode
+//!
+//! Exposes the agent network to MCP clients (editors, other assistants) over stdio:
+//!
+//! - **`run_task`**: runs a query through the orchestrator and returns the final result.
+//!   Status events of the execution's `BidirectionalEventChannel` are forwarded as MCP
+//!   progress notifications when the client sends a progress token.
+//! - **`search_context`**: RAG retrieval over workspace, personal, system and online sources
+//! - **Read-only project tools**: reading, listing and searching files and inspecting git
+//!   history (status, diff, log, blame) under the project root the server was started in.
+//!   Mutating calls are refused: they would bypass the tool policy, HITL approval, the
+//!   change journal and the shadow workspace that agent calls go through. Changes are made
+//!   through `run_task`.
+//!
+//! HITL approvals requested while a task runs are forwarded to the client as elicitation
+//! requests. Clients without elicitation support get those tool calls rejected.
+
+use ai_agent_common::{
+    ConversationId, EventSource, EventType, ProjectScope, StatusEvent, SystemConfig,
+};
+use ai_agent_network::execution_manager::ExecutionManager;
+use ai_agent_network::tools::{SearchContextTool, ToolRegistry, ToolResult, ToolSet};
+use ai_agent_rag::context_manager::ContextManager;
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use rmcp::model::{
+    CallToolRequestParams, CallToolResult, Content, Implementation, JsonObject,
+    ListToolsResult, PaginatedRequestParams, ProgressNotificationParam, ServerCapabilities,
+    ServerInfo, Tool,
+};
+use rmcp::service::{ElicitationError, RequestContext, RoleServer};
+use rmcp::{ErrorData as McpError, Peer, ServerHandler, ServiceExt};
+use rmcp::schemars::{self, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, instrument, warn};
+
+/// Name of the tool running a full agent network task
+pub const RUN_TASK_TOOL: &str = "run_task";
+
+/// Agent tools exposed directly to MCP clients, in addition to `run_task` and `search_context`;
+/// only their read-only calls are run (e.g. `git` status but not commit)
+pub const EXPOSED_TOOLS: &[&str] = &[
+    "read_file",
+    "list_directory",
+    "file_exists",
+    "file_metadata",
+    "search_code",
+    "code_navigation",
+    "git",
+];
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct RunTaskParams {
+    /// The task or question for the agent network
+    query: String,
+}
+
+/// Answer to a HITL approval request, filled in by the MCP client's user
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct HitlApproval {
+    /// Whether the agent may perform the action
+    approved: bool,
+    /// Optional reasoning passed back to the agent
+    reasoning: Option<String>,
+}
+
+rmcp::elicit_safe!(HitlApproval);
+
+/// MCP server backed by an `ExecutionManager`
+#[derive(Clone)]
+pub struct McpServer {
+    execution_manager: Arc<ExecutionManager>,
+    project_scope: ProjectScope,
+    tools: Arc<ToolSet>,
+    approval_timeout: Duration,
+}
+
+impl McpServer {
+    /// Create an MCP server for the project containing `root`
+    pub async fn new(config: SystemConfig, root: &str) -> Result<Self> {
+        let project_scope = ContextManager::detect_project_scope(root.to_string()).await
+            .with_context(|| format!("Failed to detect project at {}", root))?;
+        info!("Initializing MCP server for project {}", project_scope.root);
+
+        let execution_manager = Arc::new(ExecutionManager::new(config.clone()).await?);
+
+        let names: Vec<String> = EXPOSED_TOOLS.iter().map(|name| name.to_string()).collect();
+        let mut tools = ToolRegistry::from_config(&config).toolset(&names, &project_scope.root)?;
+        tools.register_tool(SearchContextTool::new(
+            execution_manager.rag(),
+            project_scope.clone(),
+            ConversationId::new(),
+        ));
+
+        Ok(Self {
+            execution_manager,
+            project_scope,
+            tools: Arc::new(tools),
+            approval_timeout: Duration::from_secs(config.agent_network.hitl.approval_timeout_secs),
+        })
+    }
+
+    /// Serve MCP over stdin/stdout until the client disconnects
+    pub async fn serve_stdio(self) -> Result<()> {
+        let service = self.serve(rmcp::transport::stdio()).await
+            .context("Failed to start MCP server")?;
+        service.waiting().await?;
+        Ok(())
+    }
+
+    /// Run a query in its own subscription and wait for the final result
+    #[instrument(name = "mcp_run_task", skip(self, context), fields(query_len = query.len()))]
+    async fn run_task(&self, query: String, context: RequestContext<RoleServer>) -> Result<String> {
+        let subscription_id = self.execution_manager.create_subscription(Some("mcp".to_string())).await?;
+        let result = self.run_subscribed(&query, &subscription_id, &context).await;
+        self.execution_manager.remove_subscription(&subscription_id).await;
+        result
+    }
+
+    async fn run_subscribed(
+        &self,
+        query: &String,
+        subscription_id: &String,
+        context: &RequestContext<RoleServer>,
+    ) -> Result<String> {
+        let channel = self.execution_manager.get_channel(subscription_id).await?;
+        let mut events = self.execution_manager.connect_subscription(subscription_id).await
+            .ok_or_else(|| anyhow!("Subscription {} vanished", subscription_id))?;
+
+        self.execution_manager.execute_query(query, self.project_scope.clone(), subscription_id, false).await?;
+
+        let progress_token = context.meta.get_progress_token();
+        let mut progress = 0.0;
+        loop {
+            let event = tokio::select! {
+                _ = context.ct.cancelled() => return Err(anyhow!("Task cancelled by the client")),
+                event = events.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("MCP progress forwarding skipped {} events", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return Err(anyhow!("Execution channel closed before the task finished")),
+                },
+            };
+
+            if let (Some(token), Some(message)) = (&progress_token, progress_message(&event.event)) {
+                progress += 1.0;
+                let notification = ProgressNotificationParam {
+                    progress_token: token.clone(),
+                    progress,
+                    total: None,
+                    message: Some(message),
+                };
+                if let Err(e) = context.peer.notify_progress(notification).await {
+                    warn!("Failed to send MCP progress notification: {}", e);
+                }
+            }
+
+            match event.event {
+                EventType::ExecutionCompleted { result, .. } => return Ok(result),
+                EventType::ExecutionFailed { error } => return Err(anyhow!(error)),
+                EventType::HitlRequested { task_description, risk_level } => {
+                    let decision = self.request_approval(&context.peer, &task_description, &risk_level).await;
+                    channel.receive_inbound(StatusEvent {
+                        id: event.id.clone(),
+                        timestamp: Utc::now(),
+                        source: EventSource::Hitl { request_id: event.id },
+                        event: decision,
+                    }).await?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Ask the client's user to approve a risky agent action
+    async fn request_approval(&self, peer: &Peer<RoleServer>, task_description: &str, risk_level: &str) -> EventType {
+        let message = format!("Approve agent action ({} risk)?\n{}", risk_level, task_description);
+        let (approved, reasoning) = match peer.elicit_with_timeout::<HitlApproval>(message, Some(self.approval_timeout)).await {
+            Ok(Some(answer)) => (answer.approved, answer.reasoning),
+            Ok(None) | Err(ElicitationError::NoContent) => (false, None),
+            Err(ElicitationError::UserDeclined) | Err(ElicitationError::UserCancelled) => {
+                (false, Some("Declined by the MCP client user".to_string()))
+            }
+            Err(ElicitationError::CapabilityNotSupported) => {
+                (false, Some("The MCP client cannot answer approval requests".to_string()))
+            }
+            Err(e) => (false, Some(format!("Approval request failed: {}", e))),
+        };
+
+        EventType::HitlDecision {
+            approved,
+            modified_content: None,
+            reasoning: match (approved, reasoning) {
+                (false, None) => Some("Rejected by the MCP client user".to_string()),
+                (_, reasoning) => reasoning,
+            },
+        }
+    }
+}
+
+impl ServerHandler for McpServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: Implementation {
+                name: "agent-network".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Implementation::from_build_env()
+            },
+            instructions: Some(format!(
+                "Multi-agent coding assistant for the project at {}. Use run_task for complete tasks; \
+                 the other tools give direct access to the project and its indexed context.",
+                self.project_scope.root
+            )),
+            ..ServerInfo::default()
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let mut tools = vec![Tool::new(
+            RUN_TASK_TOOL,
+            "Run a task (coding, planning, writing, analysis) through the multi-agent network for this project. Returns the final result; progress is reported while it runs.",
+            schema_object(serde_json::to_value(schemars::schema_for!(RunTaskParams)).unwrap_or_default()),
+        )];
+
+        let mut names = self.tools.available_tools();
+        names.sort();
+        for name in names {
+            if let Some(tool) = self.tools.get(&name) {
+                tools.push(Tool::new(name, tool.description().to_string(), schema_object(tool.parameters())));
+            }
+        }
+
+        Ok(ListToolsResult { tools, ..ListToolsResult::default() })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let arguments = Value::Object(request.arguments.unwrap_or_default());
+
+        if request.name == RUN_TASK_TOOL {
+            let params: RunTaskParams = serde_json::from_value(arguments)
+                .map_err(|e| McpError::invalid_params(format!("Invalid arguments for run_task: {}", e), None))?;
+            return Ok(match self.run_task(params.query, context).await {
+                Ok(result) => CallToolResult::success(vec![Content::text(result)]),
+                Err(e) => CallToolResult::error(vec![Content::text(format!("Task failed: {:#}", e))]),
+            });
+        }
+
+        call_project_tool(&self.tools, &request.name, arguments).await
+    }
+}
+
+/// Run a call of an exposed project tool, refusing calls that are not read-only
+async fn call_project_tool(tools: &ToolSet, name: &str, arguments: Value) -> Result<CallToolResult, McpError> {
+    let tool = tools.get(name)
+        .ok_or_else(|| McpError::invalid_params(format!("Unknown tool: {}", name), None))?;
+    let arguments = arguments.to_string();
+    if !tool.is_read_only(&arguments) {
+        return Ok(CallToolResult::error(vec![Content::text(format!(
+            "{} only runs read-only calls over MCP; use run_task for changes", name
+        ))]));
+    }
+    Ok(match tool.call(&arguments).await {
+        Ok(result) => to_call_result(result),
+        Err(e) => CallToolResult::error(vec![Content::text(format!("{:#}", e))]),
+    })
+}
+
+fn schema_object(schema: Value) -> JsonObject {
+    match schema {
+        Value::Object(map) => map,
+        _ => JsonObject::new(),
+    }
+}
+
+fn to_call_result(result: ToolResult) -> CallToolResult {
+    if result.success {
+        CallToolResult::success(vec![Content::text(result.output)])
+    } else {
+        CallToolResult::error(vec![Content::text(result.output)])
+    }
+}
+
+/// Progress message for a status event; fine-grained events (tool calls, thoughts) are skipped
+fn progress_message(event: &EventType) -> Option<String> {
+    match event {
+        EventType::ExecutionStarted { .. } => Some("Execution started".to_string()),
+        EventType::PlanningStarted => Some("Planning".to_string()),
+        EventType::PlanningCompleted { task_count, .. } => Some(format!("Planned {} tasks", task_count)),
+        EventType::WaveStarted { wave_index, task_count, .. } => {
+            Some(format!("Wave {} started ({} tasks)", wave_index + 1, task_count))
+        }
+        EventType::TaskNodeStarted { agent_id, description, .. } => Some(format!("{}: {}", agent_id, description)),
+        EventType::TaskNodeCompleted { task_id, success, .. } => {
+            Some(format!("Task {} {}", task_id, if *success { "completed" } else { "failed" }))
+        }
+        EventType::TaskEvaluated { task_id, score, min_score, passed, .. } => {
+            Some(format!("Task {} scored {:.2} ({} {:.2})", task_id, score, if *passed { "≥" } else { "<" }, min_score))
+        }
+        EventType::TaskRevisionStarted { task_id, round, max_rounds, .. } => {
+            Some(format!("Revising task {} ({}/{})", task_id, round, max_rounds))
+        }
+        EventType::TaskDelegated { parent_task_id, agent_id, description, .. } => {
+            Some(format!("{} delegated to {}: {}", parent_task_id, agent_id, description))
+        }
+        EventType::WorkflowStepStarted { step_name } => Some(format!("Step: {}", step_name)),
+        EventType::HitlRequested { risk_level, .. } => Some(format!("Waiting for approval ({} risk)", risk_level)),
+        EventType::ExecutionCompleted { .. } => Some("Execution completed".to_string()),
+        EventType::ExecutionFailed { error } => Some(format!("Execution failed: {}", error)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_skips_fine_grained_events() {
+        let started = EventType::TaskNodeStarted {
+            task_id: "task-1".to_string(),
+            agent_id: "coding-1".to_string(),
+            wave_index: 0,
+            description: "Implement parser".to_string(),
+        };
+        assert_eq!(progress_message(&started).as_deref(), Some("coding-1: Implement parser"));
+
+        let thinking = EventType::AgentThinking { thought: "hmm".to_string() };
+        assert!(progress_message(&thinking).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mutating_calls_are_refused() {
+        let root = std::env::temp_dir().to_string_lossy().to_string();
+        let names: Vec<String> = EXPOSED_TOOLS.iter().map(|name| name.to_string()).collect();
+        let tools = ToolRegistry::default().toolset(&names, &root).unwrap();
+        let text = |result: &CallToolResult| serde_json::to_string(&result.content).unwrap();
+
+        let commit = call_project_tool(&tools, "git", serde_json::json!({"command": "commit", "message": "x", "paths": ["a.txt"]})).await.unwrap();
+        assert_eq!(commit.is_error, Some(true));
+        assert!(text(&commit).contains("only runs read-only calls"), "{}", text(&commit));
+
+        let log = call_project_tool(&tools, "git", serde_json::json!({"command": "log"})).await.unwrap();
+        assert!(!text(&log).contains("only runs read-only calls"), "{}", text(&log));
+
+        let write = call_project_tool(&tools, "write_file", serde_json::json!({"path": "a.txt", "content": "x"})).await;
+        assert!(write.unwrap_err().message.contains("Unknown tool: write_file"));
+    }
+
+    #[test]
+    fn test_run_task_schema_requires_query() {
+        let schema = schema_object(serde_json::to_value(schemars::schema_for!(RunTaskParams)).unwrap());
+        assert_eq!(schema["required"], serde_json::json!(["query"]));
+        assert!(schema_object(Value::Null).is_empty());
+    }
+}
@@ -80,6 +80,10 @@ standard for agent interoperability and communication.
         crate::routes::agents::list_capabilities,
         crate::routes::subscribe::create_subscription,
         crate::routes::subscribe::get_subscription_status,
+        crate::routes::shadow::get_shadow_diff,
+        crate::routes::shadow::apply_shadow,
+        crate::routes::shadow::discard_shadow,
+        crate::routes::executions::undo_execution,
         crate::server::health_check
     ),
     components(schemas(
@@ -88,6 +92,9 @@ standard for agent interoperability and communication.
         SubscribeRequest,
         SubscribeResponse,
         SubscriptionStatus,
+        ShadowDiffResponse,
+        ShadowApplyResponse,
+        UndoResponse,
         CapabilitiesResponse,
         AgentCapability,
         HealthResponse,
@@ -100,13 +107,15 @@ standard for agent interoperability and communication.
         crate::types::ExecutionPlan,
         crate::types::WaveInfo,
         crate::types::TaskInfo,
+        crate::types::TokenUsage,
         ai_agent_common::AgentType
     )),
     tags(
         (name = "query", description = "Query execution endpoints"),
         (name = "discovery", description = "Agent capability discovery"),
         (name = "health", description = "System health and status"),
-        (name = "streaming", description = "Real-time status streaming (WebSocket)")
+        (name = "streaming", description = "Real-time status streaming (WebSocket)"),
+        (name = "shadow", description = "Review and apply dry-run executions")
     ),
     external_docs(
         url = "https://agentcommunicationprotocol.dev/docs",
@@ -164,8 +173,8 @@ enum EventTypeDoc {
     #[schema(example = json!({"type": "execution_started", "query": "Analyze the code"}))]
     ExecutionStarted { query: String },
     /// Execution completed
-    #[schema(example = json!({"type": "execution_completed", "result": "Analysis complete"}))]
-    ExecutionCompleted { result: String },
+    #[schema(example = json!({"type": "execution_completed", "result": "Analysis complete", "token_usage": {"prompt_tokens": 18230, "completion_tokens": 2140, "estimated": false}}))]
+    ExecutionCompleted { result: String, token_usage: crate::types::TokenUsage },
     /// Agent started working
     #[schema(example = json!({"type": "agent_started", "context_size": 1247}))]
     AgentStarted { context_size: usize },
@@ -173,6 +182,18 @@ enum EventTypeDoc {
     #[schema(example = json!({"type": "workflow_step_started", "step_name": "Code Analysis"}))]
     WorkflowStepStarted { step_name: String },
     /// Agent completed
-    #[schema(example = json!({"type": "agent_completed", "result": "Found 3 issues"}))]
-    AgentCompleted { result: String },
+    #[schema(example = json!({"type": "agent_completed", "result": "Found 3 issues", "token_usage": {"prompt_tokens": 6120, "completion_tokens": 830, "estimated": false}}))]
+    AgentCompleted { result: String, token_usage: crate::types::TokenUsage },
+    /// A soft token budget was exceeded
+    #[schema(example = json!({"type": "token_budget_warning", "scope": "execution", "used": 52310, "limit": 50000}))]
+    TokenBudgetWarning { scope: String, used: usize, limit: usize },
+    /// The evaluator scored a task's output
+    #[schema(example = json!({"type": "task_evaluated", "task_id": "task-1", "evaluator_id": "evaluator-1", "wave_index": 0, "round": 0, "score": 0.55, "min_score": 0.7, "passed": false, "summary": "Errors are not handled", "issues": ["unwrap on user input"]}))]
+    TaskEvaluated { task_id: String, evaluator_id: String, wave_index: usize, round: usize, score: f32, min_score: f32, passed: bool, summary: String, issues: Vec<String> },
+    /// A task is re-attempted with the evaluator's critique
+    #[schema(example = json!({"type": "task_revision_started", "task_id": "task-1", "wave_index": 0, "round": 1, "max_rounds": 2, "score": 0.55}))]
+    TaskRevisionStarted { task_id: String, wave_index: usize, round: usize, max_rounds: usize, score: f32 },
+    /// An agent delegated a sub-task to another agent
+    #[schema(example = json!({"type": "task_delegated", "parent_task_id": "task-1", "task_id": "task-1-sub-1", "agent_id": "writing-1", "wave_index": 0, "depth": 1, "description": "Document the parser API"}))]
+    TaskDelegated { parent_task_id: String, task_id: String, agent_id: String, wave_index: usize, depth: usize, description: String },
 }
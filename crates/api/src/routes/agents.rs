@@ -47,12 +47,12 @@ use crate::{
                  {
                      "agent_type": "Coding",
                      "description": "Analyzes code, implements features, fixes bugs",
-                     "tools": ["file_reader", "file_writer", "lsp_client"]
+                     "tools": ["edit_file", "lsp", "read_file", "search_code", "write_file"]
                  },
                  {
                      "agent_type": "Planning",
                      "description": "Decomposes tasks and plans workflows",
-                     "tools": ["task_planner", "dependency_analyzer"]
+                     "tools": []
                  }
              ],
              "features": ["real_time_streaming", "multi_agent_orchestration"],
@@ -76,11 +76,11 @@ pub async fn list_capabilities(
                 format!("Agent {} - {}", agent_config.id, agent_config.system_prompt.trim())
             };
 
-            // Combine available and required tools
-            let mut tools = agent_config.available_tools.clone();
-            tools.extend(agent_config.required_tools.clone());
+            // Tools the agent was actually built with
+            let mut tools = state.execution_manager
+                .agent_tool_names(&agent_config.id)
+                .unwrap_or_else(|| agent_config.tool_names());
             tools.sort();
-            tools.dedup();
 
             AgentCapability {
                 agent_type: agent_config.agent_type,
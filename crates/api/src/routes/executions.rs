@@ -0,0 +1,62 @@
+//! Execution management endpoints
+//!
+//! File changes of every execution are journaled (pre- and post-image per path), so an
+//! execution can be reverted after it finished.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::Utc;
+use ai_agent_common::ConversationId;
+use tracing::{error, info, instrument, warn};
+use crate::{server::AppState, types::*};
+
+/// Undo an execution
+///
+/// Restores every file the execution changed to its state before the execution. Files
+/// changed since the execution wrote them are kept and listed as conflicting. For shadow
+/// executions the pending overlay is discarded. Only changes made through
+/// the filesystem tools are reverted; do not call this while the execution is running.
+#[utoipa::path(
+    post,
+    path = "/executions/{conversation_id}/undo",
+    responses(
+        (status = 200, description = "Execution undone", body = UndoResponse),
+        (status = 500, description = "Failed to undo the execution", body = ErrorResponse),
+    ),
+    tag = "execution"
+)]
+#[instrument(skip(state))]
+pub async fn undo_execution(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<UndoResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let id = ConversationId::from_string(conversation_id);
+    let report = match state.execution_manager.undo_execution(&id).await {
+        Ok(report) => report,
+        Err(e) => {
+            error!(error = %e, conversation_id = %id, "Failed to undo execution");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Failed to undo execution: {}", e),
+                    code: Some("UNDO_FAILED".to_string()),
+                    timestamp: Utc::now(),
+                }),
+            ));
+        }
+    };
+
+    let to_strings = |paths: Vec<std::path::PathBuf>| paths.into_iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let restored_files = to_strings(report.restored);
+    let conflicting_files = to_strings(report.conflicts);
+    if !conflicting_files.is_empty() {
+        warn!(conversation_id = %id, files = ?conflicting_files, "Kept files changed after the execution");
+    }
+    info!(conversation_id = %id, files = restored_files.len(), "Execution undone");
+    Ok(Json(UndoResponse { conversation_id: id.0, restored_files, conflicting_files }))
+}
@@ -1,5 +1,7 @@
 
 pub mod agents;
+pub mod executions;
 pub mod query;
+pub mod shadow;
 pub mod stream;
 pub mod subscribe;
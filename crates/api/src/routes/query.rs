@@ -369,16 +369,19 @@ pub async fn query_task(
     let result = state.execution_manager.execute_query(
         &req.query,
         project_scope,
-        &req.subscription_id
+        &req.subscription_id,
+        req.shadow,
     ).await;
 
-    match result {
-        Ok(()) => {
+    let conversation_id = match result {
+        Ok(conversation_id) => {
             info!(
                 subscription_id = %req.subscription_id,
                 query_length = %req.query.len(),
+                shadow = req.shadow,
                 "Query execution started successfully"
             );
+            conversation_id
         }
         Err(e) => {
             error!(
@@ -397,10 +400,11 @@ pub async fn query_task(
                 })
             ));
         }
-    }
+    };
 
     let response = QueryResponse {
         subscription_id: req.subscription_id.clone(),
+        conversation_id: conversation_id.to_string(),
         stream_url: format!("/stream/{}", req.subscription_id),
         status: "started".to_string(),
     };
@@ -0,0 +1,125 @@
+//! Shadow (dry-run) execution endpoints
+//!
+//! Queries started with `shadow: true` write all file changes into an overlay keyed by
+//! conversation id. These endpoints let clients review the combined diff and then apply
+//! or discard it.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::Utc;
+use ai_agent_common::ConversationId;
+use tracing::{error, info, instrument};
+use crate::{server::AppState, types::*};
+
+type ShadowError = (StatusCode, Json<ErrorResponse>);
+
+fn shadow_not_found(conversation_id: &str) -> ShadowError {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: format!("No shadow execution for conversation '{}'", conversation_id),
+            code: Some("SHADOW_NOT_FOUND".to_string()),
+            timestamp: Utc::now(),
+        }),
+    )
+}
+
+fn shadow_failed(action: &str, error: anyhow::Error) -> ShadowError {
+    error!(error = %error, "Failed to {} shadow changes", action);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: format!("Failed to {} shadow changes: {}", action, error),
+            code: Some("SHADOW_OPERATION_FAILED".to_string()),
+            timestamp: Utc::now(),
+        }),
+    )
+}
+
+/// Look up the shadow execution of a conversation
+fn shadow_conversation(state: &AppState, conversation_id: String) -> Result<ConversationId, ShadowError> {
+    let id = ConversationId::from_string(conversation_id);
+    if state.execution_manager.has_shadow(&id) {
+        Ok(id)
+    } else {
+        Err(shadow_not_found(&id.0))
+    }
+}
+
+/// Get the pending changes of a shadow execution
+///
+/// Returns the combined unified diff of all file changes against the project tree.
+#[utoipa::path(
+    get,
+    path = "/shadow/{conversation_id}",
+    responses(
+        (status = 200, description = "Pending shadow changes", body = ShadowDiffResponse),
+        (status = 404, description = "No shadow execution for this conversation", body = ErrorResponse),
+        (status = 500, description = "Failed to compute the diff", body = ErrorResponse),
+    ),
+    tag = "shadow"
+)]
+#[instrument(skip(state))]
+pub async fn get_shadow_diff(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<ShadowDiffResponse>, ShadowError> {
+    let id = shadow_conversation(&state, conversation_id)?;
+    let diff = state.execution_manager.shadow_diff(&id).await
+        .map_err(|e| shadow_failed("diff", e))?;
+
+    Ok(Json(ShadowDiffResponse { conversation_id: id.0, diff }))
+}
+
+/// Apply the changes of a shadow execution
+///
+/// Writes all changed files to the project tree, deletes removed files and drops the overlay.
+#[utoipa::path(
+    post,
+    path = "/shadow/{conversation_id}/apply",
+    responses(
+        (status = 200, description = "Changes applied", body = ShadowApplyResponse),
+        (status = 404, description = "No shadow execution for this conversation", body = ErrorResponse),
+        (status = 500, description = "Failed to apply the changes", body = ErrorResponse),
+    ),
+    tag = "shadow"
+)]
+#[instrument(skip(state))]
+pub async fn apply_shadow(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<ShadowApplyResponse>, ShadowError> {
+    let id = shadow_conversation(&state, conversation_id)?;
+    let applied_files = state.execution_manager.apply_shadow(&id).await
+        .map_err(|e| shadow_failed("apply", e))?;
+
+    info!(conversation_id = %id, files = applied_files.len(), "Applied shadow changes");
+    Ok(Json(ShadowApplyResponse { conversation_id: id.0, applied_files }))
+}
+
+/// Discard the changes of a shadow execution
+#[utoipa::path(
+    delete,
+    path = "/shadow/{conversation_id}",
+    responses(
+        (status = 204, description = "Changes discarded"),
+        (status = 404, description = "No shadow execution for this conversation", body = ErrorResponse),
+        (status = 500, description = "Failed to discard the changes", body = ErrorResponse),
+    ),
+    tag = "shadow"
+)]
+#[instrument(skip(state))]
+pub async fn discard_shadow(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<StatusCode, ShadowError> {
+    let id = shadow_conversation(&state, conversation_id)?;
+    state.execution_manager.discard_shadow(&id).await
+        .map_err(|e| shadow_failed("discard", e))?;
+
+    info!(conversation_id = %id, "Discarded shadow changes");
+    Ok(StatusCode::NO_CONTENT)
+}
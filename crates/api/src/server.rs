@@ -20,6 +20,8 @@ use crate::{
         stream::websocket_handler,
         agents::list_capabilities,
         subscribe::{create_subscription, get_subscription_status},
+        shadow::{apply_shadow, discard_shadow, get_shadow_diff},
+        executions::undo_execution,
     },
     middleware::logging::logging_middleware,
     openapi::ApiDoc,
@@ -71,6 +73,13 @@ impl AcpServer {
             .route("/subscribe", post(create_subscription))
             .route("/subscribe/{subscription_id}", get(get_subscription_status))
 
+            // Shadow (dry-run) executions
+            .route("/shadow/{conversation_id}", get(get_shadow_diff).delete(discard_shadow))
+            .route("/shadow/{conversation_id}/apply", post(apply_shadow))
+
+            // Revert all file changes of an execution
+            .route("/executions/{conversation_id}/undo", post(undo_execution))
+
             // Discovery and health endpoints
             .route("/capabilities", get(list_capabilities))
             .route("/health", get(health_check))
@@ -7,7 +7,7 @@ use utoipa::ToSchema;
 use ai_agent_common::AgentType;
 
 // Re-export common types to avoid qualified references in OpenAPI
-pub use ai_agent_common::{ProjectScope, StatusEvent, EventType, EventSource, ExecutionPlan, WaveInfo, TaskInfo};
+pub use ai_agent_common::{ProjectScope, StatusEvent, EventType, EventSource, ExecutionPlan, WaveInfo, TaskInfo, TokenUsage};
 
 /// Request to execute a query
 ///
@@ -36,6 +36,15 @@ pub struct QueryRequest {
     /// This ensures events are buffered and no progress is lost.
     #[schema(example = "sub_750e8400-e29b-41d4-a716-446655440123")]
     pub subscription_id: String,
+
+    /// Run in shadow (dry-run) mode
+    ///
+    /// File changes go to an overlay instead of the project tree. The combined diff
+    /// is sent as a ShadowChangesReady event and can be applied with
+    /// POST /shadow/{conversation_id}/apply.
+    #[serde(default)]
+    #[schema(example = false)]
+    pub shadow: bool,
 }
 
 /// Response when starting an execution
@@ -51,6 +60,12 @@ pub struct QueryResponse {
     #[schema(example = "sub_750e8400-e29b-41d4-a716-446655440123")]
     pub subscription_id: String,
 
+    /// Conversation ID of the started execution
+    ///
+    /// Identifies the shadow workspace of a dry-run execution.
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub conversation_id: String,
+
     /// WebSocket URL path for streaming status updates
     ///
     /// Connect to this WebSocket endpoint to receive real-time progress updates.
@@ -149,7 +164,7 @@ pub struct AgentCapability {
     /// List of tools this agent can use
     ///
     /// Tool names that this agent type has access to for completing tasks.
-    #[schema(example = json!(["file_reader", "file_writer", "lsp_client", "test_runner"]))]
+    #[schema(example = json!(["read_file", "write_file", "lsp", "run_tests"]))]
     pub tools: Vec<String>,
 }
 
@@ -209,6 +224,45 @@ pub struct SubscriptionStatus {
     pub client_id: Option<String>,
 }
 
+/// Pending changes of a shadow (dry-run) execution
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShadowDiffResponse {
+    /// Conversation ID of the shadow execution
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub conversation_id: String,
+
+    /// Combined unified diff of all changes against the project tree
+    pub diff: String,
+}
+
+/// Result of applying a shadow (dry-run) execution to the project tree
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShadowApplyResponse {
+    /// Conversation ID of the shadow execution
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub conversation_id: String,
+
+    /// Files written or deleted, relative to the project root
+    #[schema(example = json!(["src/main.rs"]))]
+    pub applied_files: Vec<String>,
+}
+
+/// Result of undoing an execution
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UndoResponse {
+    /// Conversation ID of the undone execution
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub conversation_id: String,
+
+    /// Paths restored to their state before the execution, relative to the project root
+    #[schema(example = json!(["src/main.rs"]))]
+    pub restored_files: Vec<String>,
+
+    /// Paths changed after the execution wrote them; left untouched
+    #[schema(example = json!(["src/lib.rs"]))]
+    pub conflicting_files: Vec<String>,
+}
+
 /// Error response
 ///
 /// Returned when an API request fails. Contains error details and context.
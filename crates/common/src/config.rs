@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
-use std::{fs, path::PathBuf};
+use std::{collections::HashMap, fs, path::PathBuf};
 use anyhow::{Context, anyhow};
 
 use crate::{AgentType, ErrorRecoveryStrategy, HitlMode, QualityStrategy};
@@ -12,6 +12,62 @@ pub struct SystemConfig {
     pub storage: StorageConfig,
     pub embedding: EmbeddingConfig,
     pub agent_network: AgentNetworkConfig,
+    /// Default LLM provider for agents, query routing/enhancement and summarization
+    #[serde(default)]
+    pub llm: LlmConfig,
+}
+
+/// Chat completion provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LlmProviderKind {
+    /// Local Ollama through its OpenAI-compatible endpoint
+    #[default]
+    Ollama,
+    /// OpenAI or any OpenAI-compatible server
+    OpenAi,
+}
+
+/// Whether a cassette captures live LLM responses or serves them offline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CassetteMode {
+    /// Forward requests to the provider and store every interaction
+    Record,
+    /// Answer from the stored interactions only; unmatched requests fail
+    #[default]
+    Replay,
+}
+
+/// Cassette of recorded LLM interactions, used for deterministic tests
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CassetteConfig {
+    /// JSON fixture file
+    pub path: PathBuf,
+
+    #[serde(default)]
+    pub mode: CassetteMode,
+}
+
+/// LLM provider settings; unset fields fall back to the provider's defaults
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LlmConfig {
+    #[serde(default)]
+    pub provider: LlmProviderKind,
+
+    /// API base URL (defaults to the embedding Ollama host for Ollama, api.openai.com for OpenAi)
+    #[serde(default)]
+    pub base_url: Option<String>,
+
+    /// API key (defaults to `OPENAI_API_KEY` for OpenAi)
+    #[serde(default)]
+    pub api_key: Option<String>,
+
+    /// Record LLM interactions to, or replay them from, a cassette file
+    #[serde(default)]
+    pub cassette: Option<CassetteConfig>,
+
+    /// Request token log-probabilities for confidence estimation (not every backend returns them)
+    #[serde(default)]
+    pub logprobs: bool,
 }
 
 
@@ -273,6 +329,14 @@ pub struct AgentNetworkConfig {
     pub acp: AcpConfig,
     pub tracing: TracingConfig,
     pub quality: QualityConfig,
+    #[serde(default)]
+    pub lsp: LspConfig,
+    #[serde(default)]
+    pub commands: CommandConfig,
+    #[serde(default)]
+    pub shadow: ShadowConfig,
+    #[serde(default)]
+    pub delegation: DelegationConfig,
 }
 
 impl AgentNetworkConfig {
@@ -309,6 +373,22 @@ impl AgentNetworkConfig {
             return Err(anyhow!("pruning_threshold must be between 0.0 and 1.0".to_string()));
         }
 
+        // Validate token budgets: a soft limit above its hard limit would never warn
+        let budgets = [
+            ("agent", self.token_budget.soft_tokens_per_agent, self.token_budget.hard_tokens_per_agent),
+            ("execution", self.token_budget.soft_tokens_per_execution, self.token_budget.hard_tokens_per_execution),
+        ];
+        for (scope, soft, hard) in budgets {
+            if soft == Some(0) || hard == Some(0) {
+                return Err(anyhow!("token budgets per {} must be greater than 0", scope));
+            }
+            if let (Some(soft), Some(hard)) = (soft, hard) {
+                if soft > hard {
+                    return Err(anyhow!("soft_tokens_per_{} ({}) exceeds hard_tokens_per_{} ({})", scope, soft, scope, hard));
+                }
+            }
+        }
+
         // Validate HITL settings
         if !(0.0..=1.0).contains(&self.hitl.sample_rate) {
             return Err(anyhow!("sample_rate must be between 0.0 and 1.0".to_string()));
@@ -318,6 +398,13 @@ impl AgentNetworkConfig {
         if !(0.0..=1.0).contains(&self.quality.min_quality_score) {
             return Err(anyhow!("min_quality_score must be between 0.0 and 1.0".to_string()));
         }
+        if let Some(evaluator) = &self.quality.evaluator {
+            match self.get_agent(evaluator) {
+                Some(agent) if agent.agent_type == AgentType::Evaluator && !agent.configurable => {}
+                Some(_) => return Err(anyhow!("quality.evaluator '{}' is not a built-in Evaluator agent", evaluator)),
+                None => return Err(anyhow!("quality.evaluator '{}' is not a configured agent", evaluator)),
+            }
+        }
 
         Ok(())
     }
@@ -354,6 +441,10 @@ impl Default for AgentNetworkConfig {
             acp: AcpConfig::default(),
             tracing: TracingConfig::default(),
             quality: QualityConfig::default(),
+            lsp: LspConfig::default(),
+            commands: CommandConfig::default(),
+            shadow: ShadowConfig::default(),
+            delegation: DelegationConfig::default(),
         }
     }
 }
@@ -409,6 +500,160 @@ pub struct AgentConfig {
     /// Available tools for the agent
     #[serde(default)]
     pub available_tools: Vec<String>,
+
+    /// External MCP servers whose tools are mounted for this agent
+    #[serde(default)]
+    pub mcp_servers: Vec<McpServerConfig>,
+
+    /// Maximum number of read-only tool calls of one turn executed concurrently
+    #[serde(default = "default_max_parallel_tools")]
+    pub max_parallel_tools: usize,
+
+    /// Timeout for a single tool call in seconds
+    #[serde(default = "default_tool_timeout")]
+    pub tool_timeout_secs: u64,
+
+    /// Per-tool timeout overrides in seconds, keyed by tool name
+    #[serde(default)]
+    pub tool_timeouts: HashMap<String, u64>,
+
+    /// LLM provider of this agent (defaults to `[llm]`)
+    #[serde(default)]
+    pub provider: Option<LlmProviderKind>,
+
+    /// API base URL of this agent's provider
+    #[serde(default)]
+    pub base_url: Option<String>,
+
+    /// API key of this agent's provider
+    #[serde(default)]
+    pub api_key: Option<String>,
+
+    /// Build a generic agent entirely from this config (`steps`, `output_schema`, `capabilities`)
+    /// instead of the built-in implementation of `agent_type`
+    #[serde(default)]
+    pub configurable: bool,
+
+    /// JSON schema of the output of formatted steps (configurable agents only)
+    #[serde(default)]
+    pub output_schema: Option<serde_json::Value>,
+
+    /// Workflow steps. They make up the whole workflow of a configurable agent; for built-in
+    /// agents a step replaces the built-in step with the same id or is inserted as a new one
+    #[serde(default)]
+    pub steps: Vec<WorkflowStepConfig>,
+}
+
+/// A workflow step defined in config
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WorkflowStepConfig {
+    /// Step identifier; its output is shared with later steps under this key
+    pub id: String,
+
+    /// Display name (defaults to the id)
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Instructions for the step
+    pub description: String,
+
+    /// Single LLM call or tool-using ReAct loop
+    #[serde(default)]
+    pub mode: StepMode,
+
+    /// Maximum ReAct iterations
+    #[serde(default)]
+    pub max_iterations: Option<usize>,
+
+    /// Tools the step needs; must be among the agent's available_tools
+    #[serde(default)]
+    pub required_tools: Vec<String>,
+
+    /// Tools the step may use when the agent has them. A ReAct step is offered only its
+    /// required and optional tools, or all of the agent's tools when it lists neither
+    #[serde(default)]
+    pub optional_tools: Vec<String>,
+
+    /// Whether the step answers with the agent's output schema (defaults to false, or to the
+    /// replaced built-in step's setting)
+    #[serde(default)]
+    pub formatted: Option<bool>,
+
+    /// Step-specific parameters
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+
+    /// Insert a new step before this built-in step (appended when neither anchor is set)
+    #[serde(default)]
+    pub before: Option<String>,
+
+    /// Insert a new step after this built-in step
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+/// Execution mode of a config-defined workflow step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum StepMode {
+    #[default]
+    OneShot,
+    ReAct,
+}
+
+/// An external Model Context Protocol server, reached over stdio or streamable HTTP
+///
+/// Its tools are exposed to the agent as `<name>__<tool>`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct McpServerConfig {
+    /// Short identifier used to prefix the server's tool names
+    pub name: String,
+
+    /// Executable to launch for a stdio server
+    #[serde(default)]
+    pub command: Option<String>,
+
+    /// Arguments passed to the executable
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Extra environment variables for the server process
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    /// Endpoint of a streamable HTTP server (instead of `command`)
+    #[serde(default)]
+    pub url: Option<String>,
+
+    /// Only mount these tools (by their name on the server); all tools when empty
+    #[serde(default)]
+    pub tools: Vec<String>,
+
+    /// Risk level applied to every tool of this server (defaults to High)
+    #[serde(default)]
+    pub risk_level: Option<RiskLevel>,
+
+    /// Trust the server's `readOnlyHint` annotations; annotated tools then run in shadow
+    /// (dry-run) mode and alongside other reads without approval ordering
+    #[serde(default)]
+    pub trust_read_only_hints: bool,
+}
+
+impl McpServerConfig {
+    fn validate(&self, agent_id: &str) -> anyhow::Result<()> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(anyhow!(
+                "Agent {} MCP server name '{}' must be non-empty and contain only letters, digits, '-' or '_'",
+                agent_id, self.name
+            ));
+        }
+        match (&self.command, &self.url) {
+            (Some(_), None) | (None, Some(_)) => Ok(()),
+            _ => Err(anyhow!(
+                "Agent {} MCP server '{}' needs exactly one of 'command' (stdio) or 'url' (HTTP)",
+                agent_id, self.name
+            )),
+        }
+    }
 }
 
 impl AgentConfig {
@@ -438,9 +683,75 @@ impl AgentConfig {
             return Err(anyhow!("Agent {} system_prompt cannot be empty", self.id));
         }
 
+        if self.max_parallel_tools == 0 {
+            return Err(anyhow!("Agent {} max_parallel_tools must be greater than 0", self.id));
+        }
+
+        if let Some(missing) = self.required_tools.iter().find(|t| !self.available_tools.contains(t)) {
+            return Err(anyhow!(
+                "Agent {} requires tool '{}' but it is not listed in available_tools",
+                self.id, missing
+            ));
+        }
+
+        let mut server_names = std::collections::HashSet::new();
+        for server in &self.mcp_servers {
+            server.validate(&self.id)?;
+            if !server_names.insert(&server.name) {
+                return Err(anyhow!("Agent {} has duplicate MCP server '{}'", self.id, server.name));
+            }
+        }
+
+        // The orchestrator relies on the output of the built-in Planning and Evaluator agents
+        if self.configurable && matches!(self.agent_type, AgentType::Planning | AgentType::Evaluator) {
+            return Err(anyhow!(
+                "Configurable agent {} cannot be of type {}; use Coding or Writing",
+                self.id, self.agent_type
+            ));
+        }
+
+        if self.configurable && self.steps.is_empty() {
+            return Err(anyhow!("Configurable agent {} must define at least one step", self.id));
+        }
+
+        if self.output_schema.as_ref().is_some_and(|schema| !schema.is_object()) {
+            return Err(anyhow!("Agent {} output_schema must be a table", self.id));
+        }
+
+        let mut step_ids = std::collections::HashSet::new();
+        for step in &self.steps {
+            if !step_ids.insert(&step.id) {
+                return Err(anyhow!("Agent {} has duplicate step '{}'", self.id, step.id));
+            }
+            if step.before.is_some() && step.after.is_some() {
+                return Err(anyhow!("Agent {} step '{}' cannot set both before and after", self.id, step.id));
+            }
+            if self.configurable && (step.before.is_some() || step.after.is_some()) {
+                return Err(anyhow!(
+                    "Configurable agent {} step '{}' cannot use before/after; steps run in the order listed",
+                    self.id, step.id
+                ));
+            }
+            if let Some(missing) = step.required_tools.iter().find(|t| !self.tool_names().contains(t)) {
+                return Err(anyhow!(
+                    "Agent {} step '{}' requires tool '{}' but it is not listed in available_tools",
+                    self.id, step.id, missing
+                ));
+            }
+        }
+
         Ok(())
     }
 
+    /// All tools this agent is configured with (available and required), deduplicated
+    pub fn tool_names(&self) -> Vec<String> {
+        let mut tools = self.available_tools.clone();
+        tools.extend(self.required_tools.iter().cloned());
+        tools.sort();
+        tools.dedup();
+        tools
+    }
+
     /// Get effective quality strategy
     pub fn effective_quality_strategy(&self) -> Option<QualityStrategy> {
         self.quality_strategy
@@ -476,6 +787,15 @@ impl RiskLevel {
             _ => RiskLevel::Critical,
         }
     }
+
+    /// The next level up, saturating at `Critical`
+    pub fn escalated(self) -> Self {
+        match self {
+            RiskLevel::Low => RiskLevel::Medium,
+            RiskLevel::Medium => RiskLevel::High,
+            RiskLevel::High | RiskLevel::Critical => RiskLevel::Critical,
+        }
+    }
 }
 
 
@@ -500,6 +820,55 @@ pub struct HitlConfig {
     /// Timeout for HITL approvals (seconds)
     #[serde(default = "default_hitl_timeout")]
     pub approval_timeout_secs: u64,
+
+    /// Tool risk policy; the first matching rule classifies a tool call, before the
+    /// tool's own classification and the built-in defaults
+    #[serde(default)]
+    pub rules: Vec<ToolRiskRule>,
+}
+
+/// Whether a tool call matched by a rule needs human approval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum ApprovalRequirement {
+    /// Approval when the risk level reaches `risk_threshold`
+    #[default]
+    Threshold,
+    Always,
+    Never,
+}
+
+/// A tool risk policy rule; empty or unset conditions match everything
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ToolRiskRule {
+    /// Tool names (globs, e.g. "write_file" or "github__*")
+    #[serde(default)]
+    pub tools: Vec<String>,
+
+    #[serde(default)]
+    pub agent_ids: Vec<String>,
+
+    #[serde(default)]
+    pub agent_types: Vec<AgentType>,
+
+    /// Globs for the `path` argument, relative to the project root (e.g. "src/**", "Cargo.toml")
+    #[serde(default)]
+    pub paths: Vec<String>,
+
+    /// Globs for other arguments by field name (e.g. `{ command = "push" }`)
+    #[serde(default)]
+    pub arguments: HashMap<String, String>,
+
+    /// Bounds in bytes for the larger of the existing file and the content being written
+    pub min_file_size: Option<u64>,
+    pub max_file_size: Option<u64>,
+
+    /// Match only calls creating a new file (true) or touching an existing one (false)
+    pub new_file: Option<bool>,
+
+    pub risk_level: RiskLevel,
+
+    #[serde(default)]
+    pub approval: ApprovalRequirement,
 }
 
 
@@ -553,6 +922,23 @@ pub struct TokenBudgetConfig {
     /// Pruning strategy threshold
     #[serde(default = "default_pruning_threshold")]
     pub pruning_threshold: f32,
+
+    /// Tokens one agent may consume within an execution before a warning is emitted
+    #[serde(default)]
+    pub soft_tokens_per_agent: Option<usize>,
+
+    /// Tokens one agent may consume within an execution; past it the agent's remaining
+    /// tasks fail without retries, while other agents carry on
+    #[serde(default)]
+    pub hard_tokens_per_agent: Option<usize>,
+
+    /// Tokens an execution may consume before a warning is emitted
+    #[serde(default)]
+    pub soft_tokens_per_execution: Option<usize>,
+
+    /// Tokens an execution may consume before it is aborted
+    #[serde(default)]
+    pub hard_tokens_per_execution: Option<usize>,
 }
 
 impl Default for TokenBudgetConfig {
@@ -562,6 +948,10 @@ impl Default for TokenBudgetConfig {
             enable_context_pruning: default_true(),
             enable_prompt_caching: default_true(),
             pruning_threshold: default_pruning_threshold(),
+            soft_tokens_per_agent: None,
+            hard_tokens_per_agent: None,
+            soft_tokens_per_execution: None,
+            hard_tokens_per_execution: None,
         }
     }
 }
@@ -653,6 +1043,14 @@ pub struct QualityConfig {
     /// Enable feedback loop
     #[serde(default = "default_true")]
     pub enable_feedback_loop: bool,
+
+    /// Re-attempts of a task with the evaluator's critique before its output is accepted as is
+    #[serde(default = "default_max_feedback_rounds")]
+    pub max_feedback_rounds: usize,
+
+    /// Id of the agent scoring task outputs; defaults to the first built-in Evaluator agent
+    #[serde(default)]
+    pub evaluator: Option<String>,
 }
 
 impl Default for QualityConfig {
@@ -661,14 +1059,181 @@ impl Default for QualityConfig {
             default_strategy: None,
             min_quality_score: default_min_quality_score(),
             enable_feedback_loop: default_true(),
+            max_feedback_rounds: default_max_feedback_rounds(),
+            evaluator: None,
+        }
+    }
+}
+
+/// Language server configuration for the `lsp` tool
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LspConfig {
+    /// Language servers, one per language
+    #[serde(default = "default_lsp_servers")]
+    pub servers: Vec<LspServerConfig>,
+
+    /// Timeout for a single LSP request (seconds)
+    #[serde(default = "default_lsp_request_timeout")]
+    pub request_timeout_secs: u64,
+
+    /// How long to wait for diagnostics after a document change (milliseconds)
+    #[serde(default = "default_lsp_diagnostics_wait")]
+    pub diagnostics_wait_ms: u64,
+}
+
+impl Default for LspConfig {
+    fn default() -> Self {
+        Self {
+            servers: default_lsp_servers(),
+            request_timeout_secs: default_lsp_request_timeout(),
+            diagnostics_wait_ms: default_lsp_diagnostics_wait(),
+        }
+    }
+}
+
+impl LspConfig {
+    /// Find the server responsible for a file extension
+    pub fn server_for_extension(&self, extension: &str) -> Option<&LspServerConfig> {
+        self.servers
+            .iter()
+            .find(|s| s.extensions.iter().any(|e| e.trim_start_matches('.') == extension))
+    }
+}
+
+/// A single language server launched over stdio
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LspServerConfig {
+    /// Language identifier sent to the server (e.g., "rust", "python")
+    pub language: String,
+
+    /// Executable to launch (e.g., "rust-analyzer")
+    pub command: String,
+
+    /// Arguments passed to the executable
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// File extensions handled by this server (without the dot)
+    pub extensions: Vec<String>,
+
+    /// Server-specific `initializationOptions`
+    #[serde(default)]
+    pub initialization_options: Option<serde_json::Value>,
+}
+
+/// Configuration for the `run_command` and `run_tests` tools
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommandConfig {
+    /// Programs that may run without human approval (matched by exact name). Interpreters
+    /// and generic runners (python, node, npm, npx, make) are not allowed by default: their
+    /// arguments can run arbitrary code.
+    #[serde(default = "default_allowed_commands")]
+    pub allowed_commands: Vec<String>,
+
+    /// Command (program followed by arguments) executed by `run_tests`
+    #[serde(default = "default_test_command")]
+    pub test_command: Vec<String>,
+
+    /// Wall-clock timeout for a single command (seconds)
+    #[serde(default = "default_command_timeout")]
+    pub timeout_secs: u64,
+
+    /// Maximum bytes of stdout/stderr returned; the middle is truncated beyond this
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+
+    /// Environment variables passed through to commands; all others are removed
+    #[serde(default = "default_env_passthrough")]
+    pub env_passthrough: Vec<String>,
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            allowed_commands: default_allowed_commands(),
+            test_command: default_test_command(),
+            timeout_secs: default_command_timeout(),
+            max_output_bytes: default_max_output_bytes(),
+            env_passthrough: default_env_passthrough(),
+        }
+    }
+}
+
+impl CommandConfig {
+    /// Check whether a program is on the allow-list
+    pub fn is_allowed(&self, program: &str) -> bool {
+        self.allowed_commands.iter().any(|c| c == program)
+    }
+}
+
+/// Configuration for shadow (dry-run) executions
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ShadowConfig {
+    /// Directory holding one overlay per conversation id
+    #[serde(default = "default_shadow_dir")]
+    pub dir: PathBuf,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self { dir: default_shadow_dir() }
+    }
+}
+
+fn default_shadow_dir() -> PathBuf {
+    std::env::temp_dir().join("agent-network-shadow")
+}
+
+/// Limits for sub-tasks agents spawn with the `delegate` tool
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DelegationConfig {
+    /// Nesting depth of delegated sub-tasks; 0 disables delegation
+    #[serde(default = "default_max_delegation_depth")]
+    pub max_depth: usize,
+
+    /// Sub-tasks delegated over a whole execution
+    #[serde(default = "default_max_delegated_tasks")]
+    pub max_tasks: usize,
+}
+
+impl Default for DelegationConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: default_max_delegation_depth(),
+            max_tasks: default_max_delegated_tasks(),
         }
     }
 }
 
+fn default_max_delegation_depth() -> usize {
+    2
+}
+
+fn default_max_delegated_tasks() -> usize {
+    8
+}
 
 impl SystemConfig {
     pub fn new(indexing: IndexingConfig, rag: RagConfig,agent_network: AgentNetworkConfig,  storage: StorageConfig, embedding: EmbeddingConfig) -> Self {
-        Self { indexing, rag,agent_network, storage, embedding}
+        Self { indexing, rag,agent_network, storage, embedding, llm: LlmConfig::default() }
+    }
+
+    /// Resolved LLM settings for an agent (or the system default), with base URL and key filled in
+    pub fn llm_config(&self, agent: Option<&AgentConfig>) -> LlmConfig {
+        let provider = agent.and_then(|a| a.provider).unwrap_or(self.llm.provider);
+        let base_url = agent.and_then(|a| a.base_url.clone())
+            .or_else(|| self.llm.base_url.clone().filter(|_| provider == self.llm.provider))
+            .unwrap_or_else(|| match provider {
+                LlmProviderKind::Ollama => format!("{}:{}/v1", self.embedding.ollama_host, self.embedding.ollama_port),
+                LlmProviderKind::OpenAi => "https://api.openai.com/v1".to_string(),
+            });
+        let api_key = agent.and_then(|a| a.api_key.clone())
+            .or_else(|| self.llm.api_key.clone().filter(|_| provider == self.llm.provider))
+            .or_else(|| match provider {
+                LlmProviderKind::Ollama => None,
+                LlmProviderKind::OpenAi => std::env::var("OPENAI_API_KEY").ok(),
+            });
+        LlmConfig { provider, base_url: Some(base_url), api_key, cassette: self.llm.cassette.clone(), logprobs: self.llm.logprobs }
     }
 
     /// Load configuration from TOML file
@@ -731,6 +1296,15 @@ impl SystemConfig {
             agent.validate()?
         }
 
+        // Without a key every call of the agent would fail authentication
+        for agent in &self.agent_network.agents {
+            let llm = self.llm_config(Some(agent));
+            let replays = llm.cassette.as_ref().is_some_and(|cassette| cassette.mode == CassetteMode::Replay);
+            if llm.provider == LlmProviderKind::OpenAi && llm.api_key.is_none() && !replays {
+                anyhow::bail!("Agent {} uses the OpenAi provider but has no api_key (and OPENAI_API_KEY is not set)", agent.id);
+            }
+        }
+
 
         Ok(())
     }
@@ -771,7 +1345,8 @@ impl Default for SystemConfig {
                 postgres_url: "postgresql://localhost/ai_agent".to_string(),
                 redis_url: Some("redis://localhost:6379".to_string()),
             },
-            embedding: EmbeddingConfig::default()
+            embedding: EmbeddingConfig::default(),
+            llm: LlmConfig::default(),
         }
     }
 }
@@ -790,6 +1365,14 @@ fn default_context_window() -> usize {
     8192
 }
 
+fn default_max_parallel_tools() -> usize {
+    4
+}
+
+fn default_tool_timeout() -> u64 {
+    300
+}
+
 fn default_false() -> bool {
     false
 }
@@ -846,6 +1429,10 @@ fn default_min_quality_score() -> f32 {
     0.7
 }
 
+fn default_max_feedback_rounds() -> usize {
+    2
+}
+
 
 fn default_risk_threshold() -> RiskLevel {
     RiskLevel::High
@@ -911,3 +1498,211 @@ fn default_searxng_max_results() -> usize {
 fn default_query_enhancer_vocab_path() -> PathBuf {
     PathBuf::from("vocab.txt")
 }
+
+fn default_lsp_servers() -> Vec<LspServerConfig> {
+    let server = |language: &str, command: &str, args: &[&str], extensions: &[&str]| LspServerConfig {
+        language: language.to_string(),
+        command: command.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        extensions: extensions.iter().map(|e| e.to_string()).collect(),
+        initialization_options: None,
+    };
+    vec![
+        server("rust", "rust-analyzer", &[], &["rs"]),
+        server("python", "pyright-langserver", &["--stdio"], &["py"]),
+        server("go", "gopls", &[], &["go"]),
+    ]
+}
+
+fn default_lsp_request_timeout() -> u64 {
+    30
+}
+
+fn default_lsp_diagnostics_wait() -> u64 {
+    3000
+}
+
+fn default_allowed_commands() -> Vec<String> {
+    ["cargo", "rustc", "go", "pytest"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect()
+}
+
+fn default_test_command() -> Vec<String> {
+    vec!["cargo".to_string(), "test".to_string()]
+}
+
+fn default_command_timeout() -> u64 {
+    300
+}
+
+fn default_max_output_bytes() -> usize {
+    32_000
+}
+
+fn default_env_passthrough() -> Vec<String> {
+    ["PATH", "HOME", "USER", "LANG", "LC_ALL", "TERM", "TMPDIR", "CARGO_HOME", "RUSTUP_HOME",
+     "RUSTUP_TOOLCHAIN", "GOPATH", "GOCACHE", "VIRTUAL_ENV", "NODE_PATH"]
+        .iter()
+        .map(|v| v.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(available: &[&str], required: &[&str]) -> AgentConfig {
+        let mut agent: AgentConfig = toml::from_str(r#"
+            id = "coding-1"
+            agent_type = "Coding"
+            model = "qwen3:8b"
+            system_prompt = "You write code."
+        "#).unwrap();
+        agent.available_tools = available.iter().map(|t| t.to_string()).collect();
+        agent.required_tools = required.iter().map(|t| t.to_string()).collect();
+        agent
+    }
+
+    #[test]
+    fn test_agent_tools_are_validated() {
+        assert!(agent(&["read_file", "write_file"], &["write_file"]).validate().is_ok());
+
+        let missing = agent(&["read_file"], &["write_file"]).validate().unwrap_err();
+        assert!(missing.to_string().contains("requires tool 'write_file'"));
+    }
+
+    #[test]
+    fn test_mcp_servers_are_validated() {
+        let server = |name: &str, command: Option<&str>, url: Option<&str>| McpServerConfig {
+            name: name.to_string(),
+            command: command.map(str::to_string),
+            args: vec![],
+            env: HashMap::new(),
+            url: url.map(str::to_string),
+            tools: vec![],
+            risk_level: None,
+            trust_read_only_hints: false,
+        };
+
+        let mut config = agent(&[], &[]);
+        config.mcp_servers = vec![server("github", Some("npx"), None), server("docs", None, Some("http://localhost:8000/mcp"))];
+        assert!(config.validate().is_ok());
+
+        config.mcp_servers = vec![server("both", Some("npx"), Some("http://localhost:8000/mcp"))];
+        assert!(config.validate().is_err());
+
+        config.mcp_servers = vec![server("bad name", Some("npx"), None)];
+        assert!(config.validate().is_err());
+
+        config.mcp_servers = vec![server("github", Some("npx"), None), server("github", Some("npx"), None)];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_configurable_agent_steps_are_validated() {
+        let mut config: AgentConfig = toml::from_str(r#"
+            id = "security-reviewer"
+            agent_type = "Coding"
+            model = "qwen3:8b"
+            system_prompt = "You review code for vulnerabilities."
+            configurable = true
+            available_tools = ["read_file", "search_code"]
+
+            [output_schema]
+            type = "object"
+            required = ["findings"]
+
+            [[steps]]
+            id = "inspect"
+            description = "Read the changed files."
+            mode = "ReAct"
+            max_iterations = 3
+            required_tools = ["read_file"]
+
+            [[steps]]
+            id = "report"
+            description = "Report the findings."
+            formatted = true
+        "#).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.steps[0].mode, StepMode::ReAct);
+        assert_eq!(config.steps[1].mode, StepMode::OneShot);
+
+        config.steps[1].required_tools = vec!["write_file".to_string()];
+        assert!(config.validate().unwrap_err().to_string().contains("requires tool 'write_file'"));
+
+        config.steps.clear();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_configurable_agents_cannot_plan_or_evaluate() {
+        let mut config = agent(&[], &[]);
+        config.configurable = true;
+        config.steps = vec![toml::from_str("id = \"review\"\ndescription = \"Review the change.\"").unwrap()];
+        assert!(config.validate().is_ok());
+
+        for agent_type in [AgentType::Planning, AgentType::Evaluator] {
+            config.agent_type = agent_type;
+            assert!(config.validate().unwrap_err().to_string().contains("cannot be of type"));
+        }
+    }
+
+    #[test]
+    fn test_openai_agents_need_an_api_key() {
+        let mut config = SystemConfig::default();
+        let mut remote = agent(&[], &[]);
+        remote.provider = Some(LlmProviderKind::OpenAi);
+        config.agent_network.agents = vec![remote];
+        if std::env::var("OPENAI_API_KEY").is_err() {
+            assert!(config.validate().unwrap_err().to_string().contains("has no api_key"));
+        }
+
+        // Replaying a cassette makes no live calls
+        config.llm.cassette = Some(CassetteConfig { path: PathBuf::from("agents.json"), mode: CassetteMode::Replay });
+        assert!(config.validate().is_ok());
+
+        config.llm.cassette = None;
+        config.agent_network.agents[0].api_key = Some("agent-key".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_agent_llm_settings_override_defaults() {
+        let mut config = SystemConfig::default();
+        config.llm.api_key = Some("default-key".to_string());
+
+        let ollama = config.llm_config(None);
+        assert_eq!(ollama.provider, LlmProviderKind::Ollama);
+        assert_eq!(ollama.base_url.as_deref(), Some("http://localhost:11434/v1"));
+        assert_eq!(ollama.api_key.as_deref(), Some("default-key"));
+
+        let mut remote = agent(&[], &[]);
+        remote.provider = Some(LlmProviderKind::OpenAi);
+        remote.base_url = Some("http://vllm:8000/v1".to_string());
+        remote.api_key = Some("agent-key".to_string());
+        let resolved = config.llm_config(Some(&remote));
+        assert_eq!(resolved.provider, LlmProviderKind::OpenAi);
+        assert_eq!(resolved.base_url.as_deref(), Some("http://vllm:8000/v1"));
+        assert_eq!(resolved.api_key.as_deref(), Some("agent-key"));
+    }
+
+    #[test]
+    fn test_token_budgets_are_validated() {
+        let mut config = AgentNetworkConfig::default();
+        config.agents = vec![agent(&[], &[])];
+        config.token_budget.soft_tokens_per_execution = Some(50_000);
+        config.token_budget.hard_tokens_per_execution = Some(100_000);
+        assert!(config.validate().is_ok());
+
+        config.token_budget.soft_tokens_per_agent = Some(20_000);
+        config.token_budget.hard_tokens_per_agent = Some(10_000);
+        assert!(config.validate().unwrap_err().to_string().contains("soft_tokens_per_agent"));
+
+        config.token_budget.soft_tokens_per_agent = None;
+        config.token_budget.hard_tokens_per_agent = Some(0);
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,367 @@
+//! Record/replay of LLM interactions for deterministic tests
+//!
+//! In record mode every request is forwarded to the wrapped provider and stored together with
+//! its response in a JSON cassette. In replay mode responses are served from the cassette, keyed
+//! by a hash of the normalized request, so tests run offline. Requests are normalized by
+//! replacing UUIDs, timestamps and user-supplied patterns (e.g. temp dirs) with placeholders.
+
+use anyhow::{anyhow, Context, Result};
+use async_openai::types::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+use async_trait::async_trait;
+use futures::StreamExt;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use tracing::debug;
+
+use super::{ChatChunk, ChatStream, LlmProvider};
+use crate::config::CassetteMode;
+
+static UUID: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}").unwrap()
+});
+static TIMESTAMP: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2}| UTC)?").unwrap()
+});
+
+/// Cassettes currently recorded to in this process, so recorders of the same file share it;
+/// replay providers each load their own copy so their cursors stay independent
+static OPEN_CASSETTES: Lazy<Mutex<HashMap<PathBuf, Weak<Cassette>>>> = Lazy::new(Default::default);
+
+/// Cassettes already recorded to by this process; later recorders append instead of truncating
+static RECORDED_CASSETTES: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Reply {
+    Response(CreateChatCompletionResponse),
+    Stream(Vec<ChatChunk>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    key: String,
+    /// Normalized request, kept for reviewing fixtures and diffing unmatched requests
+    request: Value,
+    #[serde(flatten)]
+    reply: Reply,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug)]
+struct Cassette {
+    path: PathBuf,
+    interactions: Mutex<Vec<Interaction>>,
+    /// Replays served per key, so identical requests get their responses in recorded order
+    cursors: Mutex<HashMap<String, usize>>,
+}
+
+impl Cassette {
+    fn open(path: &Path, mode: CassetteMode) -> Result<Arc<Self>> {
+        if mode == CassetteMode::Replay {
+            return Ok(Self::new(path, Self::load(path)?));
+        }
+
+        let mut open = OPEN_CASSETTES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cassette) = open.get(path).and_then(Weak::upgrade) {
+            return Ok(cassette);
+        }
+        // Re-recording starts from scratch once per run so stale interactions do not linger
+        let first = RECORDED_CASSETTES.lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_path_buf());
+        let interactions = if first || !path.exists() { Vec::new() } else { Self::load(path)? };
+        let cassette = Self::new(path, interactions);
+        open.insert(path.to_path_buf(), Arc::downgrade(&cassette));
+        Ok(cassette)
+    }
+
+    fn new(path: &Path, interactions: Vec<Interaction>) -> Arc<Self> {
+        Arc::new(Self {
+            path: path.to_path_buf(),
+            interactions: Mutex::new(interactions),
+            cursors: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn load(path: &Path) -> Result<Vec<Interaction>> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read cassette {}", path.display()))?;
+        Ok(serde_json::from_str::<CassetteFile>(&contents)
+            .with_context(|| format!("Failed to parse cassette {}", path.display()))?
+            .interactions)
+    }
+
+    fn record(&self, interaction: Interaction) -> Result<()> {
+        let mut interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        interactions.push(interaction);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = CassetteFile { interactions: interactions.clone() };
+        std::fs::write(&self.path, serde_json::to_string_pretty(&file)?)
+            .with_context(|| format!("Failed to write cassette {}", self.path.display()))
+    }
+
+    fn replay(&self, key: &str, request: &Value) -> Result<Reply> {
+        let interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        let matches: Vec<&Interaction> = interactions.iter().filter(|i| i.key == key).collect();
+        if matches.is_empty() {
+            return Err(self.unmatched(&interactions, request));
+        }
+
+        let mut cursors = self.cursors.lock().unwrap_or_else(|e| e.into_inner());
+        let cursor = cursors.entry(key.to_string()).or_insert(0);
+        let interaction = matches.get(*cursor).ok_or_else(|| anyhow!(
+            "Cassette {} recorded this request {} time(s) but it was sent again; re-record the cassette",
+            self.path.display(), matches.len(),
+        ))?;
+        *cursor += 1;
+        Ok(interaction.reply.clone())
+    }
+
+    /// Error for a request missing from the cassette, with a diff against the closest recorded one
+    fn unmatched(&self, interactions: &[Interaction], request: &Value) -> anyhow::Error {
+        let actual = serde_json::to_string_pretty(request).unwrap_or_default();
+        let closest = interactions.iter()
+            .map(|i| serde_json::to_string_pretty(&i.request).unwrap_or_default())
+            .max_by(|a, b| {
+                let ratio = |recorded: &str| similar::TextDiff::from_lines(recorded, actual.as_str()).ratio();
+                ratio(a).total_cmp(&ratio(b))
+            });
+
+        match closest {
+            Some(recorded) => anyhow!(
+                "No recorded LLM response in cassette {} for this request; closest recorded request:\n{}",
+                self.path.display(),
+                similar::TextDiff::from_lines(&recorded, &actual)
+                    .unified_diff()
+                    .context_radius(3)
+                    .header("recorded", "actual")
+            ),
+            None => anyhow!("Cassette {} has no recorded interactions", self.path.display()),
+        }
+    }
+}
+
+/// Provider that records interactions of a wrapped provider, or replays them offline
+#[derive(Debug)]
+pub struct CassetteProvider {
+    cassette: Arc<Cassette>,
+    /// Wrapped provider; `None` when replaying
+    inner: Option<Arc<dyn LlmProvider>>,
+    scrubs: Vec<(Regex, String)>,
+}
+
+impl CassetteProvider {
+    /// Forward requests to `inner` and store every interaction in the cassette at `path`
+    ///
+    /// The first recorder of a run replaces the file; later ones in the same process append to it.
+    pub fn record(inner: Arc<dyn LlmProvider>, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            cassette: Cassette::open(path.as_ref(), CassetteMode::Record)?,
+            inner: Some(inner),
+            scrubs: Vec::new(),
+        })
+    }
+
+    /// Serve responses from the cassette at `path`, failing on requests it does not contain
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            cassette: Cassette::open(path.as_ref(), CassetteMode::Replay)?,
+            inner: None,
+            scrubs: Vec::new(),
+        })
+    }
+
+    /// Replace matches of `pattern` before keying requests (e.g. a test's temp dir)
+    pub fn scrub(mut self, pattern: &str, replacement: impl Into<String>) -> Result<Self> {
+        self.scrubs.push((Regex::new(pattern)?, replacement.into()));
+        Ok(self)
+    }
+
+    /// Normalized request and its cassette key; streamed and plain requests are keyed apart
+    fn key(&self, request: &CreateChatCompletionRequest, stream: bool) -> Result<(String, Value)> {
+        let mut value = serde_json::to_value(request)?;
+        if let Value::Object(map) = &mut value {
+            map.remove("stream");
+            map.remove("stream_options");
+        }
+        self.normalize(&mut value);
+
+        let mut hasher = Sha256::new();
+        hasher.update(if stream { b"stream:".as_slice() } else { b"chat:".as_slice() });
+        hasher.update(serde_json::to_string(&value)?.as_bytes());
+        let key = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        Ok((key, value))
+    }
+
+    fn normalize(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => {
+                let mut normalized = UUID.replace_all(s, "<uuid>").into_owned();
+                normalized = TIMESTAMP.replace_all(&normalized, "<timestamp>").into_owned();
+                for (pattern, replacement) in &self.scrubs {
+                    normalized = pattern.replace_all(&normalized, replacement.as_str()).into_owned();
+                }
+                *s = normalized;
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.normalize(item)),
+            Value::Object(map) => map.values_mut().for_each(|item| self.normalize(item)),
+            _ => {}
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for CassetteProvider {
+    fn name(&self) -> &str {
+        self.inner.as_ref().map(|inner| inner.name()).unwrap_or("cassette")
+    }
+
+    async fn chat(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse> {
+        let (key, normalized) = self.key(&request, false)?;
+        let Some(inner) = &self.inner else {
+            debug!("Replaying LLM response {} from cassette", key);
+            return match self.cassette.replay(&key, &normalized)? {
+                Reply::Response(response) => Ok(response),
+                Reply::Stream(_) => Err(anyhow!("Cassette entry {} was recorded as a stream", key)),
+            };
+        };
+
+        let response = inner.chat(request).await?;
+        self.cassette.record(Interaction { key, request: normalized, reply: Reply::Response(response.clone()) })?;
+        Ok(response)
+    }
+
+    async fn chat_stream(&self, request: CreateChatCompletionRequest) -> Result<ChatStream> {
+        let (key, normalized) = self.key(&request, true)?;
+        let chunks = match &self.inner {
+            None => {
+                debug!("Replaying LLM stream {} from cassette", key);
+                match self.cassette.replay(&key, &normalized)? {
+                    Reply::Stream(chunks) => chunks,
+                    Reply::Response(_) => return Err(anyhow!("Cassette entry {} was not recorded as a stream", key)),
+                }
+            }
+            Some(inner) => {
+                // The stream is drained before recording, so chunks arrive at once when recording
+                let chunks: Vec<_> = inner.chat_stream(request).await?.collect().await;
+                let chunks = chunks.into_iter().collect::<Result<Vec<_>>>()?;
+                self.cassette.record(Interaction { key, request: normalized, reply: Reply::Stream(chunks.clone()) })?;
+                chunks
+            }
+        };
+        Ok(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockProvider;
+
+    fn request(prompt: &str) -> CreateChatCompletionRequest {
+        async_openai::types::CreateChatCompletionRequestArgs::default()
+            .model("m")
+            .messages(vec![async_openai::types::ChatCompletionRequestUserMessage::from(prompt).into()])
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_recorded_interactions_replay_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        {
+            let mock = Arc::new(MockProvider::new()
+                .respond_text("first")
+                .respond_text("second")
+                .respond_tool_call("read_file", serde_json::json!({"path": "a.txt"})));
+            let recorder = CassetteProvider::record(mock, &path).unwrap();
+            recorder.chat(request("task 6f1c2a8e-0d7b-4c1e-9a55-3b2f0c9d8e71")).await.unwrap();
+            recorder.chat(request("task 6f1c2a8e-0d7b-4c1e-9a55-3b2f0c9d8e71")).await.unwrap();
+            recorder.chat(request("read a.txt")).await.unwrap();
+        }
+
+        let replayer = CassetteProvider::replay(&path).unwrap();
+        // Identical requests are served in recorded order; UUIDs do not affect matching
+        let first = replayer.chat(request("task 0a1b2c3d-0d7b-4c1e-9a55-3b2f0c9d8e71")).await.unwrap();
+        let second = replayer.chat(request("task 6f1c2a8e-0d7b-4c1e-9a55-3b2f0c9d8e71")).await.unwrap();
+        assert_eq!(first.choices[0].message.content.as_deref(), Some("first"));
+        assert_eq!(second.choices[0].message.content.as_deref(), Some("second"));
+
+        let tool_call = replayer.chat(request("read a.txt")).await.unwrap();
+        assert_eq!(tool_call.choices[0].message.tool_calls.as_ref().unwrap()[0].function.name, "read_file");
+
+        // Once the recorded responses are used up, the request no longer matches
+        let err = replayer.chat(request("read a.txt")).await.unwrap_err().to_string();
+        assert!(err.contains("recorded this request 1 time(s)"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_recorders_of_one_run_share_the_cassette() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        std::fs::write(&path, r#"{"interactions": []}"#).unwrap();
+
+        // Recorders opened one after the other, e.g. by consecutive tests, append
+        CassetteProvider::record(Arc::new(MockProvider::new().respond_text("one")), &path).unwrap()
+            .chat(request("first")).await.unwrap();
+        CassetteProvider::record(Arc::new(MockProvider::new().respond_text("two")), &path).unwrap()
+            .chat(request("second")).await.unwrap();
+
+        let replayer = CassetteProvider::replay(&path).unwrap();
+        assert_eq!(replayer.chat(request("first")).await.unwrap().choices[0].message.content.as_deref(), Some("one"));
+        assert_eq!(replayer.chat(request("second")).await.unwrap().choices[0].message.content.as_deref(), Some("two"));
+    }
+
+    #[tokio::test]
+    async fn test_replayers_of_one_cassette_keep_their_own_cursors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        CassetteProvider::record(Arc::new(MockProvider::new().respond_text("ok")), &path).unwrap()
+            .chat(request("task")).await.unwrap();
+
+        // Tests replaying the same fixture concurrently each start from the first response
+        let first = CassetteProvider::replay(&path).unwrap();
+        let second = CassetteProvider::replay(&path).unwrap();
+        assert_eq!(first.chat(request("task")).await.unwrap().choices[0].message.content.as_deref(), Some("ok"));
+        assert_eq!(second.chat(request("task")).await.unwrap().choices[0].message.content.as_deref(), Some("ok"));
+        assert!(first.chat(request("task")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_request_reports_closest_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        CassetteProvider::record(Arc::new(MockProvider::new().respond_text("ok")), &path).unwrap()
+            .chat(request("summarize the parser module")).await.unwrap();
+
+        let err = CassetteProvider::replay(&path).unwrap()
+            .chat(request("summarize the lexer module")).await.unwrap_err().to_string();
+        assert!(err.contains("No recorded LLM response"));
+        assert!(err.contains("-") && err.contains("parser") && err.contains("+") && err.contains("lexer"));
+    }
+
+    #[tokio::test]
+    async fn test_streams_replay_chunk_by_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        CassetteProvider::record(Arc::new(MockProvider::new().respond_text("one two")), &path).unwrap()
+            .chat_stream(request("count")).await.unwrap()
+            .collect::<Vec<_>>().await;
+
+        let replayer = CassetteProvider::replay(&path).unwrap();
+        let chunks: Vec<_> = replayer.chat_stream(request("count")).await.unwrap().collect().await;
+        assert_eq!(chunks.len(), 3);
+        assert!(replayer.chat(request("count")).await.is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Result};
+use async_openai::types::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use super::{ChatChunk, ChatStream, LlmProvider};
+
+/// A scripted reply of the [`MockProvider`]
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    /// Plain assistant text
+    Text(String),
+    /// Assistant text preceded by reasoning, which is only visible when streaming
+    Reasoning { reasoning: String, text: String },
+    /// Structured output, returned as JSON text
+    Json(Value),
+    /// Tool calls as (tool name, arguments)
+    ToolCalls(Vec<(String, Value)>),
+    /// The call fails with this message
+    Error(String),
+}
+
+/// In-process provider replying with scripted responses in order, for tests without a model server
+///
+/// Every request is recorded so tests can assert on the prompts that were sent.
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    responses: Mutex<VecDeque<MockResponse>>,
+    requests: Mutex<Vec<CreateChatCompletionRequest>>,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn respond_text(self, text: impl Into<String>) -> Self {
+        self.push(MockResponse::Text(text.into()));
+        self
+    }
+
+    pub fn respond_with_reasoning(self, reasoning: impl Into<String>, text: impl Into<String>) -> Self {
+        self.push(MockResponse::Reasoning { reasoning: reasoning.into(), text: text.into() });
+        self
+    }
+
+    pub fn respond_json(self, value: Value) -> Self {
+        self.push(MockResponse::Json(value));
+        self
+    }
+
+    pub fn respond_tool_call(self, tool_name: impl Into<String>, arguments: Value) -> Self {
+        self.push(MockResponse::ToolCalls(vec![(tool_name.into(), arguments)]));
+        self
+    }
+
+    pub fn respond_error(self, message: impl Into<String>) -> Self {
+        self.push(MockResponse::Error(message.into()));
+        self
+    }
+
+    /// Queue a response (usable while the provider is shared)
+    pub fn push(&self, response: MockResponse) {
+        self.responses.lock().unwrap_or_else(|e| e.into_inner()).push_back(response);
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<CreateChatCompletionRequest> {
+        self.requests.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Number of scripted responses not consumed yet
+    pub fn remaining(&self) -> usize {
+        self.responses.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Record the request and take the next scripted response
+    fn next(&self, request: CreateChatCompletionRequest) -> Result<(usize, String, MockResponse)> {
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        let model = request.model.clone();
+        requests.push(request);
+        let index = requests.len();
+        match self.responses.lock().unwrap_or_else(|e| e.into_inner()).pop_front() {
+            Some(MockResponse::Error(message)) => Err(anyhow!(message)),
+            Some(response) => Ok((index, model, response)),
+            None => Err(anyhow!("MockProvider has no scripted response for request #{}", index)),
+        }
+    }
+}
+
+/// Assistant message of a scripted response, in OpenAI wire format
+fn message(response: &MockResponse) -> (Value, &'static str) {
+    match response {
+        MockResponse::Text(text) | MockResponse::Reasoning { text, .. } => {
+            (json!({"role": "assistant", "content": text}), "stop")
+        }
+        MockResponse::Json(value) => (json!({"role": "assistant", "content": value.to_string()}), "stop"),
+        MockResponse::ToolCalls(calls) => {
+            let tool_calls: Vec<Value> = calls.iter().enumerate()
+                .map(|(i, (name, arguments))| json!({
+                    "id": format!("call_{}", i),
+                    "type": "function",
+                    "function": {"name": name, "arguments": arguments.to_string()},
+                }))
+                .collect();
+            (json!({"role": "assistant", "tool_calls": tool_calls}), "tool_calls")
+        }
+        MockResponse::Error(_) => unreachable!("errors are returned before building a message"),
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn chat(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse> {
+        let (index, model, response) = self.next(request)?;
+        let (message, finish_reason) = message(&response);
+        Ok(serde_json::from_value(json!({
+            "id": format!("mock-{}", index),
+            "object": "chat.completion",
+            "created": 0,
+            "model": model,
+            "choices": [{"index": 0, "message": message, "finish_reason": finish_reason}],
+        }))?)
+    }
+
+    /// Streams reasoning and text word by word; tool calls arrive in a single chunk
+    async fn chat_stream(&self, request: CreateChatCompletionRequest) -> Result<ChatStream> {
+        let (index, model, response) = self.next(request)?;
+        let (message, finish_reason) = message(&response);
+        let chunk = |delta: Value, finish_reason: Option<&str>| ChatChunk::from_value(json!({
+            "id": format!("mock-{}", index),
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        }));
+
+        let mut chunks = Vec::new();
+        if let MockResponse::Reasoning { reasoning, .. } = &response {
+            for piece in reasoning.split_inclusive(' ') {
+                chunks.push(chunk(json!({"role": "assistant", "reasoning": piece}), None));
+            }
+        }
+        match message.get("content").and_then(Value::as_str) {
+            Some(content) => {
+                for piece in content.split_inclusive(' ') {
+                    chunks.push(chunk(json!({"role": "assistant", "content": piece}), None));
+                }
+            }
+            None => {
+                let mut tool_calls = message["tool_calls"].clone();
+                for (i, call) in tool_calls.as_array_mut().into_iter().flatten().enumerate() {
+                    call["index"] = json!(i);
+                }
+                chunks.push(chunk(json!({"role": "assistant", "tool_calls": tool_calls}), None));
+            }
+        }
+        chunks.push(chunk(json!({}), Some(finish_reason)));
+        Ok(Box::pin(futures::stream::iter(chunks)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use std::sync::Arc;
+
+    #[derive(Debug, Deserialize, JsonSchema)]
+    struct Verdict {
+        approved: bool,
+    }
+
+    #[tokio::test]
+    async fn test_scripted_responses_are_served_in_order() {
+        let mock = Arc::new(MockProvider::new()
+            .respond_text("hello world")
+            .respond_json(json!({"approved": true}))
+            .respond_tool_call("read_file", json!({"path": "a.txt"})));
+        let provider: Arc<dyn LlmProvider> = mock.clone();
+
+        assert_eq!(provider.complete("m", Some("be brief"), "hi").await.unwrap(), "hello world");
+
+        let verdict: Verdict = provider.structured("m", vec![]).await.unwrap();
+        assert!(verdict.approved);
+
+        let request = async_openai::types::CreateChatCompletionRequestArgs::default()
+            .model("m").messages(vec![]).build().unwrap();
+        let response = provider.chat(request).await.unwrap();
+        let call = &response.choices[0].message.tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.function.name, "read_file");
+        assert_eq!(call.function.arguments, r#"{"path":"a.txt"}"#);
+
+        assert!(provider.complete("m", None, "again").await.unwrap_err().to_string().contains("request #4"));
+        assert_eq!(mock.requests().len(), 4);
+        assert!(mock.requests()[2].response_format.is_none());
+        assert!(mock.requests()[1].response_format.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_structured_output_is_repaired() {
+        let mock = Arc::new(MockProvider::new()
+            .respond_text("Here it is:\n```json\n{\"approved\": \"yes\"}\n```")
+            .respond_text("```json\n{\"approved\": true}\n```"));
+        let provider: Arc<dyn LlmProvider> = mock.clone();
+
+        let verdict: Verdict = provider.structured("m", vec![]).await.unwrap();
+        assert!(verdict.approved);
+        let repair = serde_json::to_string(&mock.requests()[1].messages).unwrap();
+        assert!(repair.contains("/approved: expected boolean, found string"), "{}", repair);
+
+        let failing = MockProvider::new().respond_text("no").respond_text("still no").respond_text("never");
+        let provider: Arc<dyn LlmProvider> = Arc::new(failing);
+        let error = provider.structured::<Verdict>("m", vec![]).await.unwrap_err();
+        assert!(error.to_string().contains("after 2 repair attempts"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_stream_yields_text_chunks() {
+        let provider: Arc<dyn LlmProvider> = Arc::new(MockProvider::new().respond_text("one two three"));
+        let request = async_openai::types::CreateChatCompletionRequestArgs::default()
+            .model("m").messages(vec![]).build().unwrap();
+
+        let chunks: Vec<_> = provider.chat_stream(request).await.unwrap().collect().await;
+        let text: String = chunks.iter()
+            .filter_map(|chunk| chunk.as_ref().unwrap().response.choices[0].delta.content.clone())
+            .collect();
+        assert_eq!(text, "one two three");
+        assert_eq!(chunks.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_stream_carries_reasoning() {
+        let provider: Arc<dyn LlmProvider> = Arc::new(MockProvider::new().respond_with_reasoning("let me see", "done"));
+        let request = async_openai::types::CreateChatCompletionRequestArgs::default()
+            .model("m").messages(vec![]).build().unwrap();
+
+        let chunks: Vec<_> = provider.chat_stream(request).await.unwrap().collect().await;
+        let reasoning: String = chunks.iter()
+            .filter_map(|chunk| chunk.as_ref().unwrap().reasoning.clone())
+            .collect();
+        assert_eq!(reasoning, "let me see");
+        assert!(chunks.iter().all(|chunk| chunk.as_ref().unwrap().reasoning.is_none()
+            || chunk.as_ref().unwrap().response.choices[0].delta.content.is_none()));
+    }
+}
@@ -0,0 +1,148 @@
+//! LLM access shared by agents, RAG routing/enhancement and history summarization
+//!
+//! Every chat completion goes through an [`LlmProvider`]. Requests and responses use the
+//! OpenAI chat schema (messages, tool calls, JSON-schema response formats), which Ollama
+//! and most hosted models speak.
+
+mod cassette;
+mod embedding;
+mod mock;
+mod openai;
+pub mod structured;
+
+pub use cassette::CassetteProvider;
+pub use embedding::EmbeddingClient;
+pub use mock::{MockProvider, MockResponse};
+pub use openai::OpenAiProvider;
+
+use anyhow::{anyhow, Result};
+use async_openai::types::{
+    ChatCompletionRequestAssistantMessage, ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessage,
+    CreateChatCompletionRequest, CreateChatCompletionRequestArgs, CreateChatCompletionResponse,
+    CreateChatCompletionStreamResponse, ResponseFormat, ResponseFormatJsonSchema,
+};
+use async_trait::async_trait;
+use futures::Stream;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::config::{CassetteMode, LlmConfig};
+
+/// A chunk of a streamed chat completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatChunk {
+    pub response: CreateChatCompletionStreamResponse,
+    /// Reasoning ("thinking") text, which servers send outside the OpenAI delta schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+}
+
+impl ChatChunk {
+    /// Parse a raw chunk, picking up `reasoning` (Ollama) or `reasoning_content` (vLLM, DeepSeek) deltas
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let delta = &value["choices"][0]["delta"];
+        let reasoning = delta.get("reasoning").or_else(|| delta.get("reasoning_content"))
+            .and_then(|r| r.as_str())
+            .filter(|r| !r.is_empty())
+            .map(str::to_string);
+        Ok(Self { response: serde_json::from_value(value)?, reasoning })
+    }
+}
+
+/// Chunks of a streamed chat completion
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<ChatChunk>> + Send>>;
+
+/// A chat completion backend
+#[async_trait]
+pub trait LlmProvider: Send + Sync + std::fmt::Debug {
+    /// Provider name for tracing (e.g. "ollama")
+    fn name(&self) -> &str;
+
+    /// Run a chat completion, including tool calls and structured output requested in `request`
+    async fn chat(&self, request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse>;
+
+    /// Run a chat completion, yielding the response in chunks as it is generated
+    async fn chat_stream(&self, request: CreateChatCompletionRequest) -> Result<ChatStream>;
+}
+
+impl dyn LlmProvider {
+    /// Single-turn text completion
+    pub async fn complete(&self, model: &str, system: Option<&str>, prompt: &str) -> Result<String> {
+        let mut messages: Vec<ChatCompletionRequestMessage> = Vec::new();
+        if let Some(system) = system {
+            messages.push(ChatCompletionRequestSystemMessage::from(system).into());
+        }
+        messages.push(ChatCompletionRequestUserMessage::from(prompt).into());
+
+        let request = CreateChatCompletionRequestArgs::default()
+            .model(model)
+            .messages(messages)
+            .build()?;
+        self.content(self.chat(request).await?)
+    }
+
+    /// Chat completion constrained to the JSON schema of `T` and parsed into it
+    ///
+    /// Output that does not match the schema is sent back with the validation errors,
+    /// up to [`structured::DEFAULT_REPAIR_ATTEMPTS`] times.
+    pub async fn structured<T: JsonSchema + DeserializeOwned>(
+        &self,
+        model: &str,
+        mut messages: Vec<ChatCompletionRequestMessage>,
+    ) -> Result<T> {
+        let schema = serde_json::to_value(schemars::schema_for!(T))?;
+        let mut attempt = 0;
+        loop {
+            let request = CreateChatCompletionRequestArgs::default()
+                .model(model)
+                .messages(messages.clone())
+                .response_format(ResponseFormat::JsonSchema {
+                    json_schema: ResponseFormatJsonSchema {
+                        name: "structured_response".to_string(),
+                        description: None,
+                        schema: Some(schema.clone()),
+                        strict: Some(true),
+                    },
+                })
+                .build()?;
+            let content = self.content(self.chat(request).await?)?;
+            let errors = match structured::parse_structured(&content, &schema) {
+                Ok(value) => match serde_json::from_value(value) {
+                    Ok(parsed) => return Ok(parsed),
+                    Err(e) => vec![e.to_string()],
+                },
+                Err(errors) => errors,
+            };
+
+            if attempt >= structured::DEFAULT_REPAIR_ATTEMPTS {
+                return Err(anyhow!("{} returned output not matching the schema after {} repair attempts: {} ({})",
+                    self.name(), attempt, errors.join("; "), content));
+            }
+            attempt += 1;
+            tracing::warn!(provider = self.name(), model, attempt, errors = ?errors, "Structured output invalid, asking for a repair");
+            messages.push(ChatCompletionRequestAssistantMessage::from(content).into());
+            messages.push(ChatCompletionRequestUserMessage::from(structured::repair_prompt(&errors)).into());
+        }
+    }
+
+    fn content(&self, response: CreateChatCompletionResponse) -> Result<String> {
+        response.choices.into_iter().next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| anyhow!("{} returned no content", self.name()))
+    }
+}
+
+/// Build the provider for resolved LLM settings (see `SystemConfig::llm_config`)
+pub fn provider_from_config(config: &LlmConfig) -> Result<Arc<dyn LlmProvider>> {
+    let provider: Arc<dyn LlmProvider> = Arc::new(OpenAiProvider::new(config));
+    Ok(match &config.cassette {
+        None => provider,
+        Some(cassette) => match cassette.mode {
+            CassetteMode::Record => Arc::new(CassetteProvider::record(provider, &cassette.path)?),
+            CassetteMode::Replay => Arc::new(CassetteProvider::replay(&cassette.path)?),
+        },
+    })
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use async_openai::{
+    config::OpenAIConfig,
+    types::{CreateChatCompletionRequest, CreateChatCompletionResponse},
+    Client,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::Value;
+
+use super::{ChatChunk, ChatStream, LlmProvider};
+use crate::config::{LlmConfig, LlmProviderKind};
+
+/// Provider for OpenAI-compatible chat APIs (OpenAI, Ollama's `/v1`, vLLM, ...)
+#[derive(Debug, Clone)]
+pub struct OpenAiProvider {
+    name: &'static str,
+    client: Client<OpenAIConfig>,
+    logprobs: bool,
+}
+
+impl OpenAiProvider {
+    pub fn new(config: &LlmConfig) -> Self {
+        let name = match config.provider {
+            LlmProviderKind::Ollama => "ollama",
+            LlmProviderKind::OpenAi => "openai",
+        };
+        let base_url = config.base_url.as_deref().unwrap_or("http://localhost:11434/v1");
+        // Ollama requires a key but ignores it; a missing OpenAi key is rejected by config validation
+        let placeholder = (config.provider == LlmProviderKind::Ollama).then_some("ollama");
+        let client_config = OpenAIConfig::new()
+            .with_api_key(config.api_key.as_deref().or(placeholder).unwrap_or_default())
+            .with_api_base(base_url);
+        Self { name, client: Client::with_config(client_config), logprobs: config.logprobs }
+    }
+
+    /// Ask for token log-probabilities unless the caller already decided
+    fn request_logprobs(&self, request: &mut CreateChatCompletionRequest) {
+        if self.logprobs && request.logprobs.is_none() {
+            request.logprobs = Some(true);
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn chat(&self, mut request: CreateChatCompletionRequest) -> Result<CreateChatCompletionResponse> {
+        self.request_logprobs(&mut request);
+        Ok(self.client.chat().create(request).await?)
+    }
+
+    async fn chat_stream(&self, mut request: CreateChatCompletionRequest) -> Result<ChatStream> {
+        // Chunks are parsed from raw JSON to keep the reasoning fields the typed delta drops
+        request.stream = Some(true);
+        self.request_logprobs(&mut request);
+        let stream = self.client.chat().create_stream_byot::<_, Value>(request).await?;
+        Ok(Box::pin(stream.map(|chunk| ChatChunk::from_value(chunk?))))
+    }
+}
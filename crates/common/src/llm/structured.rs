@@ -0,0 +1,334 @@
+//! Extraction and validation of structured (JSON-schema) model output
+//!
+//! Local models asked for a JSON-schema response still wrap it in code fences, add prose
+//! around it or produce objects that violate the schema. [`parse_structured`] locates the
+//! JSON in a response and checks it against the schemars-generated schema; its errors are
+//! meant to be sent back to the model (see [`repair_prompt`]) for another attempt.
+//!
+//! The validator covers the keywords schemars emits: `$ref`, `type`, `enum`, `const`,
+//! `properties`, `required`, `additionalProperties`, `items`, `anyOf`/`oneOf`/`allOf`,
+//! numeric bounds and array lengths.
+
+use serde_json::Value;
+
+/// Repair re-prompts before a structured output is given up on
+pub const DEFAULT_REPAIR_ATTEMPTS: usize = 2;
+
+/// Locate the JSON value in a model response: the whole text, a fenced block, or the
+/// first complete object or array embedded in prose
+pub fn extract_json(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+    if let Some(value) = fenced_blocks(text).find_map(|block| serde_json::from_str(block.trim()).ok()) {
+        return Some(value);
+    }
+    text.char_indices()
+        .filter(|(_, c)| *c == '{' || *c == '[')
+        .find_map(|(start, _)| balanced(&text[start..]).and_then(|candidate| serde_json::from_str(candidate).ok()))
+}
+
+/// Contents of the ``` fenced blocks of a text, without their language tags
+fn fenced_blocks(text: &str) -> impl Iterator<Item = &str> {
+    text.split("```").skip(1).step_by(2).map(|block| {
+        // Drop the info string ("json") on the opening line
+        match block.split_once('\n') {
+            Some((info, body)) if !info.trim_start().starts_with(['{', '[']) => body,
+            _ => block,
+        }
+    })
+}
+
+/// The prefix of `text` up to the bracket closing its first one, skipping brackets in strings
+fn balanced(text: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(&text[..=i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Check `value` against a JSON schema, returning every violation found
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, schema, "", &mut errors);
+    errors
+}
+
+/// Extract the JSON from a model response and validate it against `schema`
+pub fn parse_structured(text: &str, schema: &Value) -> Result<Value, Vec<String>> {
+    let value = extract_json(text).ok_or_else(|| vec!["the response contains no JSON value".to_string()])?;
+    let errors = validate(&value, schema);
+    if errors.is_empty() { Ok(value) } else { Err(errors) }
+}
+
+/// Follow-up message asking the model to fix its structured output
+pub fn repair_prompt(errors: &[String]) -> String {
+    let mut prompt = "Your response does not match the required JSON schema:\n".to_string();
+    for error in errors {
+        prompt.push_str(&format!("- {}\n", error));
+    }
+    prompt.push_str("Reply with only the corrected JSON value, without code fences or any other text.");
+    prompt
+}
+
+fn validate_at(value: &Value, schema: &Value, root: &Value, path: &str, errors: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return errors.push(format!("{}: no value is allowed here", location(path))),
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match resolve(root, reference) {
+            Some(target) => validate_at(value, target, root, path, errors),
+            None => errors.push(format!("{}: unresolvable schema reference {}", location(path), reference)),
+        }
+    }
+
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| has_type(value, name)) {
+            // A wrong type makes the remaining keywords meaningless
+            return errors.push(format!("{}: expected {}, found {}", location(path), allowed.join(" or "), type_name(value)));
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            errors.push(format!("{}: {} is not one of {}", location(path), value, options.join(", ")));
+        }
+    }
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            errors.push(format!("{}: expected {}, found {}", location(path), constant, value));
+        }
+    }
+
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(variants) = schema.get(keyword).and_then(Value::as_array) {
+            let matches = variants.iter().filter(|variant| validate(value, &with_root(variant, root)).is_empty()).count();
+            if matches == 0 {
+                errors.push(format!("{}: {} matches none of the allowed variants", location(path), value));
+            } else if matches > 1 && keyword == "oneOf" {
+                errors.push(format!("{}: {} matches {} variants, but exactly one is allowed", location(path), value, matches));
+            }
+        }
+    }
+    if let Some(parts) = schema.get("allOf").and_then(Value::as_array) {
+        for part in parts {
+            validate_at(value, part, root, path, errors);
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                errors.push(format!("{}: {} is less than the minimum {}", location(path), value, minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                errors.push(format!("{}: {} is greater than the maximum {}", location(path), value, maximum));
+            }
+        }
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for name in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                errors.push(format!("{}: missing required property `{}`", location(path), name));
+            }
+        }
+        for (name, field) in object {
+            let field_path = format!("{}/{}", path, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => validate_at(field, property, root, &field_path, errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => errors.push(format!("{}: unexpected property `{}`", location(path), name)),
+                    Some(additional) => validate_at(field, additional, root, &field_path, errors),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    if let Value::Array(items) = value {
+        if let Some(min_items) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min_items {
+                errors.push(format!("{}: expected at least {} items, found {}", location(path), min_items, items.len()));
+            }
+        }
+        if let Some(max_items) = schema.get("maxItems").and_then(Value::as_u64) {
+            if items.len() as u64 > max_items {
+                errors.push(format!("{}: expected at most {} items, found {}", location(path), max_items, items.len()));
+            }
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (index, item) in items.iter().enumerate() {
+                validate_at(item, item_schema, root, &format!("{}/{}", path, index), errors);
+            }
+        }
+    }
+}
+
+/// A sub-schema that can be validated on its own, keeping the root's definitions for `$ref`
+fn with_root(schema: &Value, root: &Value) -> Value {
+    let mut schema = schema.clone();
+    if let (Value::Object(schema), Value::Object(root)) = (&mut schema, root) {
+        for key in ["$defs", "definitions"] {
+            if let (Some(definitions), false) = (root.get(key), schema.contains_key(key)) {
+                schema.insert(key.to_string(), definitions.clone());
+            }
+        }
+    }
+    schema
+}
+
+/// Look up a local `#/...` reference
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() { Some(root) } else { root.pointer(pointer) }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn location(path: &str) -> &str {
+    if path.is_empty() { "/" } else { path }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    enum Severity {
+        Low,
+        High,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    struct Finding {
+        file: String,
+        line: u32,
+        severity: Severity,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    struct Report {
+        summary: String,
+        findings: Vec<Finding>,
+        #[serde(default)]
+        score: Option<f32>,
+    }
+
+    fn schema() -> Value {
+        serde_json::to_value(schemars::schema_for!(Report)).unwrap()
+    }
+
+    #[test]
+    fn test_json_is_extracted_from_fences_and_prose() {
+        assert_eq!(extract_json(" {\"a\": 1} "), Some(json!({"a": 1})));
+        assert_eq!(extract_json("```json\n{\"a\": 1}\n```"), Some(json!({"a": 1})));
+        assert_eq!(extract_json("Here you go:\n```\n[1, 2]\n```\nDone."), Some(json!([1, 2])));
+        assert_eq!(
+            extract_json("Sure! {\"text\": \"braces } in { strings\", \"n\": [1]} Hope that helps {"),
+            Some(json!({"text": "braces } in { strings", "n": [1]})),
+        );
+        assert_eq!(extract_json("no json here"), None);
+    }
+
+    #[test]
+    fn test_schema_violations_are_reported() {
+        let valid = json!({"summary": "ok", "findings": [{"file": "a.rs", "line": 3, "severity": "High"}]});
+        assert!(validate(&valid, &schema()).is_empty());
+        assert!(validate(&json!({"summary": "ok", "findings": [], "score": null}), &schema()).is_empty());
+
+        let invalid = json!({"findings": [{"file": "a.rs", "line": -1, "severity": "Urgent"}], "score": "high"});
+        let errors = validate(&invalid, &schema());
+        assert!(errors.contains(&"/: missing required property `summary`".to_string()), "{:?}", errors);
+        assert!(errors.iter().any(|e| e.starts_with("/findings/0/line: -1 is less than the minimum")), "{:?}", errors);
+        assert!(errors.iter().any(|e| e.starts_with("/findings/0/severity:")), "{:?}", errors);
+        assert!(errors.iter().any(|e| e.starts_with("/score:")), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_one_of_requires_exactly_one_variant() {
+        let schema = json!({"oneOf": [{"type": "integer"}, {"type": "number", "minimum": 10}]});
+        assert!(validate(&json!(3), &schema).is_empty());
+        assert!(validate(&json!(10.5), &schema).is_empty());
+        assert_eq!(validate(&json!(12), &schema), vec!["/: 12 matches 2 variants, but exactly one is allowed".to_string()]);
+        assert_eq!(validate(&json!("x"), &schema), vec!["/: \"x\" matches none of the allowed variants".to_string()]);
+
+        let any_of = json!({"anyOf": [{"type": "integer"}, {"type": "number", "minimum": 10}]});
+        assert!(validate(&json!(12), &any_of).is_empty());
+    }
+
+    #[test]
+    fn test_parse_structured_and_repair_prompt() {
+        let text = "```json\n{\"summary\": \"ok\", \"findings\": []}\n```";
+        assert_eq!(parse_structured(text, &schema()).unwrap(), json!({"summary": "ok", "findings": []}));
+
+        let errors = parse_structured("{\"findings\": []}", &schema()).unwrap_err();
+        assert_eq!(errors, vec!["/: missing required property `summary`".to_string()]);
+        assert!(repair_prompt(&errors).contains("- /: missing required property `summary`\n"));
+        assert_eq!(parse_structured("sorry", &schema()).unwrap_err(), vec!["the response contains no JSON value".to_string()]);
+    }
+}
@@ -6,7 +6,7 @@ use opentelemetry_sdk::{Resource, runtime};
 use opentelemetry_semantic_conventions as semcov;
 use opentelemetry::{global, trace::{Span, Tracer}};
 use tracing_opentelemetry::OpenTelemetryLayer;
-use tracing_subscriber::{Registry, EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{Registry, EnvFilter, fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt};
 use anyhow::Result;
 use tracing::info;
 use opentelemetry_sdk::trace::SdkTracerProvider;
@@ -17,6 +17,15 @@ use std::sync::Mutex;
 static TRACER_PROVIDER: Lazy<Mutex<Option<SdkTracerProvider>>> = Lazy::new(|| Mutex::new(None));
 
 pub fn init_tracing_with_level(level: &str) -> Result<()> {
+    init_tracing(level, BoxMakeWriter::new(std::io::stdout))
+}
+
+/// Same as `init_tracing_with_level`, but logs to stderr (stdout carries the protocol in MCP stdio mode)
+pub fn init_tracing_to_stderr(level: &str) -> Result<()> {
+    init_tracing(level, BoxMakeWriter::new(std::io::stderr))
+}
+
+fn init_tracing(level: &str, writer: BoxMakeWriter) -> Result<()> {
     // Create OTLP HTTP exporter builder
     let exporter = HttpExporterBuilder::default()
         .with_endpoint("http://localhost:4318/v1/traces")
@@ -55,7 +64,8 @@ pub fn init_tracing_with_level(level: &str) -> Result<()> {
     let fmt_layer = tracing_subscriber::fmt::layer()
         .with_target(true)
         .with_thread_ids(false)
-        .with_line_number(false);
+        .with_line_number(false)
+        .with_writer(writer);
 
     // Register composed subscriber with layers
     Registry::default()
@@ -241,6 +241,87 @@ pub struct TaskInfo {
     pub steps: Vec<String>, // Known steps like "Code Implementation"
 }
 
+/// Tokens consumed by LLM calls, as reported by the provider or estimated with a tokenizer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenUsage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    /// At least part of the count was estimated because the provider reported no usage
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+impl TokenUsage {
+    pub fn new(prompt_tokens: usize, completion_tokens: usize) -> Self {
+        Self { prompt_tokens, completion_tokens, estimated: false }
+    }
+
+    pub fn total(&self) -> usize {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl std::ops::Add for TokenUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            estimated: self.estimated || other.estimated,
+        }
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl std::iter::Sum for TokenUsage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |total, usage| total + usage)
+    }
+}
+
+/// Kind of a prompt section, deciding how it is ranked when the context is pruned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ContextSectionKind {
+    Task,
+    Instructions,
+    RagFragment,
+    History,
+    DependencyOutput,
+    StepOutput,
+    ToolResult,
+}
+
+/// How a pruned section was shortened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PruneAction {
+    /// Condensed to an excerpt
+    Summarized,
+    /// Replaced by a placeholder
+    Elided,
+}
+
+/// A section shortened to fit the context into its token budget
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PrunedSection {
+    pub label: String,
+    pub kind: ContextSectionKind,
+    pub action: PruneAction,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+}
+
 /// Types of events that can occur during execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
@@ -253,7 +334,10 @@ pub enum EventType {
 
     /// Execution completed successfully
     ExecutionCompleted {
-        result: String
+        result: String,
+        /// Tokens consumed by all LLM calls of the execution
+        #[serde(default)]
+        token_usage: TokenUsage,
     },
 
     /// Execution failed with an error
@@ -261,6 +345,12 @@ pub enum EventType {
         error: String
     },
 
+    /// A shadow (dry-run) execution finished; its changes await apply or discard
+    ShadowChangesReady {
+        files: Vec<String>,
+        diff: String,
+    },
+
     /// An agent has started working
     AgentStarted {
         context_size: usize
@@ -273,7 +363,26 @@ pub enum EventType {
 
     /// Agent has completed its task
     AgentCompleted {
-        result: String
+        result: String,
+        /// Tokens consumed by the agent's workflow steps
+        #[serde(default)]
+        token_usage: TokenUsage,
+    },
+
+    /// Context was pruned to fit its token budget
+    ContextPruned {
+        budget: usize,
+        tokens_before: usize,
+        tokens_after: usize,
+        sections: Vec<PrunedSection>,
+    },
+
+    /// A soft token budget was exceeded; the execution continues
+    TokenBudgetWarning {
+        /// Budget that was exceeded, e.g. `execution` or `agent:coding-1`
+        scope: String,
+        used: usize,
+        limit: usize,
     },
 
     /// Agent failed to complete its task
@@ -368,6 +477,42 @@ pub enum EventType {
         wave_index: usize,
         success: bool,
     },
+
+    /// The evaluator scored a task's output
+    TaskEvaluated {
+        task_id: String,
+        evaluator_id: String,
+        wave_index: usize,
+        /// 0 for the original output, then one per revision
+        round: usize,
+        score: f32,
+        min_score: f32,
+        passed: bool,
+        summary: String,
+        #[serde(default)]
+        issues: Vec<String>,
+    },
+
+    /// A task is re-attempted with the evaluator's critique
+    TaskRevisionStarted {
+        task_id: String,
+        wave_index: usize,
+        round: usize,
+        max_rounds: usize,
+        /// Score of the output being revised
+        score: f32,
+    },
+
+    /// An agent handed a sub-task to another agent; it runs before the delegating task continues
+    TaskDelegated {
+        parent_task_id: String,
+        task_id: String,
+        agent_id: String,
+        wave_index: usize,
+        /// 1 for sub-tasks of planned tasks, 2 for theirs, ...
+        depth: usize,
+        description: String,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter, Display)]
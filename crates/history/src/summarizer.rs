@@ -1,19 +1,27 @@
 use ai_agent_common::*;
-use rig::{agent::Agent, http_client::HttpClientExt};
+use ai_agent_common::llm::LlmProvider;
 use anyhow::Result;
-use rig::providers::ollama::{CompletionModel};
+use std::sync::Arc;
 
-pub type OllamaModel = rig::providers::openai::CompletionModel;
+const SUMMARY_PROMPT: &str = "Summarize the following conversation excerpt in a few sentences. \
+Keep decisions, open questions, file names and identifiers. Return ONLY the summary.";
 
-pub struct ProgressiveSummarizer{
-    agent: Agent<OllamaModel>,
+pub struct ProgressiveSummarizer {
+    provider: Arc<dyn LlmProvider>,
+    model: String,
     summarization_interval: usize,
 }
 
 impl ProgressiveSummarizer {
-    pub fn new(model: &str, interval: usize) -> Result<Self> {
-
-        todo!("Initialize summarization agent")
+    pub fn new(provider: Arc<dyn LlmProvider>, model: &str, interval: usize) -> Result<Self> {
+        if interval == 0 {
+            anyhow::bail!("Summarization interval must be greater than 0");
+        }
+        Ok(Self {
+            provider,
+            model: model.to_string(),
+            summarization_interval: interval,
+        })
     }
 
     pub async fn should_summarize(&self, message_count: usize) -> bool {
@@ -21,6 +29,11 @@ impl ProgressiveSummarizer {
     }
 
     pub async fn summarize_chunk(&self, messages: &[Message]) -> Result<String> {
-        todo!("Generate summary with LLM")
+        let transcript = messages.iter()
+            .map(|m| format!("{:?}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let summary = self.provider.complete(&self.model, Some(SUMMARY_PROMPT), &transcript).await?;
+        Ok(summary.trim().to_string())
     }
 }
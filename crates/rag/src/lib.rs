@@ -65,7 +65,6 @@ impl SmartMultiSourceRag {
         Ok(enhanced_queries)
     }
 
-    #[instrument(name = "rag_streaming_retrieval", skip(self), fields(raw_query, project_scope, conversation_id))]
     /// Runs the multi-stage priority batched streaming retrieval pipeline
     pub async fn retrieve_stream(
         self:Arc<Self>,
@@ -73,13 +72,33 @@ impl SmartMultiSourceRag {
         project_scope: ProjectScope,
         conversation_id: ConversationId,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<ContextFragment>> + Send + 'static>>> {
+        self.retrieve_stream_for_tiers(raw_query, project_scope, conversation_id, None).await
+    }
+
+    #[instrument(name = "rag_streaming_retrieval", skip(self), fields(raw_query, project_scope, conversation_id, tiers))]
+    /// Like `retrieve_stream`, but restricted to the given tiers (all routed tiers when `None`).
+    /// Requested tiers the router did not select are queried with the raw query.
+    pub async fn retrieve_stream_for_tiers(
+        self:Arc<Self>,
+        raw_query: String,
+        project_scope: ProjectScope,
+        conversation_id: ConversationId,
+        tiers: Option<Vec<CollectionTier>>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ContextFragment>> + Send + 'static>>> {
 
         let rag = Arc::clone(&self);
 
         // Step 1: Route query to sources
-        let source_queries = rag.source_router.route_query(&raw_query, &project_scope)
+        let mut source_queries = rag.source_router.route_query(&raw_query, &project_scope)
             .await.context("Source routing failed")?;
 
+        if let Some(tiers) = tiers.filter(|tiers| !tiers.is_empty()) {
+            source_queries.retain(|tier, _| tiers.contains(tier));
+            for tier in tiers {
+                source_queries.entry(tier).or_insert_with(|| raw_query.clone());
+            }
+        }
+
         debug!("Generated source specific queries: {:?}", source_queries);
 
         // Enhance per tier (parallel, context-aware)
@@ -3,8 +3,8 @@ use ai_agent_common::SystemConfig;
 use anyhow::{Context, Result};
 use ai_agent_common::{CollectionTier, ConversationId, ProjectScope};
 use moka::future::Cache;
-use ollama_rs::generation::completion::request::GenerationRequest;
-use ollama_rs::Ollama;
+use ai_agent_common::llm::{self, LlmProvider};
+use std::sync::Arc;
 use tokenizers::pre_tokenizers::whitespace::Whitespace;
 use tokenizers::processors::template::TemplateProcessing;
 use tokenizers::{Tokenizer, models::wordpiece::WordPiece, normalizers::BertNormalizer};
@@ -13,7 +13,7 @@ use tracing::{debug, instrument, warn};
 
 #[derive(Debug)]
 pub struct QueryEnhancer {
-    ollama_client: Ollama,
+    provider: Arc<dyn LlmProvider>,
     mem_cache: Cache<String, Vec<String>>,
     redis_client: RedisCache,
     redis_cache_prefix: String,
@@ -55,7 +55,7 @@ impl QueryEnhancer {
             .ok_or_else(|| anyhow::anyhow!("Redis URL not configured"))?;
 
         Ok(Self {
-            ollama_client: Ollama::new(&config.embedding.ollama_host, config.embedding.ollama_port),
+            provider: llm::provider_from_config(&config.llm_config(None))?,
             mem_cache: Cache::new(10000),
             redis_client: RedisCache::new(redis_url).await?,
             redis_cache_prefix: "query_enhancer_cache:".to_string(),
@@ -65,6 +65,12 @@ impl QueryEnhancer {
         })
     }
 
+    /// Enhance queries with a different LLM provider
+    pub fn with_provider(mut self, provider: Arc<dyn LlmProvider>) -> Self {
+        self.provider = provider;
+        self
+    }
+
 
     #[instrument(name = "query_heuristic_expansion", skip(self), fields(raw_query))]
     /// Apply simple heuristics: synonym expansions, normalization, token filtering
@@ -257,13 +263,11 @@ impl QueryEnhancer {
         };
 
         // Query LLM with timeout protection
-        let request = GenerationRequest::new(self.model.clone(),&prompt);
         match tokio::time::timeout(
             std::time::Duration::from_secs(30),
-            self.ollama_client.generate(request)
+            self.provider.complete(&self.model, None, &prompt)
         ).await {
-            Ok(Ok(enhanced)) => {
-                let response = enhanced.response;
+            Ok(Ok(response)) => {
                 debug!("LLM enhanced query: {}",response);
                 results.push(response.clone());
 
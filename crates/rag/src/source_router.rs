@@ -1,26 +1,34 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use ai_agent_common::llm::{self, LlmProvider};
 use ai_agent_common::{CollectionTier, ProjectScope, SystemConfig};
-use ollama_rs::{generation::{chat::{request::ChatMessageRequest, ChatMessage, MessageRole}, parameters::{FormatType, JsonStructure}}, Ollama};
+use async_openai::types::{ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessage};
 use strum::IntoEnumIterator;
 use tracing::{debug, info, instrument}; // You must bring the trait into scope
 
 #[derive(Debug)]
 /// SourceRouter with hybrid intent detection: keywords + fallback LLM classification
 pub struct SourceRouter {
-    ollama: Ollama,
+    provider: Arc<dyn LlmProvider>,
     classification_model: String,
 }
 
 impl SourceRouter {
-    /// Create new SourceRouter with Ollama client endpoint URL
+    /// Create new SourceRouter using the configured LLM provider
     pub fn new(config: &SystemConfig) -> anyhow::Result<Self> {
         Ok(Self {
-            ollama: Ollama::new(&config.embedding.ollama_host, config.embedding.ollama_port),
+            provider: llm::provider_from_config(&config.llm_config(None))?,
             classification_model: config.rag.classification_model.clone()
         })
     }
 
+    /// Classify intents with a different LLM provider
+    pub fn with_provider(mut self, provider: Arc<dyn LlmProvider>) -> Self {
+        self.provider = provider;
+        self
+    }
+
     /// Fast heuristic keyword intent detection for web content
     fn detect_web_intent(&self, query: &str) -> bool {
         let web_keywords = [
@@ -37,12 +45,9 @@ impl SourceRouter {
     /// Fast heuristic keyword intent detection
 
     #[instrument(name = "intent_classification_llm", skip(self), fields(query))]
-    /// Fallback async Ollama LLM call for intent classification,
+    /// Fallback async LLM call for intent classification,
     /// returns vector of CollectionTiers or empty vec for unknown
     pub async fn classify_intent_llm(&self, query: &str) -> anyhow::Result<Vec<CollectionTier>> {
-        // Generate JSON schema structure from Rust type
-        let json_structure = JsonStructure::new::<Vec<CollectionTier>>();
-
         let categories: Vec<String> = CollectionTier::iter()
             .map(|tier| tier.to_string())
             .collect();
@@ -61,32 +66,17 @@ impl SourceRouter {
         );
 
         let messages = vec![
-            ChatMessage {
-                role: MessageRole::System,
-                content: system_prompt,
-                tool_calls: vec![],
-                images: None,
-                thinking: None,
-            },
-            ChatMessage {
-                role: MessageRole::User,
-                content: user_prompt,
-                tool_calls: vec![],
-                images: None,
-                thinking: None,
-            },
+            ChatCompletionRequestSystemMessage::from(system_prompt).into(),
+            ChatCompletionRequestUserMessage::from(user_prompt).into(),
         ];
 
         debug!("Querying intent classification model [{}]: {:?}", &self.classification_model, &messages);
-        let request = ChatMessageRequest::new(self.classification_model.clone(), messages)
-            .format(FormatType::StructuredJson(Box::new(json_structure)));
-
-        let response = self.ollama.send_chat_messages(request).await?.message.content;
-
-        debug!("Classification Result: {}", response);
+        // The response is constrained to, and parsed as, the JSON schema of the output type
+        let classification_output: Vec<CollectionTier> = self.provider
+            .structured(&self.classification_model, messages)
+            .await?;
 
-        // Deserialize response into your output type
-        let classification_output: Vec<CollectionTier> = serde_json::from_str(&response)?;
+        debug!("Classification Result: {:?}", classification_output);
 
         Ok(classification_output)
     }
@@ -129,3 +119,20 @@ impl SourceRouter {
         Ok(res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_agent_common::llm::MockProvider;
+
+    #[tokio::test]
+    async fn test_llm_classification_is_merged_with_heuristics() {
+        let mock = Arc::new(MockProvider::new().respond_json(serde_json::json!(["Workspace"])));
+        let router = SourceRouter::new(&SystemConfig::default()).unwrap().with_provider(mock.clone());
+
+        let tiers = router.route_query("find the github docs for the parser", &ProjectScope::new(".".to_string(), None, HashMap::new())).await.unwrap();
+        assert!(tiers.contains_key(&CollectionTier::Online));
+        assert!(tiers.contains_key(&CollectionTier::Workspace));
+        assert!(mock.requests()[0].response_format.is_some());
+    }
+}
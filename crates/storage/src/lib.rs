@@ -5,7 +5,7 @@ pub mod redis;
 use std::sync::Arc;
 
 use ai_agent_common::llm::EmbeddingClient;
-pub use postgres::PostgresClient;
+pub use postgres::{FileJournalRecord, PostgresClient};
 pub use qdrant::QdrantClient;
 pub use redis::RedisCache;
 
@@ -1,12 +1,35 @@
 use anyhow::Result;
 use sqlx::{PgPool, postgres::PgPoolOptions, Row};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Mutex;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+/// Clients handed out by `PostgresClient::shared`, one per database URL
+static SHARED_CLIENTS: OnceLock<Mutex<HashMap<String, Arc<PostgresClient>>>> = OnceLock::new();
+
 pub struct PostgresClient {
     pool: PgPool,
 }
 
+/// Pre-image of a file change recorded in the file journal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileJournalRecord {
+    pub task_id: String,
+    pub attempt: i32,
+    pub project_root: String,
+    /// Path relative to the project root
+    pub path: String,
+    /// 'file' (content in `pre_image`), 'missing' or 'no_directory'
+    pub kind: String,
+    pub pre_image: Option<Vec<u8>>,
+    /// State the change left the path in: 'file' (content in `post_image`) or 'missing';
+    /// `None` until the change completed
+    pub post_kind: Option<String>,
+    pub post_image: Option<Vec<u8>>,
+}
+
 impl PostgresClient {
     /// Create new PostgreSQL client with connection pool
     pub async fn new(database_url: &str) -> Result<Self> {
@@ -18,6 +41,20 @@ impl PostgresClient {
         Ok(Self { pool })
     }
 
+    /// Process-wide client for a database, connected and migrated on first use, so all
+    /// components share one connection pool
+    pub async fn shared(database_url: &str) -> Result<Arc<Self>> {
+        let mut clients = SHARED_CLIENTS.get_or_init(Default::default).lock().await;
+        if let Some(client) = clients.get(database_url) {
+            return Ok(client.clone());
+        }
+
+        let client = Arc::new(Self::new(database_url).await?);
+        client.run_migrations().await?;
+        clients.insert(database_url.to_string(), client.clone());
+        Ok(client)
+    }
+
     /// Run database migrations
     pub async fn run_migrations(&self) -> Result<()> {
         // Enable pgvector extension
@@ -101,6 +138,47 @@ impl PostgresClient {
         .execute(&self.pool)
         .await?;
 
+        // Create file journal table (pre-images of file changes, next to workflow checkpoints)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS file_journal (
+                id BIGSERIAL PRIMARY KEY,
+                execution_id TEXT NOT NULL,
+                task_id TEXT NOT NULL,
+                attempt INTEGER NOT NULL,
+                project_root TEXT NOT NULL,
+                path TEXT NOT NULL,
+                kind TEXT NOT NULL CHECK (kind IN ('file', 'missing', 'no_directory')),
+                pre_image BYTEA,
+                post_kind TEXT CHECK (post_kind IN ('file', 'missing')),
+                post_image BYTEA,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Post-images were added after the table was introduced
+        sqlx::query(
+            r#"
+            ALTER TABLE file_journal
+            ADD COLUMN IF NOT EXISTS post_kind TEXT CHECK (post_kind IN ('file', 'missing')),
+            ADD COLUMN IF NOT EXISTS post_image BYTEA
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS file_journal_execution_idx
+            ON file_journal (execution_id, id)
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
         // Create audit logs table for HITL
         sqlx::query(
             r#"
@@ -323,6 +401,104 @@ impl PostgresClient {
         Ok(state)
     }
 
+    /// Append a pre-image to the file journal of an execution
+    pub async fn record_file_change(&self, execution_id: &str, record: &FileJournalRecord) -> Result<i64> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO file_journal (execution_id, task_id, attempt, project_root, path, kind, pre_image, post_kind, post_image)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id
+            "#
+        )
+        .bind(execution_id)
+        .bind(&record.task_id)
+        .bind(record.attempt)
+        .bind(&record.project_root)
+        .bind(&record.path)
+        .bind(&record.kind)
+        .bind(&record.pre_image)
+        .bind(&record.post_kind)
+        .bind(&record.post_image)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let id: i64 = row.get("id");
+        Ok(id)
+    }
+
+    /// Store the post-image of a journaled change; returns whether the entry exists
+    pub async fn update_file_result(&self, execution_id: &str, record: &FileJournalRecord) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE file_journal SET post_kind = $5, post_image = $6
+            WHERE execution_id = $1 AND task_id = $2 AND attempt = $3 AND path = $4
+            "#
+        )
+        .bind(execution_id)
+        .bind(&record.task_id)
+        .bind(record.attempt)
+        .bind(&record.path)
+        .bind(&record.post_kind)
+        .bind(&record.post_image)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Load the file journal of an execution in recording order
+    pub async fn load_file_journal(&self, execution_id: &str) -> Result<Vec<FileJournalRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT task_id, attempt, project_root, path, kind, pre_image, post_kind, post_image
+            FROM file_journal
+            WHERE execution_id = $1
+            ORDER BY id
+            "#
+        )
+        .bind(execution_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let records = rows
+            .into_iter()
+            .map(|row| FileJournalRecord {
+                task_id: row.get("task_id"),
+                attempt: row.get("attempt"),
+                project_root: row.get("project_root"),
+                path: row.get("path"),
+                kind: row.get("kind"),
+                pre_image: row.get("pre_image"),
+                post_kind: row.get("post_kind"),
+                post_image: row.get("post_image"),
+            })
+            .collect();
+
+        Ok(records)
+    }
+
+    /// Delete journal entries of an execution, optionally only those of one task attempt
+    pub async fn delete_file_journal(&self, execution_id: &str, attempt: Option<(&str, i32)>) -> Result<u64> {
+        let result = match attempt {
+            Some((task_id, attempt)) => {
+                sqlx::query("DELETE FROM file_journal WHERE execution_id = $1 AND task_id = $2 AND attempt = $3")
+                    .bind(execution_id)
+                    .bind(task_id)
+                    .bind(attempt)
+                    .execute(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query("DELETE FROM file_journal WHERE execution_id = $1")
+                    .bind(execution_id)
+                    .execute(&self.pool)
+                    .await?
+            }
+        };
+
+        Ok(result.rows_affected())
+    }
+
     /// Record HITL audit log
     pub async fn record_audit(
         &self,
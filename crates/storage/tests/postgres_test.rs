@@ -1,4 +1,4 @@
-use ai_agent_storage::PostgresClient;
+use ai_agent_storage::{FileJournalRecord, PostgresClient};
 use uuid::Uuid;
 
 // Helper to get ISOLATED test database URL
@@ -124,6 +124,40 @@ async fn test_checkpoint_save_and_load() {
         .ok();
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_file_journal_roundtrip() {
+    let client = setup_test_db().await;
+    let execution_id = format!("test-exec-{}", Uuid::new_v4());
+
+    let record = |task_id: &str, attempt: i32, path: &str, pre_image: Option<&[u8]>| FileJournalRecord {
+        task_id: task_id.to_string(),
+        attempt,
+        project_root: "/test/project".to_string(),
+        path: path.to_string(),
+        kind: if pre_image.is_some() { "file" } else { "missing" }.to_string(),
+        pre_image: pre_image.map(|p| p.to_vec()),
+        post_kind: None,
+        post_image: None,
+    };
+    let mut first = record("task-1", 0, "src/lib.rs", Some(b"fn a() {}"));
+    let second = record("task-1", 1, "src/new.rs", None);
+
+    client.record_file_change(&execution_id, &first).await.unwrap();
+    client.record_file_change(&execution_id, &second).await.unwrap();
+    first.post_kind = Some("file".to_string());
+    first.post_image = Some(b"fn b() {}".to_vec());
+    assert!(client.update_file_result(&execution_id, &first).await.unwrap());
+    assert_eq!(client.load_file_journal(&execution_id).await.unwrap(), vec![first.clone(), second]);
+
+    // Rolling back one attempt only drops its entries
+    assert_eq!(client.delete_file_journal(&execution_id, Some(("task-1", 1))).await.unwrap(), 1);
+    assert_eq!(client.load_file_journal(&execution_id).await.unwrap(), vec![first]);
+
+    client.delete_file_journal(&execution_id, None).await.unwrap();
+    assert!(client.load_file_journal(&execution_id).await.unwrap().is_empty());
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_semantic_search() {